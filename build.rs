@@ -0,0 +1,70 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Generates one #[test] per .lox file under tests/, at any depth, so adding a
+// test is just adding a file. tests/<dir>/<name>.lox becomes <dir>_<name>,
+// with each `/` in <dir> also becoming `_`. See `run_lox_test` in
+// src/main.rs.
+fn main() {
+    println!("cargo:rerun-if-changed=tests");
+
+    let mut files = Vec::new();
+    let categories = fs::read_dir("tests")
+        .expect("Failed to read tests directory")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir());
+    for category in categories {
+        collect_tests(&category, &mut files);
+    }
+    files.sort();
+
+    let mut generated = String::new();
+    for file in files {
+        let folder = file
+            .parent()
+            .unwrap()
+            .strip_prefix("tests")
+            .unwrap()
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let name = file.file_stem().unwrap().to_string_lossy().to_string();
+        generated.push_str(&format!(
+            "#[test]\nfn {}_{}() {{\n    run_lox_test({:?}, {:?});\n}}\n\n",
+            identifier(&folder),
+            identifier(&name),
+            folder,
+            name
+        ));
+    }
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("lox_tests.rs"), generated)
+        .expect("Failed to write generated tests");
+}
+
+/// Adds every .lox file in `dir` and its subdirectories to `files`.
+fn collect_tests(dir: &Path, files: &mut Vec<PathBuf>) {
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let entries = fs::read_dir(dir).expect("Failed to read test directory");
+    for path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        if path.is_dir() {
+            collect_tests(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            files.push(path);
+        }
+    }
+}
+
+fn identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
@@ -1,6 +1,32 @@
-use crate::token::Token;
+use crate::error::{ErrorCode, LoxError};
+use crate::token::{self, Token};
 use crate::token_type::TokenType;
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+
+/// A character the scanner could not turn into a token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanError {
+    pub line: i32,
+    /// Where the offending character or unterminated string starts.
+    pub offset: usize,
+    pub column: usize,
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}] Error: {} [{}]",
+            token::position(self.line, self.column),
+            self.message,
+            self.code
+        )
+    }
+}
 
 pub struct Scanner {
     source: String,
@@ -8,54 +34,131 @@ pub struct Scanner {
     start: usize,
     current: usize,
     line: i32,
-    keywords: HashMap<String, TokenType>,
+    errors: Vec<ScanError>,
+    /// Whether whitespace, comments, and text that fails to scan are kept
+    /// as tokens.
+    trivia: bool,
+    /// The flags that turn on `#if` sections.
+    flags: HashSet<String>,
+    /// The `#if` sections the scanner is inside, outermost first.
+    sections: Vec<Section>,
+}
+
+/// An `#if` section, from its `#if` up to its `#end`.
+struct Section {
+    line: i32,
+    offset: usize,
+    /// Whether the code before the `#if` is kept.
+    enclosing: bool,
+    /// Whether the code being scanned now, on one side of the `#else` or
+    /// the other, is kept.
+    active: bool,
+    has_else: bool,
 }
 
 impl Scanner {
     // Constructor
     pub fn new(source: String) -> Scanner {
-        let mut keywords = HashMap::new();
-        keywords.insert("and".to_string(), TokenType::And);
-        keywords.insert("class".to_string(), TokenType::Class);
-        keywords.insert("else".to_string(), TokenType::Else);
-        keywords.insert("false".to_string(), TokenType::False);
-        keywords.insert("for".to_string(), TokenType::For);
-        keywords.insert("fun".to_string(), TokenType::Fun);
-        keywords.insert("if".to_string(), TokenType::If);
-        keywords.insert("nil".to_string(), TokenType::Nil);
-        keywords.insert("or".to_string(), TokenType::Or);
-        keywords.insert("print".to_string(), TokenType::Print);
-        keywords.insert("return".to_string(), TokenType::Return);
-        keywords.insert("super".to_string(), TokenType::Super);
-        keywords.insert("this".to_string(), TokenType::This);
-        keywords.insert("true".to_string(), TokenType::True);
-        keywords.insert("var".to_string(), TokenType::Var);
-        keywords.insert("while".to_string(), TokenType::While);
-
         Scanner {
             source,
             tokens: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
-            keywords,
+            errors: Vec::new(),
+            trivia: false,
+            flags: HashSet::new(),
+            sections: Vec::new(),
+        }
+    }
+
+    /// Turns on the `#if` sections naming any of `flags`. The others are
+    /// left out, along with the `#else` side of the ones turned on.
+    pub fn set_flags(&mut self, flags: HashSet<String>) {
+        self.flags = flags;
+    }
+
+    /// Every token of `source` in order, including `Whitespace` and `Comment`
+    /// trivia, so joining the lexemes gives back the source exactly. Text
+    /// that fails to scan comes out as an `Error` token; the errors
+    /// themselves are in `Tokens::errors`. Tokens are scanned as the
+    /// iterator is advanced.
+    pub fn with_trivia(source: &str) -> Tokens {
+        let mut scanner = Scanner::new(source.to_string());
+        scanner.trivia = true;
+        Tokens {
+            scanner,
+            finished: false,
+        }
+    }
+
+    /// Scans all of `source`, returning every error instead of reporting the
+    /// first one. Scanning carries on past an error, so all are collected.
+    pub fn scan(source: &str) -> Result<Vec<Token>, Vec<ScanError>> {
+        let mut scanner = Scanner::new(source.to_string());
+        scanner.scan_all();
+        if scanner.errors.is_empty() {
+            Ok(scanner.tokens)
+        } else {
+            Err(scanner.errors)
+        }
+    }
+
+    /// Whether `name` is a single identifier, and not a keyword.
+    pub fn is_identifier(name: &str) -> bool {
+        match Scanner::scan(name) {
+            Ok(tokens) => matches!(
+                tokens.as_slice(),
+                [token, _] if token.type_ == TokenType::Identifier && token.lexeme == name
+            ),
+            Err(_) => false,
+        }
+    }
+
+    /// Scans the source, returning every error as a `LoxError::Scan`.
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, LoxError> {
+        self.scan_all();
+        if self.errors.is_empty() {
+            Ok(self.tokens.clone())
+        } else {
+            Err(LoxError::Scan(self.errors.clone()))
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
+    fn scan_all(&mut self) {
         while !self.is_at_end() {
             self.start = self.current;
             self.scan_token();
         }
+        self.close_sections();
 
-        self.tokens.push(Token {
+        let eof = self.eof();
+        self.tokens.push(eof);
+    }
+
+    /// Reports an `#if` still open at the end of the source.
+    fn close_sections(&mut self) {
+        if let Some(section) = self.sections.pop() {
+            self.errors.push(ScanError {
+                line: section.line,
+                offset: section.offset,
+                column: self.column(section.offset),
+                code: ErrorCode::UnterminatedPragma,
+                message: "Expect '#end' after '#if'.".to_string(),
+            });
+            self.sections.clear();
+        }
+    }
+
+    fn eof(&self) -> Token {
+        Token {
             type_: TokenType::EoF,
             lexeme: String::new(),
             literal: None,
             line: self.line,
-        });
-
-        self.tokens.clone()
+            offset: self.current,
+            column: self.column(self.current),
+        }
     }
 
     fn scan_token(&mut self) {
@@ -65,12 +168,38 @@ impl Scanner {
             ')' => self.add_token(TokenType::RightParen),
             '{' => self.add_token(TokenType::LeftBrace),
             '}' => self.add_token(TokenType::RightBrace),
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
             ',' => self.add_token(TokenType::Comma),
             '.' => self.add_token(TokenType::Dot),
-            '-' => self.add_token(TokenType::Minus),
-            '+' => self.add_token(TokenType::Plus),
+            '-' => {
+                if self.match_char('=') {
+                    self.add_token(TokenType::MinusEqual)
+                } else if self.match_char('-') {
+                    self.add_token(TokenType::MinusMinus)
+                } else {
+                    self.add_token(TokenType::Minus)
+                }
+            }
+            '+' => {
+                if self.match_char('=') {
+                    self.add_token(TokenType::PlusEqual)
+                } else if self.match_char('+') {
+                    self.add_token(TokenType::PlusPlus)
+                } else {
+                    self.add_token(TokenType::Plus)
+                }
+            }
             ';' => self.add_token(TokenType::Semicolon),
-            '*' => self.add_token(TokenType::Star),
+            '*' => {
+                if self.match_char('=') {
+                    self.add_token(TokenType::StarEqual)
+                } else {
+                    self.add_token(TokenType::Star)
+                }
+            }
+            '?' => self.add_token(TokenType::Question),
+            ':' => self.add_token(TokenType::Colon),
             '!' => {
                 if self.match_char('=') {
                     self.add_token(TokenType::BangEqual)
@@ -88,6 +217,8 @@ impl Scanner {
             '<' => {
                 if self.match_char('=') {
                     self.add_token(TokenType::LessEqual)
+                } else if self.source[self.current..].starts_with("<<") {
+                    self.heredoc();
                 } else {
                     self.add_token(TokenType::Less)
                 }
@@ -104,22 +235,39 @@ impl Scanner {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    self.add_trivia(TokenType::Comment);
+                } else if self.match_char('*') {
+                    self.block_comment();
+                } else if self.match_char('=') {
+                    self.add_token(TokenType::SlashEqual);
                 } else {
                     self.add_token(TokenType::Slash);
                 }
             }
-            ' ' | '\r' | '\t' => {}
+            ' ' | '\r' | '\t' => {
+                while matches!(self.peek(), ' ' | '\r' | '\t') {
+                    self.advance();
+                }
+                self.add_trivia(TokenType::Whitespace);
+            }
             '\n' => {
+                // Each newline is a token of its own, so every token is on
+                // one line
+                self.add_trivia(TokenType::Whitespace);
                 self.line += 1;
             }
             '"' => self.string(),
+            '#' if self.at_line_start() && pragma_word(&self.source[self.current..]).is_some() => {
+                self.pragma()
+            }
             _ => {
                 if self.is_digit(c) {
                     self.number();
                 } else if self.is_alpha(c) {
                     self.identifier();
                 } else {
-                    crate::error(self.line, "Unexpected character.");
+                    self.error(ErrorCode::UnexpectedCharacter, "Unexpected character.");
+                    self.add_trivia(TokenType::Error);
                 }
             }
         }
@@ -130,8 +278,8 @@ impl Scanner {
             self.advance();
         }
         let text = &self.source[self.start..self.current]; // Get substring
-        let token_type = self.keywords.get(text).unwrap_or(&TokenType::Identifier); // Check if it's in the keywords, default to Identifier
-        self.add_token(token_type.clone());
+        let token_type = keyword(text).unwrap_or(TokenType::Identifier);
+        self.add_token(token_type);
     }
 
     fn number(&mut self) {
@@ -156,59 +304,321 @@ impl Scanner {
         self.add_token_with_literal(TokenType::Number, Some(value.to_string()));
     }
 
+    /// A string literal. Its literal is the text between the quotes with
+    /// each escape replaced: `\n`, `\t`, `\"`, `\\`, or `\u{...}` with one
+    /// to six hex digits.
     fn string(&mut self) {
+        let mut value = String::new();
+        let mut valid = true;
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            let c = self.advance();
+            match c {
+                '\\' => match self.escape() {
+                    Some(escaped) => value.push(escaped),
+                    None => valid = false,
+                },
+                '\n' => {
+                    self.line += 1;
+                    value.push(c);
+                }
+                _ => value.push(c),
             }
-            self.advance();
         }
 
         if self.is_at_end() {
-            crate::error(self.line, "Unterminated string.");
+            self.error(ErrorCode::UnterminatedString, "Unterminated string.");
+            self.add_trivia(TokenType::Error);
             return;
         }
 
         // Consume the closing "
         self.advance();
 
-        // Get the string content by trimming the surrounding quotes
-        let value = &self.source[self.start + 1..self.current - 1];
-        self.add_token_with_literal(TokenType::String, Some(value.to_string()));
+        if valid {
+            self.add_token_with_literal(TokenType::String, Some(value));
+        } else {
+            self.add_trivia(TokenType::Error);
+        }
+    }
+
+    /// The character the escape after a `\` stands for, reporting it if it
+    /// isn't one.
+    fn escape(&mut self) -> Option<char> {
+        // The string is reported as unterminated instead
+        if self.is_at_end() {
+            return None;
+        }
+        let backslash = self.current - 1;
+        let escaped = if self.match_char('u') {
+            self.unicode_escape()
+        } else {
+            let escaped = match self.peek() {
+                'n' => Some('\n'),
+                't' => Some('\t'),
+                c @ ('"' | '\\') => Some(c),
+                _ => None,
+            };
+            // A line break is left for the string to count
+            if self.peek() != '\n' && !self.is_at_end() {
+                self.advance();
+            }
+            escaped
+        };
+        if escaped.is_none() {
+            self.error_at(
+                backslash,
+                ErrorCode::InvalidEscape,
+                "Invalid escape sequence.",
+            );
+        }
+        escaped
+    }
+
+    /// The character of a `\u{...}` escape, after its `u`.
+    fn unicode_escape(&mut self) -> Option<char> {
+        if !self.match_char('{') {
+            return None;
+        }
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let digits = self.source[digits_start..self.current].to_string();
+        if digits.is_empty() || digits.len() > 6 || !self.match_char('}') {
+            return None;
+        }
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+    }
+
+    /// A `/* ... */` comment, which may span several lines. Block comments
+    /// nest, so each `/*` inside needs a `*/` of its own.
+    fn block_comment(&mut self) {
+        let start_line = self.line;
+        let mut depth = 1;
+        while !self.is_at_end() {
+            if self.peek() == '/' && self.peek_next() == '*' {
+                self.current += 2;
+                depth += 1;
+                continue;
+            }
+            if self.peek() == '*' && self.peek_next() == '/' {
+                self.current += 2;
+                depth -= 1;
+                if depth == 0 {
+                    self.add_trivia(TokenType::Comment);
+                    return;
+                }
+                continue;
+            }
+            if self.peek() == '\n' {
+                self.line += 1;
+            }
+            self.advance();
+        }
+
+        self.error(
+            ErrorCode::UnterminatedBlockComment,
+            "Unterminated block comment.",
+        );
+        // Reported where the comment opens, rather than at the end of the file
+        if let Some(error) = self.errors.last_mut() {
+            error.line = start_line;
+        }
+        self.add_trivia(TokenType::Error);
+    }
+
+    /// A pragma line: `#if FLAG`, `#else`, or `#end`, with an optional `//`
+    /// comment after it. The lines of a section
+    /// that is turned off are skipped, so the parser never sees them.
+    fn pragma(&mut self) {
+        while self.peek() != '\n' && !self.is_at_end() {
+            self.advance();
+        }
+        let line = self.source[self.start + 1..self.current].to_string();
+        // A `//` comment may follow the pragma
+        let text = line.split("//").next().unwrap_or_default();
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let enclosing = self.sections.iter().all(|section| section.active);
+        match words.as_slice() {
+            ["if", rest @ ..] => {
+                let flag = match rest {
+                    [flag] if Scanner::is_identifier(flag) => Some(*flag),
+                    _ => {
+                        self.error(ErrorCode::InvalidPragma, "Expect a flag name after '#if'.");
+                        None
+                    }
+                };
+                let active = enclosing && flag.is_some_and(|flag| self.flags.contains(flag));
+                self.sections.push(Section {
+                    line: self.line,
+                    offset: self.start,
+                    enclosing,
+                    active,
+                    has_else: false,
+                });
+            }
+            ["else"] => match self.sections.last_mut() {
+                Some(section) if !section.has_else => {
+                    section.has_else = true;
+                    section.active = section.enclosing && !section.active;
+                }
+                Some(_) => self.error(ErrorCode::InvalidPragma, "Expect '#end' after '#else'."),
+                None => self.error(ErrorCode::InvalidPragma, "'#else' without '#if'."),
+            },
+            ["end"] => {
+                if self.sections.pop().is_none() {
+                    self.error(ErrorCode::InvalidPragma, "'#end' without '#if'.");
+                }
+            }
+            _ => self.error(ErrorCode::InvalidPragma, "Expect nothing after the pragma."),
+        }
+        self.add_trivia(TokenType::Pragma);
+
+        if !self.sections.iter().all(|section| section.active) {
+            self.skip_section();
+        }
+    }
+
+    /// Skips the lines of a section that is turned off, up to the next line
+    /// starting with a pragma.
+    fn skip_section(&mut self) {
+        self.start = self.current;
+        if !self.match_char('\n') {
+            return;
+        }
+        self.add_trivia(TokenType::Whitespace);
+        self.line += 1;
+
+        self.start = self.current;
+        while !self.is_at_end() && !self.at_pragma() {
+            match self.source[self.current..].find('\n') {
+                Some(newline) => {
+                    self.current += newline + 1;
+                    self.line += 1;
+                }
+                None => self.current = self.source.len(),
+            }
+        }
+        if self.current > self.start {
+            self.add_trivia(TokenType::Pragma);
+        }
+    }
+
+    /// Whether the line starting at `current` is a pragma.
+    fn at_pragma(&self) -> bool {
+        let line = self.source[self.current..].trim_start_matches([' ', '\t']);
+        line.strip_prefix('#').and_then(pragma_word).is_some()
+    }
+
+    /// Whether only spaces and tabs come before the current token on its line.
+    fn at_line_start(&self) -> bool {
+        let line_start = self.source[..self.start].rfind('\n').map_or(0, |i| i + 1);
+        self.source[line_start..self.start]
+            .chars()
+            .all(|c| c == ' ' || c == '\t')
+    }
+
+    /// A heredoc: `<<<TAG` ending its line, then lines of text up to one
+    /// that holds just `TAG`, which may be followed by more code. The text
+    /// becomes a string with no escapes, less the closing tag's indentation
+    /// on every line. The lexeme is the whole heredoc, tags included.
+    fn heredoc(&mut self) {
+        // The first '<' is already consumed
+        self.current += 2;
+        let tag_start = self.current;
+        while self.is_alpha_numeric(self.peek()) {
+            self.advance();
+        }
+        let tag = self.source[tag_start..self.current].to_string();
+        self.match_char('\r');
+        if !tag.starts_with(|c| self.is_alpha(c)) || !self.match_char('\n') {
+            self.error(
+                ErrorCode::InvalidHeredoc,
+                "Expect a tag and the end of the line after '<<<'.",
+            );
+            self.add_trivia(TokenType::Error);
+            return;
+        }
+        self.line += 1;
+
+        // The byte range of each line of text, without its line break
+        let mut lines = Vec::new();
+        while !self.is_at_end() {
+            let line_start = self.current;
+            while self.peek() != '\n' && !self.is_at_end() {
+                self.advance();
+            }
+            let text = self.source[line_start..self.current].trim_end_matches('\r');
+            let content = text.trim_start_matches([' ', '\t']);
+            let closes = content
+                .strip_prefix(tag.as_str())
+                .is_some_and(|rest| !rest.starts_with(|c| self.is_alpha_numeric(c)));
+            if closes {
+                let indent = &text[..text.len() - content.len()];
+                let value: Vec<&str> = lines
+                    .iter()
+                    .map(|range: &Range<usize>| {
+                        let line = &self.source[range.clone()];
+                        line.strip_prefix(indent).unwrap_or(line)
+                    })
+                    .collect();
+                let value = value.join("\n");
+                self.current = line_start + indent.len() + tag.len();
+                self.add_token_with_literal(TokenType::String, Some(value));
+                return;
+            }
+            lines.push(line_start..line_start + text.len());
+            if self.match_char('\n') {
+                self.line += 1;
+            }
+        }
+
+        self.error(ErrorCode::UnterminatedHeredoc, "Unterminated heredoc.");
+        self.add_trivia(TokenType::Error);
+    }
+
+    fn error(&mut self, code: ErrorCode, message: &str) {
+        self.error_at(self.start, code, message);
+    }
+
+    /// Reports an error at byte `offset`, which is on the current line.
+    fn error_at(&mut self, offset: usize, code: ErrorCode, message: &str) {
+        self.errors.push(ScanError {
+            line: self.line,
+            offset,
+            column: self.column(offset),
+            code,
+            message: message.to_string(),
+        });
     }
 
     fn match_char(&mut self, expected: char) -> bool {
         if self.is_at_end() {
             return false;
         }
-        if self.source.chars().nth(self.current as usize).unwrap() != expected {
+        if self.peek() != expected {
             return false;
         }
-        self.current += 1;
+        self.current += expected.len_utf8();
         true
     }
 
+    // `current` is a byte offset, so characters are read from there rather
+    // than counted from the start of the source
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            '\0'
-        } else {
-            self.source.chars().nth(self.current as usize).unwrap()
-        }
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            '\0'
-        } else {
-            self.source
-                .chars()
-                .nth((self.current + 1) as usize)
-                .unwrap_or('\0')
-        }
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
     }
 
     fn is_alpha(&self, c: char) -> bool {
-        (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+        c.is_ascii_lowercase() || c.is_ascii_uppercase() || c == '_'
     }
 
     fn is_alpha_numeric(&self, c: char) -> bool {
@@ -216,7 +626,7 @@ impl Scanner {
     }
 
     fn is_digit(&self, c: char) -> bool {
-        c >= '0' && c <= '9'
+        c.is_ascii_digit()
     }
 
     fn is_at_end(&self) -> bool {
@@ -224,11 +634,18 @@ impl Scanner {
     }
 
     fn advance(&mut self) -> char {
-        let result = self.source.chars().nth(self.current as usize).unwrap();
-        self.current += 1;
+        let result = self.peek();
+        self.current += result.len_utf8();
         result
     }
 
+    /// Adds a token only when trivia is being kept.
+    fn add_trivia(&mut self, token_type: TokenType) {
+        if self.trivia {
+            self.add_token(token_type);
+        }
+    }
+
     fn add_token(&mut self, token_type: TokenType) {
         self.add_token_with_literal(token_type, None);
     }
@@ -240,6 +657,409 @@ impl Scanner {
             lexeme: text.to_string(),
             literal,
             line: self.line,
+            offset: self.start,
+            column: self.column(self.start),
         });
     }
+
+    /// The column of the character at byte `offset`, counting from 1.
+    fn column(&self, offset: usize) -> usize {
+        let line_start = self.source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        self.source[line_start..offset].chars().count() + 1
+    }
+}
+
+/// The lossless token stream made by `Scanner::with_trivia`, ending with
+/// `EoF`.
+pub struct Tokens {
+    scanner: Scanner,
+    finished: bool,
+}
+
+impl Tokens {
+    /// The errors met so far. Scanning goes on past them.
+    pub fn errors(&self) -> &[ScanError] {
+        &self.scanner.errors
+    }
+}
+
+impl Iterator for Tokens {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        // With trivia kept, every character scanned ends up in exactly one
+        // token, so this stops after one `scan_token`. A pragma that turns
+        // off a section adds the section too.
+        while self.scanner.tokens.is_empty() {
+            if self.scanner.is_at_end() {
+                if self.finished {
+                    return None;
+                }
+                self.scanner.close_sections();
+                self.finished = true;
+                return Some(self.scanner.eof());
+            }
+            self.scanner.start = self.scanner.current;
+            self.scanner.scan_token();
+        }
+        Some(self.scanner.tokens.remove(0))
+    }
+}
+
+/// The pragma `text`, the rest of a line after a `#`, starts with, if any.
+fn pragma_word(text: &str) -> Option<&str> {
+    let end = text
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(text.len());
+    let word = &text[..end];
+    matches!(word, "if" | "else" | "end").then_some(word)
+}
+
+/// `text` written as the inside of a string literal, with escapes for the
+/// characters that need them, so scanning it gives back `text`.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The keyword `text` spells, if any. A `match` compiles to a lookup on
+/// the length and bytes, so no table has to be built for each scanner.
+fn keyword(text: &str) -> Option<TokenType> {
+    let token_type = match text {
+        "and" => TokenType::And,
+        "class" => TokenType::Class,
+        "defer" => TokenType::Defer,
+        "else" => TokenType::Else,
+        "false" => TokenType::False,
+        "for" => TokenType::For,
+        "fun" => TokenType::Fun,
+        "if" => TokenType::If,
+        "implements" => TokenType::Implements,
+        "interface" => TokenType::Interface,
+        "is" => TokenType::Is,
+        "macro" => TokenType::Macro,
+        "nil" => TokenType::Nil,
+        "or" => TokenType::Or,
+        "print" => TokenType::Print,
+        "return" => TokenType::Return,
+        "super" => TokenType::Super,
+        "this" => TokenType::This,
+        "true" => TokenType::True,
+        "using" => TokenType::Using,
+        "var" => TokenType::Var,
+        "while" => TokenType::While,
+        _ => return None,
+    };
+    Some(token_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(source: &str) -> Vec<TokenType> {
+        Scanner::scan(source)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.type_)
+            .collect()
+    }
+
+    #[test]
+    fn scans_punctuation_and_operators() {
+        use TokenType::*;
+        assert_eq!(
+            types("(){}[],.-+;/ *"),
+            vec![
+                LeftParen,
+                RightParen,
+                LeftBrace,
+                RightBrace,
+                LeftBracket,
+                RightBracket,
+                Comma,
+                Dot,
+                Minus,
+                Plus,
+                Semicolon,
+                Slash,
+                Star,
+                EoF
+            ]
+        );
+        assert_eq!(
+            types("! != = == > >= < <="),
+            vec![
+                Bang,
+                BangEqual,
+                Equal,
+                EqualEqual,
+                Greater,
+                GreaterEqual,
+                Less,
+                LessEqual,
+                EoF
+            ]
+        );
+        // The longest operator wins
+        assert_eq!(types("!==="), vec![BangEqual, EqualEqual, EoF]);
+    }
+
+    #[test]
+    fn scans_keywords_and_identifiers() {
+        use TokenType::*;
+        assert_eq!(
+            types(
+                "and class defer else false for fun if implements interface is macro nil or print return super this true using var while"
+            ),
+            vec![
+                And, Class, Defer, Else, False, For, Fun, If, Implements, Interface, Is, Macro, Nil, Or, Print, Return, Super, This,
+                True, Using, Var, While, EoF
+            ]
+        );
+        // Keywords are case-sensitive and only match whole words
+        assert_eq!(
+            types("_a1 classy Or"),
+            vec![Identifier, Identifier, Identifier, EoF]
+        );
+    }
+
+    #[test]
+    fn scans_literals() {
+        let tokens = Scanner::scan("123 4.5 6. \"hi there\"").unwrap();
+        let literals: Vec<(TokenType, Option<&str>)> = tokens
+            .iter()
+            .map(|token| (token.type_.clone(), token.literal.as_deref()))
+            .collect();
+        assert_eq!(
+            literals,
+            vec![
+                (TokenType::Number, Some("123")),
+                (TokenType::Number, Some("4.5")),
+                // A trailing dot is not part of the number
+                (TokenType::Number, Some("6")),
+                (TokenType::Dot, None),
+                (TokenType::String, Some("hi there")),
+                (TokenType::EoF, None),
+            ]
+        );
+        assert_eq!(tokens[4].lexeme, "\"hi there\"");
+    }
+
+    #[test]
+    fn scans_non_ascii_text() {
+        let tokens = Scanner::scan("\"héllo\" // ünïcode\n1").unwrap();
+        assert_eq!(tokens[0].literal.as_deref(), Some("héllo"));
+        assert_eq!(tokens[1].lexeme, "1");
+        assert!(Scanner::scan("é").is_err());
+    }
+
+    #[test]
+    fn skips_whitespace_and_comments_and_counts_lines() {
+        let tokens = Scanner::scan("a // comment\n\t\"b\nc\"\r\n  d").unwrap();
+        let lines: Vec<(&str, i32)> = tokens
+            .iter()
+            .map(|token| (token.lexeme.as_str(), token.line))
+            .collect();
+        assert_eq!(lines, vec![("a", 1), ("\"b\nc\"", 3), ("d", 4), ("", 4)]);
+        // Columns count characters from the start of the lexeme's first line
+        let columns: Vec<usize> = tokens.iter().map(|token| token.column).collect();
+        assert_eq!(columns, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn collects_every_error() {
+        assert_eq!(
+            Scanner::scan("@ ok\n# \"open"),
+            Err(vec![
+                ScanError {
+                    line: 1,
+                    offset: 0,
+                    column: 1,
+                    code: ErrorCode::UnexpectedCharacter,
+                    message: "Unexpected character.".to_string(),
+                },
+                ScanError {
+                    line: 2,
+                    offset: 5,
+                    column: 1,
+                    code: ErrorCode::UnexpectedCharacter,
+                    message: "Unexpected character.".to_string(),
+                },
+                ScanError {
+                    line: 2,
+                    offset: 7,
+                    column: 3,
+                    code: ErrorCode::UnterminatedString,
+                    message: "Unterminated string.".to_string(),
+                },
+            ])
+        );
+        assert_eq!(
+            Scanner::scan("@").unwrap_err()[0].to_string(),
+            "[line 1:1] Error: Unexpected character. [E1002]"
+        );
+    }
+
+    #[test]
+    fn trivia_tokens_rebuild_the_source() {
+        let source = "var a = 1; // one\r\n\t\"two\nlines\" @ \"open";
+        let tokens: Vec<Token> = Scanner::with_trivia(source).collect();
+        let rebuilt: String = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        assert_eq!(rebuilt, source);
+
+        let kinds: Vec<(TokenType, &str, i32)> = tokens
+            .iter()
+            .skip(9)
+            .map(|token| (token.type_.clone(), token.lexeme.as_str(), token.line))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                // A comment runs up to the newline
+                (TokenType::Comment, "// one\r", 1),
+                (TokenType::Whitespace, "\n", 1),
+                (TokenType::Whitespace, "\t", 2),
+                (TokenType::String, "\"two\nlines\"", 3),
+                (TokenType::Whitespace, " ", 3),
+                (TokenType::Error, "@", 3),
+                (TokenType::Whitespace, " ", 3),
+                (TokenType::Error, "\"open", 3),
+                (TokenType::EoF, "", 3),
+            ]
+        );
+
+        let mut tokens = Scanner::with_trivia(source);
+        tokens.by_ref().for_each(drop);
+        let codes: Vec<ErrorCode> = tokens.errors().iter().map(|error| error.code).collect();
+        assert_eq!(
+            codes,
+            vec![
+                ErrorCode::UnexpectedCharacter,
+                ErrorCode::UnterminatedString
+            ]
+        );
+        // The tokens the parser sees don't change
+        assert_eq!(Scanner::scan("a // b\n c").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn scans_heredocs() {
+        let source = "x = <<<END\r\n  a \"b\"\r\n    c\r\n  END;\n";
+        let tokens = Scanner::scan(source).unwrap();
+        assert_eq!(tokens[2].type_, TokenType::String);
+        assert_eq!(tokens[2].literal.as_deref(), Some("a \"b\"\n  c"));
+        assert_eq!(tokens[2].lexeme, "<<<END\r\n  a \"b\"\r\n    c\r\n  END");
+        assert_eq!((tokens[3].lexeme.as_str(), tokens[3].line), (";", 4));
+        // A heredoc doesn't change the operators it starts like
+        assert_eq!(types("a << b"), types("a < < b"));
+    }
+
+    #[test]
+    fn skips_block_comments() {
+        let tokens = Scanner::scan("a /* b\n * c */ d").unwrap();
+        let names: Vec<(&str, i32)> = tokens
+            .iter()
+            .map(|token| (token.lexeme.as_str(), token.line))
+            .collect();
+        assert_eq!(names, [("a", 1), ("d", 2), ("", 2)]);
+
+        let comments: Vec<String> = Scanner::with_trivia("/* one */ x /**/ /* a /* b */ c */")
+            .filter(|token| token.type_ == TokenType::Comment)
+            .map(|token| token.lexeme)
+            .collect();
+        assert_eq!(comments, ["/* one */", "/**/", "/* a /* b */ c */"]);
+    }
+
+    #[test]
+    fn strings_replace_escapes() {
+        let tokens = Scanner::scan(r#""a\n\t\"b\"\\ \u{48}\u{1F600}""#).unwrap();
+        assert_eq!(tokens[0].literal.as_deref(), Some("a\n\t\"b\"\\ H😀"));
+        assert_eq!(tokens[0].lexeme, r#""a\n\t\"b\"\\ \u{48}\u{1F600}""#);
+        assert_eq!(escape("a\n\t\"b\"\\ H"), r#"a\n\t\"b\"\\ H"#);
+
+        let errors: Vec<(i32, usize)> =
+            Scanner::scan("\"\\q\"\n\"one\n\\u{} \\u{110000} \\u{12\" \"\\")
+                .unwrap_err()
+                .iter()
+                .map(|error| (error.line, error.column))
+                .collect();
+        assert_eq!(errors, [(1, 2), (3, 1), (3, 6), (3, 17), (3, 24)]);
+        assert_eq!(
+            Scanner::scan("\"\\q\"").unwrap_err()[0].to_string(),
+            "[line 1:2] Error: Invalid escape sequence. [E1008]"
+        );
+    }
+
+    #[test]
+    fn pragmas_keep_sections_for_their_flags() {
+        let source = "a\n#if DEBUG\nb\n  #if TRACE\nc\n  #end\n#else\nd\n#end\ne";
+        let scan = |flags: &[&str]| {
+            let mut scanner = Scanner::new(source.to_string());
+            scanner.set_flags(flags.iter().map(|flag| flag.to_string()).collect());
+            let tokens = scanner.scan_tokens().unwrap();
+            let names: Vec<(String, i32)> = tokens
+                .into_iter()
+                .map(|token| (token.lexeme, token.line))
+                .collect();
+            names
+        };
+        let names = |names: &[(&str, i32)]| -> Vec<(String, i32)> {
+            names
+                .iter()
+                .map(|(name, line)| (name.to_string(), *line))
+                .collect()
+        };
+        assert_eq!(scan(&[]), names(&[("a", 1), ("d", 8), ("e", 10), ("", 10)]));
+        assert_eq!(
+            scan(&["DEBUG"]),
+            names(&[("a", 1), ("b", 3), ("e", 10), ("", 10)])
+        );
+        assert_eq!(
+            scan(&["DEBUG", "TRACE"]),
+            names(&[("a", 1), ("b", 3), ("c", 5), ("e", 10), ("", 10)])
+        );
+        // Only the flags of enclosing sections that are on count
+        assert_eq!(scan(&["TRACE"]), scan(&[]));
+
+        let rebuilt: String = Scanner::with_trivia(source)
+            .map(|token| token.lexeme)
+            .collect();
+        assert_eq!(rebuilt, source);
+
+        let messages = |source: &str| -> Vec<String> {
+            Scanner::scan(source)
+                .unwrap_err()
+                .iter()
+                .map(ScanError::to_string)
+                .collect()
+        };
+        assert_eq!(
+            messages("#if\n#end\n#end x\n#else\n#if A\n#else\n#else"),
+            [
+                "[line 1:1] Error: Expect a flag name after '#if'. [E1007]",
+                "[line 3:1] Error: Expect nothing after the pragma. [E1007]",
+                "[line 4:1] Error: '#else' without '#if'. [E1007]",
+                "[line 7:1] Error: Expect '#end' after '#else'. [E1007]",
+                "[line 5:1] Error: Expect '#end' after '#if'. [E1006]",
+            ]
+        );
+        // A '#' that doesn't start a line, or a pragma, is just a character
+        assert_eq!(
+            messages("a #if B\n#define"),
+            [
+                "[line 1:3] Error: Unexpected character. [E1002]",
+                "[line 2:1] Error: Unexpected character. [E1002]",
+            ]
+        );
+    }
 }
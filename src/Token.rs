@@ -1,12 +1,21 @@
 use crate::token_type::TokenType;
 use std::fmt;
+use std::ops::Range;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Hash, Eq)]
 pub struct Token {
     pub type_: TokenType,
     pub lexeme: String,
+    /// The value of a string or number. On a name in a macro's expansion,
+    /// which copy it belongs to, keeping copies of one expression apart.
     pub literal: Option<String>,
     pub line: i32,
+    /// Where the lexeme starts in the source. Tells apart tokens with the
+    /// same text on one line; tokens the parser makes up have offset 0.
+    pub offset: usize,
+    /// Which character of its line the lexeme starts at, counting from 1.
+    /// Tokens the parser makes up have column 0.
+    pub column: usize,
 }
 
 impl Token {
@@ -17,14 +26,36 @@ impl Token {
             lexeme,
             literal,
             line,
+            offset: 0,
+            column: 0,
         }
     }
+
+    /// The bytes of the source the lexeme covers.
+    pub fn span(&self) -> Range<usize> {
+        self.offset..self.offset + self.lexeme.len()
+    }
+
+    /// `line:column` for diagnostics, or just the line when the column
+    /// isn't known.
+    pub fn position(&self) -> String {
+        position(self.line, self.column)
+    }
+}
+
+/// `line:column`, or just `line` for column 0.
+pub fn position(line: i32, column: usize) -> String {
+    if column == 0 {
+        line.to_string()
+    } else {
+        format!("{}:{}", line, column)
+    }
 }
 
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // Format the string without printing
-        let formatted_string = format!("{}", self.lexeme);
+        let formatted_string = self.lexeme.clone();
 
         // Return the formatted string to the formatter without printing it directly
         f.write_str(&formatted_string)
@@ -0,0 +1,121 @@
+//! Natives backed by futures, for embedders exposing async I/O to scripts.
+//!
+//! The tree-walker is synchronous, so each call blocks until its future
+//! completes. Inside a multi-threaded tokio runtime the wait goes through
+//! `block_in_place`, which keeps the runtime's other tasks running; calling
+//! from a current-thread runtime is not supported. Outside of tokio, a
+//! per-thread current-thread runtime drives the future.
+
+use crate::callable::Callable;
+use crate::error::LoxError;
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+use std::any::Any;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+pub type NativeFuture = Pin<Box<dyn Future<Output = Value>>>;
+
+thread_local! {
+    static RUNTIME: Runtime = Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to start the async native runtime");
+}
+
+#[derive(Clone)]
+pub struct AsyncNative {
+    arity: usize,
+    function: Rc<dyn Fn(Vec<Value>) -> NativeFuture>,
+}
+
+impl AsyncNative {
+    pub fn new<F>(arity: usize, function: F) -> Self
+    where
+        F: Fn(Vec<Value>) -> NativeFuture + 'static,
+    {
+        AsyncNative {
+            arity,
+            function: Rc::new(function),
+        }
+    }
+}
+
+fn block_on(future: NativeFuture) -> Value {
+    match Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(future)),
+        Err(_) => RUNTIME.with(|runtime| runtime.block_on(future)),
+    }
+}
+
+impl Callable for AsyncNative {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let arguments = arguments
+            .into_iter()
+            .map(|argument| argument.unwrap_or(Value::Nil()))
+            .collect();
+        Ok(Some(block_on((self.function)(arguments))))
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    // Futures wait on the outside world, such as I/O or timers
+    fn deterministic(&self) -> bool {
+        false
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lox;
+    use std::cell::RefCell;
+    use std::time::Duration;
+
+    #[test]
+    fn async_native_result_is_awaited() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let recorded = seen.clone();
+
+        let mut lox = Lox::new("");
+        lox.define_async_native("fetch", 1, |arguments| {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                match arguments[0] {
+                    Value::Number(n) => Value::Number(n * 2.0),
+                    _ => Value::Nil(),
+                }
+            })
+        });
+        lox.define_async_native("record", 1, move |arguments| {
+            let recorded = recorded.clone();
+            Box::pin(async move {
+                recorded.borrow_mut().push(arguments[0].clone());
+                Value::Nil()
+            })
+        });
+
+        assert!(lox.try_run("record(fetch(21));").is_ok());
+        assert_eq!(*seen.borrow(), vec![Value::Number(42.0)]);
+    }
+}
@@ -0,0 +1,197 @@
+//! Arbitrary-precision integers, enabled by the `lox-bigint` feature.
+//!
+//! Lox numbers are `f64`s, which hold every integer exactly only up to 2^53.
+//! With this feature, integer literals and `+`, `-`, `*`, and `/` on integers
+//! whose exact result falls outside that range give a `Value::BigInt` instead
+//! of a rounded number. Results that fit go back to being plain numbers, so
+//! scripts that stay in range behave exactly as before.
+
+use crate::token_type::TokenType;
+use crate::value::Value;
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+
+/// 2^53 - 1. Every integer this size or smaller is exact as an `f64`, and
+/// no result in this range can have been rounded.
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+/// Reads an integer literal too large for a number to hold exactly.
+pub fn literal(lexeme: &str) -> Option<Value> {
+    if lexeme.contains('.') {
+        return None;
+    }
+    let value: BigInt = lexeme.parse().ok()?;
+    Some(normalize(value))
+}
+
+/// Applies a binary operator when big integers are involved, or when an
+/// integer result would lose precision as a number. Returns `None` to leave
+/// the operation to the usual number rules.
+pub fn binary(operator: &TokenType, left: &Value, right: &Value) -> Option<Value> {
+    let either_big = matches!(left, Value::BigInt(_)) || matches!(right, Value::BigInt(_));
+    match operator {
+        TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash => {
+            let (a, b) = (integer(left), integer(right));
+            if let (Some(a), Some(b)) = (a, b) {
+                if either_big || !fits(operator, left, right) {
+                    return integer_arithmetic(operator, a, b);
+                }
+                return None;
+            }
+            if !either_big {
+                return None;
+            }
+            // A big integer with a fraction works in floating point
+            let (a, b) = (to_f64(left)?, to_f64(right)?);
+            Some(Value::Number(match operator {
+                TokenType::Plus => a + b,
+                TokenType::Minus => a - b,
+                TokenType::Star => a * b,
+                _ => a / b,
+            }))
+        }
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual
+            if either_big =>
+        {
+            let ordering = compare(left, right)?;
+            Some(Value::Boolean(match operator {
+                TokenType::Greater => ordering == Ordering::Greater,
+                TokenType::GreaterEqual => ordering != Ordering::Less,
+                TokenType::Less => ordering == Ordering::Less,
+                _ => ordering != Ordering::Greater,
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Negates a big integer.
+pub fn negate(value: &BigInt) -> Value {
+    normalize(-value)
+}
+
+/// Orders two numeric values, at least one of them a big integer.
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (integer(left), integer(right)) {
+        (Some(a), Some(b)) => Some(a.cmp(&b)),
+        _ => to_f64(left)?.partial_cmp(&to_f64(right)?),
+    }
+}
+
+/// Whether a big integer and a number hold the same integer.
+pub fn equals_number(big: &BigInt, number: f64) -> bool {
+    number.fract() == 0.0 && BigInt::from_f64(number).as_ref() == Some(big)
+}
+
+/// Turns results that fit back into numbers.
+fn normalize(value: BigInt) -> Value {
+    if value.abs() <= BigInt::from(MAX_SAFE_INTEGER as i64) {
+        Value::Number(value.to_f64().unwrap_or(f64::NAN))
+    } else {
+        Value::BigInt(value)
+    }
+}
+
+/// The exact integer a value holds, if any. Numbers outside the safe range
+/// may already have been rounded, so they don't count.
+fn integer(value: &Value) -> Option<BigInt> {
+    match value {
+        Value::BigInt(big) => Some(big.clone()),
+        Value::Number(n) if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER => {
+            Some(BigInt::from(*n as i64))
+        }
+        _ => None,
+    }
+}
+
+fn to_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::BigInt(big) => big.to_f64(),
+        Value::Number(n) => Some(*n),
+        _ => None,
+    }
+}
+
+/// Whether the number result of an operation on two safe integers is exact.
+fn fits(operator: &TokenType, left: &Value, right: &Value) -> bool {
+    let (Value::Number(a), Value::Number(b)) = (left, right) else {
+        return false;
+    };
+    let result = match operator {
+        TokenType::Plus => a + b,
+        TokenType::Minus => a - b,
+        TokenType::Star => a * b,
+        // Division of safe integers never leaves the safe range
+        _ => return true,
+    };
+    result.abs() <= MAX_SAFE_INTEGER
+}
+
+fn integer_arithmetic(operator: &TokenType, a: BigInt, b: BigInt) -> Option<Value> {
+    match operator {
+        TokenType::Plus => Some(normalize(a + b)),
+        TokenType::Minus => Some(normalize(a - b)),
+        TokenType::Star => Some(normalize(a * b)),
+        _ => {
+            if b.is_zero() {
+                return Some(Value::Number(a.to_f64()? / 0.0));
+            }
+            // Exact quotients stay integers; others become numbers
+            if (&a % &b).is_zero() {
+                Some(normalize(a / b))
+            } else {
+                Some(Value::Number(a.to_f64()? / b.to_f64()?))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Lox;
+    use std::env;
+    use std::fs;
+
+    fn run(name: &str, source: &str) -> String {
+        let output_file = env::temp_dir().join(format!("lox_bigint_{}.txt", name));
+        fs::write(&output_file, "").unwrap();
+        let mut lox = Lox::new(&output_file.to_string_lossy());
+        lox.try_run(source).unwrap();
+        fs::read_to_string(&output_file).unwrap()
+    }
+
+    #[test]
+    fn integers_promote_instead_of_losing_precision() {
+        let output = run(
+            "promote",
+            "fun factorial(n) {
+               if (n <= 1) return 1;
+               return n * factorial(n - 1);
+             }
+             print factorial(25);
+             print factorial(25) / factorial(24);
+             print 9007199254740992 + 1;
+             print 123456789012345678901234567890 - 123456789012345678901234567889;
+             print 0.5 + 1;",
+        );
+        assert_eq!(
+            output,
+            "15511210043330985984000000\n25\n9007199254740993\n1\n1.5\n"
+        );
+    }
+
+    #[test]
+    fn big_integers_compare_with_numbers() {
+        let output = run(
+            "compare",
+            "var big = 100000000000000000000;
+             print big > 1;
+             print -big < 0;
+             print big == 100000000000000000000;
+             print big == 100000000000000000000.0;
+             print big / 3 > 1;",
+        );
+        assert_eq!(output, "true\ntrue\ntrue\ntrue\ntrue\n");
+    }
+}
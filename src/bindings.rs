@@ -0,0 +1,181 @@
+//! Which declaration each name in a program refers to, for editor features
+//! such as go-to-definition and rename, and for analysis tools.
+//!
+//! The resolver records a `Binding` for every variable, parameter, function,
+//! and class it sees declared, along with each use of the name that resolves
+//! to it. Methods and properties are looked up at runtime, so they have no
+//! bindings; neither do `this` and `super`.
+
+use crate::error::LoxError;
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::token::Token;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// One declaration and the uses that refer to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub declaration: Token,
+    /// Reads of the name, assignments to it, and for globals any later
+    /// declaration of the same name, in source order.
+    pub uses: Vec<Token>,
+}
+
+impl Binding {
+    /// Whether the token at `offset` is the declaration or one of the uses.
+    fn covers(&self, offset: usize) -> bool {
+        std::iter::once(&self.declaration)
+            .chain(&self.uses)
+            .any(|token| token.span().contains(&offset))
+    }
+}
+
+/// The bindings of a program, in the order their declarations appear.
+#[derive(Debug, Default)]
+pub struct Bindings {
+    bindings: Vec<Binding>,
+    /// Uses of globals declared nowhere in the program, such as natives.
+    unbound: Vec<Token>,
+}
+
+impl Bindings {
+    /// Adds a binding for `declaration` and returns its index.
+    pub(crate) fn declare(&mut self, declaration: Token) -> usize {
+        self.bindings.push(Binding {
+            declaration,
+            uses: vec![],
+        });
+        self.bindings.len() - 1
+    }
+
+    pub(crate) fn add_use(&mut self, binding: usize, token: Token) {
+        self.bindings[binding].uses.push(token);
+    }
+
+    pub(crate) fn add_unbound(&mut self, token: Token) {
+        self.unbound.push(token);
+    }
+
+    /// Puts the bindings and each one's uses in source order. A use the
+    /// parser copied, as the target of `x += 1` or `x++` is, is kept once.
+    pub(crate) fn sort(&mut self) {
+        self.bindings
+            .sort_by_key(|binding| binding.declaration.offset);
+        for binding in &mut self.bindings {
+            binding.uses.sort_by_key(|token| token.offset);
+            binding.uses.dedup_by_key(|token| token.offset);
+        }
+        self.unbound.sort_by_key(|token| token.offset);
+        self.unbound.dedup_by_key(|token| token.offset);
+    }
+
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    pub fn unbound(&self) -> &[Token] {
+        &self.unbound
+    }
+
+    /// The declaration `token` refers to, or `token` itself if it is a
+    /// declaration. `None` for unbound names and tokens that aren't names.
+    pub fn declaration_of(&self, token: &Token) -> Option<&Token> {
+        self.binding_of(token).map(|binding| &binding.declaration)
+    }
+
+    /// The uses of the declaration `token` refers to.
+    pub fn uses_of(&self, token: &Token) -> Option<&[Token]> {
+        self.binding_of(token)
+            .map(|binding| binding.uses.as_slice())
+    }
+
+    /// The binding whose declaration or use is under the cursor at byte
+    /// `offset` of the source.
+    pub fn at(&self, offset: usize) -> Option<&Binding> {
+        self.bindings.iter().find(|binding| binding.covers(offset))
+    }
+
+    fn binding_of(&self, token: &Token) -> Option<&Binding> {
+        self.bindings
+            .iter()
+            .find(|binding| &binding.declaration == token || binding.uses.contains(token))
+    }
+}
+
+/// Resolves `source` and returns its bindings, without running it. Fails if
+/// the source doesn't scan, parse, or resolve.
+pub fn analyze(source: &str) -> Result<Bindings, LoxError> {
+    let tokens = Scanner::new(source.to_string()).scan_tokens()?;
+    let statements = Parser::new(tokens).parse()?;
+
+    let mut resolver = Resolver::new(Rc::new(RefCell::new(Interpreter::without_prelude(""))));
+    resolver.resolve(&statements)?;
+    let mut bindings = resolver.take_bindings();
+
+    // Leave out names the parser made up, which aren't in the source
+    let written = |token: &Token| {
+        source
+            .get(token.offset..)
+            .is_some_and(|rest| rest.starts_with(&token.lexeme))
+    };
+    bindings
+        .bindings
+        .retain(|binding| written(&binding.declaration));
+    for binding in &mut bindings.bindings {
+        binding.uses.retain(written);
+    }
+    bindings.unbound.retain(written);
+    Ok(bindings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(tokens: &[Token]) -> Vec<i32> {
+        tokens.iter().map(|token| token.line).collect()
+    }
+
+    #[test]
+    fn uses_lead_to_their_declarations() {
+        let source = "fun main() {\n\
+                      \x20 var x = 1;\n\
+                      \x20 {\n\
+                      \x20   var x = 2;\n\
+                      \x20   print x;\n\
+                      \x20 }\n\
+                      \x20 x = helper(x);\n\
+                      \x20 print clock();\n\
+                      }\n\
+                      fun helper(n) { return n; }\n\
+                      main();";
+        let bindings = analyze(source).unwrap();
+
+        // The inner `x` shadows the outer one within the block
+        let inner = bindings.at(source.find("print x").unwrap() + 6).unwrap();
+        assert_eq!(inner.declaration.line, 4);
+        assert_eq!(lines(&inner.uses), [5]);
+        let outer = bindings.at(source.find("var x").unwrap() + 4).unwrap();
+        assert_eq!(outer.declaration.line, 2);
+        assert_eq!(lines(&outer.uses), [7, 7]);
+
+        // Globals are found wherever they're declared
+        let helper = bindings.at(source.find("helper(x)").unwrap()).unwrap();
+        assert_eq!(helper.declaration.line, 10);
+        assert_eq!(
+            bindings.declaration_of(&helper.uses[0]),
+            Some(&helper.declaration)
+        );
+        let main = &bindings.bindings()[0];
+        assert_eq!(
+            bindings.uses_of(&main.declaration).map(lines),
+            Some(vec![11])
+        );
+
+        assert_eq!(lines(bindings.unbound()), [8]);
+        assert!(bindings.at(source.find("print").unwrap()).is_none());
+    }
+}
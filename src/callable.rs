@@ -1,20 +1,38 @@
+use crate::error::LoxError;
 use crate::interpreter::Interpreter;
 use crate::value::Value;
 use std::any::Any;
 use std::fmt;
 
 pub trait Callable {
+    /// Calls the function. Returning an `Err` stops the script with that error.
     fn call(
         &mut self,
         interpreter: &mut Interpreter,
         arguments: Vec<Option<Value>>,
-    ) -> Option<Value>;
+    ) -> Result<Option<Value>, LoxError>;
     fn arity(&self) -> usize;
+    /// Whether the function takes any number of arguments past `arity`.
+    fn variadic(&self) -> bool {
+        false
+    }
+    /// Whether the function's result depends only on its arguments and the
+    /// interpreter's state. Natives that read files, the network, or other
+    /// outside state should say no, and then can't be called in
+    /// deterministic mode.
+    fn deterministic(&self) -> bool {
+        true
+    }
     fn as_any(&self) -> &dyn Any;
     fn clone_box(&self) -> Box<dyn Callable>;
     fn to_string(&self) -> String {
         "Callable".to_string()
     }
+    /// The name a call to the function shows under in a stack trace.
+    /// Classes show their name and natives "<native fn>".
+    fn name(&self) -> String {
+        self.to_string()
+    }
 }
 
 impl Clone for Box<dyn Callable> {
@@ -0,0 +1,67 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag the host can trip from any thread to stop a running script. The
+/// interpreter checks it before each statement.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LoxError;
+    use crate::Lox;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn cancel_stops_infinite_loop() {
+        let token = CancellationToken::new();
+        let mut lox = Lox::new("");
+        lox.set_cancellation_token(token.clone());
+
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            token.cancel();
+        });
+        let result = lox.try_run("var i = 0; while (true) { i = i + 1; }");
+        canceller.join().unwrap();
+
+        assert!(matches!(result, Err(LoxError::Cancelled)));
+    }
+
+    #[test]
+    fn reset_token_allows_running_again() {
+        let token = CancellationToken::new();
+        let mut lox = Lox::new("");
+        lox.set_cancellation_token(token.clone());
+
+        token.cancel();
+        assert!(matches!(
+            lox.try_run("var a = 1;"),
+            Err(LoxError::Cancelled)
+        ));
+        token.reset();
+        assert!(lox.try_run("var b = 2;").is_ok());
+    }
+}
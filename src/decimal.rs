@@ -0,0 +1,259 @@
+//! Exact decimal numbers, for scripts run with `Lox::set_decimal_literals`
+//! (`--decimal` on the command line).
+//!
+//! In that mode a number literal with a fractional part, such as `0.1`,
+//! gives a `Value::Decimal` holding exactly the digits written, so
+//! `0.1 + 0.2 == 0.3` and prints `0.3`. Integer literals stay plain numbers,
+//! and a result with no fractional part goes back to being one. Arithmetic
+//! that would overflow the decimal, or that mixes in a number with a binary
+//! fraction, falls back to floating point.
+
+use crate::token_type::TokenType;
+use crate::value::Value;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Digits kept after the point when a quotient doesn't terminate.
+const MAX_SCALE: u32 = 20;
+
+/// 2^53. Integers this size or smaller convert from numbers exactly.
+const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// `units / 10^scale`, with no trailing zeros after the point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decimal {
+    units: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    fn new(units: i128, scale: u32) -> Self {
+        let (mut units, mut scale) = (units, scale);
+        while scale > 0 && units % 10 == 0 {
+            units /= 10;
+            scale -= 1;
+        }
+        Decimal { units, scale }
+    }
+
+    /// The nearest number to the decimal.
+    pub fn to_f64(self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    /// Both values' units at the larger of their scales.
+    fn align(self, other: Decimal) -> Option<(i128, i128, u32)> {
+        let scale = self.scale.max(other.scale);
+        let a = self
+            .units
+            .checked_mul(10i128.checked_pow(scale - self.scale)?)?;
+        let b = other
+            .units
+            .checked_mul(10i128.checked_pow(scale - other.scale)?)?;
+        Some((a, b, scale))
+    }
+
+    fn add(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.align(other)?;
+        Some(Decimal::new(a.checked_add(b)?, scale))
+    }
+
+    fn sub(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.align(other)?;
+        Some(Decimal::new(a.checked_sub(b)?, scale))
+    }
+
+    fn mul(self, other: Decimal) -> Option<Decimal> {
+        let units = self.units.checked_mul(other.units)?;
+        Some(Decimal::new(units, self.scale + other.scale))
+    }
+
+    /// Long division to at most `MAX_SCALE` places, rounding half away
+    /// from zero. `None` for a zero divisor or on overflow.
+    fn div(self, other: Decimal) -> Option<Decimal> {
+        if other.units == 0 {
+            return None;
+        }
+        let numerator = self.units.checked_mul(10i128.checked_pow(other.scale)?)?;
+        let denominator = other.units.checked_mul(10i128.checked_pow(self.scale)?)?;
+        let negative = (numerator < 0) != (denominator < 0);
+        let (numerator, denominator) = (numerator.checked_abs()?, denominator.checked_abs()?);
+
+        let mut units = numerator / denominator;
+        let mut remainder = numerator % denominator;
+        let mut scale = 0;
+        while remainder != 0 && scale < MAX_SCALE {
+            remainder = remainder.checked_mul(10)?;
+            units = units
+                .checked_mul(10)?
+                .checked_add(remainder / denominator)?;
+            remainder %= denominator;
+            scale += 1;
+        }
+        if remainder.checked_mul(2)? >= denominator {
+            units = units.checked_add(1)?;
+        }
+        Some(Decimal::new(if negative { -units } else { units }, scale))
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.units.unsigned_abs().to_string();
+        let sign = if self.units < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, whole, fraction)
+    }
+}
+
+/// Reads a number literal with a fractional part as a decimal. Integer
+/// literals, and ones with too many digits, are left to the usual rules.
+pub fn literal(lexeme: &str) -> Option<Value> {
+    let (whole, fraction) = lexeme.split_once('.')?;
+    let units: i128 = format!("{}{}", whole, fraction).parse().ok()?;
+    Some(normalize(Decimal::new(units, fraction.len() as u32)))
+}
+
+/// Applies a binary operator when a decimal is involved. Returns `None` to
+/// leave the operation to the usual number rules.
+pub fn binary(operator: &TokenType, left: &Value, right: &Value) -> Option<Value> {
+    if !matches!(left, Value::Decimal(_)) && !matches!(right, Value::Decimal(_)) {
+        return None;
+    }
+    match operator {
+        TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash => {
+            if let (Some(a), Some(b)) = (decimal(left), decimal(right)) {
+                let result = match operator {
+                    TokenType::Plus => a.add(b),
+                    TokenType::Minus => a.sub(b),
+                    TokenType::Star => a.mul(b),
+                    _ => a.div(b),
+                };
+                if let Some(result) = result {
+                    return Some(normalize(result));
+                }
+            }
+            // Overflow, division by zero, or a binary fraction
+            let (a, b) = (to_f64(left)?, to_f64(right)?);
+            Some(Value::Number(match operator {
+                TokenType::Plus => a + b,
+                TokenType::Minus => a - b,
+                TokenType::Star => a * b,
+                _ => a / b,
+            }))
+        }
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => {
+            let ordering = compare(left, right)?;
+            Some(Value::Boolean(match operator {
+                TokenType::Greater => ordering == Ordering::Greater,
+                TokenType::GreaterEqual => ordering != Ordering::Less,
+                TokenType::Less => ordering == Ordering::Less,
+                _ => ordering != Ordering::Greater,
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Negates a decimal.
+pub fn negate(value: &Decimal) -> Value {
+    normalize(Decimal::new(-value.units, value.scale))
+}
+
+/// Orders two numeric values, at least one of them a decimal.
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (decimal(left), decimal(right)) {
+        if let Some((a, b, _)) = a.align(b) {
+            return Some(a.cmp(&b));
+        }
+    }
+    to_f64(left)?.partial_cmp(&to_f64(right)?)
+}
+
+/// Turns results with no fractional part back into numbers.
+fn normalize(value: Decimal) -> Value {
+    if value.scale == 0 && (value.units as f64).abs() <= MAX_EXACT_INTEGER {
+        Value::Number(value.units as f64)
+    } else {
+        Value::Decimal(value)
+    }
+}
+
+/// The exact decimal a value holds, if any. Only integral numbers count,
+/// since a number's binary fraction has no exact decimal meant by the script.
+fn decimal(value: &Value) -> Option<Decimal> {
+    match value {
+        Value::Decimal(decimal) => Some(*decimal),
+        Value::Number(n) if n.fract() == 0.0 && n.abs() <= MAX_EXACT_INTEGER => {
+            Some(Decimal::new(*n as i128, 0))
+        }
+        _ => None,
+    }
+}
+
+fn to_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Decimal(decimal) => Some(decimal.to_f64()),
+        Value::Number(n) => Some(*n),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Lox;
+    use std::env;
+    use std::fs;
+
+    fn run(name: &str, decimal: bool, source: &str) -> String {
+        let output_file = env::temp_dir().join(format!("lox_decimal_{}.txt", name));
+        fs::write(&output_file, "").unwrap();
+        let mut lox = Lox::new(&output_file.to_string_lossy());
+        lox.set_decimal_literals(decimal);
+        lox.try_run(source).unwrap();
+        fs::read_to_string(&output_file).unwrap()
+    }
+
+    #[test]
+    fn decimal_literals_are_exact() {
+        let source = "print 0.1 + 0.2;
+                      print 0.1 + 0.2 == 0.3;
+                      print 1.10 * 3;
+                      print 0.5 + 0.5;
+                      print -0.05 - 1;
+                      print 0.1 / 3;
+                      print 2.50 / 0.5;
+                      print 0.1 < 0.2;";
+        assert_eq!(
+            run("exact", true, source),
+            "0.3\ntrue\n3.3\n1\n-1.05\n0.03333333333333333333\n5\ntrue\n"
+        );
+        // Without the mode, literals are binary floats as before
+        assert_eq!(
+            run("float", false, "print 0.1 + 0.2; print 0.1 + 0.2 == 0.3;"),
+            "0.30000000000000004\nfalse\n"
+        );
+    }
+
+    #[test]
+    fn numeric_natives_accept_decimals() {
+        let source = "print isFinite(0.5);
+                      print isNaN(0.5);
+                      print sqrt(0.25);
+                      print log(1.0);";
+        assert_eq!(run("natives", true, source), "true\nfalse\n0.5\n0\n");
+    }
+
+    #[test]
+    fn decimals_fall_back_to_floating_point() {
+        let source = "print 1.5 / 0;
+                      var big = 12345678901234567890.5;
+                      print big * big > 100000000000000000000000000000000000000;";
+        assert_eq!(run("fallback", true, source), "inf\ntrue\n");
+    }
+}
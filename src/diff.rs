@@ -0,0 +1,156 @@
+//! The comparison behind the `diff(a, b)` native, for assertion helpers
+//! that need to say how two values differ rather than just that they do.
+
+use crate::inspect::inspect;
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+use std::rc::Rc;
+
+/// One line for each place `a` and `b` differ, as `path: left != right`.
+/// The path leads from the top-level values through instance fields
+/// (`.name`) and the items of lists and maps (`[0]`, `["key"]`), and is left
+/// off when the top-level values themselves differ. A field or item only
+/// one side has shows `<missing>` on the other. Values are rendered as by
+/// `inspect`. Equal values give no lines.
+pub fn diff(interpreter: &Interpreter, a: &Value, b: &Value) -> Vec<String> {
+    let mut differ = Differ {
+        interpreter,
+        path: Vec::new(),
+        pairs: Vec::new(),
+        lines: Vec::new(),
+    };
+    differ.compare(Some(a), Some(b));
+    differ.lines
+}
+
+struct Differ<'a> {
+    interpreter: &'a Interpreter,
+    // The field and item names leading to the values being compared
+    path: Vec<String>,
+    // The pairs of objects being compared, outermost first, to spot cycles
+    pairs: Vec<(*const (), *const ())>,
+    lines: Vec<String>,
+}
+
+impl Differ<'_> {
+    fn compare(&mut self, a: Option<&Value>, b: Option<&Value>) {
+        let (Some(a), Some(b)) = (a, b) else {
+            self.report(a, b);
+            return;
+        };
+        match (a, b) {
+            (Value::Instance(left), Value::Instance(right)) if !Rc::ptr_eq(left, right) => {
+                // Every instance has its own copy of its class
+                let same_class = left.borrow().klass.borrow().to_string()
+                    == right.borrow().klass.borrow().to_string();
+                if !same_class {
+                    self.report(Some(a), Some(b));
+                    return;
+                }
+                let pair = (
+                    Rc::as_ptr(left) as *const (),
+                    Rc::as_ptr(right) as *const (),
+                );
+                let (left, right) = (left.borrow(), right.borrow());
+                let mut names: Vec<&String> =
+                    left.fields.keys().chain(right.fields.keys()).collect();
+                names.sort();
+                names.dedup();
+                let entries = names.into_iter().map(|name| {
+                    (
+                        format!(".{}", name),
+                        left.fields.get(name).cloned(),
+                        right.fields.get(name).cloned(),
+                    )
+                });
+                self.compare_entries(pair, entries.collect());
+            }
+            (Value::List(left), Value::List(right)) if !Rc::ptr_eq(left, right) => {
+                let (left_items, right_items) = (left.borrow(), right.borrow());
+                let entries = (0..left_items.len().max(right_items.len())).map(|index| {
+                    (
+                        format!("[{}]", index),
+                        left_items.get(index).cloned(),
+                        right_items.get(index).cloned(),
+                    )
+                });
+                let pair = (
+                    Rc::as_ptr(left) as *const (),
+                    Rc::as_ptr(right) as *const (),
+                );
+                self.compare_entries(pair, entries.collect());
+            }
+            (Value::NativeInstance(left), Value::NativeInstance(right))
+                if !Rc::ptr_eq(left, right) =>
+            {
+                let (left_entries, right_entries) =
+                    (left.borrow().entries(), right.borrow().entries());
+                let same_class = left.borrow().class_name() == right.borrow().class_name();
+                let (Some(left_entries), Some(right_entries), true) =
+                    (left_entries, right_entries, same_class)
+                else {
+                    self.report(Some(a), Some(b));
+                    return;
+                };
+                // In the left side's order, then the keys only the right has
+                let mut entries: Vec<(String, Option<Value>, Option<Value>)> = left_entries
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let other = right_entries
+                            .iter()
+                            .find(|(other, _)| *other == key)
+                            .map(|(_, value)| value.clone());
+                        (key, Some(value), other)
+                    })
+                    .collect();
+                for (key, value) in right_entries {
+                    if !entries.iter().any(|(other, _, _)| *other == key) {
+                        entries.push((key, None, Some(value)));
+                    }
+                }
+                let pair = (
+                    Rc::as_ptr(left) as *const (),
+                    Rc::as_ptr(right) as *const (),
+                );
+                self.compare_entries(pair, entries);
+            }
+            _ => {
+                if !Interpreter::is_equal(Some(a.clone()), Some(b.clone())) {
+                    self.report(Some(a), Some(b));
+                }
+            }
+        }
+    }
+
+    fn compare_entries(
+        &mut self,
+        pair: (*const (), *const ()),
+        entries: Vec<(String, Option<Value>, Option<Value>)>,
+    ) {
+        // The same two objects are already being compared further up
+        if self.pairs.contains(&pair) {
+            return;
+        }
+        self.pairs.push(pair);
+        for (key, left, right) in entries {
+            self.path.push(key);
+            self.compare(left.as_ref(), right.as_ref());
+            self.path.pop();
+        }
+        self.pairs.pop();
+    }
+
+    fn report(&mut self, a: Option<&Value>, b: Option<&Value>) {
+        let render = |value: Option<&Value>| match value {
+            Some(value) => inspect(self.interpreter, value),
+            None => "<missing>".to_string(),
+        };
+        let change = format!("{} != {}", render(a), render(b));
+        if self.path.is_empty() {
+            self.lines.push(change);
+        } else {
+            self.lines
+                .push(format!("{}: {}", self.path.concat(), change));
+        }
+    }
+}
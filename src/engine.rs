@@ -0,0 +1,195 @@
+//! Setting up an interpreter once and running many small scripts from it,
+//! for hosts that evaluate a script per request.
+//!
+//! An `Engine` holds everything that's the same from one run to the next:
+//! the natives, the settings, and any library loaded with `Engine::load`,
+//! already parsed and resolved. Each `Session` starts from a copy of the
+//! engine's globals, so what one script defines or assigns is never seen by
+//! another, while making a session costs no more than copying that table.
+//!
+//! ```
+//! use lox_interpreter::engine::Engine;
+//!
+//! let mut engine = Engine::new();
+//! engine.load("fun discount(total) { return total * 0.9; }").unwrap();
+//! for total in [100, 250] {
+//!     let mut session = engine.session("");
+//!     session.try_run(&format!("var price = discount({});", total)).unwrap();
+//! }
+//! ```
+
+use crate::callable::Callable;
+use crate::error::LoxError;
+use crate::limits::InterpreterLimits;
+use crate::lints::{Lint, Severity};
+use crate::logging::LogLevel;
+use crate::reporter::ErrorReporter;
+use crate::value::Value;
+use crate::Lox;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// One run's interpreter, made by `Engine::session`. It is a full `Lox`, so
+/// a session can go on to run more source, define more globals, or change
+/// its settings without affecting the engine.
+pub type Session = Lox;
+
+/// The shared setup sessions are made from.
+pub struct Engine {
+    lox: Lox,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine { lox: Lox::new("") }
+    }
+
+    /// Runs `source` on the engine, keeping the globals it defines for every
+    /// session made afterwards. Its `print` output goes to stdout. Instances
+    /// it creates are shared by the sessions, not copied.
+    pub fn load(&mut self, source: &str) -> Result<(), LoxError> {
+        self.lox.try_run(source)
+    }
+
+    /// A new session with its own copy of the engine's globals, printing to
+    /// `output_file`, or stdout if it is empty.
+    pub fn session(&self, output_file: &str) -> Session {
+        Lox {
+            interpreter: Rc::new(RefCell::new(
+                self.lox.interpreter.borrow().fork(output_file),
+            )),
+            warnings: self.lox.warnings,
+            lints: self.lox.lints,
+            flags: self.lox.flags.clone(),
+            macros: self.lox.macros.clone(),
+        }
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.lox.define(name, value);
+    }
+
+    pub fn define_native(&mut self, name: &str, function: Box<dyn Callable>) {
+        self.lox.define_native(name, function);
+    }
+
+    pub fn set_limits(&mut self, limits: InterpreterLimits) {
+        self.lox.set_limits(limits);
+    }
+
+    pub fn set_log_level(&mut self, level: LogLevel) {
+        self.lox.set_log_level(level);
+    }
+
+    pub fn set_decimal_literals(&mut self, enabled: bool) {
+        self.lox.set_decimal_literals(enabled);
+    }
+
+    pub fn set_checked_division(&mut self, enabled: bool) {
+        self.lox.set_checked_division(enabled);
+    }
+
+    pub fn set_checked_integers(&mut self, enabled: bool) {
+        self.lox.set_checked_integers(enabled);
+    }
+
+    pub fn set_strict_math(&mut self, enabled: bool) {
+        self.lox.set_strict_math(enabled);
+    }
+
+    pub fn set_deterministic(&mut self, enabled: bool) {
+        self.lox.set_deterministic(enabled);
+    }
+
+    pub fn set_warnings(&mut self, enabled: bool) {
+        self.lox.set_warnings(enabled);
+    }
+
+    pub fn set_lint_severity(&mut self, lint: Lint, severity: Severity) {
+        self.lox.set_lint_severity(lint, severity);
+    }
+
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.lox.set_strict(enabled);
+    }
+
+    pub fn set_flag(&mut self, name: &str, enabled: bool) {
+        self.lox.set_flag(name, enabled);
+    }
+
+    /// Sends the diagnostics of the engine and of every later session to
+    /// `reporter`.
+    pub fn set_reporter(&mut self, reporter: Rc<RefCell<dyn ErrorReporter>>) {
+        self.lox.set_reporter(reporter);
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::BufferReporter;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn sessions_start_from_the_engine_but_stay_apart() {
+        let buffer = Rc::new(RefCell::new(BufferReporter::new()));
+        let mut engine = Engine::new();
+        engine.set_reporter(buffer.clone());
+        engine
+            .load(
+                "var runs = 0;
+                 class Counter { init() { this.count = 0; } }
+                 fun bump(counter) { counter.count = counter.count + 1; return counter.count; }",
+            )
+            .unwrap();
+        engine.define("limit", Value::Number(2.0));
+
+        let output_file = env::temp_dir().join("lox_engine_sessions.txt");
+        let output_file = output_file.to_string_lossy();
+        for _ in 0..2 {
+            fs::write(&*output_file, "").unwrap();
+            let mut session = engine.session(&output_file);
+            session
+                .try_run("runs = runs + 1; var c = Counter(); bump(c); print bump(c) == limit;")
+                .unwrap();
+            session.try_run("print runs;").unwrap();
+            assert_eq!(fs::read_to_string(&*output_file).unwrap(), "true\n1\n");
+        }
+
+        // Neither session's globals reached the engine
+        let mut session = engine.session(&output_file);
+        assert!(session.try_run("print c;").is_err());
+        assert_eq!(
+            buffer.borrow_mut().take(),
+            ["Undefined variable 'c'. [E3002]\n[line 1:7]"]
+        );
+    }
+
+    #[test]
+    fn cached_global_slots_survive_reordering_and_redefinition() {
+        let mut engine = Engine::new();
+        engine.load("fun read() { return value; }").unwrap();
+
+        // The sessions share `read`, but put `value` in different slots
+        let output_file = env::temp_dir().join("lox_engine_slots.txt");
+        let output_file = output_file.to_string_lossy();
+        fs::write(&*output_file, "").unwrap();
+        let mut first = engine.session(&output_file);
+        first
+            .try_run("var other = 1; var value = 2; print read();")
+            .unwrap();
+        let mut second = engine.session(&output_file);
+        second
+            .try_run("var value = 3; print read(); var value = 4; print read();")
+            .unwrap();
+        first.try_run("value = 5; print read();").unwrap();
+        assert_eq!(fs::read_to_string(&*output_file).unwrap(), "2\n3\n4\n5\n");
+    }
+}
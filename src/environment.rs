@@ -1,4 +1,6 @@
+use crate::error::{ErrorCode, LoxError};
 use crate::runtime_error::RuntimeError;
+use crate::suggest;
 use crate::token::Token;
 use crate::value::Value;
 
@@ -9,69 +11,158 @@ use std::rc::Rc;
 #[derive(Debug, Clone)]
 pub struct Environment {
     pub enclosing: Option<Rc<RefCell<Environment>>>,
-    pub values: HashMap<String, Option<Value>>,
+    pub values: Values,
+}
+
+/// The variables of one environment, in the order they were first defined.
+/// A variable keeps its slot when it is assigned or defined again, so a slot
+/// found once leads back to it without hashing the name.
+#[derive(Debug, Clone, Default)]
+pub struct Values {
+    slots: Vec<(String, Option<Value>)>,
+    index: HashMap<String, usize>,
+}
+
+impl Values {
+    pub fn get(&self, name: &str) -> Option<&Option<Value>> {
+        self.index.get(name).map(|&slot| &self.slots[slot].1)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.index.contains_key(name)
+    }
+
+    pub fn insert(&mut self, name: String, value: Option<Value>) {
+        match self.index.get(&name) {
+            Some(&slot) => self.slots[slot].1 = value,
+            None => {
+                self.index.insert(name.clone(), self.slots.len());
+                self.slots.push((name, value));
+            }
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.slots.iter().map(|(name, _)| name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Option<Value>)> {
+        self.slots.iter().map(|(name, value)| (name, value))
+    }
+
+    /// The slot `name` is defined in.
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        self.index.get(name).copied()
+    }
+
+    /// The variable in `slot`, if that slot holds `name`. Checking the name
+    /// lets a slot cached for one environment be tried against another.
+    pub fn get_slot(&self, slot: usize, name: &str) -> Option<&Option<Value>> {
+        match self.slots.get(slot) {
+            Some((defined, value)) if defined == name => Some(value),
+            _ => None,
+        }
+    }
 }
 
 impl Environment {
     pub fn new(enclosing: Option<Rc<RefCell<Environment>>>) -> Environment {
         Environment {
             enclosing,
-            values: HashMap::new(),
+            values: Values::default(),
         }
     }
 
-    pub fn get(&self, name: &Token) -> Value {
+    pub fn get(&self, name: &Token) -> Result<Value, LoxError> {
         if let Some(value) = self.values.get(&name.lexeme) {
             let v = value.clone();
             match v {
-                Some(val) => return val,
-                None => return Value::Nil(),
+                Some(val) => return Ok(val),
+                None => return Ok(Value::Nil()),
             }
         }
 
         if let Some(enclosing_env) = self.enclosing.as_ref() {
             return enclosing_env.borrow_mut().get(name);
         }
-        let error = RuntimeError::new(name.clone(), "Variable not found");
-        crate::runtime_error(error); // Return None or handle type error appropriately
+        Err(self.undefined(name))
+    }
 
-        return Value::String("".to_string());
+    /// The error for reading or assigning `name` where it isn't defined,
+    /// suggesting the closest name visible from this environment.
+    pub fn undefined(&self, name: &Token) -> LoxError {
+        let names = self.names();
+        let message = suggest::did_you_mean(
+            format!("Undefined variable '{}'.", name.lexeme),
+            &name.lexeme,
+            names.iter().map(String::as_str),
+        );
+        LoxError::Runtime(RuntimeError::new(
+            name.clone(),
+            ErrorCode::UndefinedVariable,
+            &message,
+        ))
     }
 
-    pub fn get_at(&self, _distance: usize, name: &Token) -> Value {
-        // self.ancestor(distance).borrow_mut().get(name)
-        self.get(name)
+    /// Every name defined here or in an enclosing environment.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.values.keys().cloned().collect();
+        if let Some(enclosing) = &self.enclosing {
+            names.extend(enclosing.borrow().names());
+        }
+        names
+    }
+
+    /// Reads a variable the resolver found `distance` scopes out.
+    pub fn get_at(&self, distance: usize, name: &Token) -> Value {
+        let value = if distance == 0 {
+            self.values.get(&name.lexeme).cloned()
+        } else {
+            self.ancestor(distance)
+                .borrow()
+                .values
+                .get(&name.lexeme)
+                .cloned()
+        };
+        value.flatten().unwrap_or(Value::Nil())
     }
 
+    /// The environment `distance` scopes out, which must be at least one.
     pub fn ancestor(&self, distance: usize) -> Rc<RefCell<Environment>> {
-        let mut environment = Rc::new(RefCell::new(self.clone()));
-        for _ in 0..distance {
-            let next_environment = environment.borrow_mut().enclosing.clone().unwrap();
+        let mut environment = self.enclosing.clone().expect("Resolved scope is missing.");
+        for _ in 1..distance {
+            let next_environment = environment
+                .borrow()
+                .enclosing
+                .clone()
+                .expect("Resolved scope is missing.");
             environment = next_environment;
         }
         environment
     }
 
-    pub fn assign(&mut self, name: Token, value: Value) {
+    pub fn assign(&mut self, name: Token, value: Value) -> Result<(), LoxError> {
         if self.values.contains_key(&name.lexeme) {
             self.values.insert(name.lexeme.clone(), Some(value.clone()));
+            Ok(())
         } else if let Some(ref enclosing_env) = self.enclosing {
             // Recursively assign in the enclosing environment
-            enclosing_env.borrow_mut().assign(name, value.clone());
+            enclosing_env.borrow_mut().assign(name, value.clone())
         } else {
             // Throw an error if the variable is not found
-            let error = RuntimeError::new(
-                name.clone(),
-                &format!("Undefined variable '{}'", name.lexeme),
-            );
-            crate::runtime_error(error);
-            panic!("Undefined variable '{}'", name.lexeme);
+            Err(self.undefined(&name))
         }
     }
 
-    pub fn assign_at(&mut self, _distance: usize, name: Token, value: Value) {
-        //self.ancestor(distance).borrow_mut().assign(name, value)
-        self.assign(name, value)
+    pub fn assign_at(&mut self, distance: usize, name: Token, value: Value) {
+        if distance == 0 {
+            self.values.insert(name.lexeme, Some(value));
+        } else {
+            self.ancestor(distance)
+                .borrow_mut()
+                .values
+                .insert(name.lexeme, Some(value));
+        }
     }
 
     pub fn define(&mut self, name: String, value: Option<Value>) {
@@ -0,0 +1,544 @@
+use crate::parser::ParseError;
+use crate::resolver::ResolveError;
+use crate::runtime_error::RuntimeError;
+use crate::scanner::ScanError;
+use std::fmt;
+
+/// A stable code for each kind of error, printed after the message as in
+/// `[E2004]` so that tests and tools can match on it rather than on the
+/// message text. The first digit is the phase that found the error: 1 for
+/// the scanner, 2 the parser, 3 the interpreter, and 4 the resolver. A code
+/// is never reused for a different kind of error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    // Scanner errors
+    /// A string literal with no closing quote.
+    UnterminatedString,
+    /// A character that can't start any token.
+    UnexpectedCharacter,
+    /// A heredoc with no line holding its closing tag.
+    UnterminatedHeredoc,
+    /// `<<<` not followed by a tag and the end of the line.
+    InvalidHeredoc,
+    /// A `/*` comment with no `*/` to close it.
+    UnterminatedBlockComment,
+    /// An `#if` with no `#end` to close it.
+    UnterminatedPragma,
+    /// A malformed pragma, or an `#else` or `#end` with no `#if`.
+    InvalidPragma,
+    /// A `\` in a string that doesn't start a known escape.
+    InvalidEscape,
+
+    // Parser errors
+    /// An expression was expected.
+    ExpectExpression,
+    /// A `)` was expected, as after arguments or a condition.
+    ExpectRightParen,
+    /// A name was expected, as after `var`, `fun`, or `.`.
+    ExpectName,
+    /// A `;` was expected at the end of a statement.
+    ExpectSemicolon,
+    /// A `(` was expected, as after `if` or `while`.
+    ExpectLeftParen,
+    /// A `{` was expected before a body.
+    ExpectLeftBrace,
+    /// A `}` was expected at the end of a block or class body.
+    ExpectRightBrace,
+    /// Some other token was expected, such as the `=` of a `using` resource.
+    ExpectToken,
+    /// The left side of `=` isn't a variable, property, or list item.
+    InvalidAssignmentTarget,
+    /// A call with more than 255 arguments.
+    TooManyArguments,
+    /// A function with more than 255 parameters.
+    TooManyParameters,
+    /// A format string given the wrong number of values.
+    FormatArgumentCount,
+    /// A macro used with the wrong number of arguments.
+    MacroArgumentCount,
+    /// A macro used where a value is needed, rather than as a statement.
+    MacroInExpression,
+
+    // Runtime errors
+    /// A unary operator applied to something other than a number.
+    OperandMustBeNumber,
+    /// A variable read or assigned where it isn't defined.
+    UndefinedVariable,
+    /// A property or method an instance or class doesn't have.
+    UndefinedProperty,
+    /// A comparison or arithmetic operator applied to non-numbers.
+    OperandsMustBeNumbers,
+    /// `+` applied to anything but two numbers or two strings.
+    OperandsMustBeNumbersOrStrings,
+    /// A call to something that isn't a function or class.
+    NotCallable,
+    /// A call with the wrong number of arguments.
+    WrongArity,
+    /// Calls nested past the call depth limit.
+    StackOverflow,
+    /// A property read or set on something that isn't an instance.
+    NotAnInstance,
+    /// A class inheriting from something that isn't a class.
+    SuperclassMustBeClass,
+    /// `super` used where no method is running.
+    SuperOutsideMethod,
+    /// Division by zero, with checked division on.
+    DivisionByZero,
+    /// Integer arithmetic out of range, with checked integers on.
+    IntegerOverflow,
+    /// A `using` resource that isn't an instance with a `close()` method.
+    InvalidResource,
+    /// An assignment to a property a native instance doesn't allow to be set.
+    ReadOnlyProperty,
+    /// An `expectEqual` whose values differ.
+    ExpectationFailed,
+    /// `value[index]` on something that isn't a list.
+    NotAList,
+    /// A list index that isn't a whole number.
+    InvalidIndex,
+    /// A list index past either end of the list, or between two items.
+    IndexOutOfRange,
+    /// A call to a nondeterministic native in deterministic mode.
+    NondeterministicCall,
+    /// Arithmetic on finite numbers that gives NaN or an infinity, with
+    /// strict math on.
+    NonFiniteResult,
+    /// The right operand of `is` that isn't a class.
+    IsNotClass,
+    /// A timer delay that isn't a non-negative number of milliseconds.
+    InvalidDelay,
+    /// A value sent to a worker or channel that can't be copied across
+    /// threads.
+    NotSendable,
+    /// A `spawn` of anything but a function declared at the top level.
+    NotSpawnable,
+    /// A `send` or `receive` on something that isn't a channel.
+    NotAChannel,
+    /// A `lock` or `unlock` on something that isn't a mutex.
+    NotAMutex,
+    /// A `lock` of a mutex the thread already holds.
+    MutexAlreadyLocked,
+    /// An `unlock` of a mutex the thread doesn't hold.
+    MutexNotLocked,
+    /// An `atomicAdd` on something that isn't an atomic.
+    NotAnAtomic,
+    /// An `implements` naming something that isn't an interface.
+    NotAnInterface,
+    /// A class missing a method one of its interfaces requires, or
+    /// defining it with the wrong number of parameters.
+    InterfaceNotImplemented,
+    /// A native needing filesystem, network, or process access the
+    /// interpreter's limits don't allow.
+    PermissionDenied,
+    /// A `receive` on an empty channel that was given to a worker that then
+    /// stopped with an error.
+    WorkerFailed,
+    /// A `format` whose pattern isn't a string.
+    InvalidFormatString,
+    /// An `exit` status that isn't a whole number from 0 to 255.
+    InvalidExitStatus,
+
+    // Resolver errors
+    /// A local variable read in its own initializer.
+    ReadInOwnInitializer,
+    /// `super` used outside of a class.
+    SuperOutsideClass,
+    /// `super` used in a class with no superclass.
+    SuperWithoutSuperclass,
+    /// `this` used outside of a class.
+    ThisOutsideClass,
+    /// A class that names itself as its superclass.
+    InheritsFromItself,
+    /// `defer` used outside of a function.
+    DeferOutsideFunction,
+    /// `return` used outside of a function.
+    ReturnAtTopLevel,
+    /// `return` with a value in an `init` method.
+    ReturnValueFromInitializer,
+    /// `super(...)` used anywhere but directly in an `init` method.
+    SuperCallOutsideInitializer,
+    /// A `return` in a deferred statement, which runs after the function
+    /// has already returned.
+    ReturnInDefer,
+    /// A second local with the same name in one scope.
+    AlreadyDeclared,
+    /// A local variable that is never read. A warning unless `--strict`.
+    UnusedLocal,
+    /// A local shadowing one in an enclosing scope. A warning unless `--strict`.
+    Shadowing,
+    /// An assignment to a variable declared nowhere. A warning unless `--strict`.
+    UndeclaredAssignment,
+}
+
+impl ErrorCode {
+    /// The code's number, as in 2004 for `E2004`.
+    pub fn number(self) -> u16 {
+        match self {
+            ErrorCode::UnterminatedString => 1001,
+            ErrorCode::UnexpectedCharacter => 1002,
+            ErrorCode::UnterminatedHeredoc => 1003,
+            ErrorCode::InvalidHeredoc => 1004,
+            ErrorCode::UnterminatedBlockComment => 1005,
+            ErrorCode::UnterminatedPragma => 1006,
+            ErrorCode::InvalidPragma => 1007,
+            ErrorCode::InvalidEscape => 1008,
+            ErrorCode::ExpectExpression => 2001,
+            ErrorCode::ExpectRightParen => 2002,
+            ErrorCode::ExpectName => 2003,
+            ErrorCode::ExpectSemicolon => 2004,
+            ErrorCode::ExpectLeftParen => 2005,
+            ErrorCode::ExpectLeftBrace => 2006,
+            ErrorCode::ExpectRightBrace => 2007,
+            ErrorCode::ExpectToken => 2008,
+            ErrorCode::InvalidAssignmentTarget => 2009,
+            ErrorCode::TooManyArguments => 2010,
+            ErrorCode::TooManyParameters => 2011,
+            ErrorCode::FormatArgumentCount => 2012,
+            ErrorCode::MacroArgumentCount => 2013,
+            ErrorCode::MacroInExpression => 2014,
+            ErrorCode::OperandMustBeNumber => 3001,
+            ErrorCode::UndefinedVariable => 3002,
+            ErrorCode::UndefinedProperty => 3003,
+            ErrorCode::OperandsMustBeNumbers => 3004,
+            ErrorCode::OperandsMustBeNumbersOrStrings => 3005,
+            ErrorCode::NotCallable => 3006,
+            ErrorCode::WrongArity => 3007,
+            ErrorCode::StackOverflow => 3008,
+            ErrorCode::NotAnInstance => 3009,
+            ErrorCode::SuperclassMustBeClass => 3010,
+            ErrorCode::SuperOutsideMethod => 3011,
+            ErrorCode::DivisionByZero => 3012,
+            ErrorCode::IntegerOverflow => 3013,
+            ErrorCode::InvalidResource => 3014,
+            ErrorCode::ReadOnlyProperty => 3015,
+            ErrorCode::ExpectationFailed => 3016,
+            ErrorCode::NotAList => 3017,
+            ErrorCode::InvalidIndex => 3018,
+            ErrorCode::IndexOutOfRange => 3019,
+            ErrorCode::NondeterministicCall => 3020,
+            ErrorCode::NonFiniteResult => 3021,
+            ErrorCode::IsNotClass => 3022,
+            ErrorCode::InvalidDelay => 3023,
+            ErrorCode::NotSendable => 3024,
+            ErrorCode::NotSpawnable => 3025,
+            ErrorCode::NotAChannel => 3026,
+            ErrorCode::NotAMutex => 3027,
+            ErrorCode::MutexAlreadyLocked => 3028,
+            ErrorCode::MutexNotLocked => 3029,
+            ErrorCode::NotAnAtomic => 3030,
+            ErrorCode::NotAnInterface => 3031,
+            ErrorCode::InterfaceNotImplemented => 3032,
+            ErrorCode::PermissionDenied => 3033,
+            ErrorCode::WorkerFailed => 3034,
+            ErrorCode::InvalidFormatString => 3035,
+            ErrorCode::InvalidExitStatus => 3036,
+            ErrorCode::ReadInOwnInitializer => 4001,
+            ErrorCode::SuperOutsideClass => 4002,
+            ErrorCode::SuperWithoutSuperclass => 4003,
+            ErrorCode::ThisOutsideClass => 4004,
+            ErrorCode::InheritsFromItself => 4005,
+            ErrorCode::DeferOutsideFunction => 4006,
+            ErrorCode::ReturnAtTopLevel => 4007,
+            ErrorCode::ReturnValueFromInitializer => 4008,
+            ErrorCode::AlreadyDeclared => 4009,
+            ErrorCode::SuperCallOutsideInitializer => 4010,
+            ErrorCode::ReturnInDefer => 4011,
+            ErrorCode::UnusedLocal => 4101,
+            ErrorCode::Shadowing => 4102,
+            ErrorCode::UndeclaredAssignment => 4103,
+        }
+    }
+
+    /// The name of the code, as in `ExpectSemicolon`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCode::UnterminatedString => "UnterminatedString",
+            ErrorCode::UnexpectedCharacter => "UnexpectedCharacter",
+            ErrorCode::UnterminatedHeredoc => "UnterminatedHeredoc",
+            ErrorCode::InvalidHeredoc => "InvalidHeredoc",
+            ErrorCode::UnterminatedBlockComment => "UnterminatedBlockComment",
+            ErrorCode::UnterminatedPragma => "UnterminatedPragma",
+            ErrorCode::InvalidPragma => "InvalidPragma",
+            ErrorCode::InvalidEscape => "InvalidEscape",
+            ErrorCode::ExpectExpression => "ExpectExpression",
+            ErrorCode::ExpectRightParen => "ExpectRightParen",
+            ErrorCode::ExpectName => "ExpectName",
+            ErrorCode::ExpectSemicolon => "ExpectSemicolon",
+            ErrorCode::ExpectLeftParen => "ExpectLeftParen",
+            ErrorCode::ExpectLeftBrace => "ExpectLeftBrace",
+            ErrorCode::ExpectRightBrace => "ExpectRightBrace",
+            ErrorCode::ExpectToken => "ExpectToken",
+            ErrorCode::InvalidAssignmentTarget => "InvalidAssignmentTarget",
+            ErrorCode::TooManyArguments => "TooManyArguments",
+            ErrorCode::TooManyParameters => "TooManyParameters",
+            ErrorCode::FormatArgumentCount => "FormatArgumentCount",
+            ErrorCode::MacroArgumentCount => "MacroArgumentCount",
+            ErrorCode::MacroInExpression => "MacroInExpression",
+            ErrorCode::OperandMustBeNumber => "OperandMustBeNumber",
+            ErrorCode::UndefinedVariable => "UndefinedVariable",
+            ErrorCode::UndefinedProperty => "UndefinedProperty",
+            ErrorCode::OperandsMustBeNumbers => "OperandsMustBeNumbers",
+            ErrorCode::OperandsMustBeNumbersOrStrings => "OperandsMustBeNumbersOrStrings",
+            ErrorCode::NotCallable => "NotCallable",
+            ErrorCode::WrongArity => "WrongArity",
+            ErrorCode::StackOverflow => "StackOverflow",
+            ErrorCode::NotAnInstance => "NotAnInstance",
+            ErrorCode::SuperclassMustBeClass => "SuperclassMustBeClass",
+            ErrorCode::SuperOutsideMethod => "SuperOutsideMethod",
+            ErrorCode::DivisionByZero => "DivisionByZero",
+            ErrorCode::IntegerOverflow => "IntegerOverflow",
+            ErrorCode::InvalidResource => "InvalidResource",
+            ErrorCode::ReadOnlyProperty => "ReadOnlyProperty",
+            ErrorCode::ExpectationFailed => "ExpectationFailed",
+            ErrorCode::NotAList => "NotAList",
+            ErrorCode::InvalidIndex => "InvalidIndex",
+            ErrorCode::IndexOutOfRange => "IndexOutOfRange",
+            ErrorCode::NondeterministicCall => "NondeterministicCall",
+            ErrorCode::NonFiniteResult => "NonFiniteResult",
+            ErrorCode::IsNotClass => "IsNotClass",
+            ErrorCode::InvalidDelay => "InvalidDelay",
+            ErrorCode::NotSendable => "NotSendable",
+            ErrorCode::NotSpawnable => "NotSpawnable",
+            ErrorCode::NotAChannel => "NotAChannel",
+            ErrorCode::NotAMutex => "NotAMutex",
+            ErrorCode::MutexAlreadyLocked => "MutexAlreadyLocked",
+            ErrorCode::MutexNotLocked => "MutexNotLocked",
+            ErrorCode::NotAnAtomic => "NotAnAtomic",
+            ErrorCode::NotAnInterface => "NotAnInterface",
+            ErrorCode::InterfaceNotImplemented => "InterfaceNotImplemented",
+            ErrorCode::PermissionDenied => "PermissionDenied",
+            ErrorCode::WorkerFailed => "WorkerFailed",
+            ErrorCode::InvalidFormatString => "InvalidFormatString",
+            ErrorCode::InvalidExitStatus => "InvalidExitStatus",
+            ErrorCode::ReadInOwnInitializer => "ReadInOwnInitializer",
+            ErrorCode::SuperOutsideClass => "SuperOutsideClass",
+            ErrorCode::SuperWithoutSuperclass => "SuperWithoutSuperclass",
+            ErrorCode::ThisOutsideClass => "ThisOutsideClass",
+            ErrorCode::InheritsFromItself => "InheritsFromItself",
+            ErrorCode::DeferOutsideFunction => "DeferOutsideFunction",
+            ErrorCode::ReturnAtTopLevel => "ReturnAtTopLevel",
+            ErrorCode::ReturnValueFromInitializer => "ReturnValueFromInitializer",
+            ErrorCode::SuperCallOutsideInitializer => "SuperCallOutsideInitializer",
+            ErrorCode::ReturnInDefer => "ReturnInDefer",
+            ErrorCode::AlreadyDeclared => "AlreadyDeclared",
+            ErrorCode::UnusedLocal => "UnusedLocal",
+            ErrorCode::Shadowing => "Shadowing",
+            ErrorCode::UndeclaredAssignment => "UndeclaredAssignment",
+        }
+    }
+}
+
+/// Writes the code as in `E2004`.
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "E{}", self.number())
+    }
+}
+
+/// Why a call to `Lox::try_run` stopped early. Every phase returns this,
+/// and `Lox::try_run` prints it once it reaches the top.
+#[derive(Debug)]
+pub enum LoxError {
+    /// Characters the scanner could not turn into tokens, in order.
+    Scan(Vec<ScanError>),
+    /// Syntax errors, in order. The parser recovers after each one.
+    Parse(Vec<ParseError>),
+    /// A program that parses but breaks scoping rules, with every rule
+    /// broken, in order.
+    Resolve(Vec<ResolveError>),
+    Runtime(RuntimeError),
+    /// The host tripped the interpreter's `CancellationToken`.
+    Cancelled,
+    /// The script went over one of its `InterpreterLimits`.
+    LimitExceeded(String),
+    /// The script called `exit(status)`.
+    Exit(i32),
+}
+
+impl LoxError {
+    /// Whether the program was rejected before it started running.
+    pub fn is_static(&self) -> bool {
+        matches!(
+            self,
+            LoxError::Scan(_) | LoxError::Parse(_) | LoxError::Resolve(_)
+        )
+    }
+
+    /// The code of each error, in the order they're reported. Errors that
+    /// aren't about the script itself, such as cancellation, have none.
+    pub fn codes(&self) -> Vec<ErrorCode> {
+        match self {
+            LoxError::Scan(errors) => errors.iter().map(|error| error.code).collect(),
+            LoxError::Parse(errors) => errors.iter().map(|error| error.code).collect(),
+            LoxError::Resolve(errors) => errors.iter().map(|error| error.code).collect(),
+            LoxError::Runtime(error) => vec![error.code],
+            LoxError::Cancelled | LoxError::LimitExceeded(_) | LoxError::Exit(_) => vec![],
+        }
+    }
+
+    /// The process exit status for a run that stopped with this error,
+    /// following sysexits.h as jlox does: 65 (EX_DATAERR) for static errors
+    /// and 70 (EX_SOFTWARE) for errors while running.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LoxError::Exit(status) => *status,
+            error if error.is_static() => 65,
+            _ => 70,
+        }
+    }
+}
+
+/// Writes each error on its own line.
+fn write_lines<T: fmt::Display>(f: &mut fmt::Formatter<'_>, errors: &[T]) -> fmt::Result {
+    for (index, error) in errors.iter().enumerate() {
+        if index > 0 {
+            writeln!(f)?;
+        }
+        write!(f, "{}", error)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoxError::Scan(errors) => write_lines(f, errors),
+            LoxError::Parse(errors) => write_lines(f, errors),
+            LoxError::Resolve(errors) => write_lines(f, errors),
+            LoxError::Runtime(error) => {
+                write!(
+                    f,
+                    "{} [{}]\n[line {}]",
+                    error.message,
+                    error.code,
+                    error.token.position()
+                )
+            }
+            LoxError::Cancelled => write!(f, "Execution cancelled."),
+            LoxError::LimitExceeded(message) => write!(f, "{}", message),
+            LoxError::Exit(status) => write!(f, "Exited with status {}.", status),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lox;
+
+    #[test]
+    fn errors_are_returned_and_the_interpreter_stays_usable() {
+        let mut lox = Lox::new("");
+        assert!(lox.try_run("var a = 1;").is_ok());
+
+        match lox.try_run("var;") {
+            Err(LoxError::Parse(errors)) => {
+                assert_eq!(
+                    errors[0].to_string(),
+                    "[line 1:4] Error at ';': Expect variable name. [E2003]"
+                )
+            }
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+        match lox.try_run("fun f() { return a + nil; }\nf();") {
+            Err(LoxError::Runtime(error)) => {
+                assert_eq!(
+                    error.message,
+                    "Operands must be two numbers or two strings."
+                );
+                assert_eq!(error.token.line, 1);
+            }
+            other => panic!("Expected a runtime error, got {:?}", other),
+        }
+
+        assert!(lox.try_run("a = a + 1;").is_ok());
+        assert!(lox.try_run("f;").is_ok());
+    }
+
+    #[test]
+    fn every_syntax_error_in_a_run_is_reported() {
+        match Lox::new("").try_run("var;\nprint 1;\nprint (2;\nvar b = 3") {
+            Err(error @ LoxError::Parse(_)) => assert_eq!(
+                error.to_string(),
+                "[line 1:4] Error at ';': Expect variable name. [E2003]\n\
+                 [line 3:9] Error at ';': Expect ')' after expression. [E2002]\n\
+                 [line 4:10] Error at end: Expect ';' after variable declaration. [E2004]"
+            ),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn resolution_carries_on_past_errors() {
+        let source = "return 1;\n\
+                      class A < A {}\n\
+                      fun f() { var a = 1; var a = a; }\n\
+                      print this;";
+        match Lox::new("").try_run(source) {
+            Err(error @ LoxError::Resolve(_)) => assert_eq!(
+                error.to_string(),
+                "[line 1:1] Error at 'return': Can't return from top-level code. [E4007]\n\
+                 [line 2:11] Error at 'A': A class can't inherit from itself. [E4005]\n\
+                 [line 3:26] Error at 'a': Already a variable with this name in this scope. [E4009]\n\
+                 [line 4:7] Error at 'this': Can't use 'this' outside of a class. [E4004]"
+            ),
+            other => panic!("Expected a resolve error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn each_phase_has_its_own_variant() {
+        let mut lox = Lox::new("");
+        assert!(matches!(lox.try_run("@"), Err(LoxError::Scan(_))));
+        assert!(matches!(lox.try_run("print;"), Err(LoxError::Parse(_))));
+        match lox.try_run("return 1;") {
+            Err(error @ LoxError::Resolve(_)) => {
+                assert!(error.is_static());
+                assert_eq!(
+                    error.to_string(),
+                    "[line 1:1] Error at 'return': Can't return from top-level code. [E4007]"
+                );
+            }
+            other => panic!("Expected a resolve error, got {:?}", other),
+        }
+        match lox.try_run("nil();") {
+            Err(error @ LoxError::Runtime(_)) => assert!(!error.is_static()),
+            other => panic!("Expected a runtime error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn each_error_has_a_code() {
+        let mut lox = Lox::new("");
+        let mut codes = |source: &str| lox.try_run(source).unwrap_err().codes();
+        assert_eq!(codes("\"open"), [ErrorCode::UnterminatedString]);
+        assert_eq!(
+            codes("var;\nprint 1"),
+            [ErrorCode::ExpectName, ErrorCode::ExpectSemicolon]
+        );
+        assert_eq!(codes("return 1;"), [ErrorCode::ReturnAtTopLevel]);
+        assert_eq!(codes("print missing;"), [ErrorCode::UndefinedVariable]);
+        assert_eq!(codes("exit(2);"), []);
+
+        assert_eq!(ErrorCode::ExpectSemicolon.to_string(), "E2004");
+        assert_eq!(ErrorCode::UndefinedVariable.name(), "UndefinedVariable");
+        assert_eq!(
+            lox.try_run("print -nil;").unwrap_err().to_string(),
+            "Operand must be a number. [E3001]\n[line 1:7]"
+        );
+    }
+
+    #[test]
+    fn run_returns_the_exit_status() {
+        let mut lox = Lox::new("");
+        assert_eq!(lox.run("var a = 1;"), 0);
+        assert_eq!(lox.run("var;"), 65);
+        assert_eq!(lox.run("a();"), 70);
+        assert_eq!(lox.run("fun quit() { exit(3); } quit(); a = 2;"), 3);
+        // The script stopped before the assignment
+        assert_eq!(lox.run("exit(a);"), 1);
+        // A status a process can't exit with is a runtime error
+        assert_eq!(lox.run("exit(\"no\");"), 70);
+        assert_eq!(lox.run("exit(0.5);"), 70);
+    }
+}
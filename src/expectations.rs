@@ -0,0 +1,565 @@
+//! Expectations written into `.lox` test scripts as comments, following the
+//! convention of the Crafting Interpreters test suite. Shared by the test
+//! harness in main.rs and the `lox-conformance` runner.
+
+use crate::error::LoxError;
+use crate::reporter::BufferReporter;
+use crate::Lox;
+use std::cell::RefCell;
+use std::fs;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::rc::Rc;
+
+/// What a test script expects to print and how it expects to stop.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Expectations {
+    /// Lines from `// expect: ...`, in order.
+    pub output: Vec<String>,
+    /// From `// expect runtime error: ...`, as reported with its line.
+    pub runtime_error: Option<String>,
+    /// From `// Error ...` and `// [line N] Error ...`, with the line filled in.
+    pub static_errors: Vec<String>,
+    /// From `// tags: ...`, for runners to filter on.
+    pub tags: Vec<String>,
+}
+
+impl Expectations {
+    pub fn parse(source: &str) -> Self {
+        let mut expectations = Expectations::default();
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            // Markers may follow other comment text, as in "// // expect: ..."
+            if let Some(index) = line.find("// expect:") {
+                let value = line[index + "// expect:".len()..].trim();
+                // A literal "\n" stands for a line break in the printed value
+                for value in value.split("\\n") {
+                    expectations.output.push(value.to_string());
+                }
+            } else if let Some(index) = line.find("// expect runtime error:") {
+                let message = line[index + "// expect runtime error:".len()..].trim();
+                expectations.runtime_error = Some(format!("{}\n[line {}]", message, line_number));
+            } else if let Some(index) = line.find("// tags:") {
+                let tags = line[index + "// tags:".len()..].split(',');
+                expectations.tags.extend(
+                    tags.map(|tag| tag.trim().to_string())
+                        .filter(|tag| !tag.is_empty()),
+                );
+            } else if let Some(index) = line.find("// [line ") {
+                expectations
+                    .static_errors
+                    .push(line[index + 3..].trim().to_string());
+            } else if let Some(index) = line.find("// Error") {
+                let message = line[index + 3..].trim();
+                expectations
+                    .static_errors
+                    .push(format!("[line {}] {}", line_number, message));
+            }
+        }
+        expectations
+    }
+
+    /// The diagnostics the script should stop with: every static error, one
+    /// per line, or else the runtime error.
+    pub fn error(&self) -> Option<String> {
+        if self.static_errors.is_empty() {
+            self.runtime_error.clone()
+        } else {
+            Some(self.static_errors.join("\n"))
+        }
+    }
+
+    /// Compares how a run ended and what it printed against the expectations,
+    /// describing the first difference.
+    pub fn check(&self, result: &Result<(), LoxError>, output: &[String]) -> Result<(), String> {
+        match (result, self.error()) {
+            (Ok(()), Some(expected)) => {
+                return Err(format!(
+                    "expected an error but the script ran to completion.\nExpected: '{}'",
+                    expected
+                ));
+            }
+            (Err(error), None) => {
+                return Err(format!(
+                    "the script raised an unexpected error.\nActual: '{}'",
+                    error
+                ));
+            }
+            (Err(error), Some(expected)) if as_reference(&error.to_string()) != expected => {
+                return Err(format!(
+                    "actual and expected errors differ.\nActual: '{}'\nExpected: '{}'",
+                    error, expected
+                ));
+            }
+            _ => {}
+        }
+
+        if output.len() != self.output.len() {
+            return Err(format!(
+                "printed and expected output have different numbers of lines.\nActual: {}\nExpected: {}",
+                output.len(),
+                self.output.len()
+            ));
+        }
+
+        // Compare the output line by line
+        for (output_line, expected_line) in output.iter().zip(self.output.iter()) {
+            if output_line != expected_line {
+                return Err(format!(
+                    "actual and expected values differ.\nActual: '{}'\nExpected: '{}'",
+                    output_line, expected_line
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `diagnostic` as the reference implementation prints it, without columns
+/// or error codes, which is also how test scripts spell out errors.
+pub fn as_reference(diagnostic: &str) -> String {
+    without_codes(&without_columns(diagnostic))
+}
+
+/// Drops each ` [E1234]` error code from `diagnostic`.
+pub fn without_codes(diagnostic: &str) -> String {
+    let mut result = String::with_capacity(diagnostic.len());
+    let mut rest = diagnostic;
+    while let Some(index) = rest.find(" [E") {
+        let after = &rest[index + " [E".len()..];
+        let digits = after
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(after.len());
+        if digits == 4 && after[digits..].starts_with(']') {
+            result.push_str(&rest[..index]);
+            rest = &after[digits + 1..];
+        } else {
+            result.push_str(&rest[..index + " [E".len()]);
+            rest = after;
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Drops the column from each `[line N:C]` in `diagnostic`. Test scripts
+/// only give lines, as does the reference implementation.
+pub fn without_columns(diagnostic: &str) -> String {
+    let mut result = String::with_capacity(diagnostic.len());
+    let mut rest = diagnostic;
+    while let Some(index) = rest.find("[line ") {
+        let (before, after) = rest.split_at(index + "[line ".len());
+        result.push_str(before);
+        let digits = after
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(after.len());
+        result.push_str(&after[..digits]);
+        rest = &after[digits..];
+        if let Some(column) = rest.strip_prefix(':') {
+            let end = column
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(column.len());
+            if column[end..].starts_with(']') {
+                rest = &column[end..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// The result of running one test script.
+#[derive(Debug)]
+pub struct ScriptRun {
+    pub expectations: Expectations,
+    pub result: Result<(), LoxError>,
+    /// The lines the script printed.
+    pub output: Vec<String>,
+    /// What the run reported about its error, as it would appear on stderr.
+    pub diagnostics: Vec<String>,
+}
+
+impl ScriptRun {
+    pub fn check(&self) -> Result<(), String> {
+        self.expectations.check(&self.result, &self.output)
+    }
+
+    /// What the script wrote to stdout and stderr, in the form stored by
+    /// snapshot tests.
+    pub fn transcript(&self) -> String {
+        let mut transcript = String::from("--- stdout ---\n");
+        for line in &self.output {
+            transcript.push_str(line);
+            transcript.push('\n');
+        }
+        transcript.push_str("--- stderr ---\n");
+        if let Err(error) = &self.result {
+            transcript.push_str(&error.to_string());
+            transcript.push('\n');
+        }
+        transcript
+    }
+}
+
+/// Runs the script at `script` in a fresh interpreter that prints to
+/// `output_file`. Fails only if the files can't be read or written.
+pub fn run_script(script: &Path, output_file: &Path) -> Result<ScriptRun, String> {
+    let source = fs::read_to_string(script).map_err(|_| "Failed to read test file")?;
+    let expectations = Expectations::parse(&source);
+
+    // Clear the output file
+    File::create(output_file).map_err(|_| "Failed to clear output file")?;
+
+    // Collect diagnostics rather than printing them over the test output
+    let reporter = Rc::new(RefCell::new(BufferReporter::new()));
+    let mut lox = Lox::new(&output_file.to_string_lossy());
+    lox.set_reporter(reporter.clone());
+    let result = lox.try_run(&source);
+    let diagnostics = reporter.borrow_mut().take();
+
+    // Read back what the script printed
+    let file = File::open(output_file).map_err(|_| "Failed to open output file")?;
+    let output: Vec<String> = BufReader::new(file)
+        .lines()
+        .collect::<Result<_, _>>()
+        .map_err(|_| "Failed to read from output file")?;
+
+    Ok(ScriptRun {
+        expectations,
+        result,
+        output,
+        diagnostics,
+    })
+}
+
+/// Rewrites the expectation comments in `source` to match what `run`
+/// actually did, leaving every other line alone. Printed lines fill the
+/// `// expect:` comments in order, and lines printed past the last one are
+/// appended at the end. A runtime error is expected on the line it was
+/// raised on. Each static error is expected on its line with `// Error`, or
+/// with a `// [line N] Error` comment where it had one before or where the
+/// line can't take another comment.
+pub fn bless(source: &str, run: &ScriptRun) -> String {
+    let runtime_error = match &run.result {
+        Err(LoxError::Runtime(error)) => Some(error),
+        _ => None,
+    };
+    let source_lines: Vec<&str> = source.lines().collect();
+    let markers: Vec<Option<Marker>> = source_lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| Marker::find(line, index + 1))
+        .collect();
+
+    // Decide where each static error goes before writing anything
+    let mut inline: Vec<Option<String>> = vec![None; source_lines.len() + 1];
+    let mut at_marker: Vec<Option<(usize, String)>> = vec![None; source_lines.len() + 1];
+    let mut before: Vec<Vec<(usize, String)>> = vec![Vec::new(); source_lines.len() + 2];
+    for (line, message) in static_errors(&run.result) {
+        // Reuse a comment that expected an error on the same line
+        let previous = markers.iter().enumerate().position(|(index, marker)| {
+            matches!(marker, Some(Marker::Error { line: expected, .. }) if *expected == line)
+                && inline[index + 1].is_none()
+                && at_marker[index + 1].is_none()
+        });
+        match previous {
+            Some(index) => match markers[index] {
+                Some(Marker::Error {
+                    explicit: false, ..
+                }) => inline[index + 1] = Some(message),
+                _ => at_marker[index + 1] = Some((line, message)),
+            },
+            None if line <= source_lines.len()
+                && inline[line].is_none()
+                && at_marker[line].is_none()
+                && !code(source_lines[line - 1], &markers[line - 1])
+                    .trim()
+                    .is_empty() =>
+            {
+                inline[line] = Some(message)
+            }
+            None => before[line.min(source_lines.len() + 1)].push((line, message)),
+        }
+    }
+
+    let mut output = run.output.iter();
+    let mut lines: Vec<Blessed> = Vec::new();
+    // Where each line of `source` ends up, for numbering `[line N]` comments
+    let mut moved = vec![0; source_lines.len() + 1];
+    for (index, line) in source_lines.iter().enumerate() {
+        let line_number = index + 1;
+        for (line, message) in &before[line_number] {
+            lines.push(Blessed::Error(String::new(), *line, message.clone()));
+        }
+        moved[line_number] = lines.len() + 1;
+
+        let marker = &markers[index];
+        let code = code(line, marker);
+        // Keep the spacing before a comment that is rewritten in place
+        let prefix = match marker {
+            Some(_) => code.to_string(),
+            None => format!("{} ", line),
+        };
+        if let Some((line, message)) = at_marker[line_number].take() {
+            lines.push(Blessed::Error(code.to_string(), line, message));
+        } else if let Some(message) = &inline[line_number] {
+            lines.push(Blessed::Line(format!("{}// {}", prefix, message)));
+        } else if let Some(error) =
+            runtime_error.filter(|error| error.token.line as usize == line_number)
+        {
+            lines.push(Blessed::Line(format!(
+                "{}// expect runtime error: {}",
+                prefix, error.message
+            )));
+        } else if let Some(Marker::Output(index)) = marker {
+            // Keep a comment that spans several printed lines the same size
+            let count = line[*index..].split("\\n").count();
+            let values: Vec<&str> = output.by_ref().take(count).map(String::as_str).collect();
+            if values.is_empty() {
+                push_code(&mut lines, code);
+            } else {
+                lines.push(Blessed::Line(format!(
+                    "{}// expect: {}",
+                    code,
+                    values.join("\\n")
+                )));
+            }
+        } else if marker.is_some() {
+            push_code(&mut lines, code);
+        } else {
+            lines.push(Blessed::Line(line.to_string()));
+        }
+    }
+    for value in output {
+        lines.push(Blessed::Line(format!("// expect: {}", value)));
+    }
+    for (line, message) in &before[source_lines.len() + 1] {
+        lines.push(Blessed::Error(String::new(), *line, message.clone()));
+    }
+
+    // Lines past the end of `source` stay past the end of the result
+    let total = lines.len();
+    let line_of = |line: usize| match moved.get(line) {
+        Some(&line) if line > 0 => line,
+        _ => total + line - source_lines.len(),
+    };
+    let mut blessed = lines
+        .into_iter()
+        .map(|line| match line {
+            Blessed::Line(line) => line,
+            Blessed::Error(code, line, message) => {
+                format!("{}// [line {}] {}", code, line_of(line), message)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if source.ends_with('\n') {
+        blessed.push('\n');
+    }
+    blessed
+}
+
+/// An expectation comment found on a line of a test script.
+enum Marker {
+    /// `// expect: ...`, starting at the index.
+    Output(usize),
+    /// `// expect runtime error: ...`.
+    RuntimeError(usize),
+    /// `// Error ...`, or `// [line N] Error ...` when `explicit`, about
+    /// `line`.
+    Error {
+        index: usize,
+        line: usize,
+        explicit: bool,
+    },
+}
+
+impl Marker {
+    /// The comment on `line`, checked in the order `Expectations::parse`
+    /// checks them.
+    fn find(line: &str, line_number: usize) -> Option<Marker> {
+        if let Some(index) = line.find("// expect:") {
+            Some(Marker::Output(index))
+        } else if let Some(index) = line.find("// expect runtime error:") {
+            Some(Marker::RuntimeError(index))
+        } else if line.contains("// tags:") {
+            None
+        } else if let Some(index) = line.find("// [line ") {
+            let number = &line[index + "// [line ".len()..];
+            let digits = number
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(number.len());
+            Some(Marker::Error {
+                index,
+                line: number[..digits].parse().ok()?,
+                explicit: true,
+            })
+        } else {
+            line.find("// Error").map(|index| Marker::Error {
+                index,
+                line: line_number,
+                explicit: false,
+            })
+        }
+    }
+}
+
+/// `line` without its expectation comment.
+fn code<'a>(line: &'a str, marker: &Option<Marker>) -> &'a str {
+    match marker {
+        Some(Marker::Output(index) | Marker::RuntimeError(index) | Marker::Error { index, .. }) => {
+            &line[..*index]
+        }
+        None => line,
+    }
+}
+
+/// A line of a blessed script. `[line N]` comments are numbered last, once
+/// every line that adds or removes lines before them has been written.
+enum Blessed {
+    Line(String),
+    /// The code before a `// [line N] Error ...` comment, then the line of
+    /// `source` the error is on and its message.
+    Error(String, usize, String),
+}
+
+/// The line and message of each static error `result` reports, as test
+/// scripts spell them.
+fn static_errors(result: &Result<(), LoxError>) -> Vec<(usize, String)> {
+    match result {
+        Err(error) if error.is_static() => as_reference(&error.to_string())
+            .lines()
+            .filter_map(|line| {
+                let (number, message) = line.strip_prefix("[line ")?.split_once("] ")?;
+                Some((number.parse().ok()?, message.to_string()))
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Keeps the code before a dropped expectation, unless nothing is left.
+fn push_code(lines: &mut Vec<Blessed>, code: &str) {
+    let code = code.trim_end();
+    if !code.is_empty() {
+        lines.push(Blessed::Line(code.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_kind_of_expectation() {
+        let expectations = Expectations::parse(
+            "print 1; // expect: 1\n\
+             print \"a\\nb\"; // expect: a\\nb\n\
+             var a = ; // Error at ';': Expect expression.\n\
+             // [line 7] Error at end: Expect '}' after block.\n\
+             err; // // expect runtime error: Undefined variable 'err'.\n\
+             // tags: slow, classes\n",
+        );
+        assert_eq!(expectations.output, vec!["1", "a", "b"]);
+        assert_eq!(
+            expectations.static_errors,
+            vec![
+                "[line 3] Error at ';': Expect expression.",
+                "[line 7] Error at end: Expect '}' after block.",
+            ]
+        );
+        assert_eq!(
+            expectations.runtime_error.as_deref(),
+            Some("Undefined variable 'err'.\n[line 5]")
+        );
+        assert_eq!(
+            expectations.error().as_deref(),
+            Some("[line 3] Error at ';': Expect expression.\n[line 7] Error at end: Expect '}' after block.")
+        );
+        assert_eq!(expectations.tags, vec!["slow", "classes"]);
+    }
+
+    // For scripts that stop before printing anything
+    fn run(source: &str) -> ScriptRun {
+        ScriptRun {
+            expectations: Expectations::parse(source),
+            result: Lox::new("").try_run(source),
+            output: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn bless_rewrites_expectations_from_a_run() {
+        let source = "print 1; // expect: one\n\
+                      print 2; // expect: 2\\n3\n\
+                      print 4; // expect: 4\n";
+        let mut script = ScriptRun {
+            expectations: Expectations::parse(source),
+            result: Ok(()),
+            output: vec!["1".to_string(), "2".to_string()],
+            diagnostics: Vec::new(),
+        };
+        assert_eq!(
+            bless(source, &script),
+            "print 1; // expect: 1\nprint 2; // expect: 2\nprint 4;\n"
+        );
+
+        script.output.push("5".to_string());
+        script.output.push("6".to_string());
+        script.output.push("7".to_string());
+        assert_eq!(
+            bless(source, &script),
+            "print 1; // expect: 1\nprint 2; // expect: 2\\n5\nprint 4; // expect: 6\n// expect: 7\n"
+        );
+    }
+
+    #[test]
+    fn bless_records_runtime_errors() {
+        let source = "var a = 1;\na.b;\n";
+        assert_eq!(
+            bless(source, &run(source)),
+            "var a = 1;\na.b; // expect runtime error: Only instances have properties.\n"
+        );
+
+        let source = "nil(); // expect runtime error: Old message.\n";
+        assert_eq!(
+            bless(source, &run(source)),
+            "nil(); // expect runtime error: Can only call functions and classes.\n"
+        );
+
+        // An expectation left on a line that no longer raises it moves
+        let source = "var a; // expect runtime error: Only instances have properties.\na.b;\n";
+        assert_eq!(
+            bless(source, &run(source)),
+            "var a;\na.b; // expect runtime error: Only instances have properties.\n"
+        );
+    }
+
+    #[test]
+    fn bless_records_static_errors() {
+        let source = "print 1; // expect: 1\n\
+                      var a = ;\n\
+                      // [line 4] Error at '(': Old message.\n\
+                      print (1;\n\
+                      print 2; // Error at '2': Old message.\n";
+        assert_eq!(
+            bless(source, &run(source)),
+            "print 1;\n\
+             var a = ; // Error at ';': Expect expression.\n\
+             // [line 4] Error at ';': Expect ')' after expression.\n\
+             print (1;\n\
+             print 2;\n"
+        );
+
+        // Errors the line can't take are numbered for where the line ends up
+        let source = "// expect: 1\n// expect: 2\nvar = 1; var;\n";
+        assert_eq!(
+            bless(source, &run(source)),
+            "// [line 2] Error at ';': Expect variable name.\n\
+             var = 1; var; // Error at '=': Expect variable name.\n"
+        );
+    }
+}
@@ -1,6 +1,9 @@
-use crate::interpreter::Visitor;
+use crate::interpreter::{EvalResult, Visitor};
+use crate::stmt::Stmt;
 use crate::token::Token;
-use crate::value::Value;
+use std::cell::Cell;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
 
 #[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub enum Expr {
@@ -8,6 +11,14 @@ pub enum Expr {
         name: Token,
         value: Box<Expr>,
     },
+    /// `target += value` and the like, where `target` is a variable, a
+    /// property, or a list item. Any object and index in `target` are
+    /// evaluated once, for both reading and assigning.
+    CompoundAssign {
+        target: Box<Expr>,
+        operator: Token,
+        value: Box<Expr>,
+    },
     Binary {
         left: Box<Expr>,
         operator: Token,
@@ -30,12 +41,29 @@ pub enum Expr {
     },
     Variable {
         name: Token,
+        /// Where the variable was last found among the globals.
+        slot: SlotCache,
     },
     Logical {
         left: Box<Expr>,
         operator: Token,
         right: Box<Expr>,
     },
+    /// `condition ? then_branch : else_branch`, which evaluates only the
+    /// branch it picks.
+    Ternary {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+    },
+    /// `++target`, `target++`, `--target`, or `target--`, which add or
+    /// subtract 1 like `+=` and `-=` do. The prefix forms give the new value
+    /// and the postfix forms the old one.
+    Increment {
+        operator: Token,
+        target: Box<Expr>,
+        prefix: bool,
+    },
     Call {
         callee: Box<Expr>,
         paren: Token,
@@ -45,6 +73,8 @@ pub enum Expr {
         object: Box<Expr>,
         name: Token,
     },
+    /// `super.method`, or the `super` of `super(...)`, which is shorthand
+    /// for `super.init(...)` and has a `method` token of type `Super`.
     Super {
         keyword: Token,
         method: Token,
@@ -52,45 +82,163 @@ pub enum Expr {
     This {
         keyword: Token,
     },
+    /// An anonymous class, as in `var Point = class { ... };`.
+    Class {
+        keyword: Token,
+        superclass: Option<Box<Expr>>,
+        interfaces: Vec<Expr>,
+        methods: Vec<Stmt>,
+    },
+    /// `[a, b, c]`, which makes a new list each time it runs.
+    ListLiteral {
+        bracket: Token,
+        elements: Vec<Expr>,
+    },
+    /// `object[index]`. `bracket` is the closing `]`, for errors.
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    /// `object[index] = value`.
+    IndexSet {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+}
+
+/// The globals slot a variable expression was found in the last time it
+/// ran, shared by its clones. It isn't part of the expression, so it never
+/// affects equality or hashing.
+#[derive(Debug, Clone, Default)]
+pub struct SlotCache(Rc<Cell<Option<usize>>>);
+
+impl SlotCache {
+    pub fn get(&self) -> Option<usize> {
+        self.0.get()
+    }
+
+    pub fn set(&self, slot: usize) {
+        self.0.set(Some(slot));
+    }
+}
+
+impl PartialEq for SlotCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for SlotCache {}
+
+impl Hash for SlotCache {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
 }
 
 impl Expr {
+    /// Renders the tree as an s-expression, with every child spelled out, so
+    /// two expressions print the same only if they have the same shape.
     pub fn accept(&self) -> String {
         match self {
-            Expr::Assign { name, value } => self.parenthesize(&name.lexeme, vec![value]),
+            Expr::Assign { name, value } => {
+                self.parenthesize(&format!("= {}", name.lexeme), &[value])
+            }
+            Expr::CompoundAssign {
+                target,
+                operator,
+                value,
+            } => self.parenthesize(&operator.lexeme, &[target, value]),
             Expr::Binary {
                 left,
                 operator,
                 right,
-            } => self.parenthesize(&operator.lexeme, vec![left, right]),
-            Expr::Grouping { expression } => self.parenthesize("group", vec![expression]),
+            } => self.parenthesize(&operator.lexeme, &[left, right]),
+            Expr::Grouping { expression } => self.parenthesize("group", &[expression]),
             Expr::Literal { value } => value.to_string(),
             Expr::Set {
                 object,
                 name,
                 value,
-            } => self.parenthesize(&name.lexeme, vec![object, value]),
-            Expr::Unary { operator, right } => self.parenthesize(&operator.lexeme, vec![right]),
-            Expr::Variable { name } => name.to_string(),
+            } => self.parenthesize(&format!("set {}", name.lexeme), &[object, value]),
+            Expr::Unary { operator, right } => self.parenthesize(&operator.lexeme, &[right]),
+            Expr::Variable { name, .. } => name.to_string(),
             Expr::Logical {
                 left,
                 operator,
                 right,
-            } => self.parenthesize(&operator.lexeme, vec![left, right]),
+            } => self.parenthesize(&operator.lexeme, &[left, right]),
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => self.parenthesize("?:", &[condition, then_branch, else_branch]),
+            Expr::Increment {
+                operator,
+                target,
+                prefix,
+            } => {
+                let fixity = if *prefix { "pre" } else { "post" };
+                self.parenthesize(&format!("{}{}", fixity, operator.lexeme), &[target])
+            }
             Expr::Call {
-                callee: _,
-                paren,
-                arguments: _,
-            } => self.parenthesize(&paren.lexeme, vec![]),
-            Expr::Get { object, name } => self.parenthesize(&name.lexeme, vec![object]),
+                callee, arguments, ..
+            } => {
+                let mut result = format!("(call {}", callee.accept());
+                for argument in arguments {
+                    result.push(' ');
+                    result.push_str(&argument.accept());
+                }
+                result.push(')');
+                result
+            }
+            Expr::Get { object, name } => {
+                self.parenthesize(&format!("get {}", name.lexeme), &[object])
+            }
             Expr::This { keyword } => keyword.to_string(),
-            Expr::Super { keyword, method: _ } => keyword.to_string(),
+            Expr::Super { method, .. } => format!("(super {})", method.lexeme),
+            Expr::Class {
+                superclass,
+                methods,
+                ..
+            } => {
+                let mut result = "(class".to_string();
+                if let Some(superclass) = superclass {
+                    result.push_str(&format!(" < {}", superclass.accept()));
+                }
+                for method in methods {
+                    if let Stmt::Function { name, .. } = method {
+                        result.push(' ');
+                        result.push_str(&name.lexeme);
+                    }
+                }
+                result.push(')');
+                result
+            }
+            Expr::ListLiteral { elements, .. } => {
+                let mut result = "(list".to_string();
+                for element in elements {
+                    result.push(' ');
+                    result.push_str(&element.accept());
+                }
+                result.push(')');
+                result
+            }
+            Expr::Index { object, index, .. } => self.parenthesize("index", &[object, index]),
+            Expr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => self.parenthesize("index=", &[object, index, value]),
         }
     }
 
-    pub fn accept_interp<V: Visitor>(&self, visitor: &mut V) -> Option<Value> {
+    pub fn accept_interp<V: Visitor + ?Sized>(&self, visitor: &mut V) -> EvalResult {
         match self {
             Expr::Assign { name: _, value: _ } => visitor.visit_assign_expr(self),
+            Expr::CompoundAssign { .. } => visitor.visit_compound_assign_expr(self),
             Expr::Binary {
                 left: _,
                 operator: _,
@@ -102,12 +250,14 @@ impl Expr {
                 operator: _,
                 right: _,
             } => visitor.visit_unary_expr(self),
-            Expr::Variable { name: _ } => visitor.visit_variable_expr(self),
+            Expr::Variable { .. } => visitor.visit_variable_expr(self),
             Expr::Logical {
                 left: _,
                 operator: _,
                 right: _,
             } => visitor.visit_logical_expr(self),
+            Expr::Ternary { .. } => visitor.visit_ternary_expr(self),
+            Expr::Increment { .. } => visitor.visit_increment_expr(self),
             Expr::Call {
                 callee: _,
                 paren: _,
@@ -124,10 +274,14 @@ impl Expr {
                 keyword: _,
                 method: _,
             } => visitor.visit_super_expr(self),
+            Expr::Class { .. } => visitor.visit_class_expr(self),
+            Expr::ListLiteral { .. } => visitor.visit_list_expr(self),
+            Expr::Index { .. } => visitor.visit_index_expr(self),
+            Expr::IndexSet { .. } => visitor.visit_index_set_expr(self),
         }
     }
 
-    fn parenthesize(&self, name: &str, exprs: Vec<&Box<Expr>>) -> String {
+    fn parenthesize(&self, name: &str, exprs: &[&Expr]) -> String {
         let mut result = String::new();
         result.push('(');
         result.push_str(name);
@@ -0,0 +1,289 @@
+//! C interface for embedding the interpreter in non-Rust hosts.
+//!
+//! A handle from `lox_new` must only be used on the thread that created it.
+//! `lox_run` returns 0 on success, 65 after a scan/parse/resolve error and
+//! 70 after a runtime error, following the Crafting Interpreters exit codes.
+
+use crate::callable::Callable;
+use crate::error::LoxError;
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+use crate::Lox;
+use std::any::Any;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+
+pub const LOX_OK: c_int = 0;
+pub const LOX_STATIC_ERROR: c_int = 65;
+pub const LOX_RUNTIME_ERROR: c_int = 70;
+pub const LOX_INVALID_ARGUMENT: c_int = -1;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoxValueTag {
+    Nil,
+    Boolean,
+    Number,
+    String,
+}
+
+/// A Lox value as seen by C code. Only the field matching `tag` is meaningful.
+/// Strings handed to a native are valid for the duration of the call; strings
+/// returned by a native are copied before the native's next invocation.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LoxValue {
+    pub tag: LoxValueTag,
+    pub boolean: bool,
+    pub number: f64,
+    pub string: *const c_char,
+}
+
+impl LoxValue {
+    fn nil() -> Self {
+        LoxValue {
+            tag: LoxValueTag::Nil,
+            boolean: false,
+            number: 0.0,
+            string: std::ptr::null(),
+        }
+    }
+}
+
+pub type LoxNativeFn =
+    extern "C" fn(userdata: *mut c_void, args: *const LoxValue, argc: usize) -> LoxValue;
+
+#[derive(Clone)]
+struct ForeignFunction {
+    arity: usize,
+    function: LoxNativeFn,
+    userdata: *mut c_void,
+}
+
+impl Callable for ForeignFunction {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        // Keep the C strings alive until the native returns
+        let mut strings: Vec<CString> = Vec::new();
+        let mut args: Vec<LoxValue> = Vec::new();
+        for argument in arguments {
+            let mut arg = LoxValue::nil();
+            match argument {
+                Some(Value::Boolean(b)) => {
+                    arg.tag = LoxValueTag::Boolean;
+                    arg.boolean = b;
+                }
+                Some(Value::Number(n)) => {
+                    arg.tag = LoxValueTag::Number;
+                    arg.number = n;
+                }
+                Some(Value::Decimal(decimal)) => {
+                    arg.tag = LoxValueTag::Number;
+                    arg.number = decimal.to_f64();
+                }
+                Some(Value::String(s)) => {
+                    // Lox strings keep their surrounding quotes
+                    let text = &s[1..(s.len() - 1)];
+                    strings.push(CString::new(text).unwrap_or_default());
+                    arg.tag = LoxValueTag::String;
+                    arg.string = strings.last().unwrap().as_ptr();
+                }
+                Some(Value::Callable(c)) => {
+                    strings.push(CString::new(c.to_string()).unwrap_or_default());
+                    arg.tag = LoxValueTag::String;
+                    arg.string = strings.last().unwrap().as_ptr();
+                }
+                Some(Value::Instance(i)) => {
+                    strings.push(CString::new(i.borrow().to_string()).unwrap_or_default());
+                    arg.tag = LoxValueTag::String;
+                    arg.string = strings.last().unwrap().as_ptr();
+                }
+                Some(Value::List(items)) => {
+                    let text = interpreter.stringify(Some(Value::List(items)));
+                    strings.push(CString::new(text).unwrap_or_default());
+                    arg.tag = LoxValueTag::String;
+                    arg.string = strings.last().unwrap().as_ptr();
+                }
+                Some(Value::NativeInstance(i)) => {
+                    let text = format!("{} instance", i.borrow().class_name());
+                    strings.push(CString::new(text).unwrap_or_default());
+                    arg.tag = LoxValueTag::String;
+                    arg.string = strings.last().unwrap().as_ptr();
+                }
+                // Passed as their digits, since a double would round them
+                #[cfg(feature = "lox-bigint")]
+                Some(Value::BigInt(big)) => {
+                    strings.push(CString::new(big.to_string()).unwrap_or_default());
+                    arg.tag = LoxValueTag::String;
+                    arg.string = strings.last().unwrap().as_ptr();
+                }
+                Some(Value::Nil()) | None => {}
+            }
+            args.push(arg);
+        }
+
+        let result = (self.function)(self.userdata, args.as_ptr(), args.len());
+
+        let value = match result.tag {
+            LoxValueTag::Nil => Value::Nil(),
+            LoxValueTag::Boolean => Value::Boolean(result.boolean),
+            LoxValueTag::Number => Value::Number(result.number),
+            LoxValueTag::String => {
+                if result.string.is_null() {
+                    return Ok(Some(Value::Nil()));
+                }
+                let text = unsafe { CStr::from_ptr(result.string) }.to_string_lossy();
+                Value::String(format!("\"{}\"", text))
+            }
+        };
+        Ok(Some(value))
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    // Foreign code can do anything
+    fn deterministic(&self) -> bool {
+        false
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// Creates a new interpreter that prints to stdout.
+#[no_mangle]
+pub extern "C" fn lox_new() -> *mut Lox {
+    Box::into_raw(Box::new(Lox::new("")))
+}
+
+/// Runs `source` in the interpreter, keeping globals from earlier runs.
+/// Returns `LOX_OK`, `LOX_STATIC_ERROR`, `LOX_RUNTIME_ERROR`, or the status
+/// the script passed to `exit()`.
+///
+/// # Safety
+///
+/// `lox` must come from `lox_new` and `source` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lox_run(lox: *mut Lox, source: *const c_char) -> c_int {
+    if lox.is_null() || source.is_null() {
+        return LOX_INVALID_ARGUMENT;
+    }
+    let source = match CStr::from_ptr(source).to_str() {
+        Ok(source) => source,
+        Err(_) => return LOX_INVALID_ARGUMENT,
+    };
+    let lox = &mut *lox;
+
+    // The codes match `Lox::run`'s exit statuses
+    lox.run(source)
+}
+
+/// Defines a global native function `name` taking `arity` arguments.
+/// `userdata` is passed back to `function` on every call.
+///
+/// # Safety
+///
+/// `lox` must come from `lox_new` and `name` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lox_register_native(
+    lox: *mut Lox,
+    name: *const c_char,
+    arity: usize,
+    function: LoxNativeFn,
+    userdata: *mut c_void,
+) -> c_int {
+    if lox.is_null() || name.is_null() {
+        return LOX_INVALID_ARGUMENT;
+    }
+    let name = match CStr::from_ptr(name).to_str() {
+        Ok(name) => name,
+        Err(_) => return LOX_INVALID_ARGUMENT,
+    };
+    (*lox).define_native(
+        name,
+        Box::new(ForeignFunction {
+            arity,
+            function,
+            userdata,
+        }),
+    );
+    LOX_OK
+}
+
+/// Destroys an interpreter created by `lox_new`.
+///
+/// # Safety
+///
+/// `lox` must come from `lox_new` and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn lox_free(lox: *mut Lox) {
+    if !lox.is_null() {
+        drop(Box::from_raw(lox));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn record_sum(
+        userdata: *mut c_void,
+        args: *const LoxValue,
+        argc: usize,
+    ) -> LoxValue {
+        let args = unsafe { std::slice::from_raw_parts(args, argc) };
+        let sum = args[0].number + args[1].number;
+        unsafe { *(userdata as *mut f64) = sum };
+        let mut result = LoxValue::nil();
+        result.tag = LoxValueTag::Number;
+        result.number = sum;
+        result
+    }
+
+    #[test]
+    fn native_receives_arguments_and_globals_persist() {
+        let mut recorded = 0.0_f64;
+        unsafe {
+            let lox = lox_new();
+            let name = CString::new("add").unwrap();
+            let userdata = &mut recorded as *mut f64 as *mut c_void;
+            assert_eq!(
+                lox_register_native(lox, name.as_ptr(), 2, record_sum, userdata),
+                LOX_OK
+            );
+            let first = CString::new("var total = add(1, 2);").unwrap();
+            assert_eq!(lox_run(lox, first.as_ptr()), LOX_OK);
+            let second = CString::new("add(total, 4);").unwrap();
+            assert_eq!(lox_run(lox, second.as_ptr()), LOX_OK);
+            lox_free(lox);
+        }
+        assert_eq!(recorded, 7.0);
+    }
+
+    #[test]
+    fn runtime_error_returns_status() {
+        unsafe {
+            let lox = lox_new();
+            let source = CString::new("1 < \"oops\";").unwrap();
+            assert_eq!(lox_run(lox, source.as_ptr()), LOX_RUNTIME_ERROR);
+            let source = CString::new("var;").unwrap();
+            assert_eq!(lox_run(lox, source.as_ptr()), LOX_STATIC_ERROR);
+            let source = CString::new("exit(3);").unwrap();
+            assert_eq!(lox_run(lox, source.as_ptr()), 3);
+            lox_free(lox);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::callable::Callable;
+use crate::runtime_error::RuntimeError;
+use crate::stmt::Stmt;
+use crate::token::Token;
+use crate::value::Value;
+use std::fmt;
+
+/// Callbacks an embedder can install on an `Interpreter` to observe execution.
+/// Every method has an empty default, so a hook only overrides what it needs.
+/// Tools such as debuggers, profilers, and coverage trackers are meant to be
+/// written against this trait rather than patching the interpreter.
+pub trait InterpreterHooks {
+    fn on_statement(&mut self, _stmt: &Stmt) {}
+    fn on_call_enter(&mut self, _callee: &dyn Callable, _paren: &Token) {}
+    fn on_call_exit(&mut self, _callee: &dyn Callable, _result: Option<&Value>) {}
+    fn on_var_define(&mut self, _name: &Token, _value: Option<&Value>) {}
+    fn on_var_assign(&mut self, _name: &Token, _value: &Value) {}
+    fn on_error(&mut self, _error: &RuntimeError) {}
+}
+
+impl fmt::Debug for dyn InterpreterHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "InterpreterHooks")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::InterpreterLimits;
+    use crate::Lox;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Default)]
+    struct Recorder {
+        statements: usize,
+        calls: Vec<String>,
+        defines: Vec<String>,
+        assigns: Vec<String>,
+        errors: Vec<String>,
+    }
+
+    impl InterpreterHooks for Recorder {
+        fn on_statement(&mut self, _stmt: &Stmt) {
+            self.statements += 1;
+        }
+
+        fn on_call_enter(&mut self, callee: &dyn Callable, _paren: &Token) {
+            self.calls.push(format!("enter {}", callee.to_string()));
+        }
+
+        fn on_call_exit(&mut self, callee: &dyn Callable, _result: Option<&Value>) {
+            self.calls.push(format!("exit {}", callee.to_string()));
+        }
+
+        fn on_var_define(&mut self, name: &Token, _value: Option<&Value>) {
+            self.defines.push(name.lexeme.clone());
+        }
+
+        fn on_var_assign(&mut self, name: &Token, _value: &Value) {
+            self.assigns.push(name.lexeme.clone());
+        }
+
+        fn on_error(&mut self, error: &RuntimeError) {
+            self.errors.push(error.message.clone());
+        }
+    }
+
+    #[test]
+    fn hooks_observe_execution() {
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+        let mut lox = Lox::new("");
+        lox.set_hooks(recorder.clone());
+        lox.run("fun f(a) { return a; } var x = 1; x = f(2);");
+
+        let recorder = recorder.borrow();
+        assert_eq!(recorder.statements, 4);
+        assert_eq!(recorder.calls, vec!["enter <fn f>", "exit <fn f>"]);
+        assert_eq!(recorder.defines, vec!["f", "x"]);
+        assert_eq!(recorder.assigns, vec!["x"]);
+        assert!(recorder.errors.is_empty());
+    }
+
+    #[test]
+    fn hooks_observe_runtime_errors() {
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+        let mut lox = Lox::new("");
+        lox.set_hooks(recorder.clone());
+        let result = lox.try_run("1 < \"a\";");
+
+        assert!(result.is_err());
+        assert_eq!(recorder.borrow().errors, vec!["Operands must be numbers."]);
+    }
+
+    #[test]
+    fn failed_calls_are_exited_and_refused_ones_are_not_entered() {
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+        let mut lox = Lox::new("");
+        lox.set_hooks(recorder.clone());
+        assert!(lox.try_run("fun f() { return 1 < \"a\"; } f();").is_err());
+        assert_eq!(recorder.borrow().calls, vec!["enter <fn f>", "exit <fn f>"]);
+
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+        lox.set_hooks(recorder.clone());
+        lox.set_limits(InterpreterLimits {
+            max_call_depth: Some(2),
+            ..InterpreterLimits::default()
+        });
+        assert!(lox.try_run("fun g(n) { g(n + 1); } g(0);").is_err());
+        assert_eq!(
+            recorder.borrow().calls,
+            vec!["enter <fn g>", "enter <fn g>", "exit <fn g>", "exit <fn g>"]
+        );
+    }
+}
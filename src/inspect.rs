@@ -0,0 +1,83 @@
+//! The rendering behind the `inspect(value)` native. Unlike `print`, it shows
+//! strings with their quotes and instances with their fields, recursively.
+
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+use std::rc::Rc;
+
+/// Renders `value` for debugging. Instances show as `Point { x: 1, y: 2 }`,
+/// with fields sorted by name, and lists as `[1, "two"]`. An instance or list
+/// that contains itself shows the inner reference as `<cycle>`.
+pub fn inspect(interpreter: &Interpreter, value: &Value) -> String {
+    Inspector {
+        interpreter,
+        path: Vec::new(),
+    }
+    .inspect(value)
+}
+
+struct Inspector<'a> {
+    interpreter: &'a Interpreter,
+    // The objects being rendered, outermost first, to spot cycles
+    path: Vec<*const ()>,
+}
+
+impl Inspector<'_> {
+    fn inspect(&mut self, value: &Value) -> String {
+        match value {
+            // Strings keep their quotes internally
+            Value::String(s) => s.clone(),
+            Value::Instance(instance) => {
+                let address = Rc::as_ptr(instance) as *const ();
+                if self.path.contains(&address) {
+                    return "<cycle>".to_string();
+                }
+                self.path.push(address);
+
+                let instance = instance.borrow();
+                let mut names: Vec<&String> = instance.fields.keys().collect();
+                names.sort();
+                let fields: Vec<String> = names
+                    .into_iter()
+                    .map(|name| format!("{}: {}", name, self.inspect(&instance.fields[name])))
+                    .collect();
+                let class_name = instance.klass.borrow().to_string();
+
+                self.path.pop();
+                if fields.is_empty() {
+                    format!("{} {{}}", class_name)
+                } else {
+                    format!("{} {{ {} }}", class_name, fields.join(", "))
+                }
+            }
+            Value::List(list) => {
+                let address = Rc::as_ptr(list) as *const ();
+                if self.path.contains(&address) {
+                    return "<cycle>".to_string();
+                }
+                self.path.push(address);
+                let items: Vec<String> = list
+                    .borrow()
+                    .iter()
+                    .map(|item| self.inspect(item))
+                    .collect();
+                self.path.pop();
+                format!("[{}]", items.join(", "))
+            }
+            Value::NativeInstance(instance) => {
+                let address = Rc::as_ptr(instance) as *const ();
+                if self.path.contains(&address) {
+                    return "<cycle>".to_string();
+                }
+                self.path.push(address);
+                let rendered = instance
+                    .borrow()
+                    .inspect(&mut |value| self.inspect(value))
+                    .unwrap_or_else(|| format!("{} instance", instance.borrow().class_name()));
+                self.path.pop();
+                rendered
+            }
+            _ => self.interpreter.stringify(Some(value.clone())),
+        }
+    }
+}
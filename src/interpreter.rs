@@ -1,12 +1,29 @@
 use crate::callable::Callable;
+use crate::cancellation::CancellationToken;
 use crate::environment::Environment;
+use crate::error::{ErrorCode, LoxError};
 use crate::expr::Expr;
+use crate::hooks::InterpreterHooks;
+use crate::inspect;
+use crate::limits::{InterpreterLimits, Permission, SharedRun};
+use crate::logging::LogLevel;
 use crate::lox_class::LoxClass;
 use crate::lox_function::LoxFunction;
+use crate::lox_instance::LoxInstance;
+use crate::lox_interface::LoxInterface;
+use crate::lox_list;
 use crate::native_functions;
+use crate::native_instance;
+use crate::prelude;
+use crate::program::Program;
+use crate::random::{Random, DETERMINISTIC_SEED};
+use crate::reporter::{ErrorReporter, StderrReporter};
 use crate::return_value::ReturnValue;
-use crate::runtime_error::RuntimeError;
+use crate::runtime_error::{CallFrame, RuntimeError};
 use crate::stmt::Stmt;
+use crate::suggest;
+use crate::testing::TestCounts;
+use crate::timers::Timers;
 use crate::token::Token;
 use crate::token_type::TokenType;
 use crate::value::Value;
@@ -15,6 +32,12 @@ use crate::write_output::write_output;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long after the Unix epoch the clock stands in deterministic mode:
+/// midnight UTC on 1 January 2000.
+pub const DETERMINISTIC_TIME: Duration = Duration::from_secs(946_684_800);
 
 #[derive(Debug, Clone)]
 pub struct Interpreter {
@@ -22,96 +45,405 @@ pub struct Interpreter {
     pub globals: Rc<RefCell<Environment>>,
     output_file: String,
     locals: HashMap<Expr, usize>,
+    hooks: Option<Rc<RefCell<dyn InterpreterHooks>>>,
+    reporter: Rc<RefCell<dyn ErrorReporter>>,
+    cancellation: Option<CancellationToken>,
+    limits: InterpreterLimits,
+    log_level: LogLevel,
+    /// Whether the prelude's helpers were defined.
+    prelude: bool,
+    /// Whether fractional number literals evaluate to exact decimals.
+    decimal_literals: bool,
+    /// Whether dividing by zero is a runtime error rather than infinity.
+    checked_division: bool,
+    /// Whether integer arithmetic that leaves the exact range of a number
+    /// is a runtime error rather than rounding.
+    checked_integers: bool,
+    /// Whether arithmetic on finite numbers that gives NaN or an infinity is
+    /// a runtime error.
+    strict_math: bool,
+    /// Whether the clock is frozen, `random()` starts from a fixed seed, and
+    /// nondeterministic natives can't be called.
+    deterministic: bool,
+    random: Random,
+    /// The instructions the current run has executed and when it began,
+    /// for `max_instructions` and `max_duration`.
+    run: SharedRun,
+    /// The calls in progress, outermost first.
+    frames: Vec<CallFrame>,
+    /// The objects the current run has allocated, for `max_allocations`.
+    allocations: usize,
+    /// Statements deferred by each active function call, innermost last,
+    /// with the scope each `defer` ran in.
+    deferred: Vec<Vec<(Stmt, Rc<RefCell<Environment>>)>>,
+    /// The results of the `test` calls so far.
+    tests: TestCounts,
+    /// The timers set by `setTimeout` and `setInterval`, which fire once the
+    /// script has finished.
+    timers: Timers,
 }
 
+/// What evaluating an expression produces, or the error that stopped it.
+pub type EvalResult = Result<Option<Value>, LoxError>;
+
+/// What executing a statement produces: the value of a `return` that is
+/// unwinding to its function, if any, or the error that stopped it.
+pub type ExecResult = Result<Option<ReturnValue>, LoxError>;
+
+/// A pass over expressions. Each method defaults to `walk_expr`, which
+/// visits the children, so a pass only overrides the kinds it cares about.
 pub trait Visitor {
-    fn visit_assign_expr(&mut self, expr: &Expr) -> Option<Value>;
-    fn visit_literal_expr(&mut self, expr: &Expr) -> Option<Value>;
-    fn visit_grouping_expr(&mut self, expr: &Expr) -> Option<Value>;
-    fn visit_unary_expr(&mut self, expr: &Expr) -> Option<Value>;
-    fn visit_binary_expr(&mut self, expr: &Expr) -> Option<Value>;
-    fn visit_call_expr(&mut self, expr: &Expr) -> Option<Value>;
-    fn visit_get_expr(&mut self, expr: &Expr) -> Option<Value>;
-    fn visit_variable_expr(&mut self, expr: &Expr) -> Option<Value>;
-    fn visit_logical_expr(&mut self, expr: &Expr) -> Option<Value>;
-    fn visit_set_expr(&mut self, expr: &Expr) -> Option<Value>;
-    fn visit_this_expr(&mut self, expr: &Expr) -> Option<Value>;
-    fn visit_super_expr(&mut self, expr: &Expr) -> Option<Value>;
+    fn visit_assign_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_compound_assign_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_literal_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_grouping_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_unary_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_binary_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_call_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_get_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_variable_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_logical_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_ternary_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_increment_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_set_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_this_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_super_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    /// The methods of a class expression are statements, which `walk_expr`
+    /// can't visit; a `StmtVisitor` that needs them overrides this and calls
+    /// `walk_stmt` on each.
+    fn visit_class_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_list_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_index_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
+    fn visit_index_set_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)
+    }
 }
 
-pub trait StmtVisitor {
-    fn visit_block_stmt(&mut self, stmts: Vec<Stmt>) -> Option<ReturnValue>;
+/// A pass over statements, and so over the expressions in them too. Each
+/// method defaults to `walk_stmt` on the statement it was given.
+pub trait StmtVisitor: Visitor {
+    fn visit_block_stmt(&mut self, stmts: Vec<Stmt>) -> ExecResult {
+        walk_stmt(self, &Stmt::Block(stmts))
+    }
     fn visit_class_stmt(
         &mut self,
         name: Token,
         superclass: Option<Expr>,
+        interfaces: Vec<Expr>,
         methods: Vec<Stmt>,
-    ) -> Option<ReturnValue>;
-    fn visit_expression_stmt(&mut self, expr: Expr) -> Option<ReturnValue>;
+    ) -> ExecResult {
+        walk_stmt(
+            self,
+            &Stmt::Class {
+                name,
+                superclass,
+                interfaces,
+                methods,
+            },
+        )
+    }
+    fn visit_defer_stmt(&mut self, keyword: Token, body: Box<Stmt>) -> ExecResult {
+        walk_stmt(self, &Stmt::Defer { keyword, body })
+    }
+    fn visit_expression_stmt(&mut self, expr: Expr) -> ExecResult {
+        walk_stmt(self, &Stmt::Expression(expr))
+    }
     fn visit_function_stmt(
         &mut self,
         name: Token,
         params: Vec<Token>,
         body: Vec<Stmt>,
-    ) -> Option<ReturnValue>;
+    ) -> ExecResult {
+        walk_stmt(self, &Stmt::Function { name, params, body })
+    }
+    fn visit_interface_stmt(
+        &mut self,
+        name: Token,
+        methods: Vec<(Token, Vec<Token>)>,
+    ) -> ExecResult {
+        walk_stmt(self, &Stmt::Interface { name, methods })
+    }
     fn visit_if_stmt(
         &mut self,
         condition: Expr,
         then_branch: Box<Stmt>,
         else_branch: Box<Option<Stmt>>,
-    ) -> Option<ReturnValue>;
-    fn visit_print_stmt(&mut self, expr: Expr) -> Option<ReturnValue>;
-    fn visit_return_stmt(&mut self, keyword: Token, value: Option<Expr>) -> Option<ReturnValue>;
-    fn visit_var_stmt(&mut self, name: Token, initializer: Option<Expr>) -> Option<ReturnValue>;
-    fn visit_while_stmt(&mut self, condition: Expr, body: Box<Stmt>) -> Option<ReturnValue>;
+    ) -> ExecResult {
+        walk_stmt(
+            self,
+            &Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            },
+        )
+    }
+    fn visit_print_stmt(&mut self, expr: Expr) -> ExecResult {
+        walk_stmt(self, &Stmt::Print(expr))
+    }
+    fn visit_return_stmt(&mut self, keyword: Token, value: Option<Expr>) -> ExecResult {
+        walk_stmt(self, &Stmt::Return { keyword, value })
+    }
+    fn visit_using_stmt(
+        &mut self,
+        keyword: Token,
+        name: Token,
+        initializer: Expr,
+        body: Box<Stmt>,
+    ) -> ExecResult {
+        walk_stmt(
+            self,
+            &Stmt::Using {
+                keyword,
+                name,
+                initializer,
+                body,
+            },
+        )
+    }
+    fn visit_var_stmt(&mut self, name: Token, initializer: Option<Expr>) -> ExecResult {
+        walk_stmt(self, &Stmt::Var { name, initializer })
+    }
+    fn visit_while_stmt(&mut self, condition: Expr, body: Box<Stmt>) -> ExecResult {
+        walk_stmt(self, &Stmt::While { condition, body })
+    }
+}
+
+/// Visits the subexpressions of `expr`, left to right, stopping at the
+/// first error. Yields no value.
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) -> EvalResult {
+    match expr {
+        Expr::Assign { value, .. } => {
+            value.accept_interp(visitor)?;
+        }
+        Expr::CompoundAssign { target, value, .. } => {
+            target.accept_interp(visitor)?;
+            value.accept_interp(visitor)?;
+        }
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            left.accept_interp(visitor)?;
+            right.accept_interp(visitor)?;
+        }
+        Expr::Grouping { expression } => {
+            expression.accept_interp(visitor)?;
+        }
+        Expr::Ternary {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            condition.accept_interp(visitor)?;
+            then_branch.accept_interp(visitor)?;
+            else_branch.accept_interp(visitor)?;
+        }
+        Expr::Increment { target, .. } => {
+            target.accept_interp(visitor)?;
+        }
+        Expr::Unary { right, .. } => {
+            right.accept_interp(visitor)?;
+        }
+        Expr::Call {
+            callee, arguments, ..
+        } => {
+            callee.accept_interp(visitor)?;
+            for argument in arguments {
+                argument.accept_interp(visitor)?;
+            }
+        }
+        Expr::Get { object, .. } => {
+            object.accept_interp(visitor)?;
+        }
+        Expr::Set { object, value, .. } => {
+            object.accept_interp(visitor)?;
+            value.accept_interp(visitor)?;
+        }
+        Expr::Class {
+            superclass,
+            interfaces,
+            ..
+        } => {
+            if let Some(superclass) = superclass {
+                superclass.accept_interp(visitor)?;
+            }
+            for interface in interfaces {
+                interface.accept_interp(visitor)?;
+            }
+        }
+        Expr::ListLiteral { elements, .. } => {
+            for element in elements {
+                element.accept_interp(visitor)?;
+            }
+        }
+        Expr::Index { object, index, .. } => {
+            object.accept_interp(visitor)?;
+            index.accept_interp(visitor)?;
+        }
+        Expr::IndexSet {
+            object,
+            index,
+            value,
+            ..
+        } => {
+            object.accept_interp(visitor)?;
+            index.accept_interp(visitor)?;
+            value.accept_interp(visitor)?;
+        }
+        Expr::Literal { .. } | Expr::Variable { .. } | Expr::This { .. } | Expr::Super { .. } => {}
+    }
+    Ok(None)
+}
+
+/// Visits the statements and expressions directly inside `stmt`, in source
+/// order, stopping at the first error. Function and method bodies count as
+/// inside their declaration. Yields no return value.
+pub fn walk_stmt<V: StmtVisitor + ?Sized>(visitor: &mut V, stmt: &Stmt) -> ExecResult {
+    match stmt {
+        Stmt::Block(stmts) | Stmt::Function { body: stmts, .. } => {
+            for stmt in stmts {
+                stmt.accept(visitor)?;
+            }
+        }
+        Stmt::Class {
+            superclass,
+            interfaces,
+            methods,
+            ..
+        } => {
+            if let Some(superclass) = superclass {
+                superclass.accept_interp(visitor)?;
+            }
+            for interface in interfaces {
+                interface.accept_interp(visitor)?;
+            }
+            for method in methods {
+                method.accept(visitor)?;
+            }
+        }
+        Stmt::Defer { body, .. } => {
+            body.accept(visitor)?;
+        }
+        Stmt::Expression(expr) | Stmt::Print(expr) => {
+            expr.accept_interp(visitor)?;
+        }
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            condition.accept_interp(visitor)?;
+            then_branch.accept(visitor)?;
+            if let Some(else_branch) = else_branch.as_ref() {
+                else_branch.accept(visitor)?;
+            }
+        }
+        Stmt::Return { value, .. }
+        | Stmt::Var {
+            initializer: value, ..
+        } => {
+            if let Some(value) = value {
+                value.accept_interp(visitor)?;
+            }
+        }
+        Stmt::Using {
+            initializer, body, ..
+        } => {
+            initializer.accept_interp(visitor)?;
+            body.accept(visitor)?;
+        }
+        Stmt::While { condition, body } => {
+            condition.accept_interp(visitor)?;
+            body.accept(visitor)?;
+        }
+        Stmt::Comment { .. } | Stmt::Macro { .. } | Stmt::Interface { .. } => {}
+    }
+    Ok(None)
 }
 
 impl Visitor for Interpreter {
-    fn visit_assign_expr(&mut self, expr: &Expr) -> Option<Value> {
+    fn visit_assign_expr(&mut self, expr: &Expr) -> EvalResult {
         if let Expr::Assign { name, value } = expr {
-            let v = self.evaluate(&value);
-            let distance = self.locals.get(expr);
-            if let Some(distance) = distance {
-                if *distance == 1 {
-                    self.environment
-                        .borrow_mut()
-                        .enclosing
-                        .as_ref()
-                        .expect("REASON")
-                        .borrow_mut()
-                        .assign(name.clone(), v.clone()?);
-                } else {
-                    self.environment
-                        .borrow_mut()
-                        .assign_at(*distance, name.clone(), v.clone()?);
-                }
-            } else {
-                self.globals.borrow_mut().assign(name.clone(), v.clone()?);
-            }
-            return v;
+            let Some(v) = self.evaluate(value)? else {
+                return Ok(None);
+            };
+            self.assign_variable(name, expr, v.clone())?;
+            return Ok(Some(v));
         }
-        None
+        Ok(None)
+    }
+
+    fn visit_compound_assign_expr(&mut self, expr: &Expr) -> EvalResult {
+        let Expr::CompoundAssign {
+            target,
+            operator,
+            value,
+        } = expr
+        else {
+            return Ok(None);
+        };
+        let operator = Interpreter::arithmetic_operator(operator);
+        let (_, new) = self.update(target, |interpreter, old| {
+            let value = interpreter.evaluate(value)?;
+            interpreter.binary(&operator, old, value)
+        })?;
+        Ok(new)
     }
 
-    fn visit_literal_expr(&mut self, expr: &Expr) -> Option<Value> {
+    fn visit_literal_expr(&mut self, expr: &Expr) -> EvalResult {
         if let Expr::Literal { value } = expr {
             match value.type_ {
-                TokenType::Number => {
-                    let num = value.lexeme.parse::<f64>().unwrap();
-                    Some(Value::Number(num))
-                }
-                TokenType::String => Some(Value::String(value.lexeme.clone())),
-                TokenType::True => Some(Value::Boolean(true)),
-                TokenType::False => Some(Value::Boolean(false)),
-                TokenType::Nil => Some(Value::Nil()),
-                _ => None,
+                TokenType::Number => Ok(Some(self.number_literal(&value.lexeme))),
+                // The lexeme is the source text, escapes and all
+                TokenType::String => Ok(Some(Value::String(format!(
+                    "\"{}\"",
+                    value.literal.as_deref().unwrap_or_default()
+                )))),
+                TokenType::True => Ok(Some(Value::Boolean(true))),
+                TokenType::False => Ok(Some(Value::Boolean(false))),
+                TokenType::Nil => Ok(Some(Value::Nil())),
+                _ => Ok(None),
             }
         } else {
             panic!("Expected a Literal expression.");
         }
     }
 
-    fn visit_grouping_expr(&mut self, expr: &Expr) -> Option<Value> {
+    fn visit_grouping_expr(&mut self, expr: &Expr) -> EvalResult {
         if let Expr::Grouping { expression } = expr {
             self.evaluate(&expression.clone()) // Assuming evaluate returns a String
         } else {
@@ -119,27 +451,35 @@ impl Visitor for Interpreter {
         }
     }
 
-    fn visit_unary_expr(&mut self, expr: &Expr) -> Option<Value> {
+    fn visit_unary_expr(&mut self, expr: &Expr) -> EvalResult {
         if let Expr::Unary { operator, right } = expr {
-            let r = self.evaluate(&right.clone());
+            let r = self.evaluate(&right.clone())?;
 
             match operator.type_ {
                 TokenType::Minus => {
-                    let Some(Value::Number(num)) = r else { todo!() };
-                    Interpreter::check_number_operand(operator, r);
-                    Some(Value::Number(-num))
+                    #[cfg(feature = "lox-bigint")]
+                    if let Some(Value::BigInt(big)) = &r {
+                        return Ok(Some(crate::bigint::negate(big)));
+                    }
+                    if let Some(Value::Decimal(decimal)) = &r {
+                        return Ok(Some(crate::decimal::negate(decimal)));
+                    }
+                    Interpreter::check_number_operand(operator, r.clone())?;
+                    let Some(Value::Number(num)) = r else {
+                        return Ok(None);
+                    };
+                    Ok(Some(Value::Number(-num)))
                 }
                 TokenType::Bang => {
-                    match r {
-                        Some(Value::Nil()) => return Some(Value::Boolean(true)),
-                        _ => (),
+                    if let Some(Value::Nil()) = r {
+                        return Ok(Some(Value::Boolean(true)));
                     }
                     let Some(Value::Boolean(bool_val)) = r else {
-                        return Some(Value::Boolean(false));
+                        return Ok(Some(Value::Boolean(false)));
                     };
-                    Some(Value::Boolean(!Interpreter::is_truthy(Some(
+                    Ok(Some(Value::Boolean(!Interpreter::is_truthy(Some(
                         &Value::Boolean(bool_val),
-                    ))))
+                    )))))
                 }
                 // Handle other unary operators here if needed...
                 _ => panic!("Not Unary expression."), // Handle unreachable cases with panic
@@ -149,272 +489,337 @@ impl Visitor for Interpreter {
         }
     }
 
-    fn visit_call_expr(&mut self, expr: &Expr) -> Option<Value> {
+    fn visit_call_expr(&mut self, expr: &Expr) -> EvalResult {
         if let Expr::Call {
             callee,
             paren,
             arguments,
         } = expr
         {
-            let function = self.evaluate(&callee.clone());
+            let function = self.evaluate(&callee.clone())?;
             let mut args = Vec::new();
             for arg in arguments {
-                args.push(self.evaluate(&arg.clone()));
+                args.push(self.evaluate(&arg.clone())?);
             }
             match function {
                 Some(Value::Callable(mut callable)) => {
-                    if args.len() != callable.arity() {
+                    if callable.variadic() && args.len() < callable.arity() {
                         let error = RuntimeError::new(
                             paren.clone(),
+                            ErrorCode::WrongArity,
+                            &format!(
+                                "Expected at least {} arguments but got {}.",
+                                callable.arity(),
+                                args.len()
+                            ),
+                        );
+                        return Err(LoxError::Runtime(error));
+                    }
+                    if !callable.variadic() && args.len() != callable.arity() {
+                        let error = RuntimeError::new(
+                            paren.clone(),
+                            ErrorCode::WrongArity,
                             &format!(
                                 "Expected {} arguments but got {}.",
                                 callable.arity(),
                                 args.len()
                             ),
                         );
-                        crate::runtime_error(error);
-                        panic!(
-                            "Expected {} arguments but got {}.",
-                            callable.arity(),
-                            args.len()
+                        return Err(LoxError::Runtime(error));
+                    }
+                    if self.deterministic && !callable.deterministic() {
+                        let error = RuntimeError::new(
+                            paren.clone(),
+                            ErrorCode::NondeterministicCall,
+                            "Can't call a nondeterministic native in deterministic mode.",
                         );
+                        return Err(LoxError::Runtime(error));
+                    }
+                    if let Some(max) = self.limits.max_call_depth {
+                        if self.frames.len() >= max {
+                            let error = RuntimeError::new(
+                                paren.clone(),
+                                ErrorCode::StackOverflow,
+                                "Stack overflow.",
+                            );
+                            return Err(LoxError::Runtime(error));
+                        }
+                    }
+                    if let Some(hooks) = &self.hooks {
+                        hooks.borrow_mut().on_call_enter(callable.as_ref(), paren);
                     }
-                    let ret = Some(callable.call(self, args)?);
-                    return ret;
+                    self.frames.push(CallFrame {
+                        function: callable.name(),
+                        paren: paren.clone(),
+                    });
+                    let ret = callable.call(self, args).map_err(|error| self.trace(error));
+                    self.frames.pop();
+                    // Every call entered is exited, with no result if it failed
+                    if let Some(hooks) = &self.hooks {
+                        let result = ret.as_ref().ok().and_then(Option::as_ref);
+                        hooks.borrow_mut().on_call_exit(callable.as_ref(), result);
+                    }
+                    ret
                 }
                 _ => {
-                    let error =
-                        RuntimeError::new(paren.clone(), "Can only call functions and classes");
-                    crate::runtime_error(error);
-                    panic!("Can only call functions and classes");
+                    let error = RuntimeError::new(
+                        paren.clone(),
+                        ErrorCode::NotCallable,
+                        "Can only call functions and classes.",
+                    );
+                    Err(LoxError::Runtime(error))
                 }
             }
         } else {
-            None
+            Ok(None)
         }
     }
 
-    fn visit_get_expr(&mut self, expr: &Expr) -> Option<Value> {
+    fn visit_get_expr(&mut self, expr: &Expr) -> EvalResult {
         if let Expr::Get { object, name } = expr {
             // Evaluate the object expression
-            let object_value = self.evaluate(&*object); // Dereference the Box<Expr>
+            let object_value = self.evaluate(object)?; // Dereference the Box<Expr>
 
-            // Check if the evaluated object is an instance of LoxInstance
-            match object_value {
-                Some(Value::Instance(instance)) => {
-                    // Call the get method on the LoxInstance with the property name
-
-                    return instance.borrow_mut().get(name);
-                }
-                _ => {
-                    // Throw a runtime error if the object is not an instance
-                    let runtime_error =
-                        RuntimeError::new(name.clone(), "Only instances have properties.");
-
-                    // Handle the runtime error, e.g., logging or panicking
-                    crate::runtime_error(runtime_error);
-                }
-            }
+            return Interpreter::get_property(&object_value, name);
         }
-        None
+        Ok(None)
     }
 
-    fn visit_binary_expr(&mut self, expr: &Expr) -> Option<Value> {
+    fn visit_binary_expr(&mut self, expr: &Expr) -> EvalResult {
         if let Expr::Binary {
             operator,
             left,
             right,
         } = expr
         {
-            let r = self.evaluate(&right.clone());
-            let l = self.evaluate(&left.clone());
-
-            match operator.type_ {
-                TokenType::Greater => {
-                    Interpreter::check_number_operands(&operator, l.clone(), r.clone());
-                    Some(Value::Boolean(l > r))
-                }
-                TokenType::GreaterEqual => {
-                    Interpreter::check_number_operands(&operator, l.clone(), r.clone());
-                    Some(Value::Boolean(l >= r))
-                }
-                TokenType::Less => {
-                    Interpreter::check_number_operands(&operator, l.clone(), r.clone());
-                    Some(Value::Boolean(l < r))
-                }
-                TokenType::LessEqual => {
-                    Interpreter::check_number_operands(&operator, l.clone(), r.clone());
-                    Some(Value::Boolean(l <= r))
-                }
-                TokenType::BangEqual => Some(Value::Boolean(!Interpreter::is_equal(l, r))),
-                TokenType::EqualEqual => Some(Value::Boolean(Interpreter::is_equal(l, r))),
-                TokenType::Minus => {
-                    Interpreter::check_number_operands(&operator, l.clone(), r.clone());
-                    let (Some(Value::Number(left_val)), Some(Value::Number(right_val))) = (l, r)
-                    else {
-                        todo!()
-                    };
-                    Some(Value::Number(left_val - right_val))
-                }
-                TokenType::Slash => {
-                    Interpreter::check_number_operands(&operator, l.clone(), r.clone());
-                    let (Some(Value::Number(left_val)), Some(Value::Number(right_val))) = (l, r)
-                    else {
-                        todo!()
-                    };
-                    Some(Value::Number(left_val / right_val))
-                }
-                TokenType::Star => {
-                    Interpreter::check_number_operands(&operator, l.clone(), r.clone());
-                    let (Some(Value::Number(left_val)), Some(Value::Number(right_val))) = (l, r)
-                    else {
-                        todo!()
-                    };
-                    Some(Value::Number(left_val * right_val))
-                }
-                TokenType::Plus => {
-                    match (self.evaluate(&left.clone()), self.evaluate(&right.clone())) {
-                        (Some(Value::Number(l)), Some(Value::Number(r))) => {
-                            Some(Value::Number(l + r))
-                        }
-                        (Some(Value::String(l_str)), Some(Value::String(r_str))) => {
-                            // l_str and r_str are the actual `String` values inside the `Value::String`
-                            let l = &l_str[1..(l_str.len() - 1)];
-                            let r = &r_str[1..(r_str.len() - 1)];
-                            Some(Value::String(format!("\"{}{}\"", l, r)))
-                        }
-
-                        _ => {
-                            let error =
-                                RuntimeError::new(operator.clone(), "Operand must be a number");
-                            crate::runtime_error(error);
-                            None
-                        } // Return None or handle type error appropriately
-                    }
-                }
-                _ => None,
-            }
+            let l = self.evaluate(left)?;
+            let r = self.evaluate(right)?;
+            self.binary(operator, l, r)
         } else {
-            None
+            Ok(None)
         }
     }
 
-    fn visit_variable_expr(&mut self, expr: &Expr) -> Option<Value> {
-        if let Expr::Variable { name } = expr {
+    fn visit_variable_expr(&mut self, expr: &Expr) -> EvalResult {
+        if let Expr::Variable { name, .. } = expr {
             self.lookup_variable(name, expr)
         } else {
-            None
+            Ok(None)
         }
     }
 
-    fn visit_logical_expr(&mut self, expr: &Expr) -> Option<Value> {
+    fn visit_logical_expr(&mut self, expr: &Expr) -> EvalResult {
         if let Expr::Logical {
             left,
             operator,
             right,
         } = expr
         {
-            let l = self.evaluate(&left.clone());
+            let l = self.evaluate(&left.clone())?;
             if operator.type_ == TokenType::Or {
                 if Interpreter::is_truthy(l.as_ref()) {
-                    return l;
+                    return Ok(l);
                 }
             } else {
                 if !Interpreter::is_truthy(l.as_ref()) {
-                    return l;
+                    return Ok(l);
                 }
             }
             return self.evaluate(&right.clone());
         }
-        None
+        Ok(None)
+    }
+
+    fn visit_ternary_expr(&mut self, expr: &Expr) -> EvalResult {
+        if let Expr::Ternary {
+            condition,
+            then_branch,
+            else_branch,
+        } = expr
+        {
+            let condition = self.evaluate(condition)?;
+            if Interpreter::is_truthy(condition.as_ref()) {
+                return self.evaluate(then_branch);
+            }
+            return self.evaluate(else_branch);
+        }
+        Ok(None)
+    }
+
+    fn visit_increment_expr(&mut self, expr: &Expr) -> EvalResult {
+        if let Expr::Increment {
+            operator,
+            target,
+            prefix,
+        } = expr
+        {
+            let sign = Interpreter::arithmetic_operator(operator);
+            let (old, new) = self.update(target, |interpreter, old| {
+                let is_number = matches!(old, Some(Value::Number(_) | Value::Decimal(_)));
+                #[cfg(feature = "lox-bigint")]
+                let is_number = is_number || matches!(old, Some(Value::BigInt(_)));
+                if !is_number {
+                    Interpreter::check_number_operand(operator, old.clone())?;
+                }
+                let one = interpreter.number_literal("1");
+                interpreter.binary(&sign, old, Some(one))
+            })?;
+            return Ok(if *prefix { new } else { old });
+        }
+        Ok(None)
     }
 
-    fn visit_set_expr(&mut self, expr: &Expr) -> Option<Value> {
+    fn visit_set_expr(&mut self, expr: &Expr) -> EvalResult {
         if let Expr::Set {
             object,
             name,
             value,
         } = expr
         {
-            let object_value = self.evaluate(&*object);
-
-            if let Some(Value::Instance(instance)) = object_value {
-                let value_evaluated = self.evaluate(&*value);
-
-                instance
-                    .borrow_mut()
-                    .set(name.clone(), value_evaluated.clone());
-                return value_evaluated;
-            } else {
-                let error = RuntimeError::new(name.clone(), "Operand must be a number");
-                crate::runtime_error(error);
-                return None;
-            }
+            let object_value = self.evaluate(object)?;
+            Interpreter::check_fields(&object_value, name)?;
+            let value_evaluated = self.evaluate(value)?;
+            Interpreter::set_property(&object_value, name, value_evaluated.clone())?;
+            return Ok(value_evaluated);
         }
 
-        None
+        Ok(None)
     }
 
-    fn visit_super_expr(&mut self, expr: &Expr) -> Option<Value> {
-        let distance = match self.locals.get(expr) {
-            Some(&distance) => distance,
-            None => return None, // Return None if no distance found
+    /// `super.name` finds `name` among the superclass's methods, skipping any
+    /// override in the current class. Fields belong to the instance rather
+    /// than a class, so if no method matches, a field on `this` is used.
+    fn visit_super_expr(&mut self, expr: &Expr) -> EvalResult {
+        let Expr::Super { keyword, method } = expr else {
+            return Ok(None);
         };
-        let mut token = None;
-        let mut super_method = None;
-        if let Expr::Super { keyword, method } = expr {
-            token = Some(keyword);
-            super_method = Some(method);
-        }
-        let superclass = match self.environment.borrow_mut().get_at(distance, token?) {
-            Value::Callable(instance) => instance.as_any().downcast_ref::<LoxClass>().cloned(), // Assuming superclass is of type Instance
-            _ => panic!("Expected superclass to be an instance."),
+        let Some(&distance) = self.locals.get(expr) else {
+            return Ok(None);
         };
-        let token = Token {
-            type_: TokenType::This,
-            lexeme: "this".to_string(),
-            literal: None,
-            line: 0,
+
+        let superclass = match self.environment.borrow().get_at(distance, keyword) {
+            Value::Callable(callable) => callable.as_any().downcast_ref::<LoxClass>().cloned(),
+            _ => None,
         };
-        let object = match self.environment.borrow_mut().get_at(distance, &token) {
-            Value::Instance(instance) => instance.clone(),
-            _ => panic!("Expected superclass to be an instance."),
+        let Some(superclass) = superclass else {
+            return Err(LoxError::Runtime(RuntimeError::new(
+                keyword.clone(),
+                ErrorCode::SuperclassMustBeClass,
+                "Superclass must be a class.",
+            )));
         };
-        // let supe: Rc<RefCell<LoxClass>> = superclass.borrow().klass.clone();
-        let method;
-        if let Some(lox_class) = superclass {
-            // Store the method for later use, instead of returning it immediately
-            let meth = lox_class.find_method(super_method.unwrap().lexeme.clone());
-
-            // You can now store `method` in a variable and use it later in your logic
-            if let Some(func) = meth {
-                // Store the method for later use (e.g., in a class property or another variable)
-                method = Some(func);
-            } else {
-                panic!("Undefined property '{}'.", super_method.unwrap().lexeme);
+
+        let this = Token::new(TokenType::This, "this".to_string(), None, keyword.line);
+        // `this` is bound in the scope just inside the one holding `super`
+        let object = match self.environment.borrow().get_at(distance - 1, &this) {
+            Value::Instance(instance) => instance.clone(),
+            _ => {
+                return Err(LoxError::Runtime(RuntimeError::new(
+                    keyword.clone(),
+                    ErrorCode::SuperOutsideMethod,
+                    "Can't use 'super' outside of a method.",
+                )));
             }
-        } else {
-            panic!("Superclass must be a class.");
-        }
-        // let method = class.find_method(method_name?);
+        };
 
-        if method.is_none() {
-            panic!("Undefined property in super.");
+        if let Some(function) = superclass.find_method(method.lexeme.clone()) {
+            return Ok(function.bind(object));
+        }
+        if let Some(value) = object.borrow().fields.get(&method.lexeme) {
+            return Ok(Some(value.clone()));
         }
 
-        return method?.bind(object.borrow_mut().clone());
+        let mut names = superclass.method_names();
+        names.extend(object.borrow().fields.keys().cloned());
+        let message = suggest::did_you_mean(
+            format!("Undefined property '{}'.", method.lexeme),
+            &method.lexeme,
+            names.iter().map(String::as_str),
+        );
+        Err(LoxError::Runtime(RuntimeError::new(
+            method.clone(),
+            ErrorCode::UndefinedProperty,
+            &message,
+        )))
     }
 
-    fn visit_this_expr(&mut self, expr: &Expr) -> Option<Value> {
+    fn visit_this_expr(&mut self, expr: &Expr) -> EvalResult {
         if let Expr::This { keyword } = expr {
             return self.lookup_variable(keyword, expr);
         }
-        None
+        Ok(None)
+    }
+
+    fn visit_class_expr(&mut self, expr: &Expr) -> EvalResult {
+        if let Expr::Class {
+            keyword,
+            superclass,
+            interfaces,
+            methods,
+        } = expr
+        {
+            // Anonymous classes display as "anonymous"
+            let name = Token {
+                lexeme: "anonymous".to_string(),
+                ..keyword.clone()
+            };
+            let superclass = superclass.as_deref().cloned();
+            return Ok(Some(self.class(
+                name,
+                superclass,
+                interfaces.clone(),
+                methods.clone(),
+            )?));
+        }
+        Ok(None)
+    }
+
+    fn visit_list_expr(&mut self, expr: &Expr) -> EvalResult {
+        let Expr::ListLiteral { elements, .. } = expr else {
+            return Ok(None);
+        };
+        let mut items = Vec::with_capacity(elements.len());
+        for element in elements {
+            items.push(self.evaluate(element)?.unwrap_or(Value::Nil()));
+        }
+        self.track_allocation()?;
+        Ok(Some(lox_list::new(items)))
+    }
+
+    fn visit_index_expr(&mut self, expr: &Expr) -> EvalResult {
+        let Expr::Index {
+            object,
+            bracket,
+            index,
+        } = expr
+        else {
+            return Ok(None);
+        };
+        let list = self.list(object, bracket)?;
+        let index = self.evaluate(index)?;
+        Ok(Some(lox_list::index(&list, bracket, index)?))
+    }
+
+    fn visit_index_set_expr(&mut self, expr: &Expr) -> EvalResult {
+        let Expr::IndexSet {
+            object,
+            bracket,
+            index,
+            value,
+        } = expr
+        else {
+            return Ok(None);
+        };
+        let list = self.list(object, bracket)?;
+        let index = self.evaluate(index)?;
+        let value = self.evaluate(value)?.unwrap_or(Value::Nil());
+        lox_list::set_index(&list, bracket, index, value.clone())?;
+        Ok(Some(value))
     }
 }
 
 impl StmtVisitor for Interpreter {
-    fn visit_block_stmt(&mut self, stmts: Vec<Stmt>) -> Option<ReturnValue> {
+    fn visit_block_stmt(&mut self, stmts: Vec<Stmt>) -> ExecResult {
         let new_environment = Rc::new(RefCell::new(Environment::new(Some(
             self.environment.clone(),
         ))));
@@ -425,74 +830,38 @@ impl StmtVisitor for Interpreter {
         &mut self,
         name: Token,
         superclass: Option<Expr>,
-        ref methods: Vec<Stmt>,
-    ) -> Option<ReturnValue> {
-        let mut supclass = None;
-        let mut downcast_superclass = None;
-        if let Some(ref superclass_expr) = superclass {
-            // Evaluate the superclass expression
-            let evaluated_superclass = self.evaluate(superclass_expr);
-            supclass = evaluated_superclass.clone();
-            // Check if it's a LoxClass
-            if let Some(Value::Callable(class)) = evaluated_superclass {
-                // Downcast using the as_any method
-                // Successfully downcasted to LoxClass
-                if let Some(lox_class) = class.as_any().downcast_ref::<LoxClass>() {
-                    // Successfully downcasted to LoxClass, now pass it to the function
-                    downcast_superclass = Some(lox_class.clone());
-                } else {
-                    panic!("Superclass must be a class.");
-                }
-            } else {
-                panic!("Superclass must be a class.");
-            }
-        }
-
+        interfaces: Vec<Expr>,
+        methods: Vec<Stmt>,
+    ) -> ExecResult {
         self.environment
             .borrow_mut()
             .define(name.lexeme.clone(), None);
 
-        if let Some(ref _superclass) = superclass {
-            self.environment = Rc::new(RefCell::new(Environment::new(Some(
-                self.environment.clone(),
-            ))));
-            self.environment
-                .borrow_mut()
-                .define("super".to_string(), supclass.clone());
-        }
+        let klass = self.class(name.clone(), superclass, interfaces, methods)?;
 
-        let mut meths: HashMap<String, LoxFunction> = HashMap::new();
-        for method in methods {
-            match method {
-                Stmt::Function {
-                    name,
-                    params: _,
-                    body: _,
-                } => {
-                    let function = LoxFunction::new(
-                        method.clone(),
-                        Rc::new(RefCell::new(self.environment.borrow_mut().clone())), //self.environment.clone(),
-                        name.lexeme == "init",
-                    );
-                    meths.insert(name.lexeme.clone(), function);
-                }
-                _ => {}
-            }
+        if let Some(hooks) = &self.hooks {
+            hooks.borrow_mut().on_var_define(&name, Some(&klass));
         }
-        let klass = Value::Callable(Box::new(LoxClass::new(
-            meths,
-            Stmt::Class {
-                name: name.clone(),
-                superclass: superclass.clone(),
-                methods: methods.clone(),
-            },
-            Rc::new(RefCell::new(self.environment.borrow_mut().clone())),
-            name.lexeme.clone(),
-            downcast_superclass,
-        )));
+        self.environment.borrow_mut().assign(name, klass)?;
+        Ok(None)
+    }
 
-        self.environment.borrow_mut().assign(name, klass);
-        None
+    fn visit_interface_stmt(
+        &mut self,
+        name: Token,
+        methods: Vec<(Token, Vec<Token>)>,
+    ) -> ExecResult {
+        let interface = Value::Callable(Box::new(LoxInterface::new(Stmt::Interface {
+            name: name.clone(),
+            methods,
+        })));
+        if let Some(hooks) = &self.hooks {
+            hooks.borrow_mut().on_var_define(&name, Some(&interface));
+        }
+        self.environment
+            .borrow_mut()
+            .define(name.lexeme, Some(interface));
+        Ok(None)
     }
 
     fn visit_function_stmt(
@@ -500,20 +869,23 @@ impl StmtVisitor for Interpreter {
         name: Token,
         params: Vec<Token>,
         body: Vec<Stmt>,
-    ) -> Option<ReturnValue> {
+    ) -> ExecResult {
         let function = Value::Callable(Box::new(LoxFunction::new(
             Stmt::Function {
                 name: name.clone(),
                 params,
                 body,
             },
-            Rc::new(RefCell::new(self.environment.borrow_mut().clone())),
+            self.environment.clone(),
             false,
         )));
+        if let Some(hooks) = &self.hooks {
+            hooks.borrow_mut().on_var_define(&name, Some(&function));
+        }
         self.environment
             .borrow_mut()
             .define(name.lexeme.clone(), Some(function));
-        None
+        Ok(None)
     }
 
     fn visit_if_stmt(
@@ -521,30 +893,34 @@ impl StmtVisitor for Interpreter {
         condition: Expr,
         then_branch: Box<Stmt>,
         else_branch: Box<Option<Stmt>>,
-    ) -> Option<ReturnValue> {
-        if Interpreter::is_truthy(self.evaluate(&condition).as_ref()) {
+    ) -> ExecResult {
+        if Interpreter::is_truthy(self.evaluate(&condition)?.as_ref()) {
             return self.execute(Some(*then_branch));
         } else if let Some(else_branch) = *else_branch {
             return self.execute(Some(else_branch));
         }
-        None
+        Ok(None)
     }
 
-    fn visit_return_stmt(&mut self, _keyword: Token, value: Option<Expr>) -> Option<ReturnValue> {
+    fn visit_return_stmt(&mut self, _keyword: Token, value: Option<Expr>) -> ExecResult {
         let return_value;
         if let Some(expr) = value {
-            return_value = self.evaluate(&expr);
+            return_value = self.evaluate(&expr)?;
         } else {
             return_value = Some(Value::Nil());
         }
-        Some(ReturnValue::new(return_value?))
+        Ok(return_value.map(ReturnValue::new))
     }
 
-    fn visit_var_stmt(&mut self, name: Token, initializer: Option<Expr>) -> Option<ReturnValue> {
+    fn visit_var_stmt(&mut self, name: Token, initializer: Option<Expr>) -> ExecResult {
         let mut value = None;
         // Evaluate the initializer if it exists
         if let Some(init) = initializer {
-            value = self.evaluate(&init);
+            value = self.evaluate(&init)?;
+        }
+
+        if let Some(hooks) = &self.hooks {
+            hooks.borrow_mut().on_var_define(&name, value.as_ref());
         }
 
         // Define the variable in the environment
@@ -552,58 +928,787 @@ impl StmtVisitor for Interpreter {
             .borrow_mut()
             .define(name.lexeme.clone(), value);
 
-        None
+        Ok(None)
     }
 
-    fn visit_while_stmt(&mut self, condition: Expr, body: Box<Stmt>) -> Option<ReturnValue> {
+    fn visit_while_stmt(&mut self, condition: Expr, body: Box<Stmt>) -> ExecResult {
         let previous_environment = self.environment.clone();
-        while Interpreter::is_truthy(self.evaluate(&condition).as_ref()) {
-            let ret = self.execute(Some(*body.clone()));
+        while Interpreter::is_truthy(self.evaluate(&condition)?.as_ref()) {
+            let ret = self.execute(Some(*body.clone()))?;
             if let Some(ReturnValue { value }) = ret {
                 self.environment = previous_environment;
-                return Some(ReturnValue::new(value));
+                return Ok(Some(ReturnValue::new(value)));
             }
         }
         self.environment = previous_environment;
-        None
+        Ok(None)
+    }
+
+    fn visit_using_stmt(
+        &mut self,
+        keyword: Token,
+        name: Token,
+        initializer: Expr,
+        body: Box<Stmt>,
+    ) -> ExecResult {
+        let Some(resource) = self.evaluate(&initializer)? else {
+            return Ok(None);
+        };
+
+        // Look up close() before running the body, so a resource that can't
+        // be closed is never used
+        let close_token = Token {
+            type_: TokenType::Identifier,
+            lexeme: "close".to_string(),
+            ..keyword.clone()
+        };
+        let close = match &resource {
+            Value::Instance(instance) => LoxInstance::get(instance, &close_token)?,
+            Value::NativeInstance(instance) => native_instance::get(instance, &close_token)?,
+            Value::Nil() => None,
+            _ => {
+                let error = RuntimeError::new(
+                    keyword.clone(),
+                    ErrorCode::InvalidResource,
+                    "Only instances can be used as resources.",
+                );
+                return Err(LoxError::Runtime(error));
+            }
+        };
+        let close = match close {
+            Some(Value::Callable(close)) if close.arity() == 0 => Some(close),
+            None => None,
+            _ => {
+                let error = RuntimeError::new(
+                    keyword,
+                    ErrorCode::InvalidResource,
+                    "A resource's 'close' must be a method that takes no arguments.",
+                );
+                return Err(LoxError::Runtime(error));
+            }
+        };
+
+        let environment = Rc::new(RefCell::new(Environment::new(Some(
+            self.environment.clone(),
+        ))));
+        environment
+            .borrow_mut()
+            .define(name.lexeme.clone(), Some(resource));
+
+        // Close the resource even if the body stops with an error
+        let result = self.execute_block(&[*body], environment);
+        if let Some(mut close) = close {
+            close.call(self, Vec::new())?;
+        }
+        result
+    }
+
+    fn visit_defer_stmt(&mut self, _keyword: Token, body: Box<Stmt>) -> ExecResult {
+        if let Some(frame) = self.deferred.last_mut() {
+            frame.push((*body, self.environment.clone()));
+        }
+        Ok(None)
     }
 
-    fn visit_expression_stmt(&mut self, expr: Expr) -> Option<ReturnValue> {
-        self.evaluate(&expr); // Assuming evaluate returns Option<Value>
-        None
+    fn visit_expression_stmt(&mut self, expr: Expr) -> ExecResult {
+        self.evaluate(&expr)?;
+        Ok(None)
     }
 
-    fn visit_print_stmt(&mut self, expr: Expr) -> Option<ReturnValue> {
-        if let Some(value) = self.evaluate(&expr) {
+    fn visit_print_stmt(&mut self, expr: Expr) -> ExecResult {
+        if let Some(value) = self.evaluate(&expr)? {
             let _ = write_output(&self.output_file, &self.stringify(Some(value)));
         } else {
             // Handle evaluation error if needed, for example:
             eprintln!("Failed to evaluate expression.");
         }
-        None
+        Ok(None)
     }
 }
 
 impl Interpreter {
+    /// An interpreter with the natives and the prelude's helpers defined.
     pub fn new(output_file: &str) -> Self {
+        let mut interpreter = prelude::load(Interpreter::without_prelude(output_file));
+        interpreter.prelude = true;
+        interpreter
+    }
+
+    /// Whether this interpreter was made by `new` rather than
+    /// `without_prelude`.
+    pub fn has_prelude(&self) -> bool {
+        self.prelude
+    }
+
+    /// An interpreter with only the natives defined.
+    pub fn without_prelude(output_file: &str) -> Self {
         let globals = Rc::new(RefCell::new(Environment::new(None)));
         globals.borrow_mut().define(
             "clock".to_string(),
             Some(Value::Callable(Box::new(native_functions::Clock))),
         );
+        globals.borrow_mut().define(
+            "random".to_string(),
+            Some(Value::Callable(Box::new(native_functions::RandomFn))),
+        );
+        globals.borrow_mut().define(
+            "weakRef".to_string(),
+            Some(Value::Callable(Box::new(native_functions::WeakRefFn))),
+        );
+        globals.borrow_mut().define(
+            "isNaN".to_string(),
+            Some(Value::Callable(Box::new(native_functions::IsNaN))),
+        );
+        globals.borrow_mut().define(
+            "isFinite".to_string(),
+            Some(Value::Callable(Box::new(native_functions::IsFinite))),
+        );
+        for (name, function) in [("sqrt", f64::sqrt as fn(f64) -> f64), ("log", f64::ln)] {
+            globals.borrow_mut().define(
+                name.to_string(),
+                Some(Value::Callable(Box::new(native_functions::MathFn {
+                    function,
+                }))),
+            );
+        }
+        for (name, level) in [
+            ("logInfo", LogLevel::Info),
+            ("logWarn", LogLevel::Warn),
+            ("logError", LogLevel::Error),
+        ] {
+            globals.borrow_mut().define(
+                name.to_string(),
+                Some(Value::Callable(Box::new(native_functions::Log { level }))),
+            );
+        }
+        globals.borrow_mut().define(
+            "globals".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Globals))),
+        );
+        globals.borrow_mut().define(
+            "locals".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Locals))),
+        );
+        globals.borrow_mut().define(
+            "stackTrace".to_string(),
+            Some(Value::Callable(Box::new(native_functions::StackTrace))),
+        );
+        globals.borrow_mut().define(
+            "bindArgs".to_string(),
+            Some(Value::Callable(Box::new(native_functions::BindArgs))),
+        );
+        globals.borrow_mut().define(
+            "compose".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Compose))),
+        );
+        globals.borrow_mut().define(
+            "pipe".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Pipe))),
+        );
+        for (name, repeat) in [("setTimeout", false), ("setInterval", true)] {
+            globals.borrow_mut().define(
+                name.to_string(),
+                Some(Value::Callable(Box::new(native_functions::SetTimer {
+                    repeat,
+                }))),
+            );
+        }
+        for name in ["clearTimeout", "clearInterval"] {
+            globals.borrow_mut().define(
+                name.to_string(),
+                Some(Value::Callable(Box::new(native_functions::ClearTimer))),
+            );
+        }
+        #[cfg(feature = "lox-threads")]
+        {
+            use crate::threads;
+            let natives: [(&str, Box<dyn Callable>); 9] = [
+                ("spawn", Box::new(threads::Spawn)),
+                ("channel", Box::new(threads::ChannelFn)),
+                ("send", Box::new(threads::Send)),
+                ("receive", Box::new(threads::Receive)),
+                ("mutex", Box::new(threads::MutexFn)),
+                ("lock", Box::new(threads::Lock)),
+                ("unlock", Box::new(threads::Unlock)),
+                ("atomic", Box::new(threads::AtomicFn)),
+                ("atomicAdd", Box::new(threads::AtomicAdd)),
+            ];
+            for (name, native) in natives {
+                globals
+                    .borrow_mut()
+                    .define(name.to_string(), Some(Value::Callable(native)));
+            }
+        }
+        globals.borrow_mut().define(
+            "memoize".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Memoize))),
+        );
+        globals.borrow_mut().define(
+            "className".to_string(),
+            Some(Value::Callable(Box::new(native_functions::ClassName))),
+        );
+        globals.borrow_mut().define(
+            "superclassOf".to_string(),
+            Some(Value::Callable(Box::new(native_functions::SuperclassOf))),
+        );
+        globals.borrow_mut().define(
+            "inspect".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Inspect))),
+        );
+        globals.borrow_mut().define(
+            "diff".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Diff))),
+        );
+        globals.borrow_mut().define(
+            "expectEqual".to_string(),
+            Some(Value::Callable(Box::new(native_functions::ExpectEqual))),
+        );
+        globals.borrow_mut().define(
+            "test".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Test))),
+        );
+        globals.borrow_mut().define(
+            "format".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Format))),
+        );
+        globals.borrow_mut().define(
+            "template".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Template))),
+        );
+        globals.borrow_mut().define(
+            "write".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Write))),
+        );
+        globals.borrow_mut().define(
+            "eprintln".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Eprintln))),
+        );
+        globals.borrow_mut().define(
+            "exit".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Exit))),
+        );
+        globals.borrow_mut().define(
+            "charCode".to_string(),
+            Some(Value::Callable(Box::new(native_functions::CharCode))),
+        );
+        globals.borrow_mut().define(
+            "fromCharCode".to_string(),
+            Some(Value::Callable(Box::new(native_functions::FromCharCode))),
+        );
+        globals.borrow_mut().define(
+            "chars".to_string(),
+            Some(Value::Callable(Box::new(native_functions::Chars))),
+        );
+        for (name, value) in native_functions::NUMBER_CONSTANTS {
+            globals
+                .borrow_mut()
+                .define(name.to_string(), Some(Value::Number(value)));
+        }
         Interpreter {
             environment: globals.clone(),
             globals,
             output_file: output_file.to_string(),
             locals: HashMap::new(),
+            hooks: None,
+            reporter: Rc::new(RefCell::new(StderrReporter)),
+            cancellation: None,
+            limits: InterpreterLimits::default(),
+            log_level: LogLevel::default(),
+            prelude: false,
+            decimal_literals: false,
+            checked_division: false,
+            checked_integers: false,
+            strict_math: false,
+            deterministic: false,
+            random: Random::from_entropy(),
+            run: SharedRun::new(),
+            frames: Vec::new(),
+            allocations: 0,
+            deferred: Vec::new(),
+            tests: TestCounts::default(),
+            timers: Timers::default(),
+        }
+    }
+
+    pub fn set_hooks(&mut self, hooks: Rc<RefCell<dyn InterpreterHooks>>) {
+        self.hooks = Some(hooks);
+    }
+
+    pub fn set_reporter(&mut self, reporter: Rc<RefCell<dyn ErrorReporter>>) {
+        self.reporter = reporter;
+    }
+
+    /// Where diagnostics for errors that stop a run are sent.
+    pub fn reporter(&self) -> Rc<RefCell<dyn ErrorReporter>> {
+        self.reporter.clone()
+    }
+
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation = Some(token);
+    }
+
+    pub fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
+    pub fn set_limits(&mut self, limits: InterpreterLimits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &InterpreterLimits {
+        &self.limits
+    }
+
+    /// Fails with a runtime error at the call unless the limits allow
+    /// `permission`. Natives call this before touching the filesystem, the
+    /// network, or other processes.
+    pub fn require(&self, permission: Permission) -> Result<(), LoxError> {
+        if self.limits.allows(permission) {
+            return Ok(());
+        }
+        Err(self.native_error(ErrorCode::PermissionDenied, permission.denied_message()))
+    }
+
+    pub fn set_log_level(&mut self, level: LogLevel) {
+        self.log_level = level;
+    }
+
+    /// The least severe message the logging natives write.
+    pub fn log_level(&self) -> LogLevel {
+        self.log_level
+    }
+
+    /// Where `print` writes: a file path, or stdout if empty.
+    pub fn output_file(&self) -> &str {
+        &self.output_file
+    }
+
+    /// Makes number literals with a fractional part exact decimals. See
+    /// `decimal`.
+    pub fn set_decimal_literals(&mut self, enabled: bool) {
+        self.decimal_literals = enabled;
+    }
+
+    pub fn decimal_literals(&self) -> bool {
+        self.decimal_literals
+    }
+
+    /// Makes dividing a number by zero raise a "Division by zero." runtime
+    /// error instead of giving infinity or NaN.
+    pub fn set_checked_division(&mut self, enabled: bool) {
+        self.checked_division = enabled;
+    }
+
+    pub fn checked_division(&self) -> bool {
+        self.checked_division
+    }
+
+    /// Makes `+`, `-`, and `*` on two integers raise an "Integer overflow."
+    /// runtime error when the exact result is beyond 2^53 - 1 either way,
+    /// past which a number can no longer hold every integer. Otherwise such
+    /// results round to the nearest number, or with `lox-bigint` become big
+    /// integers.
+    pub fn set_checked_integers(&mut self, enabled: bool) {
+        self.checked_integers = enabled;
+    }
+
+    pub fn checked_integers(&self) -> bool {
+        self.checked_integers
+    }
+
+    /// Makes arithmetic and the math natives raise a runtime error when they
+    /// give NaN or an infinity from finite numbers, as `0 / 0`, `sqrt(-1)`,
+    /// and `log(0)` do. NaN and the infinities passed in still go through.
+    pub fn set_strict_math(&mut self, enabled: bool) {
+        self.strict_math = enabled;
+    }
+
+    pub fn strict_math(&self) -> bool {
+        self.strict_math
+    }
+
+    /// The error strict math raises for `result`, computed from `operands`,
+    /// if any.
+    pub(crate) fn math_error(&self, operands: &[f64], result: f64) -> Option<&'static str> {
+        if !self.strict_math || result.is_finite() || !operands.iter().all(|n| n.is_finite()) {
+            return None;
+        }
+        Some(if result.is_nan() {
+            "Math result is NaN."
+        } else {
+            "Math result is infinite."
+        })
+    }
+
+    /// Makes runs repeat exactly: `clock()` and log timestamps always give
+    /// `DETERMINISTIC_TIME`, `random()` starts from a fixed seed, and calling
+    /// a native that isn't `Callable::deterministic` is a runtime error.
+    pub fn set_deterministic(&mut self, enabled: bool) {
+        self.deterministic = enabled;
+        self.random = self.fresh_random();
+    }
+
+    /// A generator for `random()` to start from, seeded as the mode asks.
+    fn fresh_random(&self) -> Random {
+        if self.deterministic {
+            Random::new(DETERMINISTIC_SEED)
+        } else {
+            Random::from_entropy()
+        }
+    }
+
+    /// Whether deterministic mode is on, for natives that call functions
+    /// themselves.
+    pub(crate) fn deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// The current time, as `clock()` and the log natives see it.
+    pub fn now(&self) -> SystemTime {
+        if self.deterministic {
+            // Moving on as timers fire
+            UNIX_EPOCH + DETERMINISTIC_TIME + self.timers.now(true)
+        } else {
+            SystemTime::now()
+        }
+    }
+
+    /// The next number `random()` gives.
+    pub fn random(&mut self) -> f64 {
+        self.random.next_f64()
+    }
+
+    /// A copy of this interpreter for a separate run that prints to
+    /// `output_file`. Its globals start as a copy of these, and settings and
+    /// resolved variables carry over, but per-run state starts afresh.
+    pub fn fork(&self, output_file: &str) -> Interpreter {
+        let globals = Rc::new(RefCell::new(self.globals.borrow().clone()));
+        Interpreter {
+            environment: globals.clone(),
+            globals,
+            output_file: output_file.to_string(),
+            locals: self.locals.clone(),
+            hooks: self.hooks.clone(),
+            reporter: self.reporter.clone(),
+            cancellation: self.cancellation.clone(),
+            limits: self.limits.clone(),
+            log_level: self.log_level,
+            prelude: self.prelude,
+            decimal_literals: self.decimal_literals,
+            checked_division: self.checked_division,
+            checked_integers: self.checked_integers,
+            strict_math: self.strict_math,
+            deterministic: self.deterministic,
+            random: self.fresh_random(),
+            run: SharedRun::new(),
+            frames: Vec::new(),
+            allocations: 0,
+            deferred: Vec::new(),
+            tests: TestCounts::default(),
+            timers: Timers::default(),
+        }
+    }
+
+    /// Resets per-run state before executing a new program.
+    pub fn begin_run(&mut self) {
+        self.environment = self.globals.clone();
+        self.run = SharedRun::new();
+        self.allocations = 0;
+        self.frames.clear();
+        self.deferred.clear();
+        self.timers = Timers::default();
+    }
+
+    /// The calls in progress, outermost first.
+    pub fn call_stack(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    /// Every scope from the current one out to the globals, each headed by
+    /// its depth (`depth 0` is the innermost) and then `globals`, with a
+    /// `name = value` line for each variable, rendered by `inspect`. The
+    /// natives, number constants, and prelude functions every interpreter
+    /// defines are left out of the globals.
+    pub fn dump_environment(&self) -> String {
+        let prelude = prelude::statements();
+        let mut dump = String::new();
+        let mut environment = Some(self.environment.clone());
+        let mut depth = 0;
+        while let Some(scope) = environment {
+            let is_globals = Rc::ptr_eq(&scope, &self.globals);
+            if is_globals {
+                dump.push_str("globals\n");
+            } else {
+                dump.push_str(&format!("depth {}\n", depth));
+            }
+            let scope = scope.borrow();
+            for (name, value) in scope.values.iter() {
+                let value = value.clone().unwrap_or(Value::Nil());
+                if is_globals && Interpreter::is_builtin(name, &value, &prelude) {
+                    continue;
+                }
+                dump.push_str(&format!(
+                    "  {} = {}\n",
+                    name,
+                    inspect::inspect(self, &value)
+                ));
+            }
+            environment = scope.enclosing.clone();
+            depth += 1;
+        }
+        dump
+    }
+
+    /// Whether the global `name` holding `value` is one the interpreter
+    /// defines itself rather than one a script made.
+    fn is_builtin(name: &str, value: &Value, prelude: &[Stmt]) -> bool {
+        match value {
+            Value::Callable(callable) => {
+                let any = callable.as_any();
+                match any.downcast_ref::<LoxFunction>() {
+                    Some(function) => prelude.contains(&function.declaration),
+                    None => {
+                        !any.is::<LoxClass>()
+                            && !any.is::<LoxInterface>()
+                            && !any.is::<native_functions::BoundFunction>()
+                    }
+                }
+            }
+            _ => native_functions::NUMBER_CONSTANTS
+                .iter()
+                .any(|(constant, _)| *constant == name),
+        }
+    }
+
+    /// How many calls to the `test` native have passed and failed.
+    pub fn test_counts(&self) -> TestCounts {
+        self.tests
+    }
+
+    pub(crate) fn record_test(&mut self, passed: bool) {
+        if passed {
+            self.tests.passed += 1;
+        } else {
+            self.tests.failed += 1;
+        }
+    }
+
+    /// Applies a binary operator to two already evaluated operands.
+    fn binary(&mut self, operator: &Token, l: Option<Value>, r: Option<Value>) -> EvalResult {
+        if self.checked_division
+            && operator.type_ == TokenType::Slash
+            && Interpreter::is_numeric(&l)
+            && matches!(r, Some(Value::Number(divisor)) if divisor == 0.0)
+        {
+            let error = RuntimeError::new(
+                operator.clone(),
+                ErrorCode::DivisionByZero,
+                "Division by zero.",
+            );
+            return Err(LoxError::Runtime(error));
+        }
+        if self.checked_integers && Interpreter::integer_overflows(&operator.type_, &l, &r) {
+            let error = RuntimeError::new(
+                operator.clone(),
+                ErrorCode::IntegerOverflow,
+                "Integer overflow.",
+            );
+            return Err(LoxError::Runtime(error));
+        }
+
+        #[cfg(feature = "lox-bigint")]
+        if let (Some(l), Some(r)) = (&l, &r) {
+            if let Some(result) = crate::bigint::binary(&operator.type_, l, r) {
+                return self.checked_result(operator, l, r, result);
+            }
+        }
+        if let (Some(l), Some(r)) = (&l, &r) {
+            if let Some(result) = crate::decimal::binary(&operator.type_, l, r) {
+                return self.checked_result(operator, l, r, result);
+            }
+        }
+
+        match operator.type_ {
+            TokenType::Greater => {
+                Interpreter::check_number_operands(operator, l.clone(), r.clone())?;
+                Ok(Some(Value::Boolean(l > r)))
+            }
+            TokenType::GreaterEqual => {
+                Interpreter::check_number_operands(operator, l.clone(), r.clone())?;
+                Ok(Some(Value::Boolean(l >= r)))
+            }
+            TokenType::Less => {
+                Interpreter::check_number_operands(operator, l.clone(), r.clone())?;
+                Ok(Some(Value::Boolean(l < r)))
+            }
+            TokenType::LessEqual => {
+                Interpreter::check_number_operands(operator, l.clone(), r.clone())?;
+                Ok(Some(Value::Boolean(l <= r)))
+            }
+            TokenType::Is => Interpreter::is_instance(operator, l, r),
+            TokenType::BangEqual => Ok(Some(Value::Boolean(!Interpreter::is_equal(l, r)))),
+            TokenType::EqualEqual => Ok(Some(Value::Boolean(Interpreter::is_equal(l, r)))),
+            TokenType::Minus => {
+                Interpreter::check_number_operands(operator, l.clone(), r.clone())?;
+                let (Some(Value::Number(left_val)), Some(Value::Number(right_val))) = (l, r) else {
+                    todo!()
+                };
+                self.arithmetic(operator, left_val, right_val, left_val - right_val)
+            }
+            TokenType::Slash => {
+                Interpreter::check_number_operands(operator, l.clone(), r.clone())?;
+                let (Some(Value::Number(left_val)), Some(Value::Number(right_val))) = (l, r) else {
+                    todo!()
+                };
+                self.arithmetic(operator, left_val, right_val, left_val / right_val)
+            }
+            TokenType::Star => {
+                Interpreter::check_number_operands(operator, l.clone(), r.clone())?;
+                let (Some(Value::Number(left_val)), Some(Value::Number(right_val))) = (l, r) else {
+                    todo!()
+                };
+                self.arithmetic(operator, left_val, right_val, left_val * right_val)
+            }
+            TokenType::Plus => {
+                match (l, r) {
+                    (Some(Value::Number(l)), Some(Value::Number(r))) => {
+                        self.arithmetic(operator, l, r, l + r)
+                    }
+                    (Some(Value::String(l_str)), Some(Value::String(r_str))) => {
+                        // l_str and r_str are the actual `String` values inside the `Value::String`
+                        let l = &l_str[1..(l_str.len() - 1)];
+                        let r = &r_str[1..(r_str.len() - 1)];
+                        Ok(Some(Value::String(format!("\"{}{}\"", l, r))))
+                    }
+
+                    _ => {
+                        let error = RuntimeError::new(
+                            operator.clone(),
+                            ErrorCode::OperandsMustBeNumbersOrStrings,
+                            "Operands must be two numbers or two strings.",
+                        );
+                        Err(LoxError::Runtime(error))
+                    }
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The result of arithmetic on `left` and `right`, checked for strict
+    /// math.
+    fn arithmetic(&self, operator: &Token, left: f64, right: f64, result: f64) -> EvalResult {
+        match self.math_error(&[left, right], result) {
+            Some(message) => {
+                let error =
+                    RuntimeError::new(operator.clone(), ErrorCode::NonFiniteResult, message);
+                Err(LoxError::Runtime(error))
+            }
+            None => Ok(Some(Value::Number(result))),
+        }
+    }
+
+    /// A result from the decimal or big integer rules, checked like
+    /// `arithmetic` when it fell back to a float.
+    fn checked_result(
+        &self,
+        operator: &Token,
+        left: &Value,
+        right: &Value,
+        result: Value,
+    ) -> EvalResult {
+        match (&result, left.as_f64(), right.as_f64()) {
+            (Value::Number(n), Some(a), Some(b)) => self.arithmetic(operator, a, b, *n),
+            _ => Ok(Some(result)),
+        }
+    }
+
+    /// A runtime error raised by a native, reported at the closing
+    /// parenthesis of the call to it, as errors in the call itself are.
+    pub(crate) fn native_error(&self, code: ErrorCode, message: &str) -> LoxError {
+        let token = match self.frames.last() {
+            Some(frame) => frame.paren.clone(),
+            None => Token::new(TokenType::Identifier, String::new(), None, 0),
+        };
+        LoxError::Runtime(RuntimeError::new(token, code, message))
+    }
+
+    /// Evaluates the list that `object[...]` indexes.
+    fn list(&mut self, object: &Expr, bracket: &Token) -> Result<lox_list::List, LoxError> {
+        match self.evaluate(object)? {
+            Some(Value::List(list)) => Ok(list),
+            _ => {
+                let error = RuntimeError::new(
+                    bracket.clone(),
+                    ErrorCode::NotAList,
+                    "Only lists can be indexed.",
+                );
+                Err(LoxError::Runtime(error))
+            }
+        }
+    }
+
+    /// Counts a newly allocated instance, list, or other object against
+    /// `max_allocations`. Natives that make objects call this too.
+    pub fn track_allocation(&mut self) -> Result<(), LoxError> {
+        self.allocations += 1;
+        if let Some(max) = self.limits.max_allocations {
+            if self.allocations > max {
+                return Err(LoxError::LimitExceeded(format!(
+                    "Allocation limit of {} exceeded.",
+                    max
+                )));
+            }
         }
+        Ok(())
     }
 
-    fn evaluate(&mut self, expr: &Expr) -> Option<Value> {
+    /// Counts one instruction, stopping the run if it has been cancelled or
+    /// has gone past a limit.
+    fn step(&mut self) -> Result<(), LoxError> {
+        let instructions = self.run.step();
+        if let Some(max) = self.limits.max_instructions {
+            if instructions > max {
+                return Err(LoxError::LimitExceeded(format!(
+                    "Instruction limit of {} exceeded.",
+                    max
+                )));
+            }
+        }
+        self.check_interrupts()
+    }
+
+    /// Stops the run if it has been cancelled or has run out of time, or if
+    /// it is a worker's and the script that spawned it has ended.
+    pub(crate) fn check_interrupts(&self) -> Result<(), LoxError> {
+        if let Some(token) = &self.cancellation {
+            if token.is_cancelled() {
+                return Err(LoxError::Cancelled);
+            }
+        }
+        if self.run.has_ended() {
+            return Err(LoxError::Cancelled);
+        }
+        if let Some(max) = self.limits.max_duration {
+            if self.run.elapsed() > max {
+                return Err(LoxError::LimitExceeded(format!(
+                    "Time limit of {} ms exceeded.",
+                    max.as_millis()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> EvalResult {
         expr.accept_interp(self) // Call accept to recursively evaluate the expression
     }
 
-    fn execute(&mut self, stmt: Option<Stmt>) -> Option<ReturnValue> {
+    fn execute(&mut self, stmt: Option<Stmt>) -> ExecResult {
+        self.step()?;
+        if let (Some(hooks), Some(stmt)) = (&self.hooks, &stmt) {
+            hooks.borrow_mut().on_statement(stmt);
+        }
         stmt.clone().expect("REASON").accept(self)
     }
 
@@ -611,53 +1716,88 @@ impl Interpreter {
         self.locals.insert(expr.clone(), depth);
     }
 
+    /// Everything `resolve` has recorded, leaving none behind.
+    pub(crate) fn take_locals(&mut self) -> Vec<(Expr, usize)> {
+        self.locals.drain().collect()
+    }
+
+    /// The budget of the current run, for the workers it spawns to share.
+    #[cfg(feature = "lox-threads")]
+    pub(crate) fn shared_run(&self) -> &SharedRun {
+        &self.run
+    }
+
+    /// Counts this interpreter's instructions and time against `run`, which
+    /// a worker shares with the interpreter that spawned it.
+    #[cfg(feature = "lox-threads")]
+    pub(crate) fn share_run(&mut self, run: SharedRun) {
+        self.run = run;
+    }
+
+    /// A copy of the scope depths the resolver has recorded, for a worker
+    /// running the same declarations.
+    #[cfg(feature = "lox-threads")]
+    pub(crate) fn locals(&self) -> Vec<(Expr, usize)> {
+        self.locals
+            .iter()
+            .map(|(expr, depth)| (expr.clone(), *depth))
+            .collect()
+    }
+
     pub fn execute_block(
         &mut self,
         statements: &[Stmt],
         environment: Rc<RefCell<Environment>>,
-    ) -> Option<ReturnValue> {
+    ) -> ExecResult {
         // Store the current environment
         let previous = std::mem::replace(&mut self.environment, environment.clone());
-        // Execute statements in the new environment
+        // Execute statements in the new environment, stopping at a return or an error
+        let mut result = Ok(None);
         for statement in statements {
-            let result = self.execute(Some(statement.clone()));
-            match result {
-                Some(ReturnValue { ref value }) => {
-                    //std::mem::replace(&mut self.environment, previous.clone());
-                    self.environment = previous;
-                    return Some(ReturnValue::new(value.clone()));
-                }
-                _ => (),
+            result = self.execute(Some(statement.clone()));
+            if !matches!(result, Ok(None)) {
+                break;
             }
         }
 
         // Restore the previous environment
-        // std::mem::replace(&mut self.environment, previous.clone());
         self.environment = previous;
-        None
+        result
     }
 
     pub fn execute_function_block(
         &mut self,
         statements: &[Stmt],
         environment: Rc<RefCell<Environment>>,
-    ) -> Option<ReturnValue> {
+    ) -> ExecResult {
         let previous = std::mem::replace(&mut self.environment, environment.clone());
+        self.deferred.push(Vec::new());
 
+        let mut result = Ok(None);
         for statement in statements {
-            let result = self.execute(Some(statement.clone()));
-            if let Some(ReturnValue { ref value }) = result {
-                // Restore the previous environment before returning
-                // std::mem::replace(&mut self.environment, previous.clone());
-                self.environment = previous.clone();
-                return Some(ReturnValue::new(value.clone()));
+            result = self.execute(Some(statement.clone()));
+            if !matches!(result, Ok(None)) {
+                break;
+            }
+        }
+
+        // Deferred statements run last-in first-out, after any return value
+        // is evaluated, and even when the body or another deferred statement
+        // fails. The resolver rejects `return` inside them. The first error
+        // is the one reported.
+        let deferred = self.deferred.pop().unwrap_or_default();
+        for (statement, environment) in deferred.into_iter().rev() {
+            self.environment = environment;
+            if let Err(error) = self.execute(Some(statement)) {
+                if result.is_ok() {
+                    result = Err(error);
+                }
             }
         }
 
         // Restore the previous environment after executing all statements
-        // std::mem::replace(&mut self.environment, previous);
-        self.environment = previous.clone();
-        None
+        self.environment = previous;
+        result
     }
 
     fn _parse_string(&self, s: &str) -> Option<Value> {
@@ -684,7 +1824,7 @@ impl Interpreter {
         }
     }
 
-    fn is_equal(a: Option<Value>, b: Option<Value>) -> bool {
+    pub(crate) fn is_equal(a: Option<Value>, b: Option<Value>) -> bool {
         match (a, b) {
             (None, None) => true,
             (None, _) | (_, None) => false,
@@ -713,52 +1853,176 @@ impl Interpreter {
         }
     }
 
-    fn check_number_operand(operator: &Token, operand: Option<Value>) {
-        match operand {
-            Some(Value::Number(_)) => return,
-            _ => {
-                let error = RuntimeError::new(operator.clone(), "Operand must be a number");
-                crate::runtime_error(error); // Return None or handle type error appropriately
+    /// `value is class`: whether `value` is an instance of `class` or of a
+    /// class that inherits from it. Values other than instances never are.
+    fn is_instance(operator: &Token, value: Option<Value>, class: Option<Value>) -> EvalResult {
+        let class = match &class {
+            Some(Value::Callable(callable)) => callable.as_any().downcast_ref::<LoxClass>(),
+            _ => None,
+        };
+        let Some(class) = class else {
+            let error = RuntimeError::new(
+                operator.clone(),
+                ErrorCode::IsNotClass,
+                "Right operand of 'is' must be a class.",
+            );
+            return Err(LoxError::Runtime(error));
+        };
+        let is_instance = match value {
+            Some(Value::Instance(instance)) => {
+                instance.borrow().klass.borrow().is_subclass_of(class)
             }
+            _ => false,
+        };
+        Ok(Some(Value::Boolean(is_instance)))
+    }
+
+    fn check_number_operand(operator: &Token, operand: Option<Value>) -> Result<(), LoxError> {
+        if let Some(Value::Number(_)) = operand {
+            return Ok(());
         }
-        // Assuming RuntimeError is defined and implemented elsewhere
-        let error = RuntimeError::new(operator.clone(), "Operand must be a number");
-        crate::runtime_error(error); // Return None or handle type error appropriately
+        let error = RuntimeError::new(
+            operator.clone(),
+            ErrorCode::OperandMustBeNumber,
+            "Operand must be a number.",
+        );
+        Err(LoxError::Runtime(error))
     }
 
-    fn check_number_operands(operator: &Token, left: Option<Value>, right: Option<Value>) {
-        match left {
-            Some(Value::Number(_)) => match right {
-                Some(Value::Number(_)) => return,
-                _ => {
-                    let error = RuntimeError::new(operator.clone(), "Operand must be a number");
-                    crate::runtime_error(error); // Return None or handle type error appropriately
-                }
-            },
-            _ => {
-                let error = RuntimeError::new(operator.clone(), "Operand must be a number");
-                crate::runtime_error(error); // Return None or handle type error appropriately
+    /// Whether `value` is a number of any kind.
+    fn is_numeric(value: &Option<Value>) -> bool {
+        match value {
+            Some(Value::Number(_)) | Some(Value::Decimal(_)) => true,
+            #[cfg(feature = "lox-bigint")]
+            Some(Value::BigInt(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether `left operator right` is integer arithmetic whose exact
+    /// result can't be held exactly by a number. Operands already outside
+    /// that range aren't treated as integers.
+    fn integer_overflows(
+        operator: &TokenType,
+        left: &Option<Value>,
+        right: &Option<Value>,
+    ) -> bool {
+        const MAX_SAFE_INTEGER: i128 = (1 << 53) - 1;
+        let integer = |value: &Option<Value>| match value {
+            Some(Value::Number(n)) if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER as f64 => {
+                Some(*n as i128)
             }
+            _ => None,
+        };
+        let (Some(a), Some(b)) = (integer(left), integer(right)) else {
+            return false;
+        };
+        let result = match operator {
+            TokenType::Plus => a + b,
+            TokenType::Minus => a - b,
+            TokenType::Star => a * b,
+            _ => return false,
+        };
+        result.abs() > MAX_SAFE_INTEGER
+    }
+
+    fn check_number_operands(
+        operator: &Token,
+        left: Option<Value>,
+        right: Option<Value>,
+    ) -> Result<(), LoxError> {
+        if let (Some(Value::Number(_)), Some(Value::Number(_))) = (left, right) {
+            return Ok(());
         }
+        let error = RuntimeError::new(
+            operator.clone(),
+            ErrorCode::OperandsMustBeNumbers,
+            "Operands must be numbers.",
+        );
+        Err(LoxError::Runtime(error))
+    }
 
-        // Assuming RuntimeError is defined elsewhere
-        let error = RuntimeError::new(operator.clone(), "Operand must be a number");
-        crate::runtime_error(error); // Return None or handle type error appropriately
+    /// Runs a compiled program, as if its source had just been resolved.
+    pub fn run_program(&mut self, program: &Program) -> Result<(), LoxError> {
+        self.begin_run();
+        for (expr, depth) in program.locals() {
+            self.resolve(expr, *depth);
+        }
+        self.interpret(program.statements().to_vec())
     }
 
-    pub fn interpret(&mut self, statements: Vec<Option<Stmt>>) -> Option<ReturnValue> {
-        for statement in statements {
-            match self.execute(statement) {
-                Some(ReturnValue { value }) => {
-                    return Some(ReturnValue::new(value));
+    pub fn interpret(&mut self, statements: Vec<Stmt>) -> Result<(), LoxError> {
+        // The resolver rejects top-level returns, so only errors stop early
+        let result = statements
+            .into_iter()
+            .try_for_each(|statement| self.execute(Some(statement)).map(|_| ()))
+            .and_then(|()| self.run_timers());
+        if result.is_err() {
+            self.timers.clear();
+        }
+        // Workers still running stop with the script
+        self.run.end();
+        if let (Some(hooks), Err(LoxError::Runtime(error))) = (&self.hooks, &result) {
+            hooks.borrow_mut().on_error(error);
+        }
+        result
+    }
+
+    /// Fires the timers the script set, waiting for each to come due, until
+    /// none are left. Timers set by the callbacks join the queue. In
+    /// deterministic mode the clock jumps ahead instead of waiting.
+    fn run_timers(&mut self) -> Result<(), LoxError> {
+        while let Some((due, mut callback)) = self.timers.take_next() {
+            if self.deterministic {
+                self.timers.advance_to(due);
+            } else {
+                // Wait in short naps, so cancellation and the time limit
+                // still stop the run
+                loop {
+                    self.check_interrupts()?;
+                    let now = self.timers.now(false);
+                    if now >= due {
+                        break;
+                    }
+                    thread::sleep((due - now).min(Duration::from_millis(10)));
                 }
-                _ => (),
             }
+            self.step()?;
+            callback.call(self, Vec::new())?;
+        }
+        Ok(())
+    }
+
+    /// Sets a timer for `setTimeout` or `setInterval`, returning its id.
+    pub(crate) fn set_timer(
+        &mut self,
+        delay: Duration,
+        interval: Option<Duration>,
+        callback: Box<dyn Callable>,
+    ) -> u64 {
+        let now = self.timers.now(self.deterministic);
+        self.timers.schedule(now, delay, interval, callback)
+    }
+
+    /// Stops the timer with `id`, for `clearTimeout` and `clearInterval`.
+    pub(crate) fn clear_timer(&mut self, id: u64) {
+        self.timers.cancel(id);
+    }
+
+    /// Records the calls in progress on a runtime error as it leaves the
+    /// innermost call, where the stack is still complete.
+    fn trace(&self, error: LoxError) -> LoxError {
+        match error {
+            LoxError::Runtime(mut error) if error.trace.is_empty() => {
+                error.trace = self.frames.iter().rev().cloned().collect();
+                LoxError::Runtime(error)
+            }
+            error => error,
         }
-        None
     }
 
-    fn stringify(&self, value: Option<Value>) -> String {
+    /// Renders a value the way `print` shows it.
+    pub fn stringify(&self, value: Option<Value>) -> String {
         match value {
             Some(v) => match v {
                 Value::Number(num) => {
@@ -767,25 +2031,535 @@ impl Interpreter {
                     if text.ends_with(".0") {
                         return text.trim_end_matches(".0").to_string();
                     }
-                    return text;
+                    text
                 }
                 Value::Boolean(b) => b.to_string(),
                 // Value::Operator(o) => (o.to_string()),
-                Value::String(s) => s.to_string(), // Handle other cases as needed
+                // Strings keep their quotes internally but print without them
+                Value::String(s) => s[1..(s.len() - 1)].to_string(),
                 Value::Callable(c) => c.to_string(),
                 Value::Instance(i) => i.borrow_mut().to_string(),
+                Value::NativeInstance(i) => format!("{} instance", i.borrow().class_name()),
+                // Shown as `inspect` shows them, so nested strings keep their quotes
+                Value::List(_) => inspect::inspect(self, &v),
                 Value::Nil() => "nil".to_string(),
+                #[cfg(feature = "lox-bigint")]
+                Value::BigInt(big) => big.to_string(),
+                Value::Decimal(decimal) => decimal.to_string(),
             },
             None => "nil".to_string(),
         }
     }
 
-    fn lookup_variable(&mut self, name: &Token, expr: &Expr) -> Option<Value> {
-        let distance = self.locals.get(expr);
-        if let Some(distance) = distance {
-            return Some(self.environment.borrow_mut().get_at(*distance, name));
+    /// Builds the class for a class declaration or expression. Its methods
+    /// close over the current scope, plus a scope holding `super` when the
+    /// class has a superclass. It must define every method its interfaces
+    /// require.
+    fn class(
+        &mut self,
+        name: Token,
+        superclass: Option<Expr>,
+        interfaces: Vec<Expr>,
+        methods: Vec<Stmt>,
+    ) -> Result<Value, LoxError> {
+        let mut supclass = None;
+        let mut downcast_superclass = None;
+        if let Some(ref superclass_expr) = superclass {
+            // Evaluate the superclass expression
+            let evaluated_superclass = self.evaluate(superclass_expr)?;
+            supclass = evaluated_superclass.clone();
+            // Check if it's a LoxClass
+            let lox_class = match evaluated_superclass {
+                // Downcast using the as_any method
+                Some(Value::Callable(class)) => class.as_any().downcast_ref::<LoxClass>().cloned(),
+                _ => None,
+            };
+            match lox_class {
+                // Successfully downcasted to LoxClass, now pass it to the function
+                Some(lox_class) => downcast_superclass = Some(lox_class),
+                None => {
+                    if let Expr::Variable { name, .. } = superclass_expr {
+                        let error = RuntimeError::new(
+                            name.clone(),
+                            ErrorCode::SuperclassMustBeClass,
+                            "Superclass must be a class.",
+                        );
+                        return Err(LoxError::Runtime(error));
+                    }
+                }
+            }
+        }
+
+        let enclosing = self.environment.clone();
+        if let Some(ref _superclass) = superclass {
+            self.environment = Rc::new(RefCell::new(Environment::new(Some(
+                self.environment.clone(),
+            ))));
+            self.environment
+                .borrow_mut()
+                .define("super".to_string(), supclass.clone());
+        }
+
+        let mut meths: HashMap<String, LoxFunction> = HashMap::new();
+        for method in &methods {
+            if let Stmt::Function { name, .. } = method {
+                let function = LoxFunction::new(
+                    method.clone(),
+                    self.environment.clone(),
+                    name.lexeme == "init",
+                );
+                meths.insert(name.lexeme.clone(), function);
+            }
+        }
+        let class_name = name.lexeme.clone();
+        let klass = LoxClass::new(
+            meths,
+            Stmt::Class {
+                name: name.clone(),
+                superclass,
+                interfaces: interfaces.clone(),
+                methods,
+            },
+            self.environment.clone(),
+            class_name,
+            downcast_superclass,
+        );
+        self.environment = enclosing;
+
+        for interface_expr in &interfaces {
+            let interface = match self.evaluate(interface_expr)? {
+                Some(Value::Callable(callable)) => {
+                    callable.as_any().downcast_ref::<LoxInterface>().cloned()
+                }
+                _ => None,
+            };
+            let error = match interface {
+                Some(interface) => interface
+                    .missing_from(&klass)
+                    .map(|message| (&name, ErrorCode::InterfaceNotImplemented, message)),
+                None => match interface_expr {
+                    Expr::Variable { name: var, .. } => Some((
+                        var,
+                        ErrorCode::NotAnInterface,
+                        "Can only implement interfaces.".to_string(),
+                    )),
+                    _ => None,
+                },
+            };
+            if let Some((token, code, message)) = error {
+                let error = RuntimeError::new(token.clone(), code, &message);
+                return Err(LoxError::Runtime(error));
+            }
+        }
+        Ok(Value::Callable(Box::new(klass)))
+    }
+
+    /// Assigns `value` to the variable `name`, looked up the way the resolver
+    /// found `expr`.
+    fn assign_variable(&mut self, name: &Token, expr: &Expr, value: Value) -> Result<(), LoxError> {
+        if let Some(distance) = self.locals.get(expr) {
+            self.environment
+                .borrow_mut()
+                .assign_at(*distance, name.clone(), value.clone());
         } else {
-            return Some(self.environment.borrow_mut().get(name));
+            let assigned = self
+                .globals
+                .borrow_mut()
+                .assign(name.clone(), value.clone());
+            if assigned.is_err() {
+                return Err(self.environment.borrow().undefined(name));
+            }
         }
+        if let Some(hooks) = &self.hooks {
+            hooks.borrow_mut().on_var_assign(name, &value);
+        }
+        Ok(())
+    }
+
+    /// The property `name` of an evaluated object.
+    fn get_property(object: &Option<Value>, name: &Token) -> EvalResult {
+        match object {
+            Some(Value::Instance(instance)) => LoxInstance::get(instance, name),
+            Some(Value::NativeInstance(instance)) => native_instance::get(instance, name),
+            Some(Value::List(list)) => lox_list::get(list, name),
+            _ => {
+                let error = RuntimeError::new(
+                    name.clone(),
+                    ErrorCode::NotAnInstance,
+                    "Only instances have properties.",
+                );
+                Err(LoxError::Runtime(error))
+            }
+        }
+    }
+
+    /// Fails unless `object` can have its field `name` set.
+    fn check_fields(object: &Option<Value>, name: &Token) -> Result<(), LoxError> {
+        match object {
+            Some(Value::Instance(_) | Value::NativeInstance(_)) => Ok(()),
+            _ => {
+                let error = RuntimeError::new(
+                    name.clone(),
+                    ErrorCode::NotAnInstance,
+                    "Only instances have fields.",
+                );
+                Err(LoxError::Runtime(error))
+            }
+        }
+    }
+
+    /// Sets the field `name` of an object that passed `check_fields`. A
+    /// native instance's fields can't be set to nothing, so that is skipped.
+    fn set_property(
+        object: &Option<Value>,
+        name: &Token,
+        value: Option<Value>,
+    ) -> Result<(), LoxError> {
+        match (object, value) {
+            (Some(Value::Instance(instance)), value) => {
+                instance.borrow_mut().set(name.clone(), value);
+                Ok(())
+            }
+            (Some(Value::NativeInstance(instance)), Some(value)) => {
+                native_instance::set(instance, name, value)
+            }
+            (Some(Value::NativeInstance(_)), None) => Ok(()),
+            _ => Interpreter::check_fields(object, name),
+        }
+    }
+
+    /// Reads the variable, property, or list item `target` and assigns what
+    /// `f` makes of its old value back to it, evaluating any object and index
+    /// in `target` only once. Gives the old and new values.
+    fn update(
+        &mut self,
+        target: &Expr,
+        f: impl FnOnce(&mut Self, Option<Value>) -> EvalResult,
+    ) -> Result<(Option<Value>, Option<Value>), LoxError> {
+        match target {
+            Expr::Variable { name, .. } => {
+                let old = self.lookup_variable(name, target)?;
+                let new = f(self, old.clone())?;
+                if let Some(value) = &new {
+                    self.assign_variable(name, target, value.clone())?;
+                }
+                Ok((old, new))
+            }
+            Expr::Get { object, name } => {
+                let object = self.evaluate(object)?;
+                Interpreter::check_fields(&object, name)?;
+                let old = Interpreter::get_property(&object, name)?;
+                let new = f(self, old.clone())?;
+                Interpreter::set_property(&object, name, new.clone())?;
+                Ok((old, new))
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let list = self.list(object, bracket)?;
+                let index = self.evaluate(index)?;
+                let old = lox_list::index(&list, bracket, index.clone())?;
+                let new = f(self, Some(old.clone()))?;
+                lox_list::set_index(&list, bracket, index, new.clone().unwrap_or(Value::Nil()))?;
+                Ok((Some(old), new))
+            }
+            _ => unreachable!("only variables, properties, and list items are assigned"),
+        }
+    }
+
+    /// The value of the number literal `lexeme`, which is a big integer or a
+    /// decimal when those are on.
+    fn number_literal(&self, lexeme: &str) -> Value {
+        #[cfg(feature = "lox-bigint")]
+        if let Some(big) = crate::bigint::literal(lexeme) {
+            return big;
+        }
+        if self.decimal_literals {
+            if let Some(decimal) = crate::decimal::literal(lexeme) {
+                return decimal;
+            }
+        }
+        Value::Number(lexeme.parse::<f64>().unwrap())
+    }
+
+    /// The arithmetic operator that `+=`, `++`, and the like apply, at the
+    /// same place in the source.
+    fn arithmetic_operator(operator: &Token) -> Token {
+        let (type_, lexeme) = match operator.type_ {
+            TokenType::PlusEqual | TokenType::PlusPlus => (TokenType::Plus, "+"),
+            TokenType::MinusEqual | TokenType::MinusMinus => (TokenType::Minus, "-"),
+            TokenType::StarEqual => (TokenType::Star, "*"),
+            _ => (TokenType::Slash, "/"),
+        };
+        Token {
+            type_,
+            lexeme: lexeme.to_string(),
+            ..operator.clone()
+        }
+    }
+
+    fn lookup_variable(&mut self, name: &Token, expr: &Expr) -> EvalResult {
+        // A variable found among the globals once is a global every time it
+        // runs, so its cached slot skips both the locals and the name lookup
+        let cache = match expr {
+            Expr::Variable { slot, .. } => Some(slot),
+            _ => None,
+        };
+        if let Some(slot) = cache.and_then(|cache| cache.get()) {
+            if let Some(value) = self.globals.borrow().values.get_slot(slot, &name.lexeme) {
+                return Ok(Some(value.clone().unwrap_or(Value::Nil())));
+            }
+        }
+        if let Some(distance) = self.locals.get(expr) {
+            return Ok(Some(self.environment.borrow().get_at(*distance, name)));
+        }
+        let slot = self.globals.borrow().values.slot(&name.lexeme);
+        match slot {
+            Some(slot) => {
+                if let Some(cache) = cache {
+                    cache.set(slot);
+                }
+                Ok(Some(self.globals.borrow().get(name)?))
+            }
+            // Suggest names from every scope the variable was used in
+            None => Err(self.environment.borrow().undefined(name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use std::time::Instant;
+
+    /// Collects the names of variables read, and counts function
+    /// declarations, leaving everything else to the default walk.
+    #[derive(Default)]
+    struct Names {
+        reads: Vec<String>,
+        functions: usize,
+    }
+
+    impl Visitor for Names {
+        fn visit_variable_expr(&mut self, expr: &Expr) -> EvalResult {
+            if let Expr::Variable { name, .. } = expr {
+                self.reads.push(name.lexeme.clone());
+            }
+            Ok(None)
+        }
+    }
+
+    impl StmtVisitor for Names {
+        fn visit_function_stmt(
+            &mut self,
+            name: Token,
+            params: Vec<Token>,
+            body: Vec<Stmt>,
+        ) -> ExecResult {
+            self.functions += 1;
+            walk_stmt(self, &Stmt::Function { name, params, body })
+        }
+    }
+
+    #[test]
+    fn passes_only_override_what_they_need() {
+        let source = "var a = b + c;
+                      fun f(x) { if (x) print d(e); else { while (g) h.i = j; } }
+                      class K < L { m() { return -n; } }";
+        let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        let mut names = Names::default();
+        for stmt in &statements {
+            stmt.accept(&mut names).unwrap();
+        }
+        assert_eq!(
+            names.reads,
+            ["b", "c", "x", "d", "e", "g", "h", "j", "L", "n"]
+        );
+        // The method counts too, since it is a function statement
+        assert_eq!(names.functions, 2);
+    }
+
+    #[test]
+    fn division_by_zero_can_be_an_error() {
+        let mut lox = crate::Lox::new("");
+        lox.set_reporter(Rc::new(
+            RefCell::new(crate::reporter::BufferReporter::new()),
+        ));
+        assert!(lox.try_run("var inf = 1 / 0; var nan = 0 / 0;").is_ok());
+
+        lox.set_checked_division(true);
+        assert_eq!(
+            lox.try_run("print 1 / 0;").unwrap_err().to_string(),
+            "Division by zero. [E3012]\n[line 1:9]"
+        );
+        assert!(lox.try_run("print 0 / 0;").is_err());
+        assert!(lox.try_run("print 1 / 2;").is_ok());
+        // Dividing something else still fails on its operands
+        assert_eq!(
+            lox.try_run("print \"a\" / 0;").unwrap_err().to_string(),
+            "Operands must be numbers. [E3004]\n[line 1:11]"
+        );
+    }
+
+    #[test]
+    fn strict_math_makes_nan_and_infinity_errors() {
+        let mut lox = crate::Lox::new("");
+        lox.set_reporter(Rc::new(
+            RefCell::new(crate::reporter::BufferReporter::new()),
+        ));
+        assert!(lox
+            .try_run("var a = 0 / 0; var b = sqrt(-1); var c = log(0);")
+            .is_ok());
+
+        lox.set_strict_math(true);
+        assert_eq!(
+            lox.try_run("print 0 / 0;").unwrap_err().to_string(),
+            "Math result is NaN. [E3021]\n[line 1:9]"
+        );
+        assert_eq!(
+            lox.try_run("print sqrt(-1);").unwrap_err().to_string(),
+            "Math result is NaN. [E3021]\n[line 1:14]"
+        );
+        assert_eq!(
+            lox.try_run("print log(0);").unwrap_err().to_string(),
+            "Math result is infinite. [E3021]\n[line 1:12]"
+        );
+        assert!(lox.try_run("print MAX_NUMBER * 2;").is_err());
+        // Results from NaN or an infinity aren't checked
+        assert!(lox
+            .try_run("print INFINITY - INFINITY; print sqrt(NAN);")
+            .is_ok());
+        assert!(lox.try_run("print sqrt(4) + log(1);").is_ok());
+    }
+
+    #[test]
+    fn integer_overflow_can_be_an_error() {
+        let mut lox = crate::Lox::new("");
+        lox.set_reporter(Rc::new(
+            RefCell::new(crate::reporter::BufferReporter::new()),
+        ));
+        lox.try_run("var max = 9007199254740991;").unwrap();
+        lox.set_checked_integers(true);
+        assert_eq!(
+            lox.try_run("print max + 1;").unwrap_err().to_string(),
+            "Integer overflow. [E3013]\n[line 1:11]"
+        );
+        assert!(lox.try_run("print -max - 1;").is_err());
+        assert!(lox.try_run("print 94906267 * 94906267;").is_err());
+        assert!(lox
+            .try_run("print max - 1; print max + 0.5; print max / 2;")
+            .is_ok());
+    }
+
+    /// A native that reads the outside world.
+    #[derive(Clone)]
+    struct ReadSensor;
+
+    impl Callable for ReadSensor {
+        fn call(&mut self, _: &mut Interpreter, _: Vec<Option<Value>>) -> EvalResult {
+            Ok(Some(Value::Number(21.5)))
+        }
+
+        fn arity(&self) -> usize {
+            0
+        }
+
+        fn deterministic(&self) -> bool {
+            false
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn clone_box(&self) -> Box<dyn Callable> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn deterministic_mode_refuses_nondeterministic_natives() {
+        let mut lox = crate::Lox::new("");
+        lox.set_reporter(Rc::new(
+            RefCell::new(crate::reporter::BufferReporter::new()),
+        ));
+        lox.define_native("readSensor", Box::new(ReadSensor));
+        lox.try_run("var reading = readSensor();").unwrap();
+
+        lox.set_deterministic(true);
+        assert_eq!(
+            lox.try_run("readSensor();").unwrap_err().to_string(),
+            "Can't call a nondeterministic native in deterministic mode. [E3020]\n[line 1:12]"
+        );
+        assert!(lox
+            .try_run("var now = clock(); var roll = random();")
+            .is_ok());
+    }
+
+    #[test]
+    fn deterministic_timers_fire_without_waiting() {
+        let mut lox = crate::Lox::new("");
+        lox.set_deterministic(true);
+        let started = Instant::now();
+        lox.try_run(
+            "var start = clock(); var waited; \
+             fun done() { waited = clock() - start; } \
+             setTimeout(done, 60000);",
+        )
+        .unwrap();
+        lox.try_run("expectEqual(waited, 60);").unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    /// Saves the environment where it is called.
+    #[derive(Clone)]
+    struct DumpHere(Rc<RefCell<String>>);
+
+    impl Callable for DumpHere {
+        fn call(&mut self, interpreter: &mut Interpreter, _: Vec<Option<Value>>) -> EvalResult {
+            *self.0.borrow_mut() = interpreter.dump_environment();
+            Ok(None)
+        }
+
+        fn arity(&self) -> usize {
+            0
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn clone_box(&self) -> Box<dyn Callable> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn dumps_each_scope_from_the_innermost() {
+        let dump = Rc::new(RefCell::new(String::new()));
+        let mut lox = crate::Lox::new("");
+        lox.define_native("dumpHere", Box::new(DumpHere(dump.clone())));
+        lox.try_run(
+            "var count = 1;
+             fun show(label) { var items = [label]; { var inner; dumpHere(); } }
+             show(\"x\");",
+        )
+        .unwrap();
+        assert_eq!(
+            *dump.borrow(),
+            "depth 0\n  inner = nil\n\
+             depth 1\n  label = \"x\"\n  items = [\"x\"]\n\
+             globals\n  count = 1\n  show = <fn show>\n"
+        );
+        assert_eq!(
+            lox.dump_environment(),
+            "globals\n  count = 1\n  show = <fn show>\n"
+        );
     }
 }
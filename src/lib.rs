@@ -0,0 +1,422 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+#[cfg(feature = "lox-async")]
+pub mod async_native;
+#[cfg(feature = "lox-bigint")]
+pub mod bigint;
+pub mod bindings;
+pub mod callable;
+pub mod cancellation;
+pub mod decimal;
+pub mod diff;
+pub mod engine;
+pub mod environment;
+pub mod error;
+pub mod expectations;
+pub mod expr;
+#[cfg(feature = "lox-ffi")]
+pub mod ffi;
+pub mod hooks;
+pub mod inspect;
+pub mod interpreter;
+pub mod limits;
+pub mod lints;
+pub mod logging;
+pub mod lox_class;
+pub mod lox_function;
+pub mod lox_instance;
+pub mod lox_interface;
+pub mod lox_list;
+pub mod lox_map;
+pub mod macros;
+pub mod metrics;
+pub mod native_functions;
+pub mod native_instance;
+pub mod parser;
+#[cfg(feature = "lox-plugins")]
+pub mod plugin;
+pub mod prelude;
+pub mod printer;
+pub mod program;
+pub mod random;
+pub mod rename;
+pub mod reporter;
+pub mod resolver;
+pub mod return_value;
+pub mod rewrite;
+pub mod runtime_error;
+pub mod scanner;
+pub mod snapshot;
+pub mod snippet;
+pub mod stmt;
+pub mod suggest;
+pub mod testing;
+#[cfg(feature = "lox-threads")]
+pub mod threads;
+pub mod timers;
+pub mod token;
+pub mod token_type;
+pub mod value;
+pub mod write_output;
+
+use crate::callable::Callable;
+use crate::cancellation::CancellationToken;
+use crate::error::LoxError;
+use crate::hooks::InterpreterHooks;
+use crate::limits::InterpreterLimits;
+use crate::lints::{Lint, Lints, Severity};
+use crate::logging::LogLevel;
+use crate::reporter::ErrorReporter;
+use crate::value::Value;
+
+/// An embeddable interpreter. Globals defined by one call to `run` stay
+/// visible to the next, so a host can load a script and then call into it.
+pub struct Lox {
+    interpreter: Rc<RefCell<interpreter::Interpreter>>,
+    warnings: bool,
+    lints: Lints,
+    flags: HashSet<String>,
+    macros: macros::Macros,
+}
+
+impl Lox {
+    pub fn new(output_file: &str) -> Self {
+        Lox::with_interpreter(interpreter::Interpreter::new(output_file))
+    }
+
+    /// A `Lox` without the prelude's helpers, such as `map`, defined. See
+    /// `prelude`.
+    pub fn without_prelude(output_file: &str) -> Self {
+        Lox::with_interpreter(interpreter::Interpreter::without_prelude(output_file))
+    }
+
+    fn with_interpreter(interpreter: interpreter::Interpreter) -> Self {
+        Lox {
+            interpreter: Rc::new(RefCell::new(interpreter)),
+            warnings: false,
+            lints: Lints::default(),
+            flags: HashSet::new(),
+            macros: macros::Macros::new(),
+        }
+    }
+
+    /// Runs `source`, reporting any error through the `ErrorReporter`, which
+    /// prints to stderr unless `set_reporter` installed another. Returns the
+    /// exit status: 0, the status passed to `exit()`, or
+    /// `LoxError::exit_code` for the error that stopped the script.
+    pub fn run(&mut self, source: &str) -> i32 {
+        match self.try_run(source) {
+            Ok(()) => 0,
+            Err(error) => error.exit_code(),
+        }
+    }
+
+    /// Runs `source` like `run`, also returning the error that stopped it.
+    pub fn try_run(&mut self, source: &str) -> Result<(), LoxError> {
+        let result = self.execute(source);
+        if let Err(error) = &result {
+            let reporter = self.interpreter.borrow().reporter();
+            report(&mut *reporter.borrow_mut(), error, source);
+        }
+        result
+    }
+
+    /// Runs a program compiled by `Program::compile`, reporting an error as
+    /// `try_run` does. Its macros aren't kept for later runs.
+    pub fn run_program(&mut self, program: &program::Program) -> Result<(), LoxError> {
+        let result = self.interpreter.borrow_mut().run_program(program);
+        if let Err(error) = &result {
+            let reporter = self.interpreter.borrow().reporter();
+            report(&mut *reporter.borrow_mut(), error, program.source());
+        }
+        result
+    }
+
+    fn execute(&mut self, source: &str) -> Result<(), LoxError> {
+        // A previous run may have stopped inside a nested scope
+        self.interpreter.borrow_mut().begin_run();
+
+        let mut scanner = scanner::Scanner::new(source.to_string());
+        scanner.set_flags(self.flags.clone());
+        let tokens = scanner.scan_tokens()?;
+        let statements = parser::Parser::new(tokens).parse()?;
+        let statements = self.macros.expand(statements)?;
+
+        let mut resolver = resolver::Resolver::new(self.interpreter.clone());
+        resolver.resolve(&statements)?;
+        let mut errors = Vec::new();
+        let reporter = self.interpreter.borrow().reporter();
+        for warning in resolver.take_warnings() {
+            match self.lints.severity(warning.lint) {
+                Severity::Allow => {}
+                Severity::Warn if self.warnings => {
+                    let token = &warning.token;
+                    let warning =
+                        with_excerpt(&warning, source, token.line, token.offset, &token.lexeme);
+                    reporter.borrow_mut().warn(&warning);
+                }
+                Severity::Warn => {}
+                Severity::Error => errors.push(resolver::ResolveError {
+                    token: warning.token,
+                    code: warning.lint.code(),
+                    message: warning.message,
+                }),
+            }
+        }
+        if !errors.is_empty() {
+            errors.sort_by_key(|error| error.token.offset);
+            return Err(LoxError::Resolve(errors));
+        }
+
+        self.interpreter.borrow_mut().interpret(statements)
+    }
+
+    /// The variables in scope, from the innermost scope out. See
+    /// `Interpreter::dump_environment`.
+    pub fn dump_environment(&self) -> String {
+        self.interpreter.borrow().dump_environment()
+    }
+
+    /// Returns Lox source that recreates the current globals. See `snapshot::snapshot`.
+    pub fn snapshot(&self) -> String {
+        snapshot::snapshot(&self.interpreter.borrow().globals.borrow())
+    }
+
+    pub fn save_snapshot(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.snapshot())
+    }
+
+    /// Loads globals saved by `save_snapshot` on top of the current ones.
+    pub fn load_snapshot(&mut self, path: &Path) -> io::Result<()> {
+        let source = fs::read_to_string(path)?;
+        self.try_run(&source)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))
+    }
+
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.interpreter.borrow_mut().set_cancellation_token(token);
+    }
+
+    pub fn set_limits(&mut self, limits: InterpreterLimits) {
+        self.interpreter.borrow_mut().set_limits(limits);
+    }
+
+    pub fn limits(&self) -> InterpreterLimits {
+        self.interpreter.borrow().limits().clone()
+    }
+
+    pub fn set_log_level(&mut self, level: LogLevel) {
+        self.interpreter.borrow_mut().set_log_level(level);
+    }
+
+    /// Evaluates `0.1` and other fractional literals as exact decimals.
+    pub fn set_decimal_literals(&mut self, enabled: bool) {
+        self.interpreter.borrow_mut().set_decimal_literals(enabled);
+    }
+
+    /// Makes dividing by zero a runtime error instead of giving infinity.
+    pub fn set_checked_division(&mut self, enabled: bool) {
+        self.interpreter.borrow_mut().set_checked_division(enabled);
+    }
+
+    /// Makes integer arithmetic past 2^53 a runtime error instead of
+    /// rounding. See `Interpreter::set_checked_integers`.
+    pub fn set_checked_integers(&mut self, enabled: bool) {
+        self.interpreter.borrow_mut().set_checked_integers(enabled);
+    }
+
+    /// Makes arithmetic and math natives that give NaN or an infinity from
+    /// finite numbers a runtime error. See `Interpreter::set_strict_math`.
+    pub fn set_strict_math(&mut self, enabled: bool) {
+        self.interpreter.borrow_mut().set_strict_math(enabled);
+    }
+
+    /// Makes runs repeatable. See `Interpreter::set_deterministic`.
+    pub fn set_deterministic(&mut self, enabled: bool) {
+        self.interpreter.borrow_mut().set_deterministic(enabled);
+    }
+
+    /// Reports warnings, such as local variables that are never read,
+    /// through the `ErrorReporter` before running each script.
+    pub fn set_warnings(&mut self, enabled: bool) {
+        self.warnings = enabled;
+    }
+
+    /// Sets whether `lint` is ignored, warned about, or stops the script
+    /// from running as a resolve error.
+    pub fn set_lint_severity(&mut self, lint: Lint, severity: Severity) {
+        self.lints.set_severity(lint, severity);
+    }
+
+    /// Makes every lint an error, so a script with unused locals, shadowed
+    /// locals, or assignments to undeclared variables doesn't run.
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.lints = if enabled {
+            Lints::strict()
+        } else {
+            Lints::default()
+        };
+    }
+
+    /// Turns the `#if name` sections of later runs on or off. They are all
+    /// off to begin with.
+    pub fn set_flag(&mut self, name: &str, enabled: bool) {
+        if enabled {
+            self.flags.insert(name.to_string());
+        } else {
+            self.flags.remove(name);
+        }
+    }
+
+    pub fn set_hooks(&mut self, hooks: Rc<RefCell<dyn InterpreterHooks>>) {
+        self.interpreter.borrow_mut().set_hooks(hooks);
+    }
+
+    /// Sends diagnostics to `reporter` instead of stderr.
+    pub fn set_reporter(&mut self, reporter: Rc<RefCell<dyn ErrorReporter>>) {
+        self.interpreter.borrow_mut().set_reporter(reporter);
+    }
+
+    /// Defines a native function in the global environment.
+    pub fn define_native(&mut self, name: &str, function: Box<dyn Callable>) {
+        self.define(name, Value::Callable(function));
+    }
+
+    /// Defines a native whose result comes from a future. See `async_native`.
+    #[cfg(feature = "lox-async")]
+    pub fn define_async_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(Vec<Value>) -> async_native::NativeFuture + 'static,
+    {
+        self.define_native(
+            name,
+            Box::new(async_native::AsyncNative::new(arity, function)),
+        );
+    }
+
+    /// Defines a global variable, such as a `Value::NativeInstance` handle.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.interpreter
+            .borrow()
+            .globals
+            .borrow_mut()
+            .define(name.to_string(), Some(value));
+    }
+
+    /// How many calls to the `test` native have passed and failed.
+    pub fn test_counts(&self) -> testing::TestCounts {
+        self.interpreter.borrow().test_counts()
+    }
+}
+
+pub fn run(source: &str, output_file: &str) -> i32 {
+    Lox::new(output_file).run(source)
+}
+
+/// Sends the diagnostics for an error that stopped a run to `reporter`.
+/// Static errors quote the line of `source` they were found on; runtime
+/// errors list the calls they passed through.
+fn report(reporter: &mut dyn ErrorReporter, error: &LoxError, source: &str) {
+    match error {
+        LoxError::Scan(errors) => {
+            for error in errors {
+                // Points at the character that stopped the scanner
+                let lexeme: String = source
+                    .get(error.offset..)
+                    .and_then(|rest| rest.chars().next())
+                    .map(String::from)
+                    .unwrap_or_default();
+                report_at(reporter, source, error, error.line, error.offset, &lexeme);
+            }
+        }
+        LoxError::Parse(errors) => {
+            for error in errors {
+                let token = &error.token;
+                report_at(
+                    reporter,
+                    source,
+                    error,
+                    token.line,
+                    token.offset,
+                    &token.lexeme,
+                );
+            }
+        }
+        LoxError::Resolve(errors) => {
+            for error in errors {
+                let token = &error.token;
+                report_at(
+                    reporter,
+                    source,
+                    error,
+                    token.line,
+                    token.offset,
+                    &token.lexeme,
+                );
+            }
+        }
+        LoxError::Runtime(error) => {
+            let mut diagnostic = format!(
+                "{} [{}]\n[line {}]",
+                error.message,
+                error.code,
+                error.token.position()
+            );
+            let mut frames = error.trace.iter().peekable();
+            while let Some(frame) = frames.next() {
+                diagnostic.push_str(&format!("\n  {}", frame));
+                // Deep recursion would otherwise list the same call many times
+                let mut repeats = 0;
+                while frames.next_if_eq(&frame).is_some() {
+                    repeats += 1;
+                }
+                if repeats > 0 {
+                    diagnostic.push_str(&format!(
+                        "\n  [previous frame repeated {} more {}]",
+                        repeats,
+                        if repeats == 1 { "time" } else { "times" }
+                    ));
+                }
+            }
+            reporter.report(&diagnostic);
+        }
+        // Left for the host to describe
+        LoxError::Cancelled | LoxError::LimitExceeded(_) | LoxError::Exit(_) => {}
+    }
+}
+
+/// Reports a static error, followed by the source line it's on with a caret
+/// under `lexeme` when that can be found at `offset`.
+fn report_at(
+    reporter: &mut dyn ErrorReporter,
+    source: &str,
+    error: &dyn fmt::Display,
+    line: i32,
+    offset: usize,
+    lexeme: &str,
+) {
+    reporter.report(&with_excerpt(error, source, line, offset, lexeme));
+}
+
+/// `message` followed by the source line it refers to, with a caret under
+/// `lexeme` when that can be found at `offset`.
+fn with_excerpt(
+    message: &dyn fmt::Display,
+    source: &str,
+    line: i32,
+    offset: usize,
+    lexeme: &str,
+) -> String {
+    let mut diagnostic = message.to_string();
+    if let Some(excerpt) = snippet::render(source, line, offset, lexeme) {
+        diagnostic.push('\n');
+        diagnostic.push_str(&excerpt);
+    }
+    diagnostic
+}
@@ -0,0 +1,304 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Calls that may be in progress at once unless a host says otherwise.
+/// Deep enough for ordinary recursion, and shallow enough that the command
+/// line's interpreter thread runs out of calls before it runs out of stack.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// Resource limits enforced while a script runs. `None` means unlimited.
+/// Exceeding a limit stops the script with `LoxError::LimitExceeded`, except
+/// for the call depth: a call past it raises a "Stack overflow." runtime
+/// error, as running out of stack would in other languages.
+///
+/// Natives that touch the filesystem, network, or other processes check the
+/// `allow_*` flags through `Interpreter::require` before doing so, and
+/// plugins only load while `allow_exec` is set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterpreterLimits {
+    /// Statements a run may execute, counting each pass through a loop body.
+    pub max_instructions: Option<usize>,
+    /// Wall-clock time a run may take, checked before each statement.
+    pub max_duration: Option<Duration>,
+    pub max_call_depth: Option<usize>,
+    /// Instances, lists, and other objects a run may allocate, whether the
+    /// script or a native makes them. Every allocation counts, even of an
+    /// object that has since been freed.
+    pub max_allocations: Option<usize>,
+    pub allow_fs: bool,
+    pub allow_net: bool,
+    pub allow_exec: bool,
+}
+
+impl Default for InterpreterLimits {
+    fn default() -> Self {
+        InterpreterLimits {
+            max_instructions: None,
+            max_duration: None,
+            max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+            max_allocations: None,
+            allow_fs: true,
+            allow_net: true,
+            allow_exec: true,
+        }
+    }
+}
+
+/// What a run has used of its limits: the statements it has executed and
+/// when it began. Workers share the run that spawned them, so a script and
+/// all its workers stay within one instruction budget and one deadline, and
+/// stop when the run ends.
+#[derive(Debug, Clone)]
+pub struct SharedRun {
+    instructions: Arc<AtomicUsize>,
+    started: Instant,
+    ended: Arc<AtomicBool>,
+    /// The threads of the workers spawned during the run.
+    workers: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl SharedRun {
+    pub fn new() -> Self {
+        SharedRun {
+            instructions: Arc::new(AtomicUsize::new(0)),
+            started: Instant::now(),
+            ended: Arc::new(AtomicBool::new(false)),
+            workers: Arc::default(),
+        }
+    }
+
+    /// Counts one more instruction, returning how many there have been.
+    pub fn step(&self) -> usize {
+        self.instructions.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Keeps a worker's thread, to wait for when the run ends.
+    pub fn add_worker(&self, worker: JoinHandle<()>) {
+        self.workers.lock().unwrap().push(worker);
+    }
+
+    /// Ends the run, stopping the workers still running at their next
+    /// statement and waiting for them to finish.
+    pub fn end(&self) {
+        self.ended.store(true, Ordering::SeqCst);
+        // Workers may spawn workers of their own while the first are joined
+        loop {
+            let workers = std::mem::take(&mut *self.workers.lock().unwrap());
+            if workers.is_empty() {
+                break;
+            }
+            for worker in workers {
+                // A worker that panicked has nothing left to stop
+                let _ = worker.join();
+            }
+        }
+    }
+
+    pub fn has_ended(&self) -> bool {
+        self.ended.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for SharedRun {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Something a script may only do when its limits allow it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    Fs,
+    Net,
+    Exec,
+}
+
+impl Permission {
+    /// Why a native asking for this permission was refused.
+    pub fn denied_message(self) -> &'static str {
+        match self {
+            Permission::Fs => "Filesystem access is not allowed.",
+            Permission::Net => "Network access is not allowed.",
+            Permission::Exec => "Running native code or other programs is not allowed.",
+        }
+    }
+}
+
+impl InterpreterLimits {
+    pub fn allows(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::Fs => self.allow_fs,
+            Permission::Net => self.allow_net,
+            Permission::Exec => self.allow_exec,
+        }
+    }
+
+    /// Conservative limits for running untrusted scripts.
+    pub fn sandboxed() -> Self {
+        InterpreterLimits {
+            max_instructions: Some(10_000_000),
+            max_duration: None,
+            max_call_depth: Some(256),
+            max_allocations: Some(100_000),
+            allow_fs: false,
+            allow_net: false,
+            allow_exec: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::callable::Callable;
+    use crate::error::LoxError;
+    use crate::interpreter::Interpreter;
+    use crate::value::Value;
+    use crate::Lox;
+
+    fn run_with(limits: InterpreterLimits, source: &str) -> Result<(), LoxError> {
+        let mut lox = Lox::new("");
+        lox.set_limits(limits);
+        lox.try_run(source)
+    }
+
+    #[test]
+    fn instruction_limit_stops_runaway_loop() {
+        let limits = InterpreterLimits {
+            max_instructions: Some(1000),
+            ..InterpreterLimits::default()
+        };
+        let result = run_with(limits, "while (true) {}");
+        assert!(matches!(result, Err(LoxError::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn time_limit_stops_runaway_loop() {
+        let limits = InterpreterLimits {
+            max_duration: Some(Duration::from_millis(20)),
+            ..InterpreterLimits::default()
+        };
+        match run_with(limits, "while (true) {}") {
+            Err(LoxError::LimitExceeded(message)) => {
+                assert_eq!(message, "Time limit of 20 ms exceeded.")
+            }
+            other => panic!("Expected the time limit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn time_limit_stops_an_endless_interval() {
+        let limits = InterpreterLimits {
+            max_duration: Some(Duration::from_millis(20)),
+            ..InterpreterLimits::default()
+        };
+        let started = std::time::Instant::now();
+        let result = run_with(limits, "fun wait() {} setInterval(wait, 1000);");
+        assert!(matches!(result, Err(LoxError::LimitExceeded(_))));
+        // The limit cuts the wait for the first tick short
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn call_depth_limit_stops_unbounded_recursion() {
+        let limits = InterpreterLimits {
+            max_call_depth: Some(20),
+            ..InterpreterLimits::default()
+        };
+        let result = run_with(limits, "fun f(n) { return f(n + 1); } f(0);");
+        match result {
+            Err(LoxError::Runtime(error)) => {
+                assert_eq!(error.message, "Stack overflow.");
+                assert_eq!(error.trace.len(), 20);
+            }
+            other => panic!("Expected a runtime error, got {:?}", other),
+        }
+
+        let limits = InterpreterLimits {
+            max_call_depth: Some(20),
+            ..InterpreterLimits::default()
+        };
+        let result = run_with(limits, "fun f(n) { if (n < 10) f(n + 1); } f(0);");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn allocation_limit_counts_instances() {
+        let limits = InterpreterLimits {
+            max_allocations: Some(5),
+            ..InterpreterLimits::default()
+        };
+        let result = run_with(
+            limits,
+            "class A {} for (var i = 0; i < 10; i = i + 1) { A(); }",
+        );
+        assert!(matches!(result, Err(LoxError::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn allocation_limit_counts_natives_and_resets_each_run() {
+        let limits = InterpreterLimits {
+            max_allocations: Some(5),
+            ..InterpreterLimits::default()
+        };
+        let mut lox = Lox::new("");
+        lox.set_limits(limits);
+        let source = "for (var i = 0; i < 3; i = i + 1) chars(\"abc\");";
+        assert!(lox.try_run(source).is_ok());
+        assert!(lox.try_run(source).is_ok());
+        match lox.try_run("for (var i = 0; i < 10; i = i + 1) chars(\"abc\");") {
+            Err(LoxError::LimitExceeded(message)) => {
+                assert_eq!(message, "Allocation limit of 5 exceeded.")
+            }
+            other => panic!("Expected the allocation limit, got {:?}", other),
+        }
+    }
+
+    /// A native that reads a file, as a host might add.
+    #[derive(Clone)]
+    struct ReadConfig;
+
+    impl Callable for ReadConfig {
+        fn call(
+            &mut self,
+            interpreter: &mut Interpreter,
+            _: Vec<Option<Value>>,
+        ) -> Result<Option<Value>, LoxError> {
+            interpreter.require(Permission::Fs)?;
+            Ok(Some(Value::String("debug = true".to_string())))
+        }
+
+        fn arity(&self) -> usize {
+            0
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn clone_box(&self) -> Box<dyn Callable> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn sandbox_refuses_filesystem_natives() {
+        let mut lox = Lox::new("");
+        lox.define_native("readConfig", Box::new(ReadConfig));
+        assert!(lox.try_run("readConfig();").is_ok());
+
+        lox.set_limits(InterpreterLimits::sandboxed());
+        match lox.try_run("readConfig();") {
+            Err(LoxError::Runtime(error)) => {
+                assert_eq!(error.message, "Filesystem access is not allowed.")
+            }
+            other => panic!("Expected a runtime error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! The kinds of warning the resolver gives, and how seriously each is
+//! taken. `--strict` makes all of them errors.
+
+use crate::error::ErrorCode;
+
+/// A kind of suspicious code the resolver warns about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A local `var` that is never read. Names starting with `_` are exempt.
+    UnusedLocal,
+    /// A local declared with the same name as a local in an enclosing scope.
+    Shadowing,
+    /// An assignment to a name the program never declares and that isn't
+    /// already a global, which fails with `Undefined variable` if it runs.
+    UndeclaredAssignment,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 3] = [
+        Lint::UnusedLocal,
+        Lint::Shadowing,
+        Lint::UndeclaredAssignment,
+    ];
+
+    /// The code reported with the lint, whether as a warning or an error.
+    pub fn code(self) -> ErrorCode {
+        match self {
+            Lint::UnusedLocal => ErrorCode::UnusedLocal,
+            Lint::Shadowing => ErrorCode::Shadowing,
+            Lint::UndeclaredAssignment => ErrorCode::UndeclaredAssignment,
+        }
+    }
+}
+
+/// What happens when a lint fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Severity {
+    /// Nothing is reported.
+    Allow,
+    /// A warning is reported if warnings are enabled, and the script runs.
+    #[default]
+    Warn,
+    /// A resolve error is reported and the script doesn't run.
+    Error,
+}
+
+/// The severity of each lint. Every lint warns by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Lints {
+    severities: [Severity; Lint::ALL.len()],
+}
+
+impl Lints {
+    /// Every lint an error, as `--strict` asks for.
+    pub fn strict() -> Self {
+        Lints {
+            severities: [Severity::Error; Lint::ALL.len()],
+        }
+    }
+
+    pub fn severity(&self, lint: Lint) -> Severity {
+        self.severities[lint as usize]
+    }
+
+    pub fn set_severity(&mut self, lint: Lint, severity: Severity) {
+        self.severities[lint as usize] = severity;
+    }
+}
@@ -0,0 +1,128 @@
+//! Levels and formatting for the `logInfo`, `logWarn`, and `logError`
+//! natives, which write to stderr so they stay out of a script's output.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The least severe message the interpreter logs. Messages below it are
+/// dropped; `Off` drops them all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LogLevel {
+    #[default]
+    Info,
+    Warn,
+    Error,
+    Off,
+}
+
+impl LogLevel {
+    /// Whether a message at `level` is shown when this is the threshold.
+    pub fn allows(self, level: LogLevel) -> bool {
+        level != LogLevel::Off && level >= self
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+            LogLevel::Off => "OFF",
+        };
+        f.pad(name)
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "info" => Ok(LogLevel::Info),
+            "warn" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            "off" => Ok(LogLevel::Off),
+            _ => Err(format!(
+                "Unknown log level '{}'. Expected info, warn, error, or off.",
+                s
+            )),
+        }
+    }
+}
+
+/// Formats a log line as `<UTC timestamp> <LEVEL> <message>`.
+pub fn log_line(level: LogLevel, message: &str, time: SystemTime) -> String {
+    format!("{} {:<5} {}", timestamp(time), level, message)
+}
+
+/// An RFC 3339 UTC timestamp with milliseconds, as in `2024-05-01T12:30:00.250Z`.
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds_of_day = seconds % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date, using Howard
+/// Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_lines_with_utc_timestamps() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_714_566_600_250);
+        assert_eq!(
+            log_line(LogLevel::Warn, "disk almost full", time),
+            "2024-05-01T12:30:00.250Z WARN  disk almost full"
+        );
+        assert_eq!(
+            log_line(LogLevel::Error, "x", UNIX_EPOCH),
+            "1970-01-01T00:00:00.000Z ERROR x"
+        );
+        // A leap day
+        let time = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        assert!(log_line(LogLevel::Info, "", time).starts_with("2000-02-29T00:00:00"));
+    }
+
+    #[test]
+    fn levels_filter_less_severe_messages() {
+        assert!(LogLevel::Info.allows(LogLevel::Info));
+        assert!(LogLevel::Warn.allows(LogLevel::Error));
+        assert!(!LogLevel::Warn.allows(LogLevel::Info));
+        assert!(!LogLevel::Off.allows(LogLevel::Error));
+        assert_eq!("warn".parse(), Ok(LogLevel::Warn));
+        assert!("loud".parse::<LogLevel>().is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::environment::Environment;
+use crate::error::LoxError;
 use crate::lox_function::LoxFunction;
 use crate::lox_instance::LoxInstance;
 use crate::stmt::Stmt;
@@ -19,6 +20,9 @@ pub struct LoxClass {
     pub methods: HashMap<String, LoxFunction>,
     name: String,
     superclass: Box<Option<LoxClass>>,
+    // Shared by the copies of one class, so two classes with the same name
+    // and methods can be told apart
+    identity: Rc<()>,
 }
 
 impl LoxClass {
@@ -30,17 +34,14 @@ impl LoxClass {
         supclass: Option<LoxClass>,
     ) -> Self {
         match declaration {
-            Stmt::Class {
-                name: _,
-                superclass: _,
-                methods: _,
-            } => Self {
+            Stmt::Class { .. } => Self {
                 arity: 0,
                 declaration,
                 closure,
                 methods,
                 name: class_name,
                 superclass: Box::new(supclass),
+                identity: Rc::new(()),
             },
             _ => panic!("Expected Stmt::Function, got {:?}", declaration),
         }
@@ -58,6 +59,33 @@ impl LoxClass {
         }
         None
     }
+
+    /// The class's name, or `anonymous` for a class expression.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The class this one inherits from, if any.
+    pub fn superclass(&self) -> Option<&LoxClass> {
+        self.superclass.as_ref().as_ref()
+    }
+
+    /// Whether `self` is `class` or inherits from it, however indirectly.
+    pub fn is_subclass_of(&self, class: &LoxClass) -> bool {
+        Rc::ptr_eq(&self.identity, &class.identity)
+            || self
+                .superclass()
+                .is_some_and(|superclass| superclass.is_subclass_of(class))
+    }
+
+    /// The names of every method, including inherited ones.
+    pub fn method_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.methods.keys().cloned().collect();
+        if let Some(superclass) = self.superclass.as_ref() {
+            names.extend(superclass.method_names());
+        }
+        names
+    }
 }
 
 impl Callable for LoxClass {
@@ -65,18 +93,17 @@ impl Callable for LoxClass {
         &mut self,
         interpreter: &mut crate::interpreter::Interpreter,
         arguments: Vec<Option<crate::value::Value>>,
-    ) -> Option<Value> {
+    ) -> Result<Option<Value>, LoxError> {
+        interpreter.track_allocation()?;
         let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::new(RefCell::new(
             self.clone(),
         )))));
         if let Some(initializer) = self.find_method("init".to_string()) {
-            if let Some(Value::Callable(mut callable)) =
-                initializer.bind(instance.borrow_mut().clone())
-            {
-                callable.call(interpreter, arguments);
+            if let Some(Value::Callable(mut callable)) = initializer.bind(instance.clone()) {
+                callable.call(interpreter, arguments)?;
             }
         }
-        Some(Value::Instance(instance.clone()))
+        Ok(Some(Value::Instance(instance.clone())))
     }
 
     fn arity(&self) -> usize {
@@ -100,11 +127,12 @@ impl Callable for LoxClass {
             methods: self.methods.clone(),
             name: self.name.clone(),
             superclass: self.superclass.clone(),
+            identity: self.identity.clone(),
         })
     }
 
     fn to_string(&self) -> String {
-        format!("{}", self.name)
+        self.name.clone()
     }
 }
 
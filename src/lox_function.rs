@@ -1,5 +1,6 @@
 use crate::callable::Callable;
 use crate::environment::Environment;
+use crate::error::LoxError;
 use crate::interpreter::Interpreter;
 use crate::lox_instance::LoxInstance;
 use crate::return_value::ReturnValue;
@@ -36,40 +37,28 @@ impl LoxFunction {
         }
     }
 
-    pub fn bind(&self, instance: LoxInstance) -> Option<Value> {
+    /// Returns a copy of the method whose closure defines `this` as `instance`.
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> Option<Value> {
         let mut environment = Environment::new(Some(self.closure.clone()));
-        environment.define(
-            "this".to_string(),
-            Some(Value::Instance(Rc::new(RefCell::new(instance)))),
-        );
+        environment.define("this".to_string(), Some(Value::Instance(instance)));
 
         let function = Value::Callable(Box::new(LoxFunction::new(
             self.declaration.clone(),
-            Rc::new(RefCell::new(environment.clone())),
+            Rc::new(RefCell::new(environment)),
             self.is_initializer,
         )));
 
         Some(function)
     }
+}
 
-    fn sync_closure_with_interpreter_env(
-        closure: Rc<RefCell<Environment>>,
-        interpreter_env: Rc<RefCell<Environment>>,
-    ) {
-        // Borrow both the closure environment and the interpreter environment
-        let closure_env = closure.borrow();
-        let mut interpreter_env_mut = interpreter_env.borrow_mut();
-
-        // Iterate over the closure's environment variables
-        for (key, value) in closure_env.values.iter() {
-            // Check if the variable exists in the interpreter's environment
-            if !interpreter_env_mut.values.contains_key(key) {
-                // If it does not exist, insert it into the interpreter's environment
-                interpreter_env_mut
-                    .values
-                    .insert(key.clone(), value.clone());
-            }
-        }
+/// The `this` an initializer returns, placed at the function's name.
+fn this_token(name: &Token) -> Token {
+    Token {
+        type_: TokenType::This,
+        lexeme: "this".to_string(),
+        literal: None,
+        ..name.clone()
     }
 }
 
@@ -78,17 +67,11 @@ impl Callable for LoxFunction {
         &mut self,
         interpreter: &mut Interpreter,
         arguments: Vec<Option<Value>>,
-    ) -> Option<Value> {
+    ) -> Result<Option<Value>, LoxError> {
         match &self.declaration {
-            Stmt::Function {
-                name: _,
-                params,
-                body,
-            } => {
+            Stmt::Function { name, params, body } => {
                 // Create a new environment for the function call, using the closure as the enclosing scope
-                let env = Rc::new(RefCell::new(Environment::new(Some(
-                    interpreter.environment.clone(),
-                ))));
+                let env = Rc::new(RefCell::new(Environment::new(Some(self.closure.clone()))));
 
                 // Define the parameters in the new environment
                 for (i, param) in params.iter().enumerate() {
@@ -96,38 +79,21 @@ impl Callable for LoxFunction {
                         .define(param.lexeme.clone(), Some(arguments[i].clone().unwrap()));
                 }
 
-                if !Rc::ptr_eq(&self.closure, &interpreter.environment) {
-                    LoxFunction::sync_closure_with_interpreter_env(
-                        self.closure.clone(),
-                        interpreter.environment.clone(),
-                    );
-                }
-
                 // Execute the function block in the new environment
-                match interpreter.execute_function_block(&body, env) {
+                match interpreter.execute_function_block(body, env)? {
                     Some(ReturnValue { value }) => {
                         if self.is_initializer {
-                            let this_token = Token {
-                                type_: TokenType::Identifier, // Replace with the appropriate type
-                                lexeme: "this".to_string(),
-                                literal: None,
-                                line: 0, // Use the appropriate line number if needed
-                            };
-                            return Some(self.closure.borrow().get_at(0, &this_token));
+                            let this_token = this_token(name);
+                            return Ok(Some(self.closure.borrow().get_at(0, &this_token)));
                         }
-                        Some(value)
+                        Ok(Some(value))
                     }
                     None => {
                         if self.is_initializer {
-                            let this_token = Token {
-                                type_: TokenType::Identifier, // Replace with the appropriate type
-                                lexeme: "this".to_string(),
-                                literal: None,
-                                line: 0, // Use the appropriate line number if needed
-                            };
-                            return Some(self.closure.borrow().get_at(0, &this_token));
+                            let this_token = this_token(name);
+                            return Ok(Some(self.closure.borrow().get_at(0, &this_token)));
                         }
-                        Some(Value::Nil())
+                        Ok(Some(Value::Nil()))
                     }
                 }
             }
@@ -164,4 +130,11 @@ impl Callable for LoxFunction {
             _ => panic!("Expected Stmt::Function, got {:?}", self.declaration),
         }
     }
+
+    fn name(&self) -> String {
+        match &self.declaration {
+            Stmt::Function { name, .. } => name.lexeme.clone(),
+            _ => panic!("Expected Stmt::Function, got {:?}", self.declaration),
+        }
+    }
 }
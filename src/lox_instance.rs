@@ -1,6 +1,8 @@
+use crate::error::{ErrorCode, LoxError};
 use crate::lox_class::LoxClass;
 use crate::runtime_error::RuntimeError;
 use crate::stmt::Stmt;
+use crate::suggest;
 use crate::token::Token;
 use crate::value::Value;
 
@@ -22,19 +24,37 @@ impl LoxInstance {
         }
     }
 
-    pub fn get(&self, name: &Token) -> Option<Value> {
-        if let Some(value) = self.fields.get(&name.lexeme) {
-            return Some(value.clone());
+    /// Looks up a field, or else a method bound to `instance`.
+    pub fn get(
+        instance: &Rc<RefCell<LoxInstance>>,
+        name: &Token,
+    ) -> Result<Option<Value>, LoxError> {
+        if let Some(value) = instance.borrow().fields.get(&name.lexeme) {
+            return Ok(Some(value.clone()));
         }
 
-        let method = self.klass.borrow_mut().find_method(name.lexeme.clone());
+        let method = instance
+            .borrow()
+            .klass
+            .borrow()
+            .find_method(name.lexeme.clone());
         if let Some(method) = method {
-            return method.bind(self.clone());
+            return Ok(method.bind(instance.clone()));
         }
 
-        let error = RuntimeError::new(name.clone(), "Undefined property.");
-        crate::runtime_error(error);
-        None
+        let instance = instance.borrow();
+        let mut names = instance.klass.borrow().method_names();
+        names.extend(instance.fields.keys().cloned());
+        let message = suggest::did_you_mean(
+            format!("Undefined property '{}'.", name.lexeme),
+            &name.lexeme,
+            names.iter().map(String::as_str),
+        );
+        Err(LoxError::Runtime(RuntimeError::new(
+            name.clone(),
+            ErrorCode::UndefinedProperty,
+            &message,
+        )))
     }
 
     pub fn set(&mut self, name: Token, value: Option<Value>) {
@@ -0,0 +1,94 @@
+use crate::callable::Callable;
+use crate::error::{ErrorCode, LoxError};
+use crate::interpreter::Interpreter;
+use crate::lox_class::LoxClass;
+use crate::stmt::Stmt;
+use crate::value::Value;
+use std::any::Any;
+
+/// An interface: the names and arities of the methods a class declared with
+/// `implements` must define, itself or through its superclasses.
+#[derive(Debug, Clone)]
+pub struct LoxInterface {
+    pub declaration: Stmt,
+    name: String,
+    methods: Vec<(String, usize)>,
+}
+
+impl LoxInterface {
+    pub fn new(declaration: Stmt) -> Self {
+        match &declaration {
+            Stmt::Interface { name, methods } => Self {
+                name: name.lexeme.clone(),
+                methods: methods
+                    .iter()
+                    .map(|(method, params)| (method.lexeme.clone(), params.len()))
+                    .collect(),
+                declaration,
+            },
+            _ => panic!("Expected Stmt::Interface, got {:?}", declaration),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Why `class` doesn't implement the interface, or `None` if it does.
+    /// Only the first method it lacks is named.
+    pub fn missing_from(&self, class: &LoxClass) -> Option<String> {
+        self.methods
+            .iter()
+            .find_map(|(method, arity)| match class.find_method(method.clone()) {
+                None => Some(format!(
+                    "Class '{}' must define '{}' to implement '{}'.",
+                    class.name(),
+                    method,
+                    self.name
+                )),
+                Some(found) if found.arity() != *arity => Some(format!(
+                    "Method '{}' of class '{}' takes {} parameters but '{}' requires {}.",
+                    method,
+                    class.name(),
+                    found.arity(),
+                    self.name,
+                    arity
+                )),
+                Some(_) => None,
+            })
+    }
+}
+
+impl Callable for LoxInterface {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        _arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        Err(interpreter.native_error(
+            ErrorCode::NotCallable,
+            "Can only call functions and classes.",
+        ))
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    // So any call reaches `call` and its error, rather than an arity error
+    fn variadic(&self) -> bool {
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn to_string(&self) -> String {
+        self.name.clone()
+    }
+}
@@ -0,0 +1,139 @@
+//! Lists: `Value::List`, made by literals such as `[1, 2, 3]` and by natives
+//! such as `chars`. Scripts index them with `list[i]`, read `list.length`,
+//! and call `list.get(index)` and `list.push(value)`.
+
+use crate::callable::Callable;
+use crate::error::{ErrorCode, LoxError};
+use crate::interpreter::Interpreter;
+use crate::runtime_error::RuntimeError;
+use crate::token::Token;
+use crate::value::Value;
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The items of a list, shared by every value that holds it.
+pub type List = Rc<RefCell<Vec<Value>>>;
+
+/// A new list holding `items`.
+pub fn new(items: Vec<Value>) -> Value {
+    Value::List(Rc::new(RefCell::new(items)))
+}
+
+/// Looks up a property for `list.name`, binding methods to the list.
+pub fn get(list: &List, name: &Token) -> Result<Option<Value>, LoxError> {
+    match name.lexeme.as_str() {
+        "length" => Ok(Some(Value::Number(list.borrow().len() as f64))),
+        "get" | "push" => Ok(Some(Value::Callable(Box::new(ListMethod {
+            list: list.clone(),
+            name: name.lexeme.clone(),
+        })))),
+        _ => {
+            let error = RuntimeError::new(
+                name.clone(),
+                ErrorCode::UndefinedProperty,
+                &format!("Undefined property '{}'.", name.lexeme),
+            );
+            Err(LoxError::Runtime(error))
+        }
+    }
+}
+
+/// `list[index]`.
+pub fn index(list: &List, bracket: &Token, index: Option<Value>) -> Result<Value, LoxError> {
+    let items = list.borrow();
+    let position = position(items.len(), bracket, index)?;
+    Ok(items[position].clone())
+}
+
+/// `list[index] = value`.
+pub fn set_index(
+    list: &List,
+    bracket: &Token,
+    index: Option<Value>,
+    value: Value,
+) -> Result<(), LoxError> {
+    let mut items = list.borrow_mut();
+    let position = position(items.len(), bracket, index)?;
+    items[position] = value;
+    Ok(())
+}
+
+/// Where `index` points in a list of `length` items, which must be a
+/// whole number less than `length`.
+fn position(length: usize, bracket: &Token, index: Option<Value>) -> Result<usize, LoxError> {
+    let Some(Value::Number(index)) = index else {
+        let error = RuntimeError::new(
+            bracket.clone(),
+            ErrorCode::InvalidIndex,
+            "List index must be a number.",
+        );
+        return Err(LoxError::Runtime(error));
+    };
+    if index.fract() != 0.0 {
+        let error = RuntimeError::new(
+            bracket.clone(),
+            ErrorCode::InvalidIndex,
+            "List index must be a whole number.",
+        );
+        return Err(LoxError::Runtime(error));
+    }
+    if index < 0.0 || index >= length as f64 {
+        let error = RuntimeError::new(
+            bracket.clone(),
+            ErrorCode::IndexOutOfRange,
+            &format!(
+                "List index {} is out of range for a list of length {}.",
+                index, length
+            ),
+        );
+        return Err(LoxError::Runtime(error));
+    }
+    Ok(index as usize)
+}
+
+/// A list's `get` or `push` method, bound to the list.
+#[derive(Clone)]
+struct ListMethod {
+    list: List,
+    name: String,
+}
+
+impl Callable for ListMethod {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let argument = arguments.into_iter().next().flatten();
+        let mut items = self.list.borrow_mut();
+        let result = match (self.name.as_str(), argument) {
+            // Indexes that aren't whole numbers in range give nil
+            ("get", Some(Value::Number(index))) if index >= 0.0 && index.fract() == 0.0 => {
+                items.get(index as usize).cloned()
+            }
+            ("push", Some(value)) => {
+                items.push(value);
+                Some(Value::Number(items.len() as f64))
+            }
+            _ => None,
+        };
+        Ok(Some(result.unwrap_or(Value::Nil())))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
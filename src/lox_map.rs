@@ -0,0 +1,162 @@
+use crate::error::LoxError;
+use crate::interpreter::Interpreter;
+use crate::lox_list;
+use crate::native_instance::NativeInstance;
+use crate::value::Value;
+use std::collections::HashMap;
+
+/// A map from strings to values, returned by natives such as `globals`.
+/// Scripts read `map.length` and call `get(key)`, `set(key, value)`,
+/// `has(key)`, and `keys()`.
+///
+/// Entries keep the order their keys were first set in, so `keys()` and
+/// `inspect` give the same output on every run. Setting an existing key
+/// changes its value but not its place.
+#[derive(Debug, Default)]
+pub struct LoxMap {
+    /// Keyed by the string's text, without the quotes `Value::String` keeps.
+    entries: Vec<(String, Value)>,
+    // Where each key sits in `entries`
+    index: HashMap<String, usize>,
+}
+
+impl LoxMap {
+    pub fn new() -> Self {
+        LoxMap::default()
+    }
+
+    /// Builds a map from `entries`, in the order given.
+    pub fn from_entries(entries: impl IntoIterator<Item = (String, Value)>) -> Self {
+        let mut map = LoxMap::new();
+        for (key, value) in entries {
+            map.insert(key, value);
+        }
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn lookup(&self, key: &str) -> Option<&Value> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn insert(&mut self, key: String, value: Value) {
+        match self.index.get(&key) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    /// The entries in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+/// The text of a string key, or `None` for keys of other types.
+fn key_text(key: &Option<Value>) -> Option<&str> {
+    match key {
+        Some(Value::String(s)) => Some(&s[1..s.len() - 1]),
+        _ => None,
+    }
+}
+
+impl NativeInstance for LoxMap {
+    fn class_name(&self) -> String {
+        "Map".to_string()
+    }
+
+    fn get(&self, name: &str) -> Option<Value> {
+        match name {
+            "length" => Some(Value::Number(self.len() as f64)),
+            _ => None,
+        }
+    }
+
+    fn method_arity(&self, name: &str) -> Option<usize> {
+        match name {
+            "keys" => Some(0),
+            "get" | "has" => Some(1),
+            "set" => Some(2),
+            _ => None,
+        }
+    }
+
+    fn call_method(
+        &mut self,
+        name: &str,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        Ok(match name {
+            "keys" => {
+                interpreter.track_allocation()?;
+                let keys = self
+                    .iter()
+                    .map(|(key, _)| Value::String(format!("\"{}\"", key)))
+                    .collect();
+                Some(lox_list::new(keys))
+            }
+            "get" => key_text(&arguments[0]).and_then(|key| self.lookup(key).cloned()),
+            "has" => Some(Value::Boolean(
+                key_text(&arguments[0]).is_some_and(|key| self.index.contains_key(key)),
+            )),
+            // Only string keys are stored; others leave the map unchanged
+            "set" => {
+                let value = arguments[1].clone().unwrap_or(Value::Nil());
+                if let Some(key) = key_text(&arguments[0]) {
+                    self.insert(key.to_string(), value.clone());
+                }
+                Some(value)
+            }
+            _ => None,
+        })
+    }
+
+    fn inspect(&self, inspect: &mut dyn FnMut(&Value) -> String) -> Option<String> {
+        let entries: Vec<String> = self
+            .iter()
+            .map(|(key, value)| format!("\"{}\": {}", key, inspect(value)))
+            .collect();
+        Some(format!("{{{}}}", entries.join(", ")))
+    }
+
+    fn entries(&self) -> Option<Vec<(String, Value)>> {
+        let entries = self.iter();
+        Some(
+            entries
+                .map(|(key, value)| (format!("[\"{}\"]", key), value.clone()))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_keep_insertion_order() {
+        let mut map = LoxMap::from_entries([
+            ("b".to_string(), Value::Number(1.0)),
+            ("a".to_string(), Value::Number(2.0)),
+        ]);
+        map.insert("c".to_string(), Value::Nil());
+        // Overwriting keeps the key where it was
+        map.insert("b".to_string(), Value::Number(3.0));
+
+        let keys: Vec<&String> = map.iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, ["b", "a", "c"]);
+        assert_eq!(map.lookup("b"), Some(&Value::Number(3.0)));
+        assert_eq!(map.len(), 3);
+    }
+}
@@ -0,0 +1,485 @@
+//! Macros: `macro name(params) { body }` declares a statement that a later
+//! `name(args);` stands for. Expansion runs after parsing and before
+//! resolving, replacing each use with a block holding a copy of the body in
+//! which every parameter is its argument's expression, evaluated wherever
+//! the parameter appears.
+//!
+//! Expansion is hygienic: the names the body declares are renamed in each
+//! copy, so they can neither capture a variable an argument uses nor clash
+//! with the caller's. A macro can use the macros declared before it, but not
+//! itself.
+
+use crate::error::{ErrorCode, LoxError};
+use crate::expr::{Expr, SlotCache};
+use crate::parser::ParseError;
+use crate::rewrite::{map_expr, map_stmt, rewrite_program, Rewriter};
+use crate::stmt::Stmt;
+use crate::token::Token;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Vec<Token>,
+    body: Vec<Stmt>,
+}
+
+/// The macros declared so far. A `Lox` keeps them from one run to the next,
+/// like its globals.
+#[derive(Debug, Clone, Default)]
+pub struct Macros {
+    macros: HashMap<String, Macro>,
+    /// How many copies of bodies and arguments expansion has made.
+    copies: usize,
+}
+
+impl Macros {
+    pub fn new() -> Self {
+        Macros::default()
+    }
+
+    /// Expands every use of a macro in `stmts`, declaring the macros they
+    /// declare as it reaches them. The declarations stay in place, and do
+    /// nothing when run.
+    pub fn expand(&mut self, stmts: Vec<Stmt>) -> Result<Vec<Stmt>, LoxError> {
+        let mut expander = Expander {
+            macros: self,
+            errors: Vec::new(),
+        };
+        let stmts = rewrite_program(&mut expander, stmts);
+        if expander.errors.is_empty() {
+            Ok(stmts)
+        } else {
+            Err(LoxError::Parse(expander.errors))
+        }
+    }
+}
+
+struct Expander<'a> {
+    macros: &'a mut Macros,
+    errors: Vec<ParseError>,
+}
+
+impl Expander<'_> {
+    /// The macro `callee` names, if it is a variable naming one.
+    fn macro_named(&self, callee: &Expr) -> Option<(Token, Macro)> {
+        match callee {
+            Expr::Variable { name, .. } => self
+                .macros
+                .macros
+                .get(&name.lexeme)
+                .map(|found| (name.clone(), found.clone())),
+            _ => None,
+        }
+    }
+
+    fn expand_use(
+        &mut self,
+        name: Token,
+        found: Macro,
+        paren: Token,
+        arguments: Vec<Expr>,
+    ) -> Stmt {
+        if arguments.len() != found.params.len() {
+            self.errors.push(ParseError {
+                token: paren,
+                code: ErrorCode::MacroArgumentCount,
+                message: format!(
+                    "Expected {} arguments to macro '{}' but got {}.",
+                    found.params.len(),
+                    name.lexeme,
+                    arguments.len()
+                ),
+            });
+            return Stmt::Block(Vec::new());
+        }
+        self.macros.copies += 1;
+        let mut expansion = Expansion {
+            suffix: format!("@{}", name.lexeme),
+            copy: self.macros.copies,
+            arguments: found
+                .params
+                .iter()
+                .map(|param| param.lexeme.clone())
+                .zip(arguments)
+                .collect(),
+            scopes: vec![HashMap::new()],
+            copies: &mut self.macros.copies,
+            errors: &mut self.errors,
+        };
+        Stmt::Block(rewrite_program(&mut expansion, found.body))
+    }
+}
+
+impl Rewriter for Expander<'_> {
+    fn rewrite_expr(&mut self, expr: Expr) -> Expr {
+        if let Expr::Call { callee, paren, .. } = &expr {
+            if let Some((name, _)) = self.macro_named(callee) {
+                self.errors.push(ParseError {
+                    token: paren.clone(),
+                    code: ErrorCode::MacroInExpression,
+                    message: format!("Macro '{}' can only be used as a statement.", name.lexeme),
+                });
+            }
+        }
+        map_expr(self, expr)
+    }
+
+    fn rewrite_stmt(&mut self, stmt: Stmt) -> Stmt {
+        match stmt {
+            Stmt::Macro { name, params, body } => {
+                // Before it's declared, so the body can't use the macro itself
+                let body = rewrite_program(self, body);
+                self.macros.macros.insert(
+                    name.lexeme.clone(),
+                    Macro {
+                        params: params.clone(),
+                        body: body.clone(),
+                    },
+                );
+                Stmt::Macro { name, params, body }
+            }
+            Stmt::Expression(Expr::Call {
+                callee,
+                paren,
+                arguments,
+            }) => match self.macro_named(&callee) {
+                Some((name, found)) => {
+                    let arguments = arguments
+                        .into_iter()
+                        .map(|argument| self.rewrite_expr(argument))
+                        .collect();
+                    self.expand_use(name, found, paren, arguments)
+                }
+                None => map_stmt(
+                    self,
+                    Stmt::Expression(Expr::Call {
+                        callee,
+                        paren,
+                        arguments,
+                    }),
+                ),
+            },
+            stmt => map_stmt(self, stmt),
+        }
+    }
+}
+
+/// Makes one use's copy of a macro body.
+struct Expansion<'a> {
+    /// Added to the names the body declares.
+    suffix: String,
+    /// Which copy this is.
+    copy: usize,
+    arguments: HashMap<String, Expr>,
+    /// The names declared in each scope of the body, outermost first, and
+    /// what they're renamed to.
+    scopes: Vec<HashMap<String, Token>>,
+    copies: &'a mut usize,
+    errors: &'a mut Vec<ParseError>,
+}
+
+impl Expansion<'_> {
+    fn declare(&mut self, name: Token) -> Token {
+        let renamed = Token {
+            lexeme: format!("{}{}", name.lexeme, self.suffix),
+            ..name
+        };
+        self.scopes
+            .last_mut()
+            .expect("The body has a scope")
+            .insert(name.lexeme, renamed.clone());
+        renamed
+    }
+
+    fn renamed(&self, name: &Token) -> Option<Token> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.lexeme))
+            .map(|renamed| Token {
+                line: name.line,
+                offset: name.offset,
+                column: name.column,
+                ..renamed.clone()
+            })
+    }
+
+    /// `token` marked as part of this copy. The interpreter tells variables
+    /// apart by their tokens, and every copy is resolved on its own. For
+    /// the same reason a copied variable gets a slot cache of its own.
+    fn mark(&self, token: Token) -> Token {
+        Token {
+            literal: Some(self.copy.to_string()),
+            ..token
+        }
+    }
+
+    /// The argument for the parameter `name`, marked as a copy of its own,
+    /// since a parameter may appear in scopes at different depths.
+    fn argument(&mut self, name: &Token) -> Option<Expr> {
+        let argument = self.arguments.get(&name.lexeme)?.clone();
+        *self.copies += 1;
+        Some(Mark(*self.copies).rewrite_expr(argument))
+    }
+
+    /// Reports an assignment to the parameter `name` whose argument can't be
+    /// assigned to.
+    fn unassignable(&mut self, name: &Token) {
+        self.errors.push(ParseError {
+            token: name.clone(),
+            code: ErrorCode::InvalidAssignmentTarget,
+            message: format!(
+                "Can't assign to '{}', whose argument isn't a variable, property, or list item.",
+                name.lexeme
+            ),
+        });
+    }
+
+    fn function(&mut self, params: Vec<Token>, body: Vec<Stmt>) -> (Vec<Token>, Vec<Stmt>) {
+        self.scopes.push(HashMap::new());
+        let params = params
+            .into_iter()
+            .map(|param| self.declare(param))
+            .collect();
+        let body = rewrite_program(self, body);
+        self.scopes.pop();
+        (params, body)
+    }
+
+    /// A class's methods keep their names, which are properties.
+    fn methods(&mut self, methods: Vec<Stmt>) -> Vec<Stmt> {
+        methods
+            .into_iter()
+            .map(|method| match method {
+                Stmt::Function { name, params, body } => {
+                    let (params, body) = self.function(params, body);
+                    Stmt::Function { name, params, body }
+                }
+                method => self.rewrite_stmt(method),
+            })
+            .collect()
+    }
+}
+
+impl Rewriter for Expansion<'_> {
+    fn rewrite_expr(&mut self, expr: Expr) -> Expr {
+        match expr {
+            Expr::Variable { name, .. } => {
+                if self.renamed(&name).is_none() {
+                    if let Some(argument) = self.argument(&name) {
+                        return argument;
+                    }
+                }
+                let name = self.renamed(&name).unwrap_or(name);
+                Expr::Variable {
+                    name: self.mark(name),
+                    slot: SlotCache::default(),
+                }
+            }
+            Expr::Assign { name, value } => {
+                let value = Box::new(self.rewrite_expr(*value));
+                if self.renamed(&name).is_none() {
+                    match self.argument(&name) {
+                        Some(Expr::Variable { name, .. }) => return Expr::Assign { name, value },
+                        Some(Expr::Get { object, name }) => {
+                            return Expr::Set {
+                                object,
+                                name,
+                                value,
+                            }
+                        }
+                        Some(Expr::Index {
+                            object,
+                            bracket,
+                            index,
+                        }) => {
+                            return Expr::IndexSet {
+                                object,
+                                bracket,
+                                index,
+                                value,
+                            }
+                        }
+                        Some(_) => self.unassignable(&name),
+                        None => {}
+                    }
+                }
+                let name = self.renamed(&name).unwrap_or(name);
+                Expr::Assign {
+                    name: self.mark(name),
+                    value,
+                }
+            }
+            Expr::CompoundAssign { ref target, .. } | Expr::Increment { ref target, .. } => {
+                if let Expr::Variable { name, .. } = target.as_ref() {
+                    let argument = self.arguments.get(&name.lexeme);
+                    let assignable = matches!(
+                        argument,
+                        None | Some(Expr::Variable { .. } | Expr::Get { .. } | Expr::Index { .. })
+                    );
+                    if self.renamed(name).is_none() && !assignable {
+                        self.unassignable(name);
+                    }
+                }
+                map_expr(self, expr)
+            }
+            Expr::This { keyword } => Expr::This {
+                keyword: self.mark(keyword),
+            },
+            Expr::Super { keyword, method } => Expr::Super {
+                keyword: self.mark(keyword),
+                method,
+            },
+            Expr::Class {
+                keyword,
+                superclass,
+                interfaces,
+                methods,
+            } => Expr::Class {
+                keyword,
+                superclass: superclass.map(|superclass| Box::new(self.rewrite_expr(*superclass))),
+                interfaces: interfaces
+                    .into_iter()
+                    .map(|interface| self.rewrite_expr(interface))
+                    .collect(),
+                methods: self.methods(methods),
+            },
+            expr => map_expr(self, expr),
+        }
+    }
+
+    fn rewrite_stmt(&mut self, stmt: Stmt) -> Stmt {
+        match stmt {
+            Stmt::Block(stmts) => {
+                self.scopes.push(HashMap::new());
+                let stmts = rewrite_program(self, stmts);
+                self.scopes.pop();
+                Stmt::Block(stmts)
+            }
+            Stmt::Var { name, initializer } => {
+                let initializer = initializer.map(|initializer| self.rewrite_expr(initializer));
+                Stmt::Var {
+                    name: self.declare(name),
+                    initializer,
+                }
+            }
+            Stmt::Function { name, params, body } => {
+                let name = self.declare(name);
+                let (params, body) = self.function(params, body);
+                Stmt::Function { name, params, body }
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                interfaces,
+                methods,
+            } => {
+                let name = self.declare(name);
+                Stmt::Class {
+                    name,
+                    superclass: superclass.map(|superclass| self.rewrite_expr(superclass)),
+                    interfaces: interfaces
+                        .into_iter()
+                        .map(|interface| self.rewrite_expr(interface))
+                        .collect(),
+                    methods: self.methods(methods),
+                }
+            }
+            Stmt::Interface { name, methods } => Stmt::Interface {
+                name: self.declare(name),
+                methods,
+            },
+            Stmt::Using {
+                keyword,
+                name,
+                initializer,
+                body,
+            } => {
+                let initializer = self.rewrite_expr(initializer);
+                self.scopes.push(HashMap::new());
+                let name = self.declare(name);
+                let body = Box::new(self.rewrite_stmt(*body));
+                self.scopes.pop();
+                Stmt::Using {
+                    keyword,
+                    name,
+                    initializer,
+                    body,
+                }
+            }
+            stmt => map_stmt(self, stmt),
+        }
+    }
+}
+
+/// Marks the variables of an argument's copy, as `Expansion::mark` does.
+struct Mark(usize);
+
+impl Mark {
+    fn token(&self, token: Token) -> Token {
+        Token {
+            literal: Some(self.0.to_string()),
+            ..token
+        }
+    }
+}
+
+impl Rewriter for Mark {
+    fn rewrite_expr(&mut self, expr: Expr) -> Expr {
+        match map_expr(self, expr) {
+            Expr::Variable { name, .. } => Expr::Variable {
+                name: self.token(name),
+                slot: SlotCache::default(),
+            },
+            Expr::Assign { name, value } => Expr::Assign {
+                name: self.token(name),
+                value,
+            },
+            Expr::This { keyword } => Expr::This {
+                keyword: self.token(keyword),
+            },
+            Expr::Super { keyword, method } => Expr::Super {
+                keyword: self.token(keyword),
+                method,
+            },
+            expr => expr,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Lox;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn macros_last_between_runs() {
+        let output_file = env::temp_dir().join("lox_macros_runs.txt");
+        fs::write(&output_file, "").unwrap();
+        let mut lox = Lox::new(&output_file.to_string_lossy());
+        lox.try_run("macro log(value) { print \"log: \" + value; }")
+            .unwrap();
+        lox.try_run("log(\"one\"); { log(\"two\"); }").unwrap();
+        assert_eq!(
+            fs::read_to_string(&output_file).unwrap(),
+            "log: one\nlog: two\n"
+        );
+
+        let error = lox
+            .try_run("macro reset(target) { target = 0; } reset(1 + 2);")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1:23] Error at 'target': Can't assign to 'target', whose argument isn't a variable, property, or list item. [E2009]"
+        );
+        let error = lox
+            .try_run("macro bump(target) { target += 1; target++; } bump(f());")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1:22] Error at 'target': Can't assign to 'target', whose argument isn't a variable, property, or list item. [E2009]\n\
+             [line 1:35] Error at 'target': Can't assign to 'target', whose argument isn't a variable, property, or list item. [E2009]"
+        );
+    }
+}
@@ -1,1706 +1,623 @@
-use std::cell::Cell;
-use std::cell::RefCell;
+use lox_interpreter::decimal;
+use lox_interpreter::error::{ErrorCode, LoxError};
+use lox_interpreter::limits::InterpreterLimits;
+use lox_interpreter::logging::LogLevel;
+use lox_interpreter::rename::RenameError;
+use lox_interpreter::scanner::Scanner;
+use lox_interpreter::suggest;
+use lox_interpreter::testing::{self, TestCounts};
+use lox_interpreter::token_type::TokenType;
+use lox_interpreter::value::Value;
+use lox_interpreter::Lox;
 use std::env;
+use std::fs;
 use std::fs::File;
 use std::io;
-use std::io::{BufRead, BufReader, Read, Write};
+use std::io::{Read, Write};
 use std::path::Path;
-use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
 
-mod callable;
-mod environment;
-mod expr;
-mod interpreter;
-mod lox_class;
-mod lox_function;
-mod lox_instance;
-mod native_functions;
-mod parser;
-mod resolver;
-mod return_value;
-mod runtime_error;
-mod scanner;
-mod stmt;
-mod token;
-mod token_type;
-mod value;
-mod write_output;
-
-thread_local! {
-    static HAD_ERROR: Cell<bool> = Cell::new(false);
-}
-thread_local! {
-    static HAD_RUNTIME_ERROR: Cell<bool> = Cell::new(false);
-}
+/// Stack for the thread that runs scripts. Each Lox call takes tens of
+/// kilobytes of Rust stack in debug builds, so the main thread's would run
+/// out well before `limits::DEFAULT_MAX_CALL_DEPTH` calls.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 2 {
-        eprintln!("Usage: cargo run <file_path>");
-        std::process::exit(1);
-    } else if args.len() == 2 {
-        run_file(&args[1], "");
-    } else {
-        run_prompt();
-    }
-}
-
-fn run_file(file_path: &str, output_file: &str) {
-    let path = Path::new(file_path);
-    let mut file = match File::open(&path) {
-        Ok(file) => file,
-        Err(err) => {
-            eprintln!("Error: Could not open file '{}'. {}", file_path, err);
-            std::process::exit(1);
-        }
-    };
-
-    let mut contents = String::new();
-    if let Err(err) = file.read_to_string(&mut contents) {
-        eprintln!("Error: Could not read from file '{}'. {}", file_path, err);
-        std::process::exit(1);
-    }
-
-    if HAD_RUNTIME_ERROR.with(|had_error| had_error.get()) {
-        std::process::exit(75);
+    let interpreter = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_cli)
+        .expect("Failed to start the interpreter thread");
+    // A panic has already printed its message
+    if interpreter.join().is_err() {
+        std::process::exit(101);
     }
-
-    run(&contents, output_file);
 }
 
-fn run_prompt() {
-    loop {
-        print!("> ");
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        let bytes_read = io::stdin().read_line(&mut input);
-        match bytes_read {
-            Ok(0) => {
-                break;
-            }
-            Ok(_) => {
-                run(&input.trim(), "");
-            }
-            Err(err) => {
-                eprintln!("Error reading input: {}", err);
-                break;
+fn run_cli() {
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("metrics") {
+        match &args[2..] {
+            [file_path] => run_metrics(file_path),
+            _ => {
+                eprintln!("Usage: cargo run metrics <file_path>");
+                std::process::exit(1);
             }
         }
+        return;
     }
-    HAD_ERROR.with(|had_error| {
-        if had_error.get() {
-            std::process::exit(65);
+    if args.get(1).map(String::as_str) == Some("fmt") {
+        match &args[2..] {
+            [file_path] => run_fmt(file_path),
+            _ => {
+                eprintln!("Usage: cargo run fmt <file_path>");
+                std::process::exit(1);
+            }
         }
-    });
-}
-
-fn run(source: &str, output_file: &str) {
-    HAD_ERROR.with(|had_error| {
-        had_error.set(false);
-    });
-
-    let src = source.to_string();
-    let mut scan = scanner::Scanner::new(src); // Create a new Scanner
-    let tokens = scan.scan_tokens(); // Scan tokens
-
-    let mut parse = parser::Parser::new(tokens.clone()); // Create a new Parser
-    let statements: Vec<Option<stmt::Stmt>> = parse.parse(); // Parse the tokens
-
-    if HAD_ERROR.with(|had_error| had_error.get()) {
         return;
     }
-
-    let interp = Rc::new(RefCell::new(interpreter::Interpreter::new(output_file)));
-
-    let mut resolver = resolver::Resolver::new(interp.clone());
-    resolver.resolve(statements.clone());
-
-    interp.borrow_mut().interpret(statements);
-}
-
-fn error(line: i32, message: &str) {
-    report(line, "", message);
-}
-
-fn runtime_error(error: runtime_error::RuntimeError) {
-    eprintln!("{}\n[line {}]", error.message, error.token.line);
-    HAD_RUNTIME_ERROR.with(|had_error| {
-        had_error.set(true);
-    }); // Assuming `had_runtime_error` is a thread-local variable
-    panic!("{}\n[line {}]", error.message, error.token.line);
-}
-
-fn error_token(token: &token::Token, message: &str) {
-    if token.type_ == token_type::TokenType::EoF {
-        report(token.line, "at end", message);
-    } else {
-        report(token.line, &format!("at '{}'", token.lexeme), message);
+    if args.get(1).map(String::as_str) == Some("test") {
+        run_tests(&args[2..]);
+        return;
     }
-}
-
-fn report(line: i32, location: &str, message: &str) {
-    eprintln!("[line {}] Error {}: {}", line, location, message);
-    HAD_ERROR.with(|had_error| {
-        had_error.set(true);
-    });
-    panic!("[line {}] Error {}: {}", line, location, message);
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    enum Success {
-        Standard,
+    if args.get(1).map(String::as_str) == Some("rename") {
+        run_rename(&args[2..]);
+        return;
     }
-
-    fn run_test(folder_name: &str, test_name: &str) -> Result<Success, String> {
-        // Define file names
-        let test_src = format!("./tests/{}/{}.lox", folder_name, test_name);
-        let test_output = format!("./output/actual/{}/{}.txt", folder_name, test_name);
-        let test_comparison = format!("./output/expected/{}/{}.txt", folder_name, test_name);
-
-        // Clear the output file
-        File::create(&test_output).map_err(|_| "Failed to clear output file")?;
-
-        // Run the test
-        run_file(&test_src, &test_output);
-
-        // Open the files
-        let output_file = File::open(&test_output).map_err(|_| "Failed to open output file")?;
-        let expected_file =
-            File::open(&test_comparison).map_err(|_| "Failed to open expected file")?;
-
-        // Compare number of lines in the files (by re-opening the files)
-        let output_line_count =
-            BufReader::new(File::open(&test_output).map_err(|_| "Failed to open output file")?)
-                .lines()
-                .count();
-        let expected_line_count = BufReader::new(
-            File::open(&test_comparison).map_err(|_| "Failed to open expected file")?,
-        )
-        .lines()
-        .count();
-
-        if output_line_count != expected_line_count {
-            let err_str = format!(
-                "Test {} {} failed: actual and expected files have different numbers of lines.\nActual: {}\nExpected: {}",
-                folder_name, test_name, output_line_count, expected_line_count
+    let mut plugins: Vec<String> = Vec::new();
+    let mut file_path: Option<String> = None;
+    let mut log_level = LogLevel::default();
+    let mut decimal = false;
+    let mut checked_division = false;
+    let mut checked_integers = false;
+    let mut strict_math = false;
+    let mut warnings = false;
+    let mut strict = false;
+    let mut deterministic = false;
+    let mut prelude = true;
+    let mut defines: Vec<(String, String)> = Vec::new();
+    let mut limits = InterpreterLimits::default();
+
+    let mut i = 1;
+    while i < args.len() {
+        if args[i] == "--plugin" && i + 1 < args.len() {
+            plugins.push(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--log-level" && i + 1 < args.len() {
+            log_level = match args[i + 1].parse() {
+                Ok(level) => level,
+                Err(err) => {
+                    eprintln!("Error: {}", err);
+                    std::process::exit(1);
+                }
+            };
+            i += 2;
+        } else if args[i] == "--decimal" {
+            decimal = true;
+            i += 1;
+        } else if args[i] == "--checked-division" {
+            checked_division = true;
+            i += 1;
+        } else if args[i] == "--checked-integers" {
+            checked_integers = true;
+            i += 1;
+        } else if args[i] == "--strict-math" {
+            strict_math = true;
+            i += 1;
+        } else if args[i] == "--max-call-depth" && i + 1 < args.len() {
+            limits.max_call_depth = Some(count_argument(&args[i], &args[i + 1]));
+            i += 2;
+        } else if args[i] == "--max-steps" && i + 1 < args.len() {
+            limits.max_instructions = Some(count_argument(&args[i], &args[i + 1]));
+            i += 2;
+        } else if args[i] == "--timeout-ms" && i + 1 < args.len() {
+            let millis = count_argument(&args[i], &args[i + 1]);
+            limits.max_duration = Some(Duration::from_millis(millis as u64));
+            i += 2;
+        } else if args[i] == "--define" && i + 1 < args.len() {
+            defines.push(define_argument(&args[i + 1]));
+            i += 2;
+        } else if args[i] == "--warnings" || args[i] == "-W" {
+            warnings = true;
+            i += 1;
+        } else if args[i] == "--strict" {
+            strict = true;
+            i += 1;
+        } else if args[i] == "--deterministic" {
+            deterministic = true;
+            i += 1;
+        } else if args[i] == "--no-prelude" {
+            prelude = false;
+            i += 1;
+        } else if file_path.is_none() && !args[i].starts_with("--") {
+            file_path = Some(args[i].clone());
+            i += 1;
+        } else {
+            eprintln!(
+                "Usage: cargo run [--plugin <library>]... [--log-level <info|warn|error|off>] [--decimal] [--checked-division] [--checked-integers] [--strict-math] [--warnings | -W] [--strict] [--deterministic] [--no-prelude] [--max-call-depth <n>] [--max-steps <n>] [--timeout-ms <n>] [--define <name>[=<value>]]... [file_path]\n       cargo run metrics <file_path>\n       cargo run fmt <file_path>\n       cargo run test [path]...\n       {}",
+                RENAME_USAGE
             );
-            return Err(err_str);
-        }
-
-        // Create buffered readers for the files
-        let output_reader = BufReader::new(output_file);
-        let expected_reader = BufReader::new(expected_file);
-
-        // Compare the contents of the files line by line
-        for (output_line, expected_line) in output_reader.lines().zip(expected_reader.lines()) {
-            let output_line = output_line.map_err(|_| "Failed to read from output file")?;
-            let expected_line = expected_line.map_err(|_| "Failed to read from expected file")?;
-
-            if output_line != expected_line {
-                let err_str = format!(
-                    "Test {} {} failed: actual and expected values differ.\nActual: '{}'\nExpected: '{}'",
-                    folder_name, test_name, output_line, expected_line
-                );
-                return Err(err_str);
-            }
+            std::process::exit(1);
         }
-
-        Ok(Success::Standard)
     }
 
-    #[test]
-    fn misc_empty_file() {
-        match run_test("misc", "empty_file") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
+    // Kept as a closure so `:reset` starts over in the same mode
+    let configured = || {
+        let mut lox = if prelude {
+            Lox::new("")
+        } else {
+            Lox::without_prelude("")
+        };
+        lox.set_log_level(log_level);
+        lox.set_decimal_literals(decimal);
+        lox.set_checked_division(checked_division);
+        lox.set_checked_integers(checked_integers);
+        lox.set_strict_math(strict_math);
+        lox.set_warnings(warnings);
+        lox.set_strict(strict);
+        lox.set_deterministic(deterministic);
+        lox.set_limits(limits.clone());
+        for (name, value) in &defines {
+            let value = global_value(value, decimal);
+            lox.set_flag(name, !matches!(value, Value::Boolean(false) | Value::Nil()));
+            lox.define(name, value);
+        }
+        for plugin in &plugins {
+            load_plugin(&mut lox, plugin);
+        }
+        lox
+    };
+    let mut lox = configured();
 
-    #[test]
-    fn misc_unexpected_character() {
-        let result = std::panic::catch_unwind(|| run_test("misc", "unexpected_character"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
+    match file_path {
+        Some(file_path) => run_file_with(&mut lox, &file_path),
+        None => run_prompt(&mut lox, &configured),
     }
+}
 
-    #[test]
-    fn misc_precedence() {
-        match run_test("misc", "precedence") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
+#[cfg(feature = "lox-plugins")]
+fn load_plugin(lox: &mut Lox, plugin: &str) {
+    if let Err(err) = lox_interpreter::plugin::load_plugin(lox, Path::new(plugin)) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
     }
+}
 
-    #[test]
-    fn comments_line_at_eof() {
-        match run_test("comments", "line_at_eof") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
+#[cfg(not(feature = "lox-plugins"))]
+fn load_plugin(_lox: &mut Lox, plugin: &str) {
+    eprintln!(
+        "Error: Could not load plugin '{}'. Rebuild with `--features lox-plugins` to enable plugins.",
+        plugin
+    );
+    std::process::exit(1);
+}
 
-    #[test]
-    fn comments_only_line_comment() {
-        match run_test("comments", "only_line_comment") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+/// Parses the value given for a flag such as `--max-steps`, exiting with a
+/// message if it isn't a whole number.
+fn count_argument(flag: &str, value: &str) -> usize {
+    match value.parse() {
+        Ok(count) => count,
+        Err(_) => {
+            eprintln!("Error: Invalid value '{}' for {}.", value, flag);
+            std::process::exit(1);
         }
     }
+}
 
-    #[test]
-    fn comments_only_line_comment_and_line() {
-        match run_test("comments", "only_line_comment_and_line") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+/// Splits a `--define` value into the global's name and its value, which
+/// is `true` if left out, exiting with a message if the name isn't an
+/// identifier.
+fn define_argument(argument: &str) -> (String, String) {
+    match argument.split_once('=') {
+        Some((name, value)) if Scanner::is_identifier(name) => {
+            (name.to_string(), value.to_string())
+        }
+        None if Scanner::is_identifier(argument) => (argument.to_string(), "true".to_string()),
+        _ => {
+            eprintln!(
+                "Error: Invalid value '{}' for --define. Expected <name>=<value>.",
+                argument
+            );
+            std::process::exit(1);
         }
     }
+}
 
-    #[test]
-    fn variable_in_nested_block() {
-        match run_test("variable", "in_nested_block") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+/// The value a `--define` gives its global: `true`, `false`, and `nil` as
+/// written, a number if it reads as a Lox number literal, and otherwise a
+/// string. Quotes force a string, as in `--define 'version="1.0"'`.
+fn global_value(value: &str, decimal: bool) -> Value {
+    match value {
+        "true" => return Value::Boolean(true),
+        "false" => return Value::Boolean(false),
+        "nil" => return Value::Nil(),
+        _ => {}
+    }
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        return Value::String(value.to_string());
+    }
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let is_number = digits.starts_with(|c: char| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.');
+    match value.parse() {
+        Ok(number) if is_number => {
+            let literal = if decimal {
+                decimal::literal(digits)
+            } else {
+                None
+            };
+            match (literal, value.starts_with('-')) {
+                (Some(Value::Decimal(literal)), true) => decimal::negate(&literal),
+                (Some(literal), false) => literal,
+                _ => Value::Number(number),
+            }
         }
+        // Strings keep their quotes, as literals do
+        _ => Value::String(format!("\"{}\"", value)),
     }
+}
 
-    #[test]
-    fn variable_scope_reuse_in_different_blocks() {
-        match run_test("variable", "scope_reuse_in_different_blocks") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
+fn run_file_with(lox: &mut Lox, file_path: &str) {
+    if let Err(error) = lox.try_run(&read_source(file_path)) {
+        describe(&error);
+        std::process::exit(error.exit_code());
     }
+}
 
-    #[test]
-    fn variable_local_from_method() {
-        match run_test("variable", "local_from_method") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+/// Prints a table of metrics for each function in the file.
+fn run_metrics(file_path: &str) {
+    match lox_interpreter::metrics::metrics(&read_source(file_path)) {
+        Ok(metrics) => print!("{}", lox_interpreter::metrics::table(&metrics)),
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(error.exit_code());
         }
     }
+}
 
-    #[test]
-    fn variable_use_global_in_initializer() {
-        match run_test("variable", "use_global_in_initializer") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+/// Prints the file reformatted, comments included.
+fn run_fmt(file_path: &str) {
+    match lox_interpreter::printer::format_source(&read_source(file_path)) {
+        Ok(formatted) => print!("{}", formatted),
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(error.exit_code());
         }
     }
+}
 
-    #[test]
-    fn variable_use_this_as_var() {
-        let result = std::panic::catch_unwind(|| run_test("variable", "use_this_as_var"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
+/// Runs every `*_test.lox` file under the paths, or the current directory,
+/// each in a fresh interpreter, then prints how many tests passed and
+/// failed. A file that stops with an error counts as a failure.
+fn run_tests(paths: &[String]) {
+    let paths = if paths.is_empty() {
+        vec![".".to_string()]
+    } else {
+        paths.to_vec()
+    };
+    let files: Vec<_> = paths
+        .iter()
+        .flat_map(|path| testing::test_files(Path::new(path)))
+        .collect();
+    if files.is_empty() {
+        eprintln!("Error: No *_test.lox files found.");
+        std::process::exit(1);
     }
 
-    #[test]
-    fn variable_redeclare_global() {
-        match run_test("variable", "redeclare_global") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+    let mut counts = TestCounts::default();
+    let mut broken_files = 0;
+    for file in &files {
+        println!("{}", file.display());
+        let mut lox = Lox::new("");
+        if lox.try_run(&read_source(&file.to_string_lossy())).is_err() {
+            broken_files += 1;
         }
+        counts.add(lox.test_counts());
     }
-
-    #[test]
-    fn variable_use_nil_as_var() {
-        let result = std::panic::catch_unwind(|| run_test("variable", "use_nil_as_var"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn variable_undefined_global() {
-        let result = std::panic::catch_unwind(|| run_test("variable", "undefined_global"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn variable_shadow_and_local() {
-        match run_test("variable", "shadow_and_local") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
+    println!(
+        "\n{} passed, {} failed in {} files",
+        counts.passed,
+        counts.failed,
+        files.len()
+    );
+    if broken_files > 0 {
+        println!("{} files stopped with an error", broken_files);
     }
-
-    #[test]
-    fn variable_duplicate_parameter() {
-        let result = std::panic::catch_unwind(|| run_test("variable", "duplicate_parameter"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
+    if counts.failed > 0 || broken_files > 0 {
+        std::process::exit(1);
     }
+}
 
-    #[test]
-    fn variable_uninitialized() {
-        match run_test("variable", "uninitialized") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+const RENAME_USAGE: &str =
+    "cargo run rename <file_path> --line <n> --col <n> --to <name> [--write]";
+
+/// Renames the variable, function, or class at a position in the file and
+/// prints the updated source, or with `--write` saves it over the file.
+fn run_rename(args: &[String]) {
+    let mut file_path = None;
+    let (mut line, mut column, mut new_name) = (None, None, None);
+    let mut write = false;
+    let mut i = 0;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("--line", Some(value)) => line = Some(count_argument("--line", value)),
+            ("--col", Some(value)) => column = Some(count_argument("--col", value)),
+            ("--to", Some(value)) => new_name = Some(value.clone()),
+            ("--write", _) => {
+                write = true;
+                i += 1;
+                continue;
+            }
+            (path, _) if file_path.is_none() && !path.starts_with("--") => {
+                file_path = Some(path.to_string());
+                i += 1;
+                continue;
+            }
+            _ => {
+                eprintln!("Usage: {}", RENAME_USAGE);
+                std::process::exit(1);
+            }
         }
+        i += 2;
     }
+    let (Some(file_path), Some(line), Some(column), Some(new_name)) =
+        (file_path, line, column, new_name)
+    else {
+        eprintln!("Usage: {}", RENAME_USAGE);
+        std::process::exit(1);
+    };
 
-    #[test]
-    fn variable_use_false_as_var() {
-        let result = std::panic::catch_unwind(|| run_test("variable", "use_false_as_var"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn variable_shadow_global() {
-        match run_test("variable", "shadow_global") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+    let source = read_source(&file_path);
+    let line = i32::try_from(line).unwrap_or(i32::MAX);
+    match lox_interpreter::rename::rename(&source, line, column, &new_name) {
+        Ok(renamed) if write => {
+            if let Err(err) = fs::write(&file_path, renamed) {
+                eprintln!("Error: Could not write to file '{}'. {}", file_path, err);
+                std::process::exit(1);
+            }
         }
-    }
-
-    #[test]
-    fn variable_duplicate_local() {
-        let result = std::panic::catch_unwind(|| run_test("variable", "duplicate_local"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn variable_in_middle_of_block() {
-        match run_test("variable", "in_middle_of_block") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+        Ok(renamed) => print!("{}", renamed),
+        Err(RenameError::Lox(error)) => {
+            eprintln!("{}", error);
+            std::process::exit(error.exit_code());
         }
-    }
-
-    #[test]
-    fn variable_shadow_local() {
-        match run_test("variable", "shadow_local") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            std::process::exit(1);
         }
     }
+}
 
-    #[test]
-    fn variable_unreached_undefined() {
-        match run_test("variable", "unreached_undefined") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+/// Reads a script, exiting with a message if it can't be read.
+fn read_source(file_path: &str) -> String {
+    let path = Path::new(file_path);
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Error: Could not open file '{}'. {}", file_path, err);
+            if err.kind() != io::ErrorKind::NotFound {
+                std::process::exit(1);
+            }
+            let suggestions = similar_names(file_path, &lox_files_in(Path::new(".")));
+            if !suggestions.is_empty() {
+                eprintln!("Did you mean:");
+                for suggestion in suggestions {
+                    eprintln!("  {}", suggestion);
+                }
+            }
+            // EX_NOINPUT from sysexits.h
+            std::process::exit(66);
         }
-    }
-
-    #[test]
-    fn variable_collide_with_parameter() {
-        let result = std::panic::catch_unwind(|| run_test("variable", "collide_with_parameter"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn variable_use_local_in_initializer() {
-        let result = std::panic::catch_unwind(|| run_test("variable", "use_local_in_initializer"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
+    };
 
-    #[test]
-    fn variable_redefine_global() {
-        match run_test("variable", "redefine_global") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
+    let mut contents = String::new();
+    if let Err(err) = file.read_to_string(&mut contents) {
+        eprintln!("Error: Could not read from file '{}'. {}", file_path, err);
+        std::process::exit(1);
     }
+    contents
+}
 
-    #[test]
-    fn variable_undefined_local() {
-        let result = std::panic::catch_unwind(|| run_test("variable", "undefined_local"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
+/// Reads entries from stdin until it closes or `:quit`, then exits with the
+/// status of the last entry, as running the same lines from a file would.
+/// `:reset` replaces `lox` with a new interpreter from `configured`.
+fn run_prompt(lox: &mut Lox, configured: &dyn Fn() -> Lox) {
+    let mut source = String::new();
+    let mut status = 0;
+    loop {
+        print!("{}", if source.is_empty() { "> " } else { "... " });
+        io::stdout().flush().unwrap();
 
-    #[test]
-    fn nil_literal() {
-        match run_test("nil", "literal") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+        let mut input = String::new();
+        let bytes_read = io::stdin().read_line(&mut input);
+        match bytes_read {
+            Ok(0) => {
+                break;
+            }
+            Ok(_) => {
+                if source.is_empty() && input.trim_start().starts_with(':') {
+                    match run_command(lox, input.trim(), configured) {
+                        Command::Continue => continue,
+                        Command::Quit => break,
+                    }
+                }
+
+                source.push_str(&input);
+                if !is_complete(&source) {
+                    continue;
+                }
+                status = run_line(lox, source.trim());
+                source.clear();
+            }
+            Err(err) => {
+                eprintln!("Error reading input: {}", err);
+                break;
+            }
         }
     }
-
-    #[test]
-    fn if_var_in_then() {
-        let result = std::panic::catch_unwind(|| run_test("if", "var_in_then"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn if_dangling_else() {
-        match run_test("if", "dangling_else") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
+    if status != 0 {
+        std::process::exit(status);
     }
+}
 
-    #[test]
-    fn if_truth() {
-        match run_test("if", "truth") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+/// Runs one entry, returning its exit status. `exit()` ends the session.
+fn run_line(lox: &mut Lox, source: &str) -> i32 {
+    match lox.try_run(source) {
+        Ok(()) => 0,
+        Err(LoxError::Exit(status)) => std::process::exit(status),
+        Err(error) => {
+            describe(&error);
+            error.exit_code()
         }
     }
+}
 
-    #[test]
-    fn if_fun_in_else() {
-        let result = std::panic::catch_unwind(|| run_test("if", "fun_in_else"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
+/// Prints the errors `try_run` leaves for the host to describe. Static and
+/// runtime errors were already printed through the reporter.
+fn describe(error: &LoxError) {
+    if matches!(error, LoxError::Cancelled | LoxError::LimitExceeded(_)) {
+        eprintln!("{}", error);
     }
+}
 
-    #[test]
-    fn if_class_in_else() {
-        let result = std::panic::catch_unwind(|| run_test("if", "class_in_else"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
+enum Command {
+    Continue,
+    Quit,
+}
 
-    #[test]
-    fn if_else() {
-        match run_test("if", "else") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
+/// Handles a REPL meta-command such as `:help`.
+fn run_command(lox: &mut Lox, command: &str, configured: &dyn Fn() -> Lox) -> Command {
+    match command {
+        ":help" => {
+            println!(":help   Show this message");
+            println!(":env    Show the variables in each scope");
+            println!(":reset  Forget every variable, function, and class");
+            println!(":quit   Leave the prompt");
+        }
+        ":env" => print!("{}", lox.dump_environment()),
+        ":reset" => *lox = configured(),
+        ":quit" => return Command::Quit,
+        _ => eprintln!("Unknown command '{}'. Type :help for a list.", command),
+    }
+    Command::Continue
+}
 
-    #[test]
-    fn if_fun_in_then() {
-        let result = std::panic::catch_unwind(|| run_test("if", "fun_in_then"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
+/// The names of the `.lox` files in `dir`, sorted.
+fn lox_files_in(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "lox"))
+        .filter_map(|path| Some(path.file_name()?.to_string_lossy().to_string()))
+        .collect();
+    names.sort();
+    names
+}
 
-    #[test]
-    fn if_class_in_then() {
-        let result = std::panic::catch_unwind(|| run_test("if", "class_in_then"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
+/// Up to three of `candidates` that look like typos of `name`, closest first.
+fn similar_names(name: &str, candidates: &[String]) -> Vec<String> {
+    let name = Path::new(name)
+        .file_name()
+        .map_or(name.to_string(), |name| name.to_string_lossy().to_string());
+    suggest::similar_names(&name, candidates.iter().map(String::as_str), 3)
+}
 
-    #[test]
-    fn if_var_in_else() {
-        let result = std::panic::catch_unwind(|| run_test("if", "var_in_else"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
+/// Whether `source` closes every brace, parenthesis, string, heredoc,
+/// block comment, and `#if` it opens, so a declaration can span several lines at the prompt.
+fn is_complete(source: &str) -> bool {
+    let mut tokens = Scanner::with_trivia(source);
+    let mut depth = 0;
+    for token in tokens.by_ref() {
+        match token.type_ {
+            TokenType::LeftBrace | TokenType::LeftParen => depth += 1,
+            TokenType::RightBrace | TokenType::RightParen => depth -= 1,
+            _ => {}
+        }
+    }
+    let unterminated = tokens.errors().iter().any(|error| {
+        matches!(
+            error.code,
+            ErrorCode::UnterminatedString
+                | ErrorCode::UnterminatedHeredoc
+                | ErrorCode::UnterminatedBlockComment
+                | ErrorCode::UnterminatedPragma
+        )
+    });
+    depth <= 0 && !unterminated
+}
 
-    #[test]
-    fn if_if() {
-        match run_test("if", "if") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
+#[cfg(test)]
+mod tests {
+    use lox_interpreter::expectations;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
 
-    #[test]
-    fn assignment_grouping() {
-        let result = std::panic::catch_unwind(|| run_test("assignment", "grouping"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
+    enum Success {
+        Standard,
     }
 
-    #[test]
-    fn assignment_syntax() {
-        match run_test("assignment", "syntax") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
+    fn run_test(folder_name: &str, test_name: &str) -> Result<Success, String> {
+        // Define file names
+        let test_src = format!("./tests/{}/{}.lox", folder_name, test_name);
+        let test_output = format!("./output/actual/{}/{}.txt", folder_name, test_name);
 
-    #[test]
-    fn assignment_global() {
-        match run_test("assignment", "global") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
+        fs::create_dir_all(format!("./output/actual/{}", folder_name))
+            .map_err(|_| "Failed to create output directory")?;
+
+        // Run the test and compare against its // expect: comments
+        let run = expectations::run_script(Path::new(&test_src), Path::new(&test_output))
+            .map_err(|err| format!("Test {} {} failed: {}", folder_name, test_name, err))?;
+        if env::var_os("LOX_BLESS").is_some() {
+            // Golden update mode: take this run as the new expected output
+            let source = fs::read_to_string(&test_src).map_err(|_| "Failed to read test file")?;
+            fs::write(&test_src, expectations::bless(&source, &run))
+                .map_err(|_| "Failed to update test file")?;
+        } else {
+            run.check()
+                .map_err(|err| format!("Test {} {} failed: {}", folder_name, test_name, err))?;
+        }
+
+        // Then against the reviewed snapshot of everything it printed
+        insta::with_settings!({
+            snapshot_path => "../output/snapshots",
+            prepend_module_to_snapshot => false,
+            input_file => &test_src,
+            omit_expression => true,
+        }, {
+            insta::assert_snapshot!(
+                format!("{}__{}", folder_name.replace('/', "__"), test_name),
+                run.transcript()
+            );
+        });
 
-    #[test]
-    fn assignment_prefix_operator() {
-        let result = std::panic::catch_unwind(|| run_test("assignment", "prefix_operator"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
+        Ok(Success::Standard)
     }
 
-    #[test]
-    fn assignment_associativity() {
-        match run_test("assignment", "associativity") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
+    fn run_lox_test(folder_name: &str, test_name: &str) {
+        if let Err(err) = run_test(folder_name, test_name) {
+            panic!("{}", err);
         }
     }
 
     #[test]
-    fn assignment_to_this() {
-        let result = std::panic::catch_unwind(|| run_test("assignment", "to_this"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn assignment_infix_operator() {
-        let result = std::panic::catch_unwind(|| run_test("assignment", "infix_operator"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
+    fn suggests_similar_file_names() {
+        let files: Vec<String> = ["fib.lox", "fizzbuzz.lox", "main.lox", "test.lox"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        assert_eq!(super::similar_names("fbi.lox", &files), vec!["fib.lox"]);
+        assert_eq!(
+            super::similar_names("scripts/mian.lox", &files),
+            vec!["main.lox"]
+        );
+        assert_eq!(super::similar_names("tset.lox", &files), vec!["test.lox"]);
+        assert!(super::similar_names("unrelated.lox", &files).is_empty());
     }
 
-    #[test]
-    fn assignment_local() {
-        match run_test("assignment", "local") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn assignment_undefined() {
-        let result = std::panic::catch_unwind(|| run_test("assignment", "undefined"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn return_after_if() {
-        match run_test("return", "after_if") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn return_after_else() {
-        match run_test("return", "after_else") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn return_at_top_level() {
-        let result = std::panic::catch_unwind(|| run_test("return", "at_top_level"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn return_return_nil_if_no_value() {
-        match run_test("return", "return_nil_if_no_value") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn return_in_method() {
-        match run_test("return", "in_method") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn return_in_function() {
-        match run_test("return", "in_function") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn return_after_while() {
-        match run_test("return", "after_while") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn function_empty_body() {
-        match run_test("function", "empty_body") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn function_too_many_arguments() {
-        let result = std::panic::catch_unwind(|| run_test("function", "too_many_arguments"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn function_missing_comma_in_parameters() {
-        let result =
-            std::panic::catch_unwind(|| run_test("function", "missing_comma_in_parameters"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn function_nested_call_with_arguments() {
-        match run_test("function", "nested_call_with_arguments") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn function_body_must_be_block() {
-        let result = std::panic::catch_unwind(|| run_test("function", "body_must_be_block"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn function_missing_arguments() {
-        let result = std::panic::catch_unwind(|| run_test("function", "missing_arguments"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn function_parameters() {
-        match run_test("function", "parameters") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn function_local_recursion() {
-        match run_test("function", "local_recursion") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn function_recursion() {
-        match run_test("function", "recursion") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn function_print() {
-        match run_test("function", "print") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn function_too_many_parameters() {
-        let result = std::panic::catch_unwind(|| run_test("function", "too_many_parameters"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn function_mutual_recursion() {
-        match run_test("function", "mutual_recursion") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn function_extra_arguments() {
-        let result = std::panic::catch_unwind(|| run_test("function", "extra_arguments"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_set_on_nil() {
-        let result = std::panic::catch_unwind(|| run_test("field", "set_on_nil"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_get_on_string() {
-        let result = std::panic::catch_unwind(|| run_test("field", "get_on_string"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_many() {
-        match run_test("field", "many") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn field_set_on_function() {
-        let result = std::panic::catch_unwind(|| run_test("field", "set_on_function"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_set_on_bool() {
-        let result = std::panic::catch_unwind(|| run_test("field", "set_on_bool"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_method() {
-        match run_test("field", "method") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn field_call_nonfunction_field() {
-        let result = std::panic::catch_unwind(|| run_test("field", "call_nonfunction_field"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_get_on_nil() {
-        let result = std::panic::catch_unwind(|| run_test("field", "get_on_nil"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_set_on_class() {
-        let result = std::panic::catch_unwind(|| run_test("field", "set_on_class"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_set_on_string() {
-        let result = std::panic::catch_unwind(|| run_test("field", "set_on_string"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_on_instance() {
-        match run_test("field", "on_instance") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn field_get_on_function() {
-        let result = std::panic::catch_unwind(|| run_test("field", "get_on_function"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_call_function_field() {
-        match run_test("field", "call_function_field") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn field_set_evaluation_order() {
-        let result = std::panic::catch_unwind(|| run_test("field", "set_evaluation_order"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_method_binds_this() {
-        match run_test("field", "method_binds_this") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn field_set_on_num() {
-        let result = std::panic::catch_unwind(|| run_test("field", "set_on_num"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_get_on_class() {
-        let result = std::panic::catch_unwind(|| run_test("field", "get_on_class"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_get_and_set_method() {
-        match run_test("field", "get_and_set_method") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn field_get_on_bool() {
-        let result = std::panic::catch_unwind(|| run_test("field", "get_on_bool"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_get_on_num() {
-        let result = std::panic::catch_unwind(|| run_test("field", "get_on_num"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn field_undefined() {
-        let result = std::panic::catch_unwind(|| run_test("field", "undefined"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn print_missing_argument() {
-        let result = std::panic::catch_unwind(|| run_test("print", "missing_argument"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn number_decimal_point_at_eof() {
-        let result = std::panic::catch_unwind(|| run_test("number", "decimal_point_at_eof"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn number_nan_equality() {
-        match run_test("number", "nan_equality") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn number_literals() {
-        match run_test("number", "literals") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn number_leading_dot() {
-        let result = std::panic::catch_unwind(|| run_test("number", "leading_dot"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn number_trailing_dot() {
-        let result = std::panic::catch_unwind(|| run_test("number", "trailing_dot"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn call_nil() {
-        let result = std::panic::catch_unwind(|| run_test("call", "nil"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn call_bool() {
-        let result = std::panic::catch_unwind(|| run_test("call", "bool"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn call_num() {
-        let result = std::panic::catch_unwind(|| run_test("call", "num"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn call_object() {
-        let result = std::panic::catch_unwind(|| run_test("call", "object"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn call_string() {
-        let result = std::panic::catch_unwind(|| run_test("call", "string"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn logical_operator_and() {
-        match run_test("logical_operator", "and") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn logical_operator_or() {
-        match run_test("logical_operator", "or") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn logical_operator_and_truth() {
-        match run_test("logical_operator", "and_truth") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn logical_operator_or_truth() {
-        match run_test("logical_operator", "or_truth") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn inheritance_inherit_from_nil() {
-        let result = std::panic::catch_unwind(|| run_test("inheritance", "inherit_from_nil"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn inheritance_inherit_from_function() {
-        let result = std::panic::catch_unwind(|| run_test("inheritance", "inherit_from_function"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn inheritance_parenthesized_superclass() {
-        let result =
-            std::panic::catch_unwind(|| run_test("inheritance", "parenthesized_superclass"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn inheritance_set_fields_from_base_class() {
-        match run_test("inheritance", "set_fields_from_base_class") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn inheritance_inherit_from_number() {
-        let result = std::panic::catch_unwind(|| run_test("inheritance", "inherit_from_number"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn inheritance_inherit_methods() {
-        match run_test("inheritance", "inherit_methods") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn inheritance_constructor() {
-        match run_test("inheritance", "constructor") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn super_no_superclass_method() {
-        let result = std::panic::catch_unwind(|| run_test("super", "no_superclass_method"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn super_call_same_method() {
-        match run_test("super", "call_same_method") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn super_no_superclass_call() {
-        let result = std::panic::catch_unwind(|| run_test("super", "no_superclass_call"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn super_no_superclass_bind() {
-        let result = std::panic::catch_unwind(|| run_test("super", "no_superclass_bind"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn super_parenthesized() {
-        let result = std::panic::catch_unwind(|| run_test("super", "parenthesized"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn super_closure() {
-        match run_test("super", "closure") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn super_super_in_top_level_function() {
-        let result = std::panic::catch_unwind(|| run_test("super", "super_in_top_level_function"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn super_call_other_method() {
-        match run_test("super", "call_other_method") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn super_missing_arguments() {
-        let result = std::panic::catch_unwind(|| run_test("super", "missing_arguments"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn super_super_without_dot() {
-        let result = std::panic::catch_unwind(|| run_test("super", "super_without_dot"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn super_indirectly_inherited() {
-        match run_test("super", "indirectly_inherited") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn super_super_at_top_level() {
-        let result = std::panic::catch_unwind(|| run_test("super", "super_at_top_level"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn super_super_without_name() {
-        let result = std::panic::catch_unwind(|| run_test("super", "super_without_name"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn super_extra_arguments() {
-        let result = std::panic::catch_unwind(|| run_test("super", "extra_arguments"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn super_bound_method() {
-        match run_test("super", "bound_method") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn super_constructor() {
-        match run_test("super", "constructor") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn super_reassign_superclass() {
-        match run_test("super", "reassign_superclass") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn bool_equality() {
-        match run_test("bool", "equality") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn bool_not() {
-        match run_test("bool", "not") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn for_return_closure() {
-        match run_test("for", "return_closure") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn for_scope() {
-        match run_test("for", "scope") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn for_var_in_body() {
-        let result = std::panic::catch_unwind(|| run_test("for", "var_in_body"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn for_syntax() {
-        match run_test("for", "syntax") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn for_return_inside() {
-        match run_test("for", "return_inside") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn for_statement_initializer() {
-        let result = std::panic::catch_unwind(|| run_test("for", "statement_initializer"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn for_statement_increment() {
-        let result = std::panic::catch_unwind(|| run_test("for", "statement_increment"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn for_statement_condition() {
-        let result = std::panic::catch_unwind(|| run_test("for", "statement_condition"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn for_class_in_body() {
-        let result = std::panic::catch_unwind(|| run_test("for", "class_in_body"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn for_fun_in_body() {
-        let result = std::panic::catch_unwind(|| run_test("for", "fun_in_body"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn class_empty() {
-        match run_test("class", "empty") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn class_local_inherit_self() {
-        let result = std::panic::catch_unwind(|| run_test("class", "local_inherit_self"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn class_local_inherit_other() {
-        match run_test("class", "local_inherit_other") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn class_inherited_method() {
-        match run_test("class", "inherited_method") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn class_reference_self() {
-        match run_test("class", "reference_self") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn class_inherit_self() {
-        let result = std::panic::catch_unwind(|| run_test("class", "inherit_self"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn class_local_reference_self() {
-        match run_test("class", "local_reference_self") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn this_this_in_method() {
-        match run_test("this", "this_in_method") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn this_this_at_top_level() {
-        let result = std::panic::catch_unwind(|| run_test("this", "this_at_top_level"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn this_closure() {
-        match run_test("this", "closure") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn this_this_in_top_level_function() {
-        let result = std::panic::catch_unwind(|| run_test("this", "this_in_top_level_function"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn this_nested_closure() {
-        match run_test("this", "nested_closure") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn this_nested_class() {
-        match run_test("this", "nested_class") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn string_error_after_multiline() {
-        let result = std::panic::catch_unwind(|| run_test("string", "error_after_multiline"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn string_multiline() {
-        match run_test("string", "multiline") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn string_unterminated() {
-        let result = std::panic::catch_unwind(|| run_test("string", "unterminated"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn while_return_closure() {
-        match run_test("while", "return_closure") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn while_var_in_body() {
-        let result = std::panic::catch_unwind(|| run_test("while", "var_in_body"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn while_syntax() {
-        match run_test("while", "syntax") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn while_return_inside() {
-        match run_test("while", "return_inside") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn while_class_in_body() {
-        let result = std::panic::catch_unwind(|| run_test("while", "class_in_body"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn while_fun_in_body() {
-        let result = std::panic::catch_unwind(|| run_test("while", "fun_in_body"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn method_empty_block() {
-        match run_test("method", "empty_block") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn method_arity() {
-        match run_test("method", "arity") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn method_refer_to_name() {
-        let result = std::panic::catch_unwind(|| run_test("method", "refer_to_name"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn method_too_many_arguments() {
-        let result = std::panic::catch_unwind(|| run_test("method", "too_many_arguments"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn method_print_bound_method() {
-        match run_test("method", "print_bound_method") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn method_missing_arguments() {
-        let result = std::panic::catch_unwind(|| run_test("method", "missing_arguments"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn method_not_found() {
-        let result = std::panic::catch_unwind(|| run_test("method", "not_found"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn method_too_many_parameters() {
-        let result = std::panic::catch_unwind(|| run_test("method", "too_many_parameters"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn method_extra_arguments() {
-        let result = std::panic::catch_unwind(|| run_test("method", "extra_arguments"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_add_num_nil() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "add_num_nil"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_equals_method() {
-        match run_test("operator", "equals_method") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn operator_equals_class() {
-        match run_test("operator", "equals_class") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn operator_subtract_num_nonnum() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "subtract_num_nonnum"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_multiply() {
-        match run_test("operator", "multiply") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn operator_negate() {
-        match run_test("operator", "negate") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn operator_divide_nonnum_num() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "divide_nonnum_num"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_comparison() {
-        match run_test("operator", "comparison") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn operator_greater_num_nonnum() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "greater_num_nonnum"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_less_or_equal_nonnum_num() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "less_or_equal_nonnum_num"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_multiply_nonnum_num() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "multiply_nonnum_num"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_not_equals() {
-        match run_test("operator", "not_equals") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn operator_add_bool_num() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "add_bool_num"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_negate_nonnum() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "negate_nonnum"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_add() {
-        match run_test("operator", "add") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn operator_greater_or_equal_nonnum_num() {
-        let result =
-            std::panic::catch_unwind(|| run_test("operator", "greater_or_equal_nonnum_num"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_equals() {
-        match run_test("operator", "equals") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn operator_less_nonnum_num() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "less_nonnum_num"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_add_bool_string() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "add_bool_string"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_divide() {
-        match run_test("operator", "divide") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn operator_add_string_nil() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "add_string_nil"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_add_bool_nil() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "add_bool_nil"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_divide_num_nonnum() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "divide_num_nonnum"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_multiply_num_nonnum() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "multiply_num_nonnum"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_less_or_equal_num_nonnum() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "less_or_equal_num_nonnum"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_greater_nonnum_num() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "greater_nonnum_num"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_not() {
-        match run_test("operator", "not") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn operator_add_nil_nil() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "add_nil_nil"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_subtract() {
-        match run_test("operator", "subtract") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn operator_subtract_nonnum_num() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "subtract_nonnum_num"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_not_class() {
-        match run_test("operator", "not_class") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn operator_greater_or_equal_num_nonnum() {
-        let result =
-            std::panic::catch_unwind(|| run_test("operator", "greater_or_equal_num_nonnum"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn operator_less_num_nonnum() {
-        let result = std::panic::catch_unwind(|| run_test("operator", "less_num_nonnum"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn constructor_return_value() {
-        let result = std::panic::catch_unwind(|| run_test("constructor", "return_value"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn constructor_init_not_method() {
-        match run_test("constructor", "init_not_method") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn constructor_missing_arguments() {
-        let result = std::panic::catch_unwind(|| run_test("constructor", "missing_arguments"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn constructor_default() {
-        match run_test("constructor", "default") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn constructor_default_arguments() {
-        let result = std::panic::catch_unwind(|| run_test("constructor", "default_arguments"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn constructor_call_init_early_return() {
-        match run_test("constructor", "call_init_early_return") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn constructor_extra_arguments() {
-        let result = std::panic::catch_unwind(|| run_test("constructor", "extra_arguments"));
-        assert!(result.is_err(), "Expected a panic but did not get one");
-    }
-
-    #[test]
-    fn constructor_return_in_nested_function() {
-        match run_test("constructor", "return_in_nested_function") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn constructor_early_return() {
-        match run_test("constructor", "early_return") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn block_empty() {
-        match run_test("block", "empty") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
-
-    #[test]
-    fn block_scope() {
-        match run_test("block", "scope") {
-            Ok(_) => assert!(true),
-            Err(err) => assert!(false, "{}", err),
-        }
-    }
+    // One test per tests/<category>/<name>.lox, generated by build.rs
+    include!(concat!(env!("OUT_DIR"), "/lox_tests.rs"));
 }
@@ -0,0 +1,330 @@
+//! Code metrics for `lox_interpreter metrics file.lox`: for each function and
+//! method, and for the top-level script, how many statements it has, how
+//! deeply its control flow nests, how many parameters it takes, and its
+//! cyclomatic complexity.
+
+use crate::error::LoxError;
+use crate::expr::Expr;
+use crate::interpreter::{walk_expr, walk_stmt, EvalResult, ExecResult, StmtVisitor, Visitor};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::stmt::Stmt;
+use crate::token::Token;
+use std::fmt;
+
+/// The metrics for one function, method, or the top-level script.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionMetrics {
+    /// The function's name, `Class.method` for methods, or `<script>`.
+    pub name: String,
+    pub line: i32,
+    pub params: usize,
+    /// Statements in the body, not counting blocks or the bodies of nested
+    /// functions.
+    pub statements: usize,
+    /// The most `if`, `while`, `for`, `using`, and `defer` statements
+    /// nested inside one another.
+    pub max_depth: usize,
+    /// One more than the number of branches: `if`, loops, `and`, and `or`.
+    pub complexity: usize,
+}
+
+impl FunctionMetrics {
+    fn new(name: String, line: i32, params: usize) -> Self {
+        FunctionMetrics {
+            name,
+            line,
+            params,
+            statements: 0,
+            max_depth: 0,
+            complexity: 1,
+        }
+    }
+}
+
+/// Measures every function in `source`, the script first and the rest in
+/// the order they are declared. Fails if the source doesn't parse.
+pub fn metrics(source: &str) -> Result<Vec<FunctionMetrics>, LoxError> {
+    let tokens = Scanner::new(source.to_string()).scan_tokens()?;
+    let statements = Parser::new(tokens).parse()?;
+
+    let mut collector = Collector {
+        functions: vec![FunctionMetrics::new("<script>".to_string(), 1, 0)],
+        stack: vec![(0, 0)],
+        class: None,
+    };
+    for stmt in &statements {
+        stmt.accept(&mut collector)?;
+    }
+    Ok(collector.functions)
+}
+
+/// Renders `metrics` as a table with one row per function.
+pub fn table(metrics: &[FunctionMetrics]) -> String {
+    let width = metrics
+        .iter()
+        .map(|function| function.name.len())
+        .chain(["function".len()])
+        .max()
+        .unwrap_or(0);
+    let mut table = format!(
+        "{:<width$}  {:>4}  {:>6}  {:>10}  {:>5}  {:>10}\n",
+        "function",
+        "line",
+        "params",
+        "statements",
+        "depth",
+        "complexity",
+        width = width
+    );
+    for function in metrics {
+        table.push_str(&format!(
+            "{:<width$}  {:>4}  {:>6}  {:>10}  {:>5}  {:>10}\n",
+            function.name,
+            function.line,
+            function.params,
+            function.statements,
+            function.max_depth,
+            function.complexity,
+            width = width
+        ));
+    }
+    table
+}
+
+impl fmt::Display for FunctionMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (line {}): params {}, statements {}, depth {}, complexity {}",
+            self.name, self.line, self.params, self.statements, self.max_depth, self.complexity
+        )
+    }
+}
+
+struct Collector {
+    functions: Vec<FunctionMetrics>,
+    /// The indexes of the functions being visited, innermost last, each
+    /// with how deeply the statement being visited is nested in it.
+    stack: Vec<(usize, usize)>,
+    /// The class whose methods are being visited.
+    class: Option<String>,
+}
+
+impl Collector {
+    fn current(&mut self) -> (&mut FunctionMetrics, &mut usize) {
+        let (index, depth) = self.stack.last_mut().unwrap();
+        (&mut self.functions[*index], depth)
+    }
+
+    /// Counts `stmt` towards the current function, then walks it.
+    fn statement(&mut self, stmt: Stmt) -> ExecResult {
+        match &stmt {
+            Stmt::Block(_) => return walk_stmt(self, &stmt),
+            Stmt::Function {
+                name: stmt_name,
+                params,
+                body,
+            } => {
+                let name = match self.class.take() {
+                    Some(class) => format!("{}.{}", class, stmt_name.lexeme),
+                    None => {
+                        self.current().0.statements += 1;
+                        stmt_name.lexeme.clone()
+                    }
+                };
+                let metrics = FunctionMetrics::new(name, stmt_name.line, params.len());
+                self.stack.push((self.functions.len(), 0));
+                self.functions.push(metrics);
+                for stmt in body {
+                    stmt.accept(self)?;
+                }
+                self.stack.pop();
+                return Ok(None);
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                interfaces,
+                methods,
+            } => {
+                self.current().0.statements += 1;
+                if let Some(superclass) = superclass {
+                    superclass.accept_interp(self)?;
+                }
+                for interface in interfaces {
+                    interface.accept_interp(self)?;
+                }
+                self.methods(&name.lexeme, methods)?;
+                return Ok(None);
+            }
+            _ => {}
+        }
+
+        let (function, depth) = self.current();
+        function.statements += 1;
+        if matches!(stmt, Stmt::If { .. } | Stmt::While { .. }) {
+            function.complexity += 1;
+        }
+        if !matches!(
+            stmt,
+            Stmt::If { .. } | Stmt::While { .. } | Stmt::Using { .. } | Stmt::Defer { .. }
+        ) {
+            return walk_stmt(self, &stmt);
+        }
+
+        *depth += 1;
+        function.max_depth = function.max_depth.max(*depth);
+        walk_stmt(self, &stmt)?;
+        *self.current().1 -= 1;
+        Ok(None)
+    }
+
+    fn methods(&mut self, class: &str, methods: &[Stmt]) -> ExecResult {
+        for method in methods {
+            self.class = Some(class.to_string());
+            method.accept(self)?;
+        }
+        Ok(None)
+    }
+}
+
+impl Visitor for Collector {
+    fn visit_logical_expr(&mut self, expr: &Expr) -> EvalResult {
+        self.current().0.complexity += 1;
+        walk_expr(self, expr)
+    }
+
+    fn visit_ternary_expr(&mut self, expr: &Expr) -> EvalResult {
+        self.current().0.complexity += 1;
+        walk_expr(self, expr)
+    }
+
+    fn visit_class_expr(&mut self, expr: &Expr) -> EvalResult {
+        walk_expr(self, expr)?;
+        if let Expr::Class { methods, .. } = expr {
+            self.methods("anonymous", methods)?;
+        }
+        Ok(None)
+    }
+}
+
+impl StmtVisitor for Collector {
+    fn visit_block_stmt(&mut self, stmts: Vec<Stmt>) -> ExecResult {
+        self.statement(Stmt::Block(stmts))
+    }
+
+    fn visit_class_stmt(
+        &mut self,
+        name: Token,
+        superclass: Option<Expr>,
+        interfaces: Vec<Expr>,
+        methods: Vec<Stmt>,
+    ) -> ExecResult {
+        self.statement(Stmt::Class {
+            name,
+            superclass,
+            interfaces,
+            methods,
+        })
+    }
+
+    fn visit_defer_stmt(&mut self, keyword: Token, body: Box<Stmt>) -> ExecResult {
+        self.statement(Stmt::Defer { keyword, body })
+    }
+
+    fn visit_expression_stmt(&mut self, expr: Expr) -> ExecResult {
+        self.statement(Stmt::Expression(expr))
+    }
+
+    fn visit_function_stmt(
+        &mut self,
+        name: Token,
+        params: Vec<Token>,
+        body: Vec<Stmt>,
+    ) -> ExecResult {
+        self.statement(Stmt::Function { name, params, body })
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Box<Option<Stmt>>,
+    ) -> ExecResult {
+        self.statement(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn visit_print_stmt(&mut self, expr: Expr) -> ExecResult {
+        self.statement(Stmt::Print(expr))
+    }
+
+    fn visit_return_stmt(&mut self, keyword: Token, value: Option<Expr>) -> ExecResult {
+        self.statement(Stmt::Return { keyword, value })
+    }
+
+    fn visit_using_stmt(
+        &mut self,
+        keyword: Token,
+        name: Token,
+        initializer: Expr,
+        body: Box<Stmt>,
+    ) -> ExecResult {
+        self.statement(Stmt::Using {
+            keyword,
+            name,
+            initializer,
+            body,
+        })
+    }
+
+    fn visit_var_stmt(&mut self, name: Token, initializer: Option<Expr>) -> ExecResult {
+        self.statement(Stmt::Var { name, initializer })
+    }
+
+    fn visit_while_stmt(&mut self, condition: Expr, body: Box<Stmt>) -> ExecResult {
+        self.statement(Stmt::While { condition, body })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_each_function() {
+        let source = "var limit = 10;
+fun classify(n, verbose) {
+  if (n < 0 or n > limit) return \"out\";
+  for (var i = 0; i < n; i = i + 1) {
+    while (verbose and i > 2) {
+      print i;
+      verbose = false;
+    }
+  }
+  fun helper() { return 1; }
+  return \"in\";
+}
+class Shape {
+  area() { return 0; }
+}";
+        let metrics: Vec<String> = metrics(source)
+            .unwrap()
+            .iter()
+            .map(FunctionMetrics::to_string)
+            .collect();
+        assert_eq!(
+            metrics,
+            [
+                "<script> (line 1): params 0, statements 3, depth 0, complexity 1",
+                "classify (line 2): params 2, statements 10, depth 2, complexity 6",
+                "helper (line 10): params 0, statements 1, depth 0, complexity 1",
+                "Shape.area (line 14): params 0, statements 1, depth 0, complexity 1",
+            ]
+        );
+    }
+}
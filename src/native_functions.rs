@@ -1,22 +1,45 @@
 use crate::callable::Callable;
+use crate::diff;
+use crate::error::{ErrorCode, LoxError};
+use crate::inspect;
 use crate::interpreter::Interpreter;
+use crate::logging::{log_line, LogLevel};
+use crate::lox_class::LoxClass;
+use crate::lox_instance::LoxInstance;
+use crate::lox_list;
+use crate::lox_map::LoxMap;
+use crate::native_instance::NativeInstance;
 use crate::value::Value;
+use crate::write_output::{write_output, write_raw};
 use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use std::time::Duration;
+
+/// Numeric constants defined as globals, for scripts that need to test for
+/// or produce edge-case numbers.
+pub const NUMBER_CONSTANTS: [(&str, f64); 4] = [
+    ("NAN", f64::NAN),
+    ("INFINITY", f64::INFINITY),
+    ("MAX_NUMBER", f64::MAX),
+    ("EPSILON", f64::EPSILON),
+];
 
 pub struct Clock;
 
 impl Callable for Clock {
     fn call(
         &mut self,
-        _interpreter: &mut Interpreter,
+        interpreter: &mut Interpreter,
         _arguments: Vec<Option<Value>>,
-    ) -> Option<Value> {
-        use std::time::{SystemTime, UNIX_EPOCH};
-        let start = SystemTime::now();
-        let since_the_epoch = start
+    ) -> Result<Option<Value>, LoxError> {
+        use std::time::UNIX_EPOCH;
+        let since_the_epoch = interpreter
+            .now()
             .duration_since(UNIX_EPOCH)
             .expect("Time went backwards");
-        Some(Value::Number(since_the_epoch.as_secs_f64()))
+        Ok(Some(Value::Number(since_the_epoch.as_secs_f64())))
     }
 
     fn arity(&self) -> usize {
@@ -35,3 +58,1515 @@ impl Callable for Clock {
         "<native fn>".to_string()
     }
 }
+
+/// The number an argument holds, of any kind, as a float.
+fn number(argument: Option<&Option<Value>>) -> Option<f64> {
+    argument?.as_ref()?.as_f64()
+}
+
+/// `isNaN(x)`, which is true only for the number NaN.
+pub struct IsNaN;
+
+impl Callable for IsNaN {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let is_nan = number(arguments.first()).is_some_and(f64::is_nan);
+        Ok(Some(Value::Boolean(is_nan)))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(IsNaN)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `isFinite(x)`, which is true for numbers other than NaN and the infinities.
+pub struct IsFinite;
+
+impl Callable for IsFinite {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let is_finite = number(arguments.first()).is_some_and(f64::is_finite);
+        Ok(Some(Value::Boolean(is_finite)))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(IsFinite)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `sqrt(x)` and `log(x)`, the square root and natural logarithm of a
+/// number. Other arguments give nil.
+#[derive(Clone)]
+pub struct MathFn {
+    pub function: fn(f64) -> f64,
+}
+
+impl Callable for MathFn {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let Some(n) = number(arguments.first()) else {
+            return Ok(Some(Value::Nil()));
+        };
+        let result = (self.function)(n);
+        match interpreter.math_error(&[n], result) {
+            Some(message) => Err(interpreter.native_error(ErrorCode::NonFiniteResult, message)),
+            None => Ok(Some(Value::Number(result))),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `charCode(s)`, which returns the code point of the first character of `s`, or nil if `s` is
+/// empty or not a string.
+pub struct CharCode;
+
+impl Callable for CharCode {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let code = match arguments.first() {
+            Some(Some(Value::String(s))) => s[1..s.len() - 1].chars().next(),
+            _ => None,
+        };
+        Ok(Some(
+            code.map_or(Value::Nil(), |c| Value::Number(c as u32 as f64)),
+        ))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(CharCode)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `fromCharCode(n)`, which returns the one-character string for code point `n`, or nil if `n` is
+/// not a valid code point.
+pub struct FromCharCode;
+
+impl Callable for FromCharCode {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let c = match arguments.first() {
+            Some(Some(Value::Number(n)))
+                if n.fract() == 0.0 && *n >= 0.0 && *n <= u32::MAX as f64 =>
+            {
+                char::from_u32(*n as u32)
+            }
+            _ => None,
+        };
+        Ok(Some(c.map_or(Value::Nil(), |c| {
+            Value::String(format!("\"{}\"", c))
+        })))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(FromCharCode)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `chars(s)`, which returns a list of the characters in `s`, or nil if `s` is not a string.
+pub struct Chars;
+
+impl Callable for Chars {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let Some(Some(Value::String(s))) = arguments.first() else {
+            return Ok(Some(Value::Nil()));
+        };
+        interpreter.track_allocation()?;
+        let items = s[1..s.len() - 1]
+            .chars()
+            .map(|c| Value::String(format!("\"{}\"", c)))
+            .collect();
+        Ok(Some(lox_list::new(items)))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Chars)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `random()`, which returns a number from 0 up to but not including 1.
+/// See `random::Random`.
+pub struct RandomFn;
+
+impl Callable for RandomFn {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        _arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        Ok(Some(Value::Number(interpreter.random())))
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(RandomFn)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `logInfo`, `logWarn`, and `logError`, which write a timestamped message
+/// to stderr unless the interpreter's log level filters it out.
+#[derive(Clone)]
+pub struct Log {
+    pub level: LogLevel,
+}
+
+impl Callable for Log {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        if interpreter.log_level().allows(self.level) {
+            let message = interpreter.stringify(arguments.into_iter().next().flatten());
+            eprintln!("{}", log_line(self.level, &message, interpreter.now()));
+        }
+        Ok(Some(Value::Nil()))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `inspect(value)`, which returns a debugging rendering of `value`. See
+/// `inspect::inspect`.
+pub struct Inspect;
+
+impl Callable for Inspect {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let value = arguments
+            .into_iter()
+            .next()
+            .flatten()
+            .unwrap_or(Value::Nil());
+        Ok(Some(Value::String(format!(
+            "\"{}\"",
+            inspect::inspect(interpreter, &value)
+        ))))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Inspect)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `diff(a, b)`, which returns a description of how `a` and `b` differ, a
+/// line per difference, or nil if they are equal. See `diff::diff`.
+pub struct Diff;
+
+impl Callable for Diff {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mut arguments = arguments
+            .into_iter()
+            .map(|value| value.unwrap_or(Value::Nil()));
+        let (Some(a), Some(b)) = (arguments.next(), arguments.next()) else {
+            return Ok(Some(Value::Nil()));
+        };
+        let lines = diff::diff(interpreter, &a, &b);
+        if lines.is_empty() {
+            return Ok(Some(Value::Nil()));
+        }
+        Ok(Some(Value::String(format!("\"{}\"", lines.join("\n")))))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Diff)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `expectEqual(actual, expected)`, which returns nil if the values are
+/// equal as `diff` compares them, and otherwise raises a runtime error
+/// saying how they differ.
+pub struct ExpectEqual;
+
+impl Callable for ExpectEqual {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mut arguments = arguments
+            .into_iter()
+            .map(|value| value.unwrap_or(Value::Nil()));
+        let (Some(actual), Some(expected)) = (arguments.next(), arguments.next()) else {
+            return Ok(Some(Value::Nil()));
+        };
+        let lines = diff::diff(interpreter, &actual, &expected);
+        if lines.is_empty() {
+            return Ok(Some(Value::Nil()));
+        }
+        let actual = inspect::inspect(interpreter, &actual);
+        let expected = inspect::inspect(interpreter, &expected);
+        // A difference below the top level is clearer on its own
+        let message = if lines == [format!("{} != {}", actual, expected)] {
+            format!("Expected {} but got {}.", expected, actual)
+        } else {
+            format!("Values differ at {}.", lines.join("; "))
+        };
+        Err(interpreter.native_error(ErrorCode::ExpectationFailed, &message))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(ExpectEqual)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `test(name, fn)`, which calls `fn` with no arguments and prints
+/// `PASS name`, or `FAIL name: ` and the runtime error it raised, counting
+/// the result for `cargo run test`. Returns whether it passed.
+pub struct Test;
+
+impl Callable for Test {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mut arguments = arguments.into_iter();
+        let (Some(Some(Value::String(name))), Some(Some(Value::Callable(mut function)))) =
+            (arguments.next(), arguments.next())
+        else {
+            return Ok(Some(Value::Nil()));
+        };
+        if function.arity() != 0 {
+            return Ok(Some(Value::Nil()));
+        }
+        let name = &name[1..name.len() - 1];
+        // Other errors, such as `exit()`, stop the whole script
+        let line = match function.call(interpreter, Vec::new()) {
+            Ok(_) => format!("PASS {}", name),
+            Err(LoxError::Runtime(error)) => {
+                let error = LoxError::Runtime(error).to_string();
+                format!("FAIL {}: {}", name, error.replace('\n', " "))
+            }
+            Err(error) => return Err(error),
+        };
+        let passed = line.starts_with("PASS");
+        interpreter.record_test(passed);
+        let _ = write_output(interpreter.output_file(), &line);
+        Ok(Some(Value::Boolean(passed)))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Test)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `format(pattern, ...)`, which returns `pattern` with each `{}` replaced
+/// by the next argument as `print` would show it. `{{` and `}}` stand for
+/// literal braces. Placeholders past the last argument are left as they are,
+/// and extra arguments are ignored. A `pattern` that is not a string is a
+/// runtime error.
+///
+/// `print "x = {}", x;` is parsed as `print format("x = {}", x);`.
+pub struct Format;
+
+impl Callable for Format {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mut arguments = arguments.into_iter();
+        let Some(Some(Value::String(pattern))) = arguments.next() else {
+            return Err(interpreter.native_error(
+                ErrorCode::InvalidFormatString,
+                "Format string must be a string.",
+            ));
+        };
+        let mut text = String::new();
+        let mut chars = pattern[1..pattern.len() - 1].chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('}')) => {
+                    chars.next();
+                    match arguments.next() {
+                        Some(value) => text.push_str(&interpreter.stringify(value)),
+                        None => text.push_str("{}"),
+                    }
+                }
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    chars.next();
+                    text.push(c);
+                }
+                _ => text.push(c),
+            }
+        }
+        Ok(Some(Value::String(format!("\"{}\"", text))))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn variadic(&self) -> bool {
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Format)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `template(text, values)`, which returns `text` with each `{{name}}`
+/// replaced by the value of `name` in `values`, shown as `print` would.
+/// `values` is a map, such as `locals()` returns, or an instance whose fields
+/// are used. Spaces just inside the braces are ignored, and names `values`
+/// doesn't have are left as they are. Returns nil if `text` is not a string.
+pub struct Template;
+
+impl Callable for Template {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mut arguments = arguments.into_iter();
+        let Some(Some(Value::String(text))) = arguments.next() else {
+            return Ok(Some(Value::Nil()));
+        };
+        let values = arguments.next().flatten();
+        let mut result = String::new();
+        let mut rest = &text[1..text.len() - 1];
+        while let Some(start) = rest.find("{{") {
+            let Some(length) = rest[start + 2..].find("}}") else {
+                break;
+            };
+            let end = start + length + 4;
+            result.push_str(&rest[..start]);
+            let name = rest[start + 2..end - 2].trim();
+            match template_value(interpreter, values.as_ref(), name)? {
+                Some(value) => result.push_str(&interpreter.stringify(Some(value))),
+                None => result.push_str(&rest[start..end]),
+            }
+            rest = &rest[end..];
+        }
+        result.push_str(rest);
+        Ok(Some(Value::String(format!("\"{}\"", result))))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Template)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// The value of `name` among an instance's fields, or in a map or any other
+/// native instance with `has(key)` and `get(key)` methods.
+fn template_value(
+    interpreter: &mut Interpreter,
+    values: Option<&Value>,
+    name: &str,
+) -> Result<Option<Value>, LoxError> {
+    match values {
+        Some(Value::Instance(instance)) => Ok(instance.borrow().fields.get(name).cloned()),
+        Some(Value::NativeInstance(instance)) => {
+            let key = vec![Some(Value::String(format!("\"{}\"", name)))];
+            let mut instance = instance.borrow_mut();
+            match instance.call_method("has", interpreter, key.clone())? {
+                Some(Value::Boolean(true)) => instance.call_method("get", interpreter, key),
+                _ => Ok(None),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// `write(value)`, which writes `value` where `print` would but without
+/// ending the line, for prompts and progress output. Returns nil.
+pub struct Write;
+
+impl Callable for Write {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let text = interpreter.stringify(arguments.into_iter().next().flatten());
+        let _ = write_raw(interpreter.output_file(), &text);
+        Ok(Some(Value::Nil()))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Write)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `eprintln(value)`, which prints `value` and a newline on stderr, apart
+/// from the output `print` writes. Returns nil.
+pub struct Eprintln;
+
+impl Callable for Eprintln {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        eprintln!(
+            "{}",
+            interpreter.stringify(arguments.into_iter().next().flatten())
+        );
+        Ok(Some(Value::Nil()))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Eprintln)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `exit(status)`, which stops the script with `LoxError::Exit`. The CLI
+/// exits the process with `status`, which must be a whole number from 0 to
+/// 255, as process exit statuses are.
+pub struct Exit;
+
+impl Callable for Exit {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        match arguments.first() {
+            Some(Some(Value::Number(n))) if n.fract() == 0.0 && (0.0..=255.0).contains(n) => {
+                Err(LoxError::Exit(*n as i32))
+            }
+            _ => Err(interpreter.native_error(
+                ErrorCode::InvalidExitStatus,
+                "Exit status must be a whole number from 0 to 255.",
+            )),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Exit)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// The number of `{}` placeholders in a `format` pattern, not counting
+/// escaped braces.
+pub fn placeholders(pattern: &str) -> usize {
+    pattern
+        .replace("{{", "")
+        .replace("}}", "")
+        .matches("{}")
+        .count()
+}
+
+/// Builds the map for `globals` or `locals`. Scopes don't record the order
+/// names were declared in, so the map lists them by name instead.
+fn scope_map(
+    interpreter: &mut Interpreter,
+    entries: HashMap<String, Value>,
+) -> Result<Value, LoxError> {
+    interpreter.track_allocation()?;
+    let mut entries: Vec<(String, Value)> = entries.into_iter().collect();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(Value::NativeInstance(Rc::new(RefCell::new(
+        LoxMap::from_entries(entries),
+    ))))
+}
+
+/// `globals()`, which returns a map of every global name to its value.
+pub struct Globals;
+
+impl Callable for Globals {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        _arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let entries = interpreter
+            .globals
+            .borrow()
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone().unwrap_or(Value::Nil())))
+            .collect();
+        Ok(Some(scope_map(interpreter, entries)?))
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Globals)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `locals()`, which returns a map of the variables in scope where it is
+/// called, other than globals. Scopes don't record which function they
+/// belong to, so variables a closure captured are included too.
+pub struct Locals;
+
+impl Callable for Locals {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        _arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mut entries: HashMap<String, Value> = HashMap::new();
+        let mut environment = interpreter.environment.clone();
+        while !Rc::ptr_eq(&environment, &interpreter.globals) {
+            let enclosing = {
+                let scope = environment.borrow();
+                for (name, value) in scope.values.iter() {
+                    // Inner scopes shadow outer ones
+                    if name != "this" && name != "super" && !entries.contains_key(name) {
+                        entries.insert(name.clone(), value.clone().unwrap_or(Value::Nil()));
+                    }
+                }
+                scope.enclosing.clone()
+            };
+            match enclosing {
+                Some(enclosing) => environment = enclosing,
+                None => break,
+            }
+        }
+        Ok(Some(scope_map(interpreter, entries)?))
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Locals)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `stackTrace()`, which returns a list of the calls in progress where it is
+/// called, innermost first, each as `"function @ line"` with the line the
+/// call was made on. Top-level code gives an empty list.
+pub struct StackTrace;
+
+impl Callable for StackTrace {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        _arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let frames = interpreter.call_stack();
+        // Leave out the call to `stackTrace` itself
+        let frames = &frames[..frames.len().saturating_sub(1)];
+        let items = frames
+            .iter()
+            .rev()
+            .map(|frame| Value::String(format!("\"{} @ {}\"", frame.function, frame.paren.line)))
+            .collect();
+        interpreter.track_allocation()?;
+        Ok(Some(lox_list::new(items)))
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(StackTrace)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `className(value)`, which returns the name of a class, or of an
+/// instance's class, and nil for anything else.
+pub struct ClassName;
+
+impl Callable for ClassName {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let name = match arguments.first() {
+            Some(Some(Value::Instance(instance))) => {
+                Some(instance.borrow().klass.borrow().name().to_string())
+            }
+            Some(Some(Value::NativeInstance(instance))) => Some(instance.borrow().class_name()),
+            Some(Some(Value::Callable(callable))) => callable
+                .as_any()
+                .downcast_ref::<LoxClass>()
+                .map(|class| class.name().to_string()),
+            _ => None,
+        };
+        Ok(Some(match name {
+            Some(name) => Value::String(format!("\"{}\"", name)),
+            None => Value::Nil(),
+        }))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(ClassName)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `superclassOf(class)`, which returns the class `class` inherits from, and
+/// nil if it has none or isn't a class.
+pub struct SuperclassOf;
+
+impl Callable for SuperclassOf {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let superclass = match arguments.first() {
+            Some(Some(Value::Callable(callable))) => callable
+                .as_any()
+                .downcast_ref::<LoxClass>()
+                .and_then(LoxClass::superclass)
+                .map(|superclass| Value::Callable(superclass.clone_box())),
+            _ => None,
+        };
+        Ok(Some(superclass.unwrap_or(Value::Nil())))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(SuperclassOf)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `bindArgs(fn, args...)`, which returns a function that calls `fn` with
+/// `args` before the arguments it is given, and so takes that many fewer.
+pub struct BindArgs;
+
+impl Callable for BindArgs {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mut arguments = arguments.into_iter();
+        let Some(Some(Value::Callable(function))) = arguments.next() else {
+            return Err(interpreter.native_error(
+                ErrorCode::NotCallable,
+                "Can only bind arguments to functions and classes.",
+            ));
+        };
+        let bound: Vec<Option<Value>> = arguments.collect();
+        if !function.variadic() && bound.len() > function.arity() {
+            return Err(interpreter.native_error(
+                ErrorCode::WrongArity,
+                &format!(
+                    "Expected at most {} arguments to bind but got {}.",
+                    function.arity(),
+                    bound.len()
+                ),
+            ));
+        }
+        Ok(Some(Value::Callable(Box::new(BoundFunction {
+            function,
+            bound,
+        }))))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn variadic(&self) -> bool {
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(BindArgs)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// The function `bindArgs` returns, which prints as the function it wraps.
+#[derive(Clone)]
+pub struct BoundFunction {
+    function: Box<dyn Callable>,
+    bound: Vec<Option<Value>>,
+}
+
+impl Callable for BoundFunction {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mut all = self.bound.clone();
+        all.extend(arguments);
+        self.function.call(interpreter, all)
+    }
+
+    fn arity(&self) -> usize {
+        self.function.arity().saturating_sub(self.bound.len())
+    }
+
+    fn variadic(&self) -> bool {
+        self.function.variadic()
+    }
+
+    fn deterministic(&self) -> bool {
+        self.function.deterministic()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn to_string(&self) -> String {
+        self.function.to_string()
+    }
+
+    fn name(&self) -> String {
+        self.function.name()
+    }
+}
+
+/// `value` as a function that can be called with one argument, for
+/// `compose` and `pipe`, whose calls the interpreter doesn't check. `verb`
+/// names what the native does with it in the error otherwise.
+fn unary_function(
+    interpreter: &Interpreter,
+    value: Option<Value>,
+    verb: &str,
+) -> Result<Box<dyn Callable>, LoxError> {
+    let Some(Value::Callable(function)) = value else {
+        return Err(interpreter.native_error(
+            ErrorCode::NotCallable,
+            &format!("Can only {} functions and classes.", verb),
+        ));
+    };
+    if function.arity() == 1 || (function.variadic() && function.arity() <= 1) {
+        Ok(function)
+    } else {
+        Err(interpreter.native_error(
+            ErrorCode::WrongArity,
+            &format!("Can only {} functions that take one argument.", verb),
+        ))
+    }
+}
+
+/// `compose(f, g)`, which returns a function that calls `g` with its
+/// arguments and then `f` with the result, so `compose(f, g)(x)` is
+/// `f(g(x))`.
+pub struct Compose;
+
+impl Callable for Compose {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mut arguments = arguments.into_iter();
+        let outer = unary_function(interpreter, arguments.next().flatten(), "compose")?;
+        let Some(Some(Value::Callable(inner))) = arguments.next() else {
+            return Err(interpreter.native_error(
+                ErrorCode::NotCallable,
+                "Can only compose functions and classes.",
+            ));
+        };
+        Ok(Some(Value::Callable(Box::new(Composed { outer, inner }))))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Compose)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// The function `compose` returns, which takes the arguments its inner
+/// function does.
+#[derive(Clone)]
+pub struct Composed {
+    outer: Box<dyn Callable>,
+    inner: Box<dyn Callable>,
+}
+
+impl Callable for Composed {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let result = self.inner.call(interpreter, arguments)?;
+        self.outer.call(interpreter, vec![result])
+    }
+
+    fn arity(&self) -> usize {
+        self.inner.arity()
+    }
+
+    fn variadic(&self) -> bool {
+        self.inner.variadic()
+    }
+
+    fn deterministic(&self) -> bool {
+        self.outer.deterministic() && self.inner.deterministic()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `pipe(value, f, g, ...)`, which passes `value` through each function in
+/// turn and returns the last result, so `pipe(x, f, g)` is `g(f(x))`.
+pub struct Pipe;
+
+impl Callable for Pipe {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mut arguments = arguments.into_iter();
+        let mut value = arguments.next().flatten();
+        // Check every stage before running any of them
+        let functions = arguments
+            .map(|function| unary_function(interpreter, function, "pipe through"))
+            .collect::<Result<Vec<_>, _>>()?;
+        for mut function in functions {
+            if interpreter.deterministic() && !function.deterministic() {
+                return Err(interpreter.native_error(
+                    ErrorCode::NondeterministicCall,
+                    "Can't call a nondeterministic native in deterministic mode.",
+                ));
+            }
+            value = function.call(interpreter, vec![value])?;
+        }
+        Ok(Some(value.unwrap_or(Value::Nil())))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn variadic(&self) -> bool {
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Pipe)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `memoize(fn)`, which returns a function that calls `fn` once for each
+/// set of arguments and then gives the same result again. Arguments match
+/// when they are `==`, so instances and lists match only themselves, and
+/// calls with NaN or a function among their arguments aren't cached.
+pub struct Memoize;
+
+impl Callable for Memoize {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let Some(Some(Value::Callable(function))) = arguments.into_iter().next() else {
+            return Err(interpreter.native_error(
+                ErrorCode::NotCallable,
+                "Can only memoize functions and classes.",
+            ));
+        };
+        Ok(Some(Value::Callable(Box::new(Memoized {
+            function,
+            cache: Rc::new(RefCell::new(HashMap::new())),
+        }))))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Memoize)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// One argument of a memoized call, in a form that can key a hash map.
+#[derive(PartialEq, Eq, Hash)]
+enum MemoKey {
+    Nil,
+    Boolean(bool),
+    /// The number's bits, with -0 as 0 since the two are equal.
+    Number(u64),
+    String(String),
+    /// The address of an instance or list, which the cached arguments keep
+    /// alive.
+    Object(*const ()),
+}
+
+impl MemoKey {
+    fn new(value: &Option<Value>) -> Option<MemoKey> {
+        Some(match value {
+            None | Some(Value::Nil()) => MemoKey::Nil,
+            Some(Value::Boolean(b)) => MemoKey::Boolean(*b),
+            Some(Value::Number(n)) if n.is_nan() => return None,
+            Some(Value::Number(n)) => MemoKey::Number((n + 0.0).to_bits()),
+            Some(Value::String(s)) => MemoKey::String(s.clone()),
+            Some(Value::Instance(instance)) => MemoKey::Object(Rc::as_ptr(instance) as *const ()),
+            Some(Value::NativeInstance(instance)) => {
+                MemoKey::Object(Rc::as_ptr(instance) as *const ())
+            }
+            Some(Value::List(list)) => MemoKey::Object(Rc::as_ptr(list) as *const ()),
+            _ => return None,
+        })
+    }
+}
+
+/// A call's arguments, kept so their addresses stay in use, and its result.
+type MemoEntry = (Vec<Option<Value>>, Option<Value>);
+
+/// The function `memoize` returns. Copies share one cache.
+#[derive(Clone)]
+pub struct Memoized {
+    function: Box<dyn Callable>,
+    cache: Rc<RefCell<HashMap<Vec<MemoKey>, MemoEntry>>>,
+}
+
+impl Callable for Memoized {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let key: Option<Vec<MemoKey>> = arguments.iter().map(MemoKey::new).collect();
+        let Some(key) = key else {
+            return self.function.call(interpreter, arguments);
+        };
+        if let Some((_, result)) = self.cache.borrow().get(&key) {
+            return Ok(result.clone());
+        }
+        // The cache isn't borrowed during the call, which may call this again
+        let result = self.function.call(interpreter, arguments.clone())?;
+        self.cache
+            .borrow_mut()
+            .insert(key, (arguments, result.clone()));
+        Ok(result)
+    }
+
+    fn arity(&self) -> usize {
+        self.function.arity()
+    }
+
+    fn variadic(&self) -> bool {
+        self.function.variadic()
+    }
+
+    fn deterministic(&self) -> bool {
+        self.function.deterministic()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn to_string(&self) -> String {
+        self.function.to_string()
+    }
+
+    fn name(&self) -> String {
+        self.function.name()
+    }
+}
+
+/// `setTimeout(fn, ms)` and `setInterval(fn, ms)`, which call `fn` once
+/// the script has finished and `ms` milliseconds have passed, and for
+/// `setInterval` every `ms` milliseconds after that. Return the timer's id
+/// for `clearTimeout` and `clearInterval`.
+pub struct SetTimer {
+    pub repeat: bool,
+}
+
+impl Callable for SetTimer {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mut arguments = arguments.into_iter();
+        let callback = match arguments.next().flatten() {
+            Some(Value::Callable(callback)) if callback.arity() == 0 => callback,
+            Some(Value::Callable(_)) => {
+                return Err(interpreter.native_error(
+                    ErrorCode::WrongArity,
+                    "Can only schedule functions that take no arguments.",
+                ))
+            }
+            _ => {
+                return Err(interpreter.native_error(
+                    ErrorCode::NotCallable,
+                    "Can only schedule functions and classes.",
+                ))
+            }
+        };
+        let delay = match arguments.next().flatten() {
+            Some(Value::Number(ms)) if ms >= 0.0 && ms.is_finite() => {
+                Duration::from_secs_f64(ms / 1000.0)
+            }
+            _ => {
+                return Err(interpreter.native_error(
+                    ErrorCode::InvalidDelay,
+                    "Timer delay must be a non-negative number of milliseconds.",
+                ))
+            }
+        };
+        let interval = self.repeat.then_some(delay);
+        let id = interpreter.set_timer(delay, interval, callback);
+        Ok(Some(Value::Number(id as f64)))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(SetTimer {
+            repeat: self.repeat,
+        })
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `clearTimeout(id)` and `clearInterval(id)`, which stop a timer from
+/// firing again. Ids of timers that are done, or that were never set, are
+/// ignored.
+pub struct ClearTimer;
+
+impl Callable for ClearTimer {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        if let Some(Some(Value::Number(id))) = arguments.first() {
+            interpreter.clear_timer(*id as u64);
+        }
+        Ok(Some(Value::Nil()))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(ClearTimer)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `weakRef(obj)`, which returns a handle whose `get()` yields `obj` until
+/// nothing else holds it, and nil after that.
+pub struct WeakRefFn;
+
+impl Callable for WeakRefFn {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        interpreter.track_allocation()?;
+        let target = match arguments.into_iter().next().flatten() {
+            Some(Value::Instance(instance)) => WeakTarget::Instance(Rc::downgrade(&instance)),
+            Some(Value::NativeInstance(instance)) => WeakTarget::Native(Rc::downgrade(&instance)),
+            Some(Value::List(list)) => WeakTarget::List(Rc::downgrade(&list)),
+            // Other values are copied rather than shared, so they are held as they are
+            Some(value) => WeakTarget::Value(value),
+            None => WeakTarget::Value(Value::Nil()),
+        };
+        Ok(Some(Value::NativeInstance(Rc::new(RefCell::new(
+            WeakRef { target },
+        )))))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(WeakRefFn)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+enum WeakTarget {
+    Instance(Weak<RefCell<LoxInstance>>),
+    Native(Weak<RefCell<dyn NativeInstance>>),
+    List(Weak<RefCell<Vec<Value>>>),
+    Value(Value),
+}
+
+/// The handle returned by `weakRef`.
+struct WeakRef {
+    target: WeakTarget,
+}
+
+impl NativeInstance for WeakRef {
+    fn class_name(&self) -> String {
+        "WeakRef".to_string()
+    }
+
+    fn get(&self, _name: &str) -> Option<Value> {
+        None
+    }
+
+    fn method_arity(&self, name: &str) -> Option<usize> {
+        match name {
+            "get" => Some(0),
+            _ => None,
+        }
+    }
+
+    fn call_method(
+        &mut self,
+        _name: &str,
+        _interpreter: &mut Interpreter,
+        _arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        Ok(match &self.target {
+            WeakTarget::Instance(instance) => instance.upgrade().map(Value::Instance),
+            WeakTarget::Native(instance) => instance.upgrade().map(Value::NativeInstance),
+            WeakTarget::List(list) => list.upgrade().map(Value::List),
+            WeakTarget::Value(value) => Some(value.clone()),
+        })
+    }
+}
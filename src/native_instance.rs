@@ -0,0 +1,224 @@
+use crate::callable::Callable;
+use crate::error::{ErrorCode, LoxError};
+use crate::interpreter::Interpreter;
+use crate::runtime_error::RuntimeError;
+use crate::token::Token;
+use crate::value::Value;
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// A Rust object that Lox code can use like an instance: `obj.field`,
+/// `obj.field = value`, and `obj.method(args)`. Embedders implement this to
+/// hand scripts handles to files, sockets, game entities, and so on.
+pub trait NativeInstance {
+    /// The name shown when the instance is printed, as in "File instance".
+    fn class_name(&self) -> String;
+
+    /// Returns the named field, or `None` if there is no such field.
+    fn get(&self, name: &str) -> Option<Value>;
+
+    /// Stores a field. Returns `false` if the field can't be assigned.
+    fn set(&mut self, _name: &str, _value: Value) -> bool {
+        false
+    }
+
+    /// The number of arguments the named method takes, or `None` if there is
+    /// no such method. Methods take precedence over fields of the same name.
+    fn method_arity(&self, _name: &str) -> Option<usize> {
+        None
+    }
+
+    /// Calls the named method, which `method_arity` has said exists. An
+    /// error stops the script, as one from a native function does.
+    fn call_method(
+        &mut self,
+        _name: &str,
+        _interpreter: &mut Interpreter,
+        _arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        Ok(None)
+    }
+
+    /// Renders the instance for the `inspect` native, calling `inspect` on
+    /// the values it holds. `None` renders it as "Name instance".
+    fn inspect(&self, _inspect: &mut dyn FnMut(&Value) -> String) -> Option<String> {
+        None
+    }
+
+    /// The values the instance holds, each under the path `diff` shows for
+    /// it, such as `[0]`. `None` makes two instances equal only if they are
+    /// the same one.
+    fn entries(&self) -> Option<Vec<(String, Value)>> {
+        None
+    }
+
+    /// The instance as `Any`, so natives can recognise instances of their
+    /// own types. `None` leaves it opaque.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+}
+
+impl fmt::Debug for dyn NativeInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class_name())
+    }
+}
+
+/// Looks up a property for `obj.name`, binding methods to the instance.
+pub fn get(
+    instance: &Rc<RefCell<dyn NativeInstance>>,
+    name: &Token,
+) -> Result<Option<Value>, LoxError> {
+    if let Some(arity) = instance.borrow().method_arity(&name.lexeme) {
+        return Ok(Some(Value::Callable(Box::new(NativeMethod {
+            instance: instance.clone(),
+            name: name.lexeme.clone(),
+            arity,
+        }))));
+    }
+
+    if let Some(value) = instance.borrow().get(&name.lexeme) {
+        return Ok(Some(value));
+    }
+
+    let error = RuntimeError::new(
+        name.clone(),
+        ErrorCode::UndefinedProperty,
+        &format!("Undefined property '{}'.", name.lexeme),
+    );
+    Err(LoxError::Runtime(error))
+}
+
+pub fn set(
+    instance: &Rc<RefCell<dyn NativeInstance>>,
+    name: &Token,
+    value: Value,
+) -> Result<(), LoxError> {
+    if !instance.borrow_mut().set(&name.lexeme, value) {
+        let error = RuntimeError::new(
+            name.clone(),
+            ErrorCode::ReadOnlyProperty,
+            &format!("Can't assign property '{}'.", name.lexeme),
+        );
+        return Err(LoxError::Runtime(error));
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+struct NativeMethod {
+    instance: Rc<RefCell<dyn NativeInstance>>,
+    name: String,
+    arity: usize,
+}
+
+impl Callable for NativeMethod {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let result = self
+            .instance
+            .borrow_mut()
+            .call_method(&self.name, interpreter, arguments)?;
+        Ok(Some(result.unwrap_or(Value::Nil())))
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lox;
+
+    struct Counter {
+        count: f64,
+        label: String,
+    }
+
+    impl NativeInstance for Counter {
+        fn class_name(&self) -> String {
+            "Counter".to_string()
+        }
+
+        fn get(&self, name: &str) -> Option<Value> {
+            match name {
+                "count" => Some(Value::Number(self.count)),
+                "label" => Some(Value::String(self.label.clone())),
+                _ => None,
+            }
+        }
+
+        fn set(&mut self, name: &str, value: Value) -> bool {
+            match (name, value) {
+                ("count", Value::Number(count)) => {
+                    self.count = count;
+                    true
+                }
+                _ => false,
+            }
+        }
+
+        fn method_arity(&self, name: &str) -> Option<usize> {
+            match name {
+                "increment" => Some(1),
+                _ => None,
+            }
+        }
+
+        fn call_method(
+            &mut self,
+            name: &str,
+            _interpreter: &mut Interpreter,
+            arguments: Vec<Option<Value>>,
+        ) -> Result<Option<Value>, LoxError> {
+            if let ("increment", Some(Value::Number(by))) = (name, &arguments[0]) {
+                self.count += by;
+                return Ok(Some(Value::Number(self.count)));
+            }
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn scripts_use_native_instances() {
+        let counter = Rc::new(RefCell::new(Counter {
+            count: 0.0,
+            label: "\"clicks\"".to_string(),
+        }));
+        let mut lox = Lox::new("");
+        lox.define("counter", Value::NativeInstance(counter.clone()));
+
+        let result = lox.try_run(
+            "var inc = counter.increment;
+             inc(2);
+             counter.increment(3);
+             counter.count = counter.count * 10;
+             var label = counter.label;",
+        );
+        assert!(result.is_ok());
+        assert_eq!(counter.borrow().count, 50.0);
+
+        assert!(lox.try_run("counter.label = 1;").is_err());
+        assert!(lox.try_run("counter.missing;").is_err());
+    }
+}
@@ -1,187 +1,452 @@
-use crate::expr::Expr;
+use crate::error::{ErrorCode, LoxError};
+use crate::expr::{Expr, SlotCache};
+use crate::native_functions;
 use crate::stmt::Stmt;
 use crate::token::Token;
 use crate::token_type::TokenType;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// A syntax error, reported at the token where it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub token: Token,
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.token.type_ == TokenType::EoF {
+            write!(
+                f,
+                "[line {}] Error at end: {} [{}]",
+                self.token.position(),
+                self.message,
+                self.code
+            )
+        } else {
+            write!(
+                f,
+                "[line {}] Error at '{}': {} [{}]",
+                self.token.position(),
+                self.token.lexeme,
+                self.message,
+                self.code
+            )
+        }
+    }
+}
+
+type ParseResult<T> = Result<T, ParseError>;
 
 #[derive(Clone)]
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    errors: Vec<ParseError>,
+    /// Set once an error is reported, so that errors following from it are
+    /// left out until the parser reaches the next statement.
+    panic_mode: bool,
+    /// How many blocks the parser is inside.
+    blocks: usize,
+    /// Comments still to be placed, for `Parser::with_comments`.
+    comments: VecDeque<Token>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Parser {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            current: 0,
+            errors: Vec::new(),
+            panic_mode: false,
+            blocks: 0,
+            comments: VecDeque::new(),
+        }
     }
-    pub fn parse(&mut self) -> Vec<Option<Stmt>> {
-        let mut statements: Vec<Option<Stmt>> = Vec::new();
+
+    /// A parser that keeps `comments`, the `Comment` tokens of the same
+    /// source, as `Stmt::Comment`s in the statement list each one falls in,
+    /// so the formatter can print them back. A comment inside a statement is
+    /// moved to before the next one.
+    pub fn with_comments(tokens: Vec<Token>, comments: Vec<Token>) -> Parser {
+        Parser {
+            comments: comments.into(),
+            ..Parser::new(tokens)
+        }
+    }
+
+    /// Parses `tokens`, returning every error instead of reporting the first
+    /// one. After an error the parser skips to the next statement and goes on.
+    pub fn parse_tokens(tokens: Vec<Token>) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        let mut parser = Parser::new(tokens);
+        let statements = parser.parse_all();
+        if parser.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(parser.errors)
+        }
+    }
+
+    /// Parses the tokens, returning every error as a `LoxError::Parse`. The
+    /// parser recovers at the next statement after each one, so a file's
+    /// syntax errors all show up in a single run.
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, LoxError> {
+        let statements = self.parse_all();
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(LoxError::Parse(self.errors.clone()))
+        }
+    }
+
+    fn parse_all(&mut self) -> Vec<Stmt> {
+        let mut statements: Vec<Stmt> = Vec::new();
 
         while !self.is_at_end() {
-            statements.push(self.declaration());
+            self.take_comments(&mut statements);
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
         }
+        self.take_comments(&mut statements);
 
         statements
     }
 
-    fn expression(&mut self) -> Expr {
+    /// Adds the comments before the next token to `statements`. A comment on
+    /// the same line as the token before it trails that token.
+    fn take_comments(&mut self, statements: &mut Vec<Stmt>) {
+        while let Some(comment) = self.comments.front() {
+            if comment.offset > self.peek().offset {
+                break;
+            }
+            let text = comment.clone();
+            self.comments.pop_front();
+            let trailing = self.current > 0 && self.previous().line == text.line;
+            statements.push(Stmt::Comment { text, trailing });
+        }
+    }
+
+    fn expression(&mut self) -> ParseResult<Expr> {
         self.assignment()
     }
 
     fn declaration(&mut self) -> Option<Stmt> {
-        if self.match_tokens(vec![TokenType::Var]) {
-            return Some(self.var_declaration());
-        }
-        if self.match_tokens(vec![TokenType::Class]) {
-            return Some(self.class_declaration());
-        }
-        if self.match_tokens(vec![TokenType::Fun]) {
-            return Some(self.function("function"));
-        }
+        let start = self.current;
+        self.panic_mode = false;
+        let result = if self.match_tokens(vec![TokenType::Var]) {
+            self.var_declaration()
+        } else if self.match_tokens(vec![TokenType::Class]) {
+            self.class_declaration()
+        } else if self.match_tokens(vec![TokenType::Fun]) {
+            self.function("function")
+        } else if self.match_tokens(vec![TokenType::Interface]) {
+            self.interface_declaration()
+        } else if self.match_tokens(vec![TokenType::Macro]) {
+            self.macro_declaration()
+        } else {
+            self.statement()
+        };
 
-        match self.statement() {
-            Some(stmt) => return Some(stmt),
-            None => {
-                self.synchronize();
-                panic!("Parse Error.")
+        match result {
+            Ok(stmt) => Some(stmt),
+            Err(error) => {
+                self.report(error);
+                self.synchronize(start);
+                self.panic_mode = false;
+                None
             }
         }
     }
 
-    fn class_declaration(&mut self) -> Stmt {
-        let name = self.consume(TokenType::Identifier, "Expect class name.");
+    /// Parses `macro name(params) { body }`, which has the shape of a function.
+    fn macro_declaration(&mut self) -> ParseResult<Stmt> {
+        match self.function("macro")? {
+            Stmt::Function { name, params, body } => Ok(Stmt::Macro { name, params, body }),
+            _ => unreachable!("function() parses a function"),
+        }
+    }
+
+    fn class_declaration(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?;
+        let (superclass, interfaces, methods) = self.class_body()?;
+
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            interfaces,
+            methods,
+        })
+    }
+
+    /// Parses an anonymous class, after its `class` keyword.
+    fn class_expression(&mut self) -> ParseResult<Expr> {
+        let keyword = self.previous().clone();
+        let (superclass, interfaces, methods) = self.class_body()?;
+
+        Ok(Expr::Class {
+            keyword,
+            superclass: superclass.map(Box::new),
+            interfaces,
+            methods,
+        })
+    }
 
+    /// Parses the optional superclass, the interfaces after `implements`, and
+    /// the methods that follow a class's name.
+    fn class_body(&mut self) -> ParseResult<(Option<Expr>, Vec<Expr>, Vec<Stmt>)> {
         let mut superclass: Option<Expr> = None;
 
         if self.match_tokens(vec![TokenType::Less]) {
-            self.consume(TokenType::Identifier, "Expect superclass name.");
+            self.consume(TokenType::Identifier, "Expect superclass name.")?;
             superclass = Some(Expr::Variable {
                 name: self.previous().clone(),
+                slot: SlotCache::default(),
             });
         }
 
-        self.consume(TokenType::LeftBrace, "Expect '{' before class body.");
+        let mut interfaces = Vec::new();
+        if self.match_tokens(vec![TokenType::Implements]) {
+            loop {
+                interfaces.push(Expr::Variable {
+                    name: self.consume(TokenType::Identifier, "Expect interface name.")?,
+                    slot: SlotCache::default(),
+                });
+                if !self.match_tokens(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
 
         let mut methods = Vec::new();
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
-            methods.push(self.function("method"));
+            self.take_comments(&mut methods);
+            methods.push(self.function("method")?);
         }
+        self.take_comments(&mut methods);
 
-        self.consume(TokenType::RightBrace, "Expect '}' after class body.");
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
 
-        Stmt::Class {
-            name,
-            superclass,
-            methods,
+        Ok((superclass, interfaces, methods))
+    }
+
+    /// Parses `interface Name { method(params); ... }`.
+    fn interface_declaration(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume(TokenType::Identifier, "Expect interface name.")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before interface body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let method = self.consume(TokenType::Identifier, "Expect method name.")?;
+            self.consume(TokenType::LeftParen, "Expect '(' after method name.")?;
+            let params = self.parameters()?;
+            self.consume(TokenType::Semicolon, "Expect ';' after method signature.")?;
+            methods.push((method, params));
         }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after interface body.")?;
+        Ok(Stmt::Interface { name, methods })
     }
 
-    fn statement(&mut self) -> Option<Stmt> {
+    fn statement(&mut self) -> ParseResult<Stmt> {
+        if self.match_tokens(vec![TokenType::Defer]) {
+            return self.defer_statement();
+        }
         if self.match_tokens(vec![TokenType::For]) {
-            return Some(self.for_statement());
+            return self.for_statement();
         }
         if self.match_tokens(vec![TokenType::If]) {
-            return Some(self.if_statement());
+            return self.if_statement();
         }
         if self.match_tokens(vec![TokenType::Print]) {
-            return Some(self.print_statement());
+            return self.print_statement();
         }
         if self.match_tokens(vec![TokenType::Return]) {
-            return Some(self.return_statement());
+            return self.return_statement();
+        }
+        if self.match_tokens(vec![TokenType::Using]) {
+            return self.using_statement();
         }
         if self.match_tokens(vec![TokenType::While]) {
-            return Some(self.while_statement());
+            return self.while_statement();
         }
 
         if self.match_tokens(vec![TokenType::LeftBrace]) {
-            return Some(Stmt::Block(self.block()));
+            return Ok(Stmt::Block(self.block()?));
         }
 
-        Some(self.expression_statement())
+        self.expression_statement()
     }
 
-    fn print_statement(&mut self) -> Stmt {
-        let value = self.expression();
-        self.consume(TokenType::Semicolon, "Expect ';' after value.");
-        Stmt::Print(value)
+    fn defer_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().clone();
+        let body = self.statement()?;
+        Ok(Stmt::Defer {
+            keyword,
+            body: Box::new(body),
+        })
     }
 
-    fn return_statement(&mut self) -> Stmt {
+    fn print_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().clone();
+        let mut value = self.expression()?;
+        if self.check(TokenType::Comma) {
+            value = self.format_arguments(keyword, value)?;
+        }
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::Print(value))
+    }
+
+    /// Desugars `print pattern, a, b;` into `print format(pattern, a, b);`.
+    /// A literal pattern must have a `{}` for each value.
+    fn format_arguments(&mut self, keyword: Token, pattern: Expr) -> ParseResult<Expr> {
+        let mut arguments = vec![pattern];
+        while self.match_tokens(vec![TokenType::Comma]) {
+            if arguments.len() >= 255 {
+                let token = self.peek().clone();
+                self.error(
+                    &token,
+                    ErrorCode::TooManyArguments,
+                    "Can't have more than 255 arguments.",
+                );
+            }
+            arguments.push(self.expression()?);
+        }
+
+        if let Expr::Literal { value } = &arguments[0] {
+            if value.type_ == TokenType::String {
+                let expected =
+                    native_functions::placeholders(value.literal.as_deref().unwrap_or_default());
+                if expected != arguments.len() - 1 {
+                    let message = format!(
+                        "Expected {} {} for the format string but got {}.",
+                        expected,
+                        if expected == 1 { "value" } else { "values" },
+                        arguments.len() - 1
+                    );
+                    let token = value.clone();
+                    self.error(&token, ErrorCode::FormatArgumentCount, &message);
+                }
+            }
+        }
+
+        Ok(Expr::Call {
+            callee: Box::new(Expr::Variable {
+                name: Token {
+                    type_: TokenType::Identifier,
+                    lexeme: "format".to_string(),
+                    ..keyword.clone()
+                },
+                slot: SlotCache::default(),
+            }),
+            paren: keyword,
+            arguments,
+        })
+    }
+
+    fn return_statement(&mut self) -> ParseResult<Stmt> {
         let keyword = self.previous().clone();
         let value = if !self.check(TokenType::Semicolon) {
-            Some(self.expression())
+            Some(self.expression()?)
         } else {
             None
         };
-        self.consume(TokenType::Semicolon, "Expect ';' after return value.");
-        Stmt::Return { keyword, value }
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return { keyword, value })
     }
 
-    fn if_statement(&mut self) -> Stmt {
-        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.");
-        let condition = self.expression();
-        self.consume(TokenType::RightParen, "Expect ')' after if condition.");
+    fn if_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
 
-        let then_branch = self.statement();
-        if self.match_tokens(vec![TokenType::Else]) {
-            return Stmt::If {
-                condition: condition,
-                then_branch: Box::new(then_branch.expect("REASON")),
-                else_branch: Box::new(Some(self.statement()).expect("REASON")),
-            };
+        let then_branch = self.statement()?;
+        let else_branch = if self.match_tokens(vec![TokenType::Else]) {
+            Some(self.statement()?)
         } else {
-            return Stmt::If {
-                condition: condition,
-                then_branch: Box::new(then_branch.expect("REASON")),
-                else_branch: Box::new(None),
-            };
+            None
         };
+        Ok(Stmt::If {
+            condition,
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        })
     }
 
-    fn while_statement(&mut self) -> Stmt {
-        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.");
-        let condition = self.expression();
-        self.consume(TokenType::RightParen, "Expect ')' after condition.");
-        let body = self.statement();
-        Stmt::While {
-            condition: condition,
-            body: Box::new(body.expect("REASON")),
-        }
+    fn using_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'using'.")?;
+        self.consume(TokenType::Var, "Expect 'var' before resource name.")?;
+        let name = self.consume(TokenType::Identifier, "Expect resource name.")?;
+        self.consume(TokenType::Equal, "Expect '=' after resource name.")?;
+        let initializer = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after resource.")?;
+        let body = self.statement()?;
+
+        Ok(Stmt::Using {
+            keyword,
+            name,
+            initializer,
+            body: Box::new(body),
+        })
     }
 
-    fn for_statement(&mut self) -> Stmt {
-        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.");
+    fn while_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = self.statement()?;
+        Ok(Stmt::While {
+            condition,
+            body: Box::new(body),
+        })
+    }
+
+    fn for_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
 
         let initializer = if self.match_tokens(vec![TokenType::Semicolon]) {
             None
         } else if self.match_tokens(vec![TokenType::Var]) {
-            Some(self.var_declaration())
+            Some(self.var_declaration()?)
         } else {
-            Some(self.expression_statement())
+            Some(self.expression_statement()?)
         };
 
         let condition = if !self.check(TokenType::Semicolon) {
-            Some(self.expression())
+            Some(self.expression()?)
         } else {
             None
         };
-        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.");
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
 
         let increment = if !self.check(TokenType::RightParen) {
-            Some(self.expression())
+            Some(self.expression()?)
         } else {
             None
         };
-        self.consume(TokenType::RightParen, "Expect ')' after for clauses.");
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
 
-        let mut body = self.statement().expect("REASON");
+        let mut body = self.statement()?;
 
         if let Some(increment) = increment {
             body = Stmt::Block(vec![body, Stmt::Expression(increment)]);
         }
 
         body = Stmt::While {
+            // A missing condition is true, placed at the `for` it came from
             condition: condition.unwrap_or(Expr::Literal {
-                value: Token::new(TokenType::True, "true".to_string(), None, 0),
+                value: Token {
+                    type_: TokenType::True,
+                    lexeme: "true".to_string(),
+                    literal: None,
+                    ..keyword
+                },
             }),
             body: Box::new(body),
         };
@@ -190,112 +455,184 @@ impl Parser {
             body = Stmt::Block(vec![initializer, body]);
         }
 
-        body
+        Ok(body)
     }
 
-    fn var_declaration(&mut self) -> Stmt {
-        let name = self.consume(TokenType::Identifier, "Expect variable name.");
+    fn var_declaration(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
         // Determine the initializer separately
-        let initializer = {
-            // This creates a new scope for the mutable borrow
-            if self.match_tokens(vec![TokenType::Equal]) {
-                Some(self.expression()) // Evaluate the expression if there is an initializer
-            } else {
-                None // No initializer
-            }
+        let initializer = if self.match_tokens(vec![TokenType::Equal]) {
+            Some(self.expression()?) // Evaluate the expression if there is an initializer
+        } else {
+            None // No initializer
         };
 
         // Consume the semicolon; now we are outside the initializer scope
         self.consume(
             TokenType::Semicolon,
             "Expect ';' after variable declaration.",
-        );
+        )?;
 
         // Return the variable declaration statement
-        Stmt::Var {
+        Ok(Stmt::Var {
             name,        // Clone the token for ownership
             initializer, // Use the initializer
-        }
+        })
     }
 
-    fn expression_statement(&mut self) -> Stmt {
-        let value = self.expression();
-        self.consume(TokenType::Semicolon, "Expect ';' after value.");
-        return Stmt::Expression(value);
+    fn expression_statement(&mut self) -> ParseResult<Stmt> {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt::Expression(value))
     }
 
-    fn function(&mut self, kind: &str) -> Stmt {
-        let name = self.consume(TokenType::Identifier, &format!("Expect {} name.", kind));
+    fn function(&mut self, kind: &str) -> ParseResult<Stmt> {
+        let name = self.consume(TokenType::Identifier, &format!("Expect {} name.", kind))?;
         self.consume(
             TokenType::LeftParen,
             &format!("Expect '(' after {} name.", kind),
-        );
+        )?;
+        let params = self.parameters()?;
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        let body = self.block()?;
+        Ok(Stmt::Function { name, params, body })
+    }
+
+    /// Parses a parameter list after its `(`, through the `)`.
+    fn parameters(&mut self) -> ParseResult<Vec<Token>> {
         let mut params: Vec<Token> = Vec::new();
         if !self.check(TokenType::RightParen) {
             loop {
                 if params.len() >= 255 {
-                    crate::error_token(self.peek(), "Cannot have more than 255 parameters.");
-                    panic!("Cannot have more than 255 parameters.");
+                    let token = self.peek().clone();
+                    self.error(
+                        &token,
+                        ErrorCode::TooManyParameters,
+                        "Can't have more than 255 parameters.",
+                    );
                 }
-                params.push(self.consume(TokenType::Identifier, "Expect parameter name."));
+                params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
                 if !self.match_tokens(vec![TokenType::Comma]) {
                     break;
                 }
             }
         }
-        self.consume(TokenType::RightParen, "Expect ')' after parameters.");
-        self.consume(
-            TokenType::LeftBrace,
-            &format!("Expect '{{' before {} body.", kind),
-        );
-        let body = self.block();
-        Stmt::Function { name, params, body }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+        Ok(params)
     }
 
-    fn block(&mut self) -> Vec<Stmt> {
+    fn block(&mut self) -> ParseResult<Vec<Stmt>> {
         let mut statements: Vec<Stmt> = Vec::new();
 
+        self.blocks += 1;
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
-            statements.push(self.declaration().expect("REASON"));
+            self.take_comments(&mut statements);
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
         }
+        self.take_comments(&mut statements);
+        self.blocks -= 1;
 
-        self.consume(TokenType::RightBrace, "Expect '}' after block.");
-        statements
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
     }
 
-    fn assignment(&mut self) -> Expr {
-        let expr = self.or();
+    fn assignment(&mut self) -> ParseResult<Expr> {
+        let expr = self.ternary()?;
 
-        if self.match_tokens(vec![TokenType::Equal]) {
-            // let equals = self.previous().clone();
-            let value = self.assignment(); // Recursive call to assignment
+        if self.match_tokens(vec![
+            TokenType::Equal,
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+        ]) {
+            let equals = self.previous().clone();
+            let value = self.assignment()?; // Recursive call to assignment
+
+            // `a += b` keeps its target whole, so that any object and index
+            // in it are evaluated once for both reading and assigning
+            let assignable = matches!(
+                expr,
+                Expr::Variable { .. } | Expr::Get { .. } | Expr::Index { .. }
+            );
+            if equals.type_ != TokenType::Equal && assignable {
+                return Ok(Expr::CompoundAssign {
+                    target: Box::new(expr),
+                    operator: equals,
+                    value: Box::new(value),
+                });
+            }
 
             // Check if the expression is a variable expression
-            if let Expr::Variable { name } = expr {
-                return Expr::Assign {
+            if let Expr::Variable { name, .. } = expr {
+                return Ok(Expr::Assign {
                     name,
                     value: Box::new(value),
-                };
+                });
             } else if let Expr::Get { object, name } = expr {
-                return Expr::Set {
+                return Ok(Expr::Set {
                     object,
                     name,
                     value: Box::new(value),
-                };
+                });
+            } else if let Expr::Index {
+                object,
+                bracket,
+                index,
+            } = expr
+            {
+                return Ok(Expr::IndexSet {
+                    object,
+                    bracket,
+                    index,
+                    value: Box::new(value),
+                });
             }
 
-            panic!("Invalid assignment target.");
+            // Reported without unwinding, since the parser isn't confused
+            self.error(
+                &equals,
+                ErrorCode::InvalidAssignmentTarget,
+                "Invalid assignment target.",
+            );
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn or(&mut self) -> Expr {
-        let mut expr = self.and();
+    /// `condition ? then : else`, which groups to the right. The middle can
+    /// be any expression, as between parentheses.
+    fn ternary(&mut self) -> ParseResult<Expr> {
+        let condition = self.or()?;
+
+        if self.match_tokens(vec![TokenType::Question]) {
+            let then_branch = self.expression()?;
+            self.consume(
+                TokenType::Colon,
+                "Expect ':' after then branch of conditional expression.",
+            )?;
+            let else_branch = self.ternary()?;
+            return Ok(Expr::Ternary {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            });
+        }
+
+        Ok(condition)
+    }
+
+    fn or(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.and()?;
 
         while self.match_tokens(vec![TokenType::Or]) {
             let operator = self.previous().clone();
-            let right = self.and();
+            let right = self.and()?;
             expr = Expr::Logical {
                 left: Box::new(expr),
                 operator,
@@ -303,15 +640,15 @@ impl Parser {
             };
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn and(&mut self) -> Expr {
-        let mut expr = self.equality();
+    fn and(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.equality()?;
 
         while self.match_tokens(vec![TokenType::And]) {
             let operator = self.previous().clone();
-            let right = self.equality();
+            let right = self.equality()?;
             expr = Expr::Logical {
                 left: Box::new(expr),
                 operator,
@@ -319,13 +656,17 @@ impl Parser {
             };
         }
 
-        expr
+        Ok(expr)
     }
 
     fn peek(&self) -> &Token {
         &self.tokens[self.current]
     }
 
+    fn peek_next(&self) -> &Token {
+        &self.tokens[(self.current + 1).min(self.tokens.len() - 1)]
+    }
+
     fn previous(&self) -> &Token {
         &self.tokens[self.current - 1]
     }
@@ -358,198 +699,606 @@ impl Parser {
         false
     }
 
-    fn equality(&mut self) -> Expr {
-        let mut comparison = self.comparison();
+    fn equality(&mut self) -> ParseResult<Expr> {
+        let mut comparison = self.comparison()?;
         while self.match_tokens(vec![TokenType::BangEqual, TokenType::EqualEqual]) {
             let operator = self.previous().clone();
-            let right = self.comparison();
+            let right = self.comparison()?;
             comparison = Expr::Binary {
                 left: Box::new(comparison),
                 operator,
                 right: Box::new(right),
             };
         }
-        comparison
+        Ok(comparison)
     }
 
-    fn comparison(&mut self) -> Expr {
-        let mut expr = self.term();
+    fn comparison(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.term()?;
         while self.match_tokens(vec![
             TokenType::Greater,
             TokenType::GreaterEqual,
             TokenType::Less,
             TokenType::LessEqual,
+            TokenType::Is,
         ]) {
             let operator = self.previous().clone();
-            let right = self.term();
+            let right = self.term()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
             };
         }
-        expr
+        Ok(expr)
     }
 
-    fn term(&mut self) -> Expr {
-        let mut expr = self.factor();
+    fn term(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.factor()?;
+        // `--` after something that can't be decremented is two minus signs,
+        // as in `1--1`
+        if self.check(TokenType::MinusMinus) {
+            self.split_minus_minus();
+        }
         while self.match_tokens(vec![TokenType::Minus, TokenType::Plus]) {
             let operator = self.previous().clone();
-            let right = self.factor();
+            let right = self.factor()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
             };
+            if self.check(TokenType::MinusMinus) {
+                self.split_minus_minus();
+            }
         }
-        expr
+        Ok(expr)
+    }
+
+    /// Replaces the current `--` token with two `-` tokens.
+    fn split_minus_minus(&mut self) {
+        let token = self.peek().clone();
+        let first = Token {
+            type_: TokenType::Minus,
+            lexeme: "-".to_string(),
+            ..token.clone()
+        };
+        let second = Token {
+            offset: token.offset + 1,
+            column: token.column + usize::from(token.column > 0),
+            ..first.clone()
+        };
+        self.tokens
+            .splice(self.current..=self.current, [first, second]);
     }
 
-    fn factor(&mut self) -> Expr {
-        let mut expr = self.unary();
+    fn factor(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.unary()?;
         while self.match_tokens(vec![TokenType::Slash, TokenType::Star]) {
             let operator = self.previous().clone();
-            let right = self.unary();
+            let right = self.unary()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
             };
         }
-        expr
+        Ok(expr)
     }
 
-    fn unary(&mut self) -> Expr {
+    fn unary(&mut self) -> ParseResult<Expr> {
+        if self.match_tokens(vec![TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous().clone();
+            let target = self.unary()?;
+            return Ok(self.increment(operator, target, true));
+        }
         if self.match_tokens(vec![TokenType::Bang, TokenType::Minus]) {
             let operator = self.previous().clone();
-            let right = self.unary();
-            return Expr::Unary {
+            let right = self.unary()?;
+            return Ok(Expr::Unary {
                 operator,
                 right: Box::new(right),
-            };
+            });
         }
         self.call()
     }
 
-    fn call(&mut self) -> Expr {
-        let mut expr = self.primary();
+    fn call(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.primary()?;
         loop {
             if self.match_tokens(vec![TokenType::LeftParen]) {
-                expr = self.finish_call(expr);
+                expr = self.finish_call(expr)?;
             } else if self.match_tokens(vec![TokenType::Dot]) {
-                let name = self.consume(TokenType::Identifier, "Expect property name after '.'.");
+                let name =
+                    self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
                 expr = Expr::Get {
                     object: Box::new(expr),
                     name,
                 };
+            } else if self.match_tokens(vec![TokenType::LeftBracket]) {
+                let index = self.expression()?;
+                let bracket = self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                };
+            } else if matches!(
+                expr,
+                Expr::Variable { .. } | Expr::Get { .. } | Expr::Index { .. }
+            ) && self.match_tokens(vec![TokenType::PlusPlus, TokenType::MinusMinus])
+            {
+                let operator = self.previous().clone();
+                expr = self.increment(operator, expr, false);
             } else {
                 break;
             }
         }
-        expr
+        Ok(expr)
     }
 
-    fn finish_call(&mut self, callee: Expr) -> Expr {
+    /// `++target` and the like, which add or subtract 1 like `+=` and `-=`
+    /// do. `--` before something that can't be assigned
+    /// is two minus signs instead, as in `--(3)`.
+    fn increment(&mut self, operator: Token, target: Expr, prefix: bool) -> Expr {
+        match &target {
+            Expr::Variable { .. } | Expr::Get { .. } | Expr::Index { .. } => {}
+            _ if operator.type_ == TokenType::MinusMinus => {
+                let sign = Token {
+                    type_: TokenType::Minus,
+                    lexeme: "-".to_string(),
+                    ..operator
+                };
+                let inner = Token {
+                    offset: sign.offset + 1,
+                    column: sign.column + usize::from(sign.column > 0),
+                    ..sign.clone()
+                };
+                return Expr::Unary {
+                    operator: sign,
+                    right: Box::new(Expr::Unary {
+                        operator: inner,
+                        right: Box::new(target),
+                    }),
+                };
+            }
+            _ => {
+                self.error(
+                    &operator,
+                    ErrorCode::InvalidAssignmentTarget,
+                    "Invalid increment target.",
+                );
+                return target;
+            }
+        }
+        Expr::Increment {
+            operator,
+            target: Box::new(target),
+            prefix,
+        }
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> ParseResult<Expr> {
         let mut arguments: Vec<Expr> = Vec::new();
         if !self.check(TokenType::RightParen) {
             loop {
                 if arguments.len() >= 255 {
-                    crate::error_token(self.peek(), "Cannot have more than 255 arguments.");
-                    panic!("Cannot have more than 255 arguments.");
+                    let token = self.peek().clone();
+                    self.error(
+                        &token,
+                        ErrorCode::TooManyArguments,
+                        "Can't have more than 255 arguments.",
+                    );
                 }
-                arguments.push(self.expression());
+                arguments.push(self.expression()?);
                 if !self.match_tokens(vec![TokenType::Comma]) {
                     break;
                 }
             }
         }
-        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.");
-        Expr::Call {
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+        Ok(Expr::Call {
             callee: Box::new(callee),
             paren,
             arguments,
-        }
+        })
     }
 
-    fn primary(&mut self) -> Expr {
-        if self.match_tokens(vec![TokenType::False]) {
-            return Expr::Literal {
-                value: Token::new(TokenType::False, "false".to_string(), None, 0),
-            };
-        }
-        if self.match_tokens(vec![TokenType::True]) {
-            return Expr::Literal {
-                value: Token::new(TokenType::True, "true".to_string(), None, 0),
-            };
-        }
-        if self.match_tokens(vec![TokenType::Nil]) {
-            return Expr::Literal {
-                value: Token::new(TokenType::Nil, "nil".to_string(), None, 0),
-            };
-        }
-        if self.match_tokens(vec![TokenType::Number, TokenType::String]) {
-            return Expr::Literal {
+    fn primary(&mut self) -> ParseResult<Expr> {
+        if self.match_tokens(vec![
+            TokenType::False,
+            TokenType::True,
+            TokenType::Nil,
+            TokenType::Number,
+            TokenType::String,
+        ]) {
+            return Ok(Expr::Literal {
                 value: self.previous().clone(),
-            };
+            });
         }
         if self.match_tokens(vec![TokenType::Super]) {
             let keyword = self.previous().clone();
-            self.consume(TokenType::Dot, "Expect '.' after 'super'.");
-            let method = self.consume(TokenType::Identifier, "Expect superclass method name.");
-            return Expr::Super { keyword, method };
+            if self.check(TokenType::LeftParen) {
+                // `super(...)` calls the superclass's `init`
+                let method = Token {
+                    lexeme: "init".to_string(),
+                    ..keyword.clone()
+                };
+                return Ok(Expr::Super { keyword, method });
+            }
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+            return Ok(Expr::Super { keyword, method });
+        }
+        // A named class is a declaration, so only `class {`, `class <`, and
+        // `class implements` start an expression
+        if self.check(TokenType::Class)
+            && matches!(
+                self.peek_next().type_,
+                TokenType::LeftBrace | TokenType::Less | TokenType::Implements
+            )
+        {
+            self.advance();
+            return self.class_expression();
         }
         if self.match_tokens(vec![TokenType::This]) {
-            return Expr::This {
+            return Ok(Expr::This {
                 keyword: self.previous().clone(),
-            };
+            });
         }
         if self.match_tokens(vec![TokenType::Identifier]) {
-            return Expr::Variable {
+            return Ok(Expr::Variable {
                 name: self.previous().clone(),
-            };
+                slot: SlotCache::default(),
+            });
         }
         if self.match_tokens(vec![TokenType::LeftParen]) {
-            let expr = self.expression();
-            self.consume(TokenType::RightParen, "Expect ')' after expression.");
-            return Expr::Grouping {
+            let expr = self.expression()?;
+            self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+            return Ok(Expr::Grouping {
                 expression: Box::new(expr),
-            };
+            });
         }
-        crate::error_token(self.peek(), "Expect expression.");
-        Expr::Literal {
-            value: Token::new(TokenType::Nil, "nil".to_string(), None, 0),
+        if self.match_tokens(vec![TokenType::LeftBracket]) {
+            let bracket = self.previous().clone();
+            let mut elements = Vec::new();
+            if !self.check(TokenType::RightBracket) {
+                loop {
+                    elements.push(self.expression()?);
+                    if !self.match_tokens(vec![TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+            return Ok(Expr::ListLiteral { bracket, elements });
         }
+        Err(self.error_at_current(ErrorCode::ExpectExpression, "Expect expression."))
     }
 
-    fn consume(&mut self, token_type: TokenType, message: &str) -> Token {
+    fn consume(&mut self, token_type: TokenType, message: &str) -> ParseResult<Token> {
+        let code = Parser::expect_code(&token_type);
         if self.check(token_type) {
-            return self.advance().clone();
+            return Ok(self.advance().clone());
+        }
+
+        Err(self.error_at_current(code, message))
+    }
+
+    /// The code for a missing token of type `token_type`.
+    fn expect_code(token_type: &TokenType) -> ErrorCode {
+        match token_type {
+            TokenType::Semicolon => ErrorCode::ExpectSemicolon,
+            TokenType::Identifier => ErrorCode::ExpectName,
+            TokenType::LeftParen => ErrorCode::ExpectLeftParen,
+            TokenType::RightParen => ErrorCode::ExpectRightParen,
+            TokenType::LeftBrace => ErrorCode::ExpectLeftBrace,
+            TokenType::RightBrace => ErrorCode::ExpectRightBrace,
+            _ => ErrorCode::ExpectToken,
         }
+    }
+
+    /// Records an error the parser can carry on from.
+    fn error(&mut self, token: &Token, code: ErrorCode, message: &str) {
+        self.report(ParseError {
+            token: token.clone(),
+            code,
+            message: message.to_string(),
+        });
+    }
+
+    /// Records `error`, unless an earlier error in the same statement has
+    /// put the parser in panic mode.
+    fn report(&mut self, error: ParseError) {
+        if !self.panic_mode {
+            self.errors.push(error);
+        }
+        self.panic_mode = true;
+    }
 
-        crate::error_token(self.peek(), message);
-        panic!("{}", message)
+    /// Builds an error at the next token, for the caller to unwind with.
+    fn error_at_current(&self, code: ErrorCode, message: &str) -> ParseError {
+        ParseError {
+            token: self.peek().clone(),
+            code,
+            message: message.to_string(),
+        }
     }
 
-    fn synchronize(&mut self) {
-        self.advance();
+    /// Skips to the start of the next statement after an error in the
+    /// declaration that began at token `start`. Any block the declaration
+    /// opened is skipped whole, so that its statements and closing brace
+    /// aren't mistaken for new ones.
+    fn synchronize(&mut self, start: usize) {
+        let mut depth = 0usize;
+        for token in &self.tokens[start..self.current] {
+            match token.type_ {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+
+        // A brace closing an enclosing block is left for the block
+        let closes_block = |parser: &Parser, depth: usize| {
+            depth == 0 && parser.blocks > 0 && parser.check(TokenType::RightBrace)
+        };
+        if closes_block(self, depth) {
+            return;
+        }
+        self.skip(&mut depth);
         while !self.is_at_end() {
-            if self.previous().type_ == TokenType::Semicolon {
+            if depth > 0 {
+                self.skip(&mut depth);
+                continue;
+            }
+            if self.previous().type_ == TokenType::Semicolon || closes_block(self, depth) {
                 return;
             }
             match self.peek().type_ {
                 TokenType::Class
+                | TokenType::Defer
                 | TokenType::Fun
+                | TokenType::Interface
+                | TokenType::Macro
                 | TokenType::Var
                 | TokenType::For
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
-                | TokenType::Return => {
+                | TokenType::Return
+                | TokenType::Using => {
                     return;
                 }
                 _ => {}
             }
-            self.advance();
+            self.skip(&mut depth);
         }
     }
+
+    /// Advances past one token, keeping count of the braces left open.
+    fn skip(&mut self, depth: &mut usize) {
+        match self.advance().type_ {
+            TokenType::LeftBrace => *depth += 1,
+            TokenType::RightBrace => *depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::print_program;
+    use crate::scanner::Scanner;
+
+    fn parse(source: &str) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        Parser::parse_tokens(Scanner::scan(source).unwrap())
+    }
+
+    /// Parses a single expression statement and renders it as an s-expression.
+    fn expression(source: &str) -> String {
+        match parse(&format!("{};", source)).unwrap().remove(0) {
+            Stmt::Expression(expr) => expr.accept(),
+            stmt => panic!("Expected an expression statement, got {:?}", stmt),
+        }
+    }
+
+    fn program(source: &str) -> String {
+        print_program(&parse(source).unwrap())
+    }
+
+    fn errors(source: &str) -> Vec<String> {
+        parse(source)
+            .unwrap_err()
+            .iter()
+            .map(ParseError::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn parses_primary_expressions() {
+        assert_eq!(expression("true"), "true");
+        assert_eq!(expression("false"), "false");
+        assert_eq!(expression("nil"), "nil");
+        assert_eq!(expression("12.5"), "12.5");
+        assert_eq!(expression("\"str\""), "\"str\"");
+        assert_eq!(expression("name"), "name");
+        assert_eq!(expression("this"), "this");
+        assert_eq!(expression("super.method"), "(super method)");
+        assert_eq!(expression("(1)"), "(group 1)");
+    }
+
+    #[test]
+    fn parses_operators_by_precedence() {
+        assert_eq!(expression("!-a"), "(! (- a))");
+        assert_eq!(expression("1 + 2 * 3 - 4 / 5"), "(- (+ 1 (* 2 3)) (/ 4 5))");
+        assert_eq!(expression("1 < 2 == 3 >= 4"), "(== (< 1 2) (>= 3 4))");
+        assert_eq!(expression("a != b <= c > d"), "(!= a (> (<= b c) d))");
+        assert_eq!(expression("a or b and c or d"), "(or (or a (and b c)) d)");
+        assert_eq!(
+            expression("a = b or c ? d : e ? f : g"),
+            "(= a (?: (or b c) d (?: e f g)))"
+        );
+        assert_eq!(expression("a.b -= c += 2"), "(-= (get b a) (+= c 2))");
+        assert_eq!(
+            expression("-a.b++ - --c"),
+            "(- (- (post++ (get b a))) (pre-- c))"
+        );
+        // Only something assignable can be decremented
+        assert_eq!(
+            expression("--(a) - 1--1"),
+            "(- (- (- (- (group a))) 1) (- 1))"
+        );
+    }
+
+    #[test]
+    fn desugared_nodes_keep_source_lines() {
+        let stmts = parse("var a;\nfor (;;)\n  a = nil;").unwrap();
+        let Stmt::While { condition, body } = &stmts[1] else {
+            panic!("Expected a while loop, got {:?}", stmts[1]);
+        };
+        let Expr::Literal { value } = condition else {
+            panic!("Expected a literal condition, got {:?}", condition);
+        };
+        assert_eq!((value.lexeme.as_str(), value.line), ("true", 2));
+
+        let Stmt::Expression(Expr::Assign { value, .. }) = body.as_ref() else {
+            panic!("Expected an assignment, got {:?}", body);
+        };
+        assert!(matches!(value.as_ref(), Expr::Literal { value } if value.line == 3));
+    }
+
+    #[test]
+    fn parses_calls_and_properties() {
+        assert_eq!(expression("f()"), "(call f)");
+        assert_eq!(expression("f(1, 2)(3)"), "(call (call f 1 2) 3)");
+        assert_eq!(expression("a.b.c(d)"), "(call (get c (get b a)) d)");
+    }
+
+    #[test]
+    fn parses_lists_and_indexes() {
+        assert_eq!(expression("[]"), "(list)");
+        assert_eq!(expression("[1, [2], a]"), "(list 1 (list 2) a)");
+        assert_eq!(expression("a[0][i + 1]"), "(index (index a 0) (+ i 1))");
+        assert_eq!(expression("f()[0].b"), "(get b (index (call f) 0))");
+        assert_eq!(expression("a[0] = 1"), "(index= a 0 1)");
+        assert_eq!(expression("a[0] *= 2"), "(*= (index a 0) 2)");
+        assert_eq!(expression("a[0]++"), "(post++ (index a 0))");
+        assert_eq!(
+            program("var a=[1,2];a[0]=a[1];"),
+            "var a = [1, 2];\na[0] = a[1];\n"
+        );
+        assert_eq!(
+            errors("[1, 2;"),
+            ["[line 1:6] Error at ';': Expect ']' after list elements. [E2008]"]
+        );
+    }
+
+    #[test]
+    fn parses_class_expressions() {
+        assert_eq!(program("var C = class {};"), "var C = class {\n};\n");
+        // A class at the start of a statement is a declaration
+        assert_eq!(expression("(class {})"), "(group (class))");
+        assert_eq!(
+            expression("(class < Base { init() {} get() {} })()"),
+            "(call (group (class < Base init get)))"
+        );
+    }
+
+    #[test]
+    fn parses_assignment() {
+        assert_eq!(expression("a = b = 1"), "(= a (= b 1))");
+        assert_eq!(expression("a.b = c"), "(set b a c)");
+    }
+
+    #[test]
+    fn parses_statements() {
+        assert_eq!(program("print 1;"), "print 1;\n");
+        assert_eq!(program("var a; var b = 2;"), "var a;\nvar b = 2;\n");
+        assert_eq!(program("{ a; }"), "{\n  a;\n}\n");
+        assert_eq!(
+            program("if (a) print 1; else print 2;"),
+            "if (a) print 1; else print 2;\n"
+        );
+        assert_eq!(program("while (a) a;"), "while (a) a;\n");
+        assert_eq!(
+            program("fun f() { defer { a; } defer print b; }"),
+            "fun f() {\n  defer {\n    a;\n  }\n  defer print b;\n}\n"
+        );
+        assert_eq!(
+            program("using (var f = open(a)) { f.read(); }"),
+            "using (var f = open(a)) {\n  f.read();\n}\n"
+        );
+        assert_eq!(
+            program("fun f(a, b) { return a; return; }"),
+            "fun f(a, b) {\n  return a;\n  return;\n}\n"
+        );
+        assert_eq!(
+            program("class A < B { m() {} }"),
+            "class A < B {\n  m() {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn desugars_for_loops() {
+        assert_eq!(
+            program("for (var i = 0; i < 2; i = i + 1) print i;"),
+            "{\n  var i = 0;\n  while (i < 2) {\n    print i;\n    i = i + 1;\n  }\n}\n"
+        );
+        assert_eq!(program("for (;;) a;"), "while (true) a;\n");
+    }
+
+    #[test]
+    fn recovers_and_collects_every_error() {
+        assert_eq!(
+            errors("var = 1;\nprint 2\nvar ok = 3;\n{"),
+            vec![
+                "[line 1:5] Error at '=': Expect variable name. [E2003]",
+                "[line 3:1] Error at 'var': Expect ';' after value. [E2004]",
+                "[line 4:2] Error at end: Expect '}' after block. [E2007]",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_errors_that_do_not_stop_parsing() {
+        // The statement still parses, so the error after it is found too
+        assert_eq!(
+            errors("1 = 2; print;"),
+            vec![
+                "[line 1:3] Error at '=': Invalid assignment target. [E2009]",
+                "[line 1:13] Error at ';': Expect expression. [E2001]",
+            ]
+        );
+
+        let arguments = vec!["a"; 256].join(", ");
+        assert_eq!(
+            errors(&format!("f({});", arguments)),
+            vec!["[line 1:768] Error at 'a': Can't have more than 255 arguments. [E2010]"]
+        );
+    }
+
+    #[test]
+    fn reports_one_error_per_mistake() {
+        // Each of these used to go on to complain about the closing brace
+        for source in [
+            "fun f( { print 1; }\nprint 2;",
+            "fun f(a, b { return a; }\nprint 2;",
+            "if (a { print 1; } else { print 2; }",
+            "for (var i = 0; i < 3 i = i + 1) { print i; }",
+            "{ class A { 1 } print 2; }",
+            "1 = 2 3;",
+        ] {
+            assert_eq!(errors(source).len(), 1, "{}", source);
+        }
+
+        // A block's closing brace still ends the block
+        assert_eq!(
+            errors("{ print 1 }\nprint 2;"),
+            vec!["[line 1:11] Error at '}': Expect ';' after value. [E2004]"]
+        );
+        // Mistakes in separate statements are all reported
+        assert_eq!(errors("{ var = 1; print; }").len(), 2);
+    }
 }
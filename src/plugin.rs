@@ -0,0 +1,90 @@
+//! Loading natives from shared libraries at startup (`--plugin`).
+//!
+//! A plugin exports a C function named `lox_plugin_init`:
+//!
+//! ```c
+//! int lox_plugin_init(void *lox, LoxRegisterNativeFn register_native);
+//! ```
+//!
+//! It calls `register_native(lox, name, arity, function, userdata)` once for
+//! each native it provides and returns 0 on success. The value and callback
+//! types are the ones described in `ffi`.
+
+use crate::ffi::{self, LoxNativeFn};
+use crate::limits::Permission;
+use crate::Lox;
+use libloading::{Library, Symbol};
+use std::ffi::{c_char, c_int, c_void};
+use std::path::Path;
+
+pub type LoxRegisterNativeFn = unsafe extern "C" fn(
+    lox: *mut Lox,
+    name: *const c_char,
+    arity: usize,
+    function: LoxNativeFn,
+    userdata: *mut c_void,
+) -> c_int;
+
+pub type LoxPluginInitFn =
+    unsafe extern "C" fn(lox: *mut Lox, register_native: LoxRegisterNativeFn) -> c_int;
+
+const PLUGIN_INIT: &[u8] = b"lox_plugin_init";
+
+/// Loads the plugin at `path` and lets it register its natives with `lox`.
+/// The library stays loaded for the rest of the process, since the natives
+/// it registered point into it. Refused unless `lox`'s limits allow
+/// `Permission::Exec`.
+pub fn load_plugin(lox: &mut Lox, path: &Path) -> Result<(), String> {
+    if !lox.limits().allows(Permission::Exec) {
+        return Err(format!(
+            "Could not load plugin '{}'. {}",
+            path.display(),
+            Permission::Exec.denied_message()
+        ));
+    }
+
+    let library = unsafe { Library::new(path) }
+        .map_err(|err| format!("Could not load plugin '{}'. {}", path.display(), err))?;
+
+    let status = unsafe {
+        let init: Symbol<LoxPluginInitFn> = library.get(PLUGIN_INIT).map_err(|err| {
+            format!(
+                "Plugin '{}' does not export lox_plugin_init. {}",
+                path.display(),
+                err
+            )
+        })?;
+        init(lox as *mut Lox, ffi::lox_register_native)
+    };
+
+    std::mem::forget(library);
+
+    if status != ffi::LOX_OK {
+        return Err(format!(
+            "Plugin '{}' failed to initialize (status {}).",
+            path.display(),
+            status
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_plugin_is_reported() {
+        let mut lox = Lox::new("");
+        let result = load_plugin(&mut lox, Path::new("./no_such_plugin.so"));
+        assert!(result.unwrap_err().contains("no_such_plugin.so"));
+    }
+
+    #[test]
+    fn sandbox_refuses_plugins() {
+        let mut lox = Lox::new("");
+        lox.set_limits(crate::limits::InterpreterLimits::sandboxed());
+        let result = load_plugin(&mut lox, Path::new("./no_such_plugin.so"));
+        assert!(result.unwrap_err().ends_with("is not allowed."));
+    }
+}
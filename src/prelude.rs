@@ -0,0 +1,39 @@
+//! The prelude: helpers written in Lox, such as `map` and `join`, that
+//! every interpreter defines before running a script. `Lox::without_prelude`
+//! and `--no-prelude` leave them out.
+
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::stmt::Stmt;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The prelude's source, bundled into the binary.
+pub const SOURCE: &str = include_str!("prelude.lox");
+
+/// The prelude's top-level statements.
+pub fn statements() -> Vec<Stmt> {
+    let tokens = Scanner::new(SOURCE.to_string())
+        .scan_tokens()
+        .expect("The prelude scans");
+    Parser::new(tokens).parse().expect("The prelude parses")
+}
+
+/// Runs the prelude on `interpreter`, defining its functions as globals.
+pub fn load(interpreter: Interpreter) -> Interpreter {
+    let interpreter = Rc::new(RefCell::new(interpreter));
+    let statements = statements();
+    Resolver::new(interpreter.clone())
+        .resolve(&statements)
+        .expect("The prelude resolves");
+    interpreter
+        .borrow_mut()
+        .interpret(statements)
+        .expect("The prelude runs");
+    match Rc::try_unwrap(interpreter) {
+        Ok(interpreter) => interpreter.into_inner(),
+        Err(_) => unreachable!("The resolver is done with the interpreter"),
+    }
+}
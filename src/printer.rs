@@ -0,0 +1,648 @@
+use crate::error::LoxError;
+use crate::expr::Expr;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::stmt::Stmt;
+use crate::token_type::TokenType;
+
+const INDENT: &str = "  ";
+
+/// Renders statements back into Lox source that parses to the same tree.
+pub fn print_program(stmts: &[Stmt]) -> String {
+    let mut result = String::new();
+    for stmt in stmts {
+        push_line(&mut result, stmt, 0, print_stmt);
+    }
+    result
+}
+
+/// Reprints `source` in the printer's layout, keeping its comments: each
+/// stays before the statement it came before, or at the end of the line of
+/// the statement it followed. Pragmas are kept like comments, and the
+/// sections they turn off without flags are left as written.
+pub fn format_source(source: &str) -> Result<String, LoxError> {
+    let tokens = Scanner::new(source.to_string()).scan_tokens()?;
+    let comments = Scanner::with_trivia(source)
+        .filter(|token| matches!(token.type_, TokenType::Comment | TokenType::Pragma))
+        .collect();
+    let stmts = Parser::with_comments(tokens, comments).parse()?;
+    Ok(print_program(&stmts))
+}
+
+/// Adds `stmt`, rendered by `print`, to `result` as a line of its own at
+/// `depth`, or, for a trailing comment, at the end of the line before.
+fn push_line(result: &mut String, stmt: &Stmt, depth: usize, print: fn(&Stmt, usize) -> String) {
+    if let Stmt::Comment {
+        text,
+        trailing: true,
+    } = stmt
+    {
+        if result.ends_with('\n') {
+            result.pop();
+            result.push(' ');
+            result.push_str(text.lexeme.trim_end());
+            result.push('\n');
+            return;
+        }
+    }
+    result.push_str(&INDENT.repeat(depth));
+    result.push_str(&print(stmt, depth));
+    result.push('\n');
+}
+
+/// Renders a single statement. Nested lines are indented relative to `depth`,
+/// but the first line is not, so callers can place it after other text.
+pub fn print_stmt(stmt: &Stmt, depth: usize) -> String {
+    match stmt {
+        Stmt::Block(stmts) => print_block(stmts, depth),
+        Stmt::Class {
+            name,
+            superclass,
+            interfaces,
+            methods,
+        } => {
+            // Anonymous classes are named after their `class` keyword
+            let name = match name.type_ {
+                TokenType::Class => None,
+                _ => Some(name.lexeme.as_str()),
+            };
+            print_class(name, superclass.as_ref(), interfaces, methods, depth)
+        }
+        Stmt::Interface { name, methods } => {
+            let mut result = format!("interface {} {{\n", name.lexeme);
+            for (method, params) in methods {
+                let params: Vec<&str> = params.iter().map(|p| p.lexeme.as_str()).collect();
+                result.push_str(&INDENT.repeat(depth + 1));
+                result.push_str(&format!("{}({});\n", method.lexeme, params.join(", ")));
+            }
+            result.push_str(&INDENT.repeat(depth));
+            result.push('}');
+            result
+        }
+        // A section turned off by a pragma starts with its indentation
+        Stmt::Comment { text, .. } => text.lexeme.trim().to_string(),
+        Stmt::Defer { body, .. } => format!("defer {}", print_stmt(body, depth)),
+        Stmt::Expression(expr) => format!("{};", print_expr(expr)),
+        Stmt::Function { .. } => format!("fun {}", print_function(stmt, depth)),
+        Stmt::Macro { name, params, body } => {
+            let params: Vec<&str> = params.iter().map(|p| p.lexeme.as_str()).collect();
+            format!(
+                "macro {}({}) {}",
+                name.lexeme,
+                params.join(", "),
+                print_block(body, depth)
+            )
+        }
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            let mut result = format!("if ({}) ", print_expr(condition));
+            match &**else_branch {
+                Some(else_branch) => {
+                    // Keep a trailing else from binding to a nested if
+                    if let Stmt::If {
+                        else_branch: inner_else,
+                        ..
+                    } = &**then_branch
+                    {
+                        if inner_else.is_none() {
+                            result.push_str(&print_block(&[*then_branch.clone()], depth));
+                        } else {
+                            result.push_str(&print_stmt(then_branch, depth));
+                        }
+                    } else {
+                        result.push_str(&print_stmt(then_branch, depth));
+                    }
+                    result.push_str(" else ");
+                    result.push_str(&print_stmt(else_branch, depth));
+                }
+                None => result.push_str(&print_stmt(then_branch, depth)),
+            }
+            result
+        }
+        Stmt::Print(expr) => format!("print {};", print_expr(expr)),
+        Stmt::Return { value, .. } => match value {
+            Some(value) => format!("return {};", print_expr(value)),
+            None => "return;".to_string(),
+        },
+        Stmt::Using {
+            name,
+            initializer,
+            body,
+            ..
+        } => format!(
+            "using (var {} = {}) {}",
+            name.lexeme,
+            print_expr(initializer),
+            print_stmt(body, depth)
+        ),
+        Stmt::Var { name, initializer } => match initializer {
+            Some(initializer) => format!("var {} = {};", name.lexeme, print_expr(initializer)),
+            None => format!("var {};", name.lexeme),
+        },
+        Stmt::While { condition, body } => {
+            format!(
+                "while ({}) {}",
+                print_expr(condition),
+                print_stmt(body, depth)
+            )
+        }
+    }
+}
+
+pub fn print_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Assign { name, value } => format!("{} = {}", name.lexeme, print_expr(value)),
+        Expr::CompoundAssign {
+            target,
+            operator,
+            value,
+        } => format!(
+            "{} {} {}",
+            print_expr(target),
+            operator.lexeme,
+            print_expr(value)
+        ),
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        } => format!(
+            "{} {} {}",
+            print_expr(left),
+            operator.lexeme,
+            print_expr(right)
+        ),
+        Expr::Grouping { expression } => format!("({})", print_expr(expression)),
+        Expr::Literal { value } => value.lexeme.clone(),
+        Expr::Set {
+            object,
+            name,
+            value,
+        } => format!(
+            "{}.{} = {}",
+            print_expr(object),
+            name.lexeme,
+            print_expr(value)
+        ),
+        Expr::Unary { operator, right } => {
+            let operand = print_expr(right);
+            // "- -x" must not run together into another token
+            if operator.type_ == TokenType::Minus && operand.starts_with('-') {
+                format!("{} {}", operator.lexeme, operand)
+            } else {
+                format!("{}{}", operator.lexeme, operand)
+            }
+        }
+        Expr::Variable { name, .. } => name.lexeme.clone(),
+        Expr::Logical {
+            left,
+            operator,
+            right,
+        } => format!(
+            "{} {} {}",
+            print_expr(left),
+            operator.lexeme,
+            print_expr(right)
+        ),
+        Expr::Ternary {
+            condition,
+            then_branch,
+            else_branch,
+        } => format!(
+            "{} ? {} : {}",
+            print_expr(condition),
+            print_expr(then_branch),
+            print_expr(else_branch)
+        ),
+        Expr::Increment {
+            operator,
+            target,
+            prefix: true,
+            ..
+        } => format!("{}{}", operator.lexeme, print_expr(target)),
+        Expr::Increment {
+            operator, target, ..
+        } => format!("{}{}", print_expr(target), operator.lexeme),
+        Expr::Call {
+            callee, arguments, ..
+        } => {
+            let arguments: Vec<String> = arguments.iter().map(print_expr).collect();
+            format!("{}({})", print_expr(callee), arguments.join(", "))
+        }
+        Expr::Get { object, name } => format!("{}.{}", print_expr(object), name.lexeme),
+        Expr::Super { keyword, method } if method.type_ == keyword.type_ => "super".to_string(),
+        Expr::Super { method, .. } => format!("super.{}", method.lexeme),
+        Expr::This { .. } => "this".to_string(),
+        Expr::Class {
+            superclass,
+            interfaces,
+            methods,
+            ..
+        } => print_class(None, superclass.as_deref(), interfaces, methods, 0),
+        Expr::ListLiteral { elements, .. } => {
+            let elements: Vec<String> = elements.iter().map(print_expr).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        Expr::Index { object, index, .. } => {
+            format!("{}[{}]", print_expr(object), print_expr(index))
+        }
+        Expr::IndexSet {
+            object,
+            index,
+            value,
+            ..
+        } => format!(
+            "{}[{}] = {}",
+            print_expr(object),
+            print_expr(index),
+            print_expr(value)
+        ),
+    }
+}
+
+/// Renders a class declaration, or a class expression when it has no name.
+fn print_class(
+    name: Option<&str>,
+    superclass: Option<&Expr>,
+    interfaces: &[Expr],
+    methods: &[Stmt],
+    depth: usize,
+) -> String {
+    let mut result = "class".to_string();
+    if let Some(name) = name {
+        result.push(' ');
+        result.push_str(name);
+    }
+    if let Some(superclass) = superclass {
+        result.push_str(&format!(" < {}", print_expr(superclass)));
+    }
+    if !interfaces.is_empty() {
+        let interfaces: Vec<String> = interfaces.iter().map(print_expr).collect();
+        result.push_str(&format!(" implements {}", interfaces.join(", ")));
+    }
+    result.push_str(" {\n");
+    for method in methods {
+        push_line(&mut result, method, depth + 1, print_function);
+    }
+    result.push_str(&INDENT.repeat(depth));
+    result.push('}');
+    result
+}
+
+fn print_block(stmts: &[Stmt], depth: usize) -> String {
+    if stmts.is_empty() {
+        return "{}".to_string();
+    }
+    let mut result = "{\n".to_string();
+    for stmt in stmts {
+        push_line(&mut result, stmt, depth + 1, print_stmt);
+    }
+    result.push_str(&INDENT.repeat(depth));
+    result.push('}');
+    result
+}
+
+/// Renders a function declaration without the leading `fun`, as used by methods.
+fn print_function(stmt: &Stmt, depth: usize) -> String {
+    match stmt {
+        Stmt::Function { name, params, body } => {
+            let params: Vec<&str> = params.iter().map(|p| p.lexeme.as_str()).collect();
+            format!(
+                "{}({}) {}",
+                name.lexeme,
+                params.join(", "),
+                print_block(body, depth)
+            )
+        }
+        _ => print_stmt(stmt, depth),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::SlotCache;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use crate::token::Token;
+    use proptest::prelude::*;
+
+    const KEYWORDS: [&str; 22] = [
+        "and",
+        "class",
+        "defer",
+        "else",
+        "false",
+        "for",
+        "fun",
+        "if",
+        "implements",
+        "interface",
+        "is",
+        "macro",
+        "nil",
+        "or",
+        "print",
+        "return",
+        "super",
+        "this",
+        "true",
+        "using",
+        "var",
+        "while",
+    ];
+
+    fn token(type_: TokenType, lexeme: &str) -> Token {
+        Token::new(type_, lexeme.to_string(), None, 1)
+    }
+
+    /// How tightly the grammar rule that produces `expr` binds, from
+    /// assignment (0) up to primary (9).
+    fn precedence(expr: &Expr) -> u8 {
+        match expr {
+            Expr::Assign { .. } | Expr::CompoundAssign { .. } | Expr::Set { .. } => 0,
+            Expr::Logical { operator, .. } if operator.type_ == TokenType::Or => 1,
+            Expr::Logical { .. } => 2,
+            Expr::Binary { operator, .. } => binary_precedence(&operator.type_),
+            Expr::Unary { .. } => 7,
+            Expr::Call { .. } | Expr::Get { .. } => 8,
+            _ => 9,
+        }
+    }
+
+    fn binary_precedence(type_: &TokenType) -> u8 {
+        match type_ {
+            TokenType::BangEqual | TokenType::EqualEqual => 3,
+            TokenType::Minus | TokenType::Plus => 5,
+            TokenType::Slash | TokenType::Star => 6,
+            _ => 4,
+        }
+    }
+
+    /// Wraps `expr` in a grouping when it binds more loosely than `min`, the
+    /// way it would have to be written in source.
+    fn operand(expr: Expr, min: u8) -> Box<Expr> {
+        if precedence(&expr) < min {
+            Box::new(Expr::Grouping {
+                expression: Box::new(expr),
+            })
+        } else {
+            Box::new(expr)
+        }
+    }
+
+    fn name() -> impl Strategy<Value = String> {
+        "[a-z_][a-z0-9_]{0,4}".prop_filter("keywords are not names", |name| {
+            !KEYWORDS.contains(&name.as_str())
+        })
+    }
+
+    fn literal() -> impl Strategy<Value = Expr> {
+        prop_oneof![
+            (0u32..1000, proptest::option::of(0u32..100)).prop_map(|(whole, fraction)| {
+                let lexeme = match fraction {
+                    Some(fraction) => format!("{}.{}", whole, fraction),
+                    None => whole.to_string(),
+                };
+                Expr::Literal {
+                    value: token(TokenType::Number, &lexeme),
+                }
+            }),
+            "[a-z ]{0,6}".prop_map(|text| Expr::Literal {
+                value: token(TokenType::String, &format!("\"{}\"", text)),
+            }),
+            prop_oneof![
+                Just((TokenType::True, "true")),
+                Just((TokenType::False, "false")),
+                Just((TokenType::Nil, "nil")),
+            ]
+            .prop_map(|(type_, lexeme)| Expr::Literal {
+                value: token(type_, lexeme),
+            }),
+            name().prop_map(|name| Expr::Variable {
+                name: token(TokenType::Identifier, &name),
+                slot: SlotCache::default(),
+            }),
+            Just(Expr::This {
+                keyword: token(TokenType::This, "this"),
+            }),
+            name().prop_map(|method| Expr::Super {
+                keyword: token(TokenType::Super, "super"),
+                method: token(TokenType::Identifier, &method),
+            }),
+        ]
+    }
+
+    fn binary_operator() -> impl Strategy<Value = Token> {
+        prop_oneof![
+            Just((TokenType::BangEqual, "!=")),
+            Just((TokenType::EqualEqual, "==")),
+            Just((TokenType::Greater, ">")),
+            Just((TokenType::GreaterEqual, ">=")),
+            Just((TokenType::Less, "<")),
+            Just((TokenType::LessEqual, "<=")),
+            Just((TokenType::Minus, "-")),
+            Just((TokenType::Plus, "+")),
+            Just((TokenType::Slash, "/")),
+            Just((TokenType::Star, "*")),
+            Just((TokenType::Is, "is")),
+        ]
+        .prop_map(|(type_, lexeme)| token(type_, lexeme))
+    }
+
+    fn compound_operator() -> impl Strategy<Value = Token> {
+        prop_oneof![
+            Just((TokenType::PlusEqual, "+=")),
+            Just((TokenType::MinusEqual, "-=")),
+            Just((TokenType::StarEqual, "*=")),
+            Just((TokenType::SlashEqual, "/=")),
+        ]
+        .prop_map(|(type_, lexeme)| token(type_, lexeme))
+    }
+
+    /// Random expression trees shaped the way the parser builds them: every
+    /// operand that binds too loosely for its position sits in a grouping.
+    fn expr() -> impl Strategy<Value = Expr> {
+        literal().prop_recursive(5, 48, 3, |inner| {
+            prop_oneof![
+                (inner.clone(), binary_operator(), inner.clone()).prop_map(
+                    |(left, operator, right)| {
+                        // Left-associative: only the right operand needs a tighter binding
+                        let precedence = binary_precedence(&operator.type_);
+                        Expr::Binary {
+                            left: operand(left, precedence),
+                            operator,
+                            right: operand(right, precedence + 1),
+                        }
+                    }
+                ),
+                (inner.clone(), prop::bool::ANY, inner.clone()).prop_map(|(left, or, right)| {
+                    let (operator, precedence) = if or {
+                        (token(TokenType::Or, "or"), 1)
+                    } else {
+                        (token(TokenType::And, "and"), 2)
+                    };
+                    Expr::Logical {
+                        left: operand(left, precedence),
+                        operator,
+                        right: operand(right, precedence + 1),
+                    }
+                }),
+                (prop::bool::ANY, inner.clone()).prop_map(|(bang, right)| Expr::Unary {
+                    operator: if bang {
+                        token(TokenType::Bang, "!")
+                    } else {
+                        token(TokenType::Minus, "-")
+                    },
+                    right: operand(right, 7),
+                }),
+                inner.clone().prop_map(|expression| Expr::Grouping {
+                    expression: Box::new(expression),
+                }),
+                (inner.clone(), prop::collection::vec(inner.clone(), 0..3)).prop_map(
+                    |(callee, arguments)| Expr::Call {
+                        callee: operand(callee, 8),
+                        paren: token(TokenType::RightParen, ")"),
+                        arguments,
+                    }
+                ),
+                (inner.clone(), name()).prop_map(|(object, name)| Expr::Get {
+                    object: operand(object, 8),
+                    name: token(TokenType::Identifier, &name),
+                }),
+                (name(), inner.clone()).prop_map(|(name, value)| Expr::Assign {
+                    name: token(TokenType::Identifier, &name),
+                    value: Box::new(value),
+                }),
+                (inner.clone(), name(), inner.clone()).prop_map(|(object, name, value)| {
+                    Expr::Set {
+                        object: operand(object, 8),
+                        name: token(TokenType::Identifier, &name),
+                        value: Box::new(value),
+                    }
+                }),
+                (inner.clone(), name(), compound_operator(), inner).prop_map(
+                    |(object, name, operator, value)| Expr::CompoundAssign {
+                        target: Box::new(Expr::Get {
+                            object: operand(object, 8),
+                            name: token(TokenType::Identifier, &name),
+                        }),
+                        operator,
+                        value: Box::new(value),
+                    }
+                ),
+            ]
+        })
+    }
+
+    fn parse_expr(source: &str) -> Expr {
+        let tokens = Scanner::new(format!("{};", source)).scan_tokens().unwrap();
+        match Parser::new(tokens).parse().unwrap().remove(0) {
+            Stmt::Expression(expr) => expr,
+            other => panic!("Expected an expression statement, got {:?}", other),
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn printed_expressions_reparse_to_the_same_tree(expr in expr()) {
+            let source = print_expr(&expr);
+            prop_assert_eq!(parse_expr(&source).accept(), expr.accept(), "source: {}", source);
+        }
+    }
+
+    #[test]
+    fn printed_programs_reparse_to_the_same_source() {
+        let source = "class A < B {\n  init(x) {\n    super(x);\n    this.x = x;\n  }\n}\n\
+                      fun f(a, b) {\n  if (a) if (b) return 1; else return 2;\n  while (a) a = a - 1;\n}\n\
+                      print - -1 + (2 * 3);\n\
+                      interface I {\n  get();\n  set(a, b);\n}\n\
+                      var C = class < A implements I {\n  get() {\n    return 1;\n  }\n};\n";
+        let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let printed = print_program(&stmts);
+
+        let tokens = Scanner::new(printed.clone()).scan_tokens().unwrap();
+        let reparsed = Parser::new(tokens).parse().unwrap();
+        assert_eq!(print_program(&reparsed), printed);
+    }
+
+    #[test]
+    fn formatting_keeps_comments_in_place() {
+        let source = "// Counts down.\n\
+                      fun countdown(n) { // from n\n\
+                      // Stop at zero\n\
+                      while (n > 0) n = n - 1;   // one at a time\n\
+                      // done\n\
+                      }\n\
+                      class A {\n\
+                      // A method\n\
+                      m() {}\n\
+                      }\n\
+                      var x = 1 + // the rest\n\
+                      2;\n\
+                      print x;\n\
+                      // The end\n";
+        let formatted = format_source(source).unwrap();
+        assert_eq!(
+            formatted,
+            "// Counts down.\n\
+             fun countdown(n) { // from n\n  \
+               // Stop at zero\n  \
+               while (n > 0) n = n - 1; // one at a time\n  \
+               // done\n\
+             }\n\
+             class A {\n  \
+               // A method\n  \
+               m() {}\n\
+             }\n\
+             var x = 1 + 2;\n\
+             // the rest\n\
+             print x;\n\
+             // The end\n"
+        );
+        // Formatting again changes nothing
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+        assert!(format_source("print 1 // no semicolon").is_err());
+    }
+
+    #[test]
+    fn formatting_keeps_compound_assignments() {
+        let source = "x += 1;\na.b -= c *= 2;\nl[i + 1] /= 2;\n";
+        assert_eq!(format_source(source).unwrap(), source);
+    }
+
+    #[test]
+    fn formatting_prints_macros() {
+        assert_eq!(
+            format_source("macro twice(body) { body; body; } twice(x);").unwrap(),
+            "macro twice(body) {\n  body;\n  body;\n}\ntwice(x);\n"
+        );
+    }
+
+    #[test]
+    fn formatting_keeps_pragmas() {
+        let source = "fun f() {\n\
+                      #if DEBUG\n\
+                      print   \"on\";\n\
+                      #else\n\
+                      print   \"off\";\n\
+                      #end\n\
+                      }\n";
+        let formatted = format_source(source).unwrap();
+        assert_eq!(
+            formatted,
+            "fun f() {\n  \
+               #if DEBUG\n  \
+               print   \"on\";\n  \
+               #else\n  \
+               print \"off\";\n  \
+               #end\n\
+             }\n"
+        );
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+}
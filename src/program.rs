@@ -0,0 +1,736 @@
+//! Scripts compiled once and run many times. `Program::compile` scans,
+//! parses, expands, and resolves a script up front, and
+//! `Interpreter::run_program` runs the result without doing any of that
+//! again. `to_bytes` and `from_bytes` save and load a compiled program, so a
+//! host can keep them in a cache across processes.
+//!
+//! ```
+//! use lox_interpreter::program::Program;
+//! use lox_interpreter::Lox;
+//!
+//! let program = Program::compile("var total = 0; total = total + 1;").unwrap();
+//! let program = Program::from_bytes(&program.to_bytes()).unwrap();
+//! let mut lox = Lox::new("");
+//! for _ in 0..3 {
+//!     lox.run_program(&program).unwrap();
+//! }
+//! ```
+
+use crate::error::LoxError;
+use crate::expr::{Expr, SlotCache};
+use crate::interpreter::Interpreter;
+use crate::macros::Macros;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::stmt::Stmt;
+use crate::token::Token;
+use crate::token_type::TokenType;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Starts every program `to_bytes` makes, with the format's version last.
+const MAGIC: &[u8] = b"LOXP\x06";
+
+/// A parsed and resolved script.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    source: String,
+    statements: Vec<Stmt>,
+    /// The scope depth of each use of a local variable, from the resolver.
+    locals: Vec<(Expr, usize)>,
+}
+
+impl Program {
+    /// Compiles `source`, returning the first phase's errors as `Lox::try_run`
+    /// would. Resolver warnings are left out. `#if` sections are all off,
+    /// and only the macros `source` declares are expanded.
+    pub fn compile(source: &str) -> Result<Program, LoxError> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens()?;
+        let statements = Parser::new(tokens).parse()?;
+        let statements = Macros::new().expand(statements)?;
+
+        let interpreter = Rc::new(RefCell::new(Interpreter::without_prelude("")));
+        Resolver::new(interpreter.clone()).resolve(&statements)?;
+        let locals = interpreter.borrow_mut().take_locals();
+        Ok(Program {
+            source: source.to_string(),
+            statements,
+            locals,
+        })
+    }
+
+    /// A program of statements that have already been resolved, such as the
+    /// declarations `spawn` sends to a worker. It has no source to quote.
+    #[cfg(feature = "lox-threads")]
+    pub(crate) fn from_parts(statements: Vec<Stmt>, locals: Vec<(Expr, usize)>) -> Program {
+        Program {
+            source: String::new(),
+            statements,
+            locals,
+        }
+    }
+
+    /// The source the program was compiled from, for error excerpts.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn statements(&self) -> &[Stmt] {
+        &self.statements
+    }
+
+    pub fn locals(&self) -> &[(Expr, usize)] {
+        &self.locals
+    }
+
+    /// The program in a compact binary form that `from_bytes` reads back.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder {
+            bytes: MAGIC.to_vec(),
+        };
+        encoder.string(&self.source);
+        encoder.stmts(&self.statements);
+        encoder.count(self.locals.len());
+        for (expr, depth) in &self.locals {
+            encoder.expr(expr);
+            encoder.count(*depth);
+        }
+        encoder.bytes
+    }
+
+    /// Reads a program saved by `to_bytes`, or `None` if `bytes` isn't one
+    /// or comes from another version of the format.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Program> {
+        let mut decoder = Decoder {
+            bytes: bytes.strip_prefix(MAGIC)?,
+        };
+        let source = decoder.string()?;
+        let statements = decoder.stmts()?;
+        let locals = (0..decoder.count()?)
+            .map(|_| Some((decoder.expr()?, decoder.count()?)))
+            .collect::<Option<_>>()?;
+        if !decoder.bytes.is_empty() {
+            return None;
+        }
+        Some(Program {
+            source,
+            statements,
+            locals,
+        })
+    }
+}
+
+struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn byte(&mut self, byte: u8) {
+        self.bytes.push(byte);
+    }
+
+    fn count(&mut self, count: usize) {
+        self.bytes.extend_from_slice(&(count as u64).to_le_bytes());
+    }
+
+    fn string(&mut self, text: &str) {
+        self.count(text.len());
+        self.bytes.extend_from_slice(text.as_bytes());
+    }
+
+    fn token(&mut self, token: &Token) {
+        self.byte(token.type_.clone() as u8);
+        self.string(&token.lexeme);
+        match &token.literal {
+            Some(literal) => {
+                self.byte(1);
+                self.string(literal);
+            }
+            None => self.byte(0),
+        }
+        self.bytes.extend_from_slice(&token.line.to_le_bytes());
+        self.count(token.offset);
+        self.count(token.column);
+    }
+
+    fn tokens(&mut self, tokens: &[Token]) {
+        self.count(tokens.len());
+        for token in tokens {
+            self.token(token);
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        self.count(exprs.len());
+        for expr in exprs {
+            self.expr(expr);
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        self.count(stmts.len());
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn optional_expr(&mut self, expr: Option<&Expr>) {
+        match expr {
+            Some(expr) => {
+                self.byte(1);
+                self.expr(expr);
+            }
+            None => self.byte(0),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assign { name, value } => {
+                self.byte(0);
+                self.token(name);
+                self.expr(value);
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.byte(1);
+                self.expr(left);
+                self.token(operator);
+                self.expr(right);
+            }
+            Expr::Grouping { expression } => {
+                self.byte(2);
+                self.expr(expression);
+            }
+            Expr::Literal { value } => {
+                self.byte(3);
+                self.token(value);
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                self.byte(4);
+                self.expr(object);
+                self.token(name);
+                self.expr(value);
+            }
+            Expr::Unary { operator, right } => {
+                self.byte(5);
+                self.token(operator);
+                self.expr(right);
+            }
+            Expr::Variable { name, .. } => {
+                self.byte(6);
+                self.token(name);
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                self.byte(7);
+                self.expr(left);
+                self.token(operator);
+                self.expr(right);
+            }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.byte(8);
+                self.expr(condition);
+                self.expr(then_branch);
+                self.expr(else_branch);
+            }
+            Expr::Increment {
+                operator,
+                target,
+                prefix,
+            } => {
+                self.byte(9);
+                self.token(operator);
+                self.expr(target);
+                self.byte(u8::from(*prefix));
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                self.byte(10);
+                self.expr(callee);
+                self.token(paren);
+                self.exprs(arguments);
+            }
+            Expr::Get { object, name } => {
+                self.byte(11);
+                self.expr(object);
+                self.token(name);
+            }
+            Expr::Super { keyword, method } => {
+                self.byte(12);
+                self.token(keyword);
+                self.token(method);
+            }
+            Expr::This { keyword } => {
+                self.byte(13);
+                self.token(keyword);
+            }
+            Expr::Class {
+                keyword,
+                superclass,
+                interfaces,
+                methods,
+            } => {
+                self.byte(14);
+                self.token(keyword);
+                self.optional_expr(superclass.as_deref());
+                self.exprs(interfaces);
+                self.stmts(methods);
+            }
+            Expr::ListLiteral { bracket, elements } => {
+                self.byte(15);
+                self.token(bracket);
+                self.exprs(elements);
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                self.byte(16);
+                self.expr(object);
+                self.token(bracket);
+                self.expr(index);
+            }
+            Expr::IndexSet {
+                object,
+                bracket,
+                index,
+                value,
+            } => {
+                self.byte(17);
+                self.expr(object);
+                self.token(bracket);
+                self.expr(index);
+                self.expr(value);
+            }
+            Expr::CompoundAssign {
+                target,
+                operator,
+                value,
+            } => {
+                self.byte(18);
+                self.expr(target);
+                self.token(operator);
+                self.expr(value);
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(stmts) => {
+                self.byte(0);
+                self.stmts(stmts);
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                interfaces,
+                methods,
+            } => {
+                self.byte(1);
+                self.token(name);
+                self.optional_expr(superclass.as_ref());
+                self.exprs(interfaces);
+                self.stmts(methods);
+            }
+            Stmt::Defer { keyword, body } => {
+                self.byte(2);
+                self.token(keyword);
+                self.stmt(body);
+            }
+            Stmt::Expression(expr) => {
+                self.byte(3);
+                self.expr(expr);
+            }
+            Stmt::Function { name, params, body } => {
+                self.byte(4);
+                self.token(name);
+                self.tokens(params);
+                self.stmts(body);
+            }
+            Stmt::Comment { text, trailing } => {
+                self.byte(5);
+                self.token(text);
+                self.byte(u8::from(*trailing));
+            }
+            Stmt::Macro { name, params, body } => {
+                self.byte(6);
+                self.token(name);
+                self.tokens(params);
+                self.stmts(body);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.byte(7);
+                self.expr(condition);
+                self.stmt(then_branch);
+                match else_branch.as_ref() {
+                    Some(else_branch) => {
+                        self.byte(1);
+                        self.stmt(else_branch);
+                    }
+                    None => self.byte(0),
+                }
+            }
+            Stmt::Print(expr) => {
+                self.byte(8);
+                self.expr(expr);
+            }
+            Stmt::Return { keyword, value } => {
+                self.byte(9);
+                self.token(keyword);
+                self.optional_expr(value.as_ref());
+            }
+            Stmt::Using {
+                keyword,
+                name,
+                initializer,
+                body,
+            } => {
+                self.byte(10);
+                self.token(keyword);
+                self.token(name);
+                self.expr(initializer);
+                self.stmt(body);
+            }
+            Stmt::Var { name, initializer } => {
+                self.byte(11);
+                self.token(name);
+                self.optional_expr(initializer.as_ref());
+            }
+            Stmt::While { condition, body } => {
+                self.byte(12);
+                self.expr(condition);
+                self.stmt(body);
+            }
+            Stmt::Interface { name, methods } => {
+                self.byte(13);
+                self.token(name);
+                self.count(methods.len());
+                for (method, params) in methods {
+                    self.token(method);
+                    self.tokens(params);
+                }
+            }
+        }
+    }
+}
+
+/// Reads what `Encoder` writes. Every method gives `None` on bytes it
+/// couldn't have written.
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, length: usize) -> Option<&[u8]> {
+        if length > self.bytes.len() {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn flag(&mut self) -> Option<bool> {
+        match self.byte()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    fn count(&mut self) -> Option<usize> {
+        let bytes = self.take(8)?.try_into().ok()?;
+        usize::try_from(u64::from_le_bytes(bytes)).ok()
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let length = self.count()?;
+        String::from_utf8(self.take(length)?.to_vec()).ok()
+    }
+
+    fn token(&mut self) -> Option<Token> {
+        let type_ = TokenType::ALL.get(usize::from(self.byte()?))?.clone();
+        let lexeme = self.string()?;
+        let literal = if self.flag()? {
+            Some(self.string()?)
+        } else {
+            None
+        };
+        let line = i32::from_le_bytes(self.take(4)?.try_into().ok()?);
+        Some(Token {
+            type_,
+            lexeme,
+            literal,
+            line,
+            offset: self.count()?,
+            column: self.count()?,
+        })
+    }
+
+    fn tokens(&mut self) -> Option<Vec<Token>> {
+        (0..self.count()?).map(|_| self.token()).collect()
+    }
+
+    fn exprs(&mut self) -> Option<Vec<Expr>> {
+        (0..self.count()?).map(|_| self.expr()).collect()
+    }
+
+    fn stmts(&mut self) -> Option<Vec<Stmt>> {
+        (0..self.count()?).map(|_| self.stmt()).collect()
+    }
+
+    fn boxed(&mut self) -> Option<Box<Expr>> {
+        self.expr().map(Box::new)
+    }
+
+    fn optional_expr(&mut self) -> Option<Option<Expr>> {
+        if self.flag()? {
+            self.expr().map(Some)
+        } else {
+            Some(None)
+        }
+    }
+
+    fn expr(&mut self) -> Option<Expr> {
+        let expr = match self.byte()? {
+            0 => Expr::Assign {
+                name: self.token()?,
+                value: self.boxed()?,
+            },
+            1 => Expr::Binary {
+                left: self.boxed()?,
+                operator: self.token()?,
+                right: self.boxed()?,
+            },
+            2 => Expr::Grouping {
+                expression: self.boxed()?,
+            },
+            3 => Expr::Literal {
+                value: self.token()?,
+            },
+            4 => Expr::Set {
+                object: self.boxed()?,
+                name: self.token()?,
+                value: self.boxed()?,
+            },
+            5 => Expr::Unary {
+                operator: self.token()?,
+                right: self.boxed()?,
+            },
+            6 => Expr::Variable {
+                name: self.token()?,
+                slot: SlotCache::default(),
+            },
+            7 => Expr::Logical {
+                left: self.boxed()?,
+                operator: self.token()?,
+                right: self.boxed()?,
+            },
+            8 => Expr::Ternary {
+                condition: self.boxed()?,
+                then_branch: self.boxed()?,
+                else_branch: self.boxed()?,
+            },
+            9 => Expr::Increment {
+                operator: self.token()?,
+                target: self.boxed()?,
+                prefix: self.flag()?,
+            },
+            10 => Expr::Call {
+                callee: self.boxed()?,
+                paren: self.token()?,
+                arguments: self.exprs()?,
+            },
+            11 => Expr::Get {
+                object: self.boxed()?,
+                name: self.token()?,
+            },
+            12 => Expr::Super {
+                keyword: self.token()?,
+                method: self.token()?,
+            },
+            13 => Expr::This {
+                keyword: self.token()?,
+            },
+            14 => Expr::Class {
+                keyword: self.token()?,
+                superclass: self.optional_expr()?.map(Box::new),
+                interfaces: self.exprs()?,
+                methods: self.stmts()?,
+            },
+            15 => Expr::ListLiteral {
+                bracket: self.token()?,
+                elements: self.exprs()?,
+            },
+            16 => Expr::Index {
+                object: self.boxed()?,
+                bracket: self.token()?,
+                index: self.boxed()?,
+            },
+            17 => Expr::IndexSet {
+                object: self.boxed()?,
+                bracket: self.token()?,
+                index: self.boxed()?,
+                value: self.boxed()?,
+            },
+            18 => Expr::CompoundAssign {
+                target: self.boxed()?,
+                operator: self.token()?,
+                value: self.boxed()?,
+            },
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    fn stmt(&mut self) -> Option<Stmt> {
+        let stmt = match self.byte()? {
+            0 => Stmt::Block(self.stmts()?),
+            1 => Stmt::Class {
+                name: self.token()?,
+                superclass: self.optional_expr()?,
+                interfaces: self.exprs()?,
+                methods: self.stmts()?,
+            },
+            2 => Stmt::Defer {
+                keyword: self.token()?,
+                body: Box::new(self.stmt()?),
+            },
+            3 => Stmt::Expression(self.expr()?),
+            4 => Stmt::Function {
+                name: self.token()?,
+                params: self.tokens()?,
+                body: self.stmts()?,
+            },
+            5 => Stmt::Comment {
+                text: self.token()?,
+                trailing: self.flag()?,
+            },
+            6 => Stmt::Macro {
+                name: self.token()?,
+                params: self.tokens()?,
+                body: self.stmts()?,
+            },
+            7 => {
+                let condition = self.expr()?;
+                let then_branch = Box::new(self.stmt()?);
+                let else_branch = if self.flag()? {
+                    Some(self.stmt()?)
+                } else {
+                    None
+                };
+                Stmt::If {
+                    condition,
+                    then_branch,
+                    else_branch: Box::new(else_branch),
+                }
+            }
+            8 => Stmt::Print(self.expr()?),
+            9 => Stmt::Return {
+                keyword: self.token()?,
+                value: self.optional_expr()?,
+            },
+            10 => Stmt::Using {
+                keyword: self.token()?,
+                name: self.token()?,
+                initializer: self.expr()?,
+                body: Box::new(self.stmt()?),
+            },
+            11 => Stmt::Var {
+                name: self.token()?,
+                initializer: self.optional_expr()?,
+            },
+            12 => Stmt::While {
+                condition: self.expr()?,
+                body: Box::new(self.stmt()?),
+            },
+            13 => Stmt::Interface {
+                name: self.token()?,
+                methods: (0..self.count()?)
+                    .map(|_| Some((self.token()?, self.tokens()?)))
+                    .collect::<Option<_>>()?,
+            },
+            _ => return None,
+        };
+        Some(stmt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lox;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn compiled_programs_run_and_round_trip() {
+        let source = "var count = 0;
+            class Counter {
+              init() { this.n = 0; }
+              bump() { this.n++; return this.n; }
+            }
+            fun twice(f) { f(); return f(); }
+            macro log(value) { print \"log \" + value; }
+            var c = Counter();
+            count = count + twice(c.bump);
+            log(\"count\");
+            print count > 1 ? \"many\" : \"few\";
+            { var local = -count; print local; }";
+        // Token types are saved as their index into `TokenType::ALL`
+        for (index, type_) in TokenType::ALL.iter().enumerate() {
+            assert_eq!(type_.clone() as usize, index);
+        }
+
+        let program = Program::compile(source).unwrap();
+        let bytes = program.to_bytes();
+        assert_eq!(Program::from_bytes(&bytes), Some(program.clone()));
+        assert_eq!(Program::from_bytes(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(Program::from_bytes(b"LOXP\x01"), None);
+
+        let output_file = env::temp_dir().join("lox_program_runs.txt");
+        fs::write(&output_file, "").unwrap();
+        let mut lox = Lox::new(&output_file.to_string_lossy());
+        lox.run_program(&program).unwrap();
+        lox.run_program(&Program::from_bytes(&bytes).unwrap())
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&output_file).unwrap(),
+            "log count\nmany\n-2\nlog count\nmany\n-2\n"
+        );
+
+        assert!(matches!(
+            Program::compile("return 1;"),
+            Err(LoxError::Resolve(_))
+        ));
+    }
+}
@@ -0,0 +1,56 @@
+//! The generator behind the `random()` native: xorshift64*, which is quick
+//! and spreads its output well, but is no good for anything secret.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// The seed `--deterministic` starts from, so every run draws the same
+/// numbers.
+pub const DETERMINISTIC_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        // An all-zero state would only ever give zero
+        Random {
+            state: if seed == 0 { DETERMINISTIC_SEED } else { seed },
+        }
+    }
+
+    /// A generator with a different seed each time, taken from the keys the
+    /// standard library makes for hash maps.
+    pub fn from_entropy() -> Self {
+        Random::new(RandomState::new().build_hasher().finish())
+    }
+
+    /// The next number, from 0 up to but not including 1.
+    pub fn next_f64(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let bits = self.state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        // The top 53 bits, which a number holds exactly
+        (bits >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_generators_repeat() {
+        let mut a = Random::new(DETERMINISTIC_SEED);
+        let mut b = Random::new(DETERMINISTIC_SEED);
+        for _ in 0..100 {
+            let number = a.next_f64();
+            assert_eq!(number, b.next_f64());
+            assert!((0.0..1.0).contains(&number));
+        }
+        assert_ne!(Random::new(1).next_f64(), Random::new(2).next_f64());
+    }
+}
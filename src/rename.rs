@@ -0,0 +1,185 @@
+//! Renaming a variable, function, or class everywhere it is used, for
+//! `lox_interpreter rename file.lox --line 10 --col 5 --to newName`.
+//!
+//! Only the name's tokens change, so comments and formatting are kept. A
+//! rename is refused if it would change what any name in the program refers
+//! to, as when the new name is already in use in the same scope.
+
+use crate::bindings::{self, Bindings};
+use crate::error::LoxError;
+use crate::scanner::Scanner;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum RenameError {
+    /// The source doesn't scan, parse, or resolve.
+    Lox(LoxError),
+    /// The new name isn't an identifier, or is a keyword.
+    InvalidName(String),
+    /// There's no variable, function, or class at the position.
+    NothingToRename { line: i32, column: usize },
+    /// The new name would shadow, or be shadowed by, another declaration.
+    Conflict(String),
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenameError::Lox(error) => write!(f, "{}", error),
+            RenameError::InvalidName(name) => write!(f, "'{}' is not a valid name.", name),
+            RenameError::NothingToRename { line, column } => {
+                write!(f, "Nothing to rename at line {}, column {}.", line, column)
+            }
+            RenameError::Conflict(name) => write!(
+                f,
+                "Renaming to '{}' would change what another name refers to.",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RenameError {}
+
+impl From<LoxError> for RenameError {
+    fn from(error: LoxError) -> Self {
+        RenameError::Lox(error)
+    }
+}
+
+/// Renames the declaration or use at `line` and `column`, both counted from
+/// 1, together with every other token bound to the same declaration.
+/// Returns the updated source.
+pub fn rename(
+    source: &str,
+    line: i32,
+    column: usize,
+    new_name: &str,
+) -> Result<String, RenameError> {
+    if !Scanner::is_identifier(new_name) {
+        return Err(RenameError::InvalidName(new_name.to_string()));
+    }
+    let bindings = bindings::analyze(source)?;
+    let nothing = RenameError::NothingToRename { line, column };
+    let binding = offset_of(source, line, column)
+        .and_then(|offset| bindings.at(offset))
+        .ok_or(nothing)?;
+
+    let old_len = binding.declaration.lexeme.len();
+    let mut offsets: Vec<usize> = std::iter::once(&binding.declaration)
+        .chain(&binding.uses)
+        .map(|token| token.offset)
+        .collect();
+    offsets.sort_unstable();
+
+    let mut renamed = String::with_capacity(source.len());
+    let mut copied = 0;
+    for &offset in &offsets {
+        renamed.push_str(&source[copied..offset]);
+        renamed.push_str(new_name);
+        copied = offset + old_len;
+    }
+    renamed.push_str(&source[copied..]);
+
+    // Every other offset moves by the change in length of the names before it
+    let moved = |offset: usize| {
+        let before = offsets.partition_point(|&renamed| renamed < offset);
+        offset + before * new_name.len() - before * old_len
+    };
+    let expected = layout(&bindings, moved);
+    match bindings::analyze(&renamed) {
+        Ok(after) if layout(&after, |offset| offset) == expected => Ok(renamed),
+        _ => Err(RenameError::Conflict(new_name.to_string())),
+    }
+}
+
+/// Where each binding's tokens are, and where the unbound names are.
+fn layout(bindings: &Bindings, offset: impl Fn(usize) -> usize) -> Vec<Vec<usize>> {
+    let mut layout: Vec<Vec<usize>> = bindings
+        .bindings()
+        .iter()
+        .map(|binding| {
+            std::iter::once(&binding.declaration)
+                .chain(&binding.uses)
+                .map(|token| offset(token.offset))
+                .collect()
+        })
+        .collect();
+    layout.push(
+        bindings
+            .unbound()
+            .iter()
+            .map(|token| offset(token.offset))
+            .collect(),
+    );
+    layout
+}
+
+/// The byte offset of `column` on `line`, if the source has that position.
+fn offset_of(source: &str, line: i32, column: usize) -> Option<usize> {
+    let start: usize = source
+        .split_inclusive('\n')
+        .take(usize::try_from(line).ok()?.checked_sub(1)?)
+        .map(str::len)
+        .sum();
+    let text = source[start..].split('\n').next()?;
+    let (index, _) = text.char_indices().nth(column.checked_sub(1)?)?;
+    Some(start + index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renames_every_reference_in_scope() {
+        let source = "var count = 0; // the count\n\
+                      fun bump() { count = count + 1; }\n\
+                      fun local() { var count = 10; return count; }\n\
+                      bump();\n\
+                      print count;\n";
+        assert_eq!(
+            rename(source, 2, 14, "total").unwrap(),
+            "var total = 0; // the count\n\
+             fun bump() { total = total + 1; }\n\
+             fun local() { var count = 10; return count; }\n\
+             bump();\n\
+             print total;\n"
+        );
+        // The local is renamed on its own
+        assert_eq!(
+            rename(source, 3, 40, "ten").unwrap(),
+            "var count = 0; // the count\n\
+             fun bump() { count = count + 1; }\n\
+             fun local() { var ten = 10; return ten; }\n\
+             bump();\n\
+             print count;\n"
+        );
+    }
+
+    #[test]
+    fn renames_the_targets_of_compound_assignments() {
+        let source = "var n = 1;\nn += 1;\nn++;\nprint --n;\n";
+        assert_eq!(
+            rename(source, 1, 5, "count").unwrap(),
+            "var count = 1;\ncount += 1;\ncount++;\nprint --count;\n"
+        );
+    }
+
+    #[test]
+    fn refuses_renames_that_change_meaning() {
+        let source = "fun f(a, b) { return a + b; }\nprint f(1, 2);\n";
+        assert!(matches!(
+            rename(source, 1, 7, "b"),
+            Err(RenameError::Conflict(_))
+        ));
+        assert!(matches!(
+            rename(source, 1, 7, "while"),
+            Err(RenameError::InvalidName(_))
+        ));
+        assert!(matches!(
+            rename(source, 2, 1, "output"),
+            Err(RenameError::NothingToRename { line: 2, column: 1 })
+        ));
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+
+/// Where an `Interpreter` sends the diagnostics for errors that stop a run.
+/// Defaults to `StderrReporter`; embedders and test harnesses can install a
+/// `BufferReporter` with `Lox::set_reporter` to collect them instead.
+pub trait ErrorReporter {
+    /// Receives one diagnostic, such as a syntax error with the source line
+    /// it quotes, or a runtime error with its trace. It may span several
+    /// lines but has no trailing newline.
+    fn report(&mut self, diagnostic: &str);
+
+    /// Receives one warning, shaped like a diagnostic. Warnings never stop
+    /// a run, and are only sent when `Lox::set_warnings` enabled them. By
+    /// default they go to `report`.
+    fn warn(&mut self, warning: &str) {
+        self.report(warning);
+    }
+}
+
+impl fmt::Debug for dyn ErrorReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ErrorReporter")
+    }
+}
+
+/// Prints each diagnostic on stderr.
+#[derive(Debug, Default)]
+pub struct StderrReporter;
+
+impl ErrorReporter for StderrReporter {
+    fn report(&mut self, diagnostic: &str) {
+        eprintln!("{}", diagnostic);
+    }
+}
+
+/// Keeps each diagnostic and warning in memory, in the order reported.
+#[derive(Debug, Default)]
+pub struct BufferReporter {
+    pub diagnostics: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl BufferReporter {
+    pub fn new() -> Self {
+        BufferReporter::default()
+    }
+
+    /// Removes and returns the diagnostics reported so far.
+    pub fn take(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+impl ErrorReporter for BufferReporter {
+    fn report(&mut self, diagnostic: &str) {
+        self.diagnostics.push(diagnostic.to_string());
+    }
+
+    fn warn(&mut self, warning: &str) {
+        self.warnings.push(warning.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lints::{Lint, Severity};
+    use crate::Lox;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn buffer_collects_diagnostics_instead_of_printing() {
+        let buffer = Rc::new(RefCell::new(BufferReporter::new()));
+        let mut lox = Lox::new("");
+        lox.set_reporter(buffer.clone());
+
+        assert!(lox.try_run("var = 1;\nprint;").is_err());
+        assert_eq!(
+            buffer.borrow_mut().take(),
+            [
+                "[line 1:5] Error at '=': Expect variable name. [E2003]\n1 | var = 1;\n  |     ^",
+                "[line 2:6] Error at ';': Expect expression. [E2001]\n2 | print;\n  |      ^",
+            ]
+        );
+
+        assert!(lox.try_run("fun f() { nil(); }\nf();").is_err());
+        assert_eq!(
+            buffer.borrow_mut().take(),
+            ["Can only call functions and classes. [E3006]\n[line 1:15]\n  at f (line 2)"]
+        );
+    }
+
+    #[test]
+    fn unused_locals_are_warned_about_when_enabled() {
+        let buffer = Rc::new(RefCell::new(BufferReporter::new()));
+        let mut lox = Lox::new("");
+        lox.set_reporter(buffer.clone());
+        let source = "var global = 1;\n\
+                      fun f(unread) {\n\
+                      \x20 var used = 1;\n\
+                      \x20 var unused = used;\n\
+                      \x20 var _ignored;\n\
+                      \x20 { var inner; }\n\
+                      }";
+
+        assert!(lox.try_run(source).is_ok());
+        assert!(buffer.borrow().warnings.is_empty());
+
+        lox.set_warnings(true);
+        assert!(lox.try_run(source).is_ok());
+        let buffer = buffer.borrow();
+        assert_eq!(
+            buffer.warnings,
+            [
+                "[line 4:7] warning: local variable 'unused' is never used [E4101]\n4 |   var unused = used;\n  |       ^~~~~~",
+                "[line 6:9] warning: local variable 'inner' is never used [E4101]\n6 |   { var inner; }\n  |         ^~~~~",
+            ]
+        );
+        assert!(buffer.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn strict_mode_makes_lints_errors() {
+        let buffer = Rc::new(RefCell::new(BufferReporter::new()));
+        let mut lox = Lox::new("");
+        lox.set_reporter(buffer.clone());
+        let source = "fun f(n) {\n\
+                      \x20 var total = 0;\n\
+                      \x20 { var n = 1; total = n; }\n\
+                      \x20 count = total;\n\
+                      \x20 later = total;\n\
+                      \x20 return total;\n\
+                      }\n\
+                      var later;";
+
+        lox.set_warnings(true);
+        assert!(lox.try_run(source).is_ok());
+        assert_eq!(
+            buffer.borrow_mut().warnings.drain(..).collect::<Vec<_>>(),
+            [
+                "[line 3:9] warning: local variable 'n' shadows one in an enclosing scope [E4102]\n3 |   { var n = 1; total = n; }\n  |         ^",
+                "[line 4:3] warning: assignment to undeclared variable 'count' [E4103]\n4 |   count = total;\n  |   ^~~~~",
+            ]
+        );
+
+        lox.set_strict(true);
+        assert!(lox.try_run(source).is_err());
+        assert_eq!(
+            buffer.borrow_mut().take(),
+            [
+                "[line 3:9] Error at 'n': local variable 'n' shadows one in an enclosing scope [E4102]\n3 |   { var n = 1; total = n; }\n  |         ^",
+                "[line 4:3] Error at 'count': assignment to undeclared variable 'count' [E4103]\n4 |   count = total;\n  |   ^~~~~",
+            ]
+        );
+
+        lox.set_lint_severity(Lint::Shadowing, Severity::Allow);
+        lox.set_lint_severity(Lint::UndeclaredAssignment, Severity::Warn);
+        assert!(lox.try_run(source).is_ok());
+    }
+}
@@ -1,12 +1,16 @@
+use crate::bindings::Bindings;
+use crate::error::{ErrorCode, LoxError};
 use crate::expr::Expr;
 use crate::interpreter::Interpreter;
 use crate::interpreter::StmtVisitor;
 use crate::interpreter::Visitor;
-use crate::return_value::ReturnValue;
+use crate::interpreter::{EvalResult, ExecResult};
+use crate::lints::Lint;
 use crate::stmt::Stmt;
 use crate::token::Token;
-use crate::value::Value;
+use crate::token_type::TokenType;
 use std::cell::RefCell;
+use std::fmt;
 use std::rc::Rc;
 
 use std::collections::HashMap;
@@ -26,236 +30,224 @@ pub enum ClassType {
     Subclass,
 }
 
+/// A program that breaks a scoping rule, such as returning from top-level
+/// code, reported at the offending token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolveError {
+    pub token: Token,
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}] Error at '{}': {} [{}]",
+            self.token.position(),
+            self.token.lexeme,
+            self.message,
+            self.code
+        )
+    }
+}
+
+/// Something suspicious that doesn't stop the program from running, such
+/// as a local variable that is never read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolveWarning {
+    pub lint: Lint,
+    pub token: Token,
+    pub message: String,
+}
+
+impl fmt::Display for ResolveWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}] warning: {} [{}]",
+            self.token.position(),
+            self.message,
+            self.lint.code()
+        )
+    }
+}
+
 pub struct Resolver {
     interpreter: Rc<RefCell<Interpreter>>,
     scopes: Vec<HashMap<String, bool>>,
+    /// The `var` declarations in each scope that haven't been read yet.
+    unread: Vec<HashMap<String, Token>>,
+    /// The binding each name declared in a scope belongs to.
+    declarations: Vec<HashMap<String, usize>>,
+    /// The bindings of global names, and the uses of names not declared in
+    /// any enclosing scope, matched to them once every global is known.
+    globals: HashMap<String, usize>,
+    global_uses: Vec<Token>,
+    /// Assignments to names not declared in any enclosing scope.
+    global_assignments: Vec<Token>,
+    bindings: Bindings,
+    errors: Vec<ResolveError>,
+    warnings: Vec<ResolveWarning>,
     current_function: FunctionType,
     current_class: ClassType,
+    /// Whether a deferred statement of the current function is being
+    /// resolved.
+    in_defer: bool,
 }
 
 impl Visitor for Resolver {
-    fn visit_assign_expr(&mut self, expr: &Expr) -> Option<Value> {
+    fn visit_assign_expr(&mut self, expr: &Expr) -> EvalResult {
         match expr {
             Expr::Assign { name, value } => {
-                self.resolve_expr(value);
-                self.resolve_local(expr, name);
-                None
-            }
-            _ => None,
-        }
-    }
-
-    fn visit_literal_expr(&mut self, _expr: &Expr) -> Option<Value> {
-        None
-    }
-
-    fn visit_grouping_expr(&mut self, expr: &Expr) -> Option<Value> {
-        match expr {
-            Expr::Grouping { expression } => {
-                self.resolve_expr(expression);
-            }
-            _ => {}
-        }
-        None
-    }
-
-    fn visit_unary_expr(&mut self, expr: &Expr) -> Option<Value> {
-        match expr {
-            Expr::Unary { right, .. } => {
-                self.resolve_expr(right);
-            }
-            _ => {}
-        }
-        None
-    }
-
-    fn visit_binary_expr(&mut self, expr: &Expr) -> Option<Value> {
-        match expr {
-            Expr::Binary { left, right, .. } => {
-                self.resolve_expr(left);
-                return self.resolve_expr(right);
-            }
-            _ => {}
-        }
-        None
-    }
-
-    fn visit_call_expr(&mut self, expr: &Expr) -> Option<Value> {
-        match expr {
-            Expr::Call {
-                callee, arguments, ..
-            } => {
-                self.resolve_expr(callee);
-                for arg in arguments {
-                    self.resolve_expr(&Box::new(arg.clone()));
+                self.resolve_expr(value)?;
+                if !self.resolve_local(expr, name) {
+                    self.global_assignments.push(name.clone());
                 }
+                Ok(None)
             }
-            _ => {}
+            _ => Ok(None),
         }
-        None
     }
 
-    fn visit_get_expr(&mut self, expr: &Expr) -> Option<Value> {
-        if let Expr::Get { object, name: _ } = expr {
-            self.resolve_expr(object);
-            return None;
-        }
-        None
-    }
-
-    fn visit_variable_expr(&mut self, expr: &Expr) -> Option<Value> {
-        if !self.scopes.is_empty() {
-            let scope = self.scopes.last().unwrap();
-            match expr {
-                Expr::Variable { name } => {
-                    if let Some(defined) = scope.get(&name.lexeme) {
-                        if !defined {
-                            panic!("Can't read local variable in its own initializer.");
-                        }
-                    }
-                    self.resolve_local(expr, &name);
-                }
-                _ => {}
-            }
+    fn visit_compound_assign_expr(&mut self, expr: &Expr) -> EvalResult {
+        if let Expr::CompoundAssign { target, value, .. } = expr {
+            self.resolve_target(target)?;
+            self.resolve_expr(value)?;
         }
-        None
+        Ok(None)
     }
 
-    fn visit_logical_expr(&mut self, expr: &Expr) -> Option<Value> {
-        match expr {
-            Expr::Logical { left, right, .. } => {
-                self.resolve_expr(left);
-                return self.resolve_expr(right);
-            }
-            _ => {}
+    fn visit_increment_expr(&mut self, expr: &Expr) -> EvalResult {
+        if let Expr::Increment { target, .. } = expr {
+            self.resolve_target(target)?;
         }
-        None
+        Ok(None)
     }
 
-    fn visit_set_expr(&mut self, expr: &Expr) -> Option<Value> {
-        match expr {
-            Expr::Set {
-                object,
-                name: _,
-                value,
-            } => {
-                self.resolve_expr(object);
-                self.resolve_expr(value);
-                return None;
+    fn visit_variable_expr(&mut self, expr: &Expr) -> EvalResult {
+        if let Expr::Variable { name, .. } = expr {
+            if let Some(scope) = self.scopes.last() {
+                if scope.get(&name.lexeme) == Some(&false) {
+                    self.error(
+                        name,
+                        ErrorCode::ReadInOwnInitializer,
+                        "Can't read local variable in its own initializer.",
+                    );
+                }
+                self.mark_read(name);
             }
-            _ => {}
+            self.resolve_local(expr, name);
         }
-        None
+        Ok(None)
     }
 
-    fn visit_super_expr(&mut self, expr: &Expr) -> Option<Value> {
-        if self.current_class == ClassType::None {
-            panic!("Can't use 'super' outside of a class.");
-        } else if self.current_class != ClassType::Subclass {
-            panic!("Can't use 'super' in a class with no superclass.");
-        }
+    fn visit_super_expr(&mut self, expr: &Expr) -> EvalResult {
         if let Expr::Super {
             ref keyword,
-            method: _,
+            ref method,
         } = expr
         {
+            if method.type_ == TokenType::Super
+                && self.current_class == ClassType::Subclass
+                && self.current_function != FunctionType::Initializer
+            {
+                self.error(
+                    keyword,
+                    ErrorCode::SuperCallOutsideInitializer,
+                    "Can't use 'super(...)' outside of an initializer.",
+                );
+            }
+            if self.current_class == ClassType::None {
+                self.error(
+                    keyword,
+                    ErrorCode::SuperOutsideClass,
+                    "Can't use 'super' outside of a class.",
+                );
+            } else if self.current_class != ClassType::Subclass {
+                self.error(
+                    keyword,
+                    ErrorCode::SuperWithoutSuperclass,
+                    "Can't use 'super' in a class with no superclass.",
+                );
+            }
             self.resolve_local(expr, keyword);
         }
-        None
+        Ok(None)
     }
 
-    fn visit_this_expr(&mut self, expr: &Expr) -> Option<Value> {
-        if self.current_class == ClassType::None {
-            panic!("Can't use 'this' outside of a class.");
+    fn visit_class_expr(&mut self, expr: &Expr) -> EvalResult {
+        if let Expr::Class {
+            superclass,
+            interfaces,
+            methods,
+            ..
+        } = expr
+        {
+            self.resolve_class(
+                superclass.as_deref().cloned(),
+                interfaces.clone(),
+                methods.clone(),
+            )?;
         }
+        Ok(None)
+    }
 
-        match expr {
-            Expr::This { keyword } => {
-                self.resolve_local(expr, keyword);
-                return None;
+    fn visit_this_expr(&mut self, expr: &Expr) -> EvalResult {
+        if let Expr::This { keyword } = expr {
+            if self.current_class == ClassType::None {
+                self.error(
+                    keyword,
+                    ErrorCode::ThisOutsideClass,
+                    "Can't use 'this' outside of a class.",
+                );
             }
-            _ => {}
+            self.resolve_local(expr, keyword);
         }
-        None
+        Ok(None)
     }
 }
 
 impl StmtVisitor for Resolver {
-    fn visit_block_stmt(&mut self, stmts: Vec<Stmt>) -> Option<ReturnValue> {
+    fn visit_block_stmt(&mut self, stmts: Vec<Stmt>) -> ExecResult {
         self.begin_scope();
-        let result = self.resolve(stmts.clone().into_iter().map(Some).collect());
+        self.resolve_stmts(&stmts)?;
         self.end_scope();
-        result
+        Ok(None)
     }
 
     fn visit_class_stmt(
         &mut self,
         name: Token,
         superclass: Option<Expr>,
+        interfaces: Vec<Expr>,
         methods: Vec<Stmt>,
-    ) -> Option<ReturnValue> {
-        let enclosing_class = self.current_class.clone();
-        self.current_class = ClassType::Class;
+    ) -> ExecResult {
         self.declare(name.clone());
         self.define(name.clone());
 
-        if let Some(ref expr) = superclass {
-            if let Expr::Variable { name: var } = expr {
-                if name.lexeme == var.lexeme {
-                    panic!("A class can't inherit from itself.");
-                }
-            }
-        }
-
-        if let Some(ref superclass) = superclass {
-            // Assuming stmt.superclass is an Option
-            self.current_class = ClassType::Subclass;
-            self.resolve_expr(&Box::new(superclass.clone())); // Assuming self has a resolve method
-        }
-
-        if let Some(ref _superclass) = superclass {
-            self.begin_scope();
-
-            // Peek at the top of the stack and insert "super" with true
-            if let Some(scope) = self.scopes.last_mut() {
-                scope.insert("super".to_string(), true);
+        if let Some(Expr::Variable { name: var, .. }) = &superclass {
+            if name.lexeme == var.lexeme {
+                self.error(
+                    var,
+                    ErrorCode::InheritsFromItself,
+                    "A class can't inherit from itself.",
+                );
             }
         }
 
-        self.begin_scope();
-        if let Some(current_scope) = self.scopes.last_mut() {
-            current_scope.insert("this".to_string(), true);
-        }
-
-        for method in &methods {
-            match method {
-                Stmt::Function { name, params, body } => {
-                    if name.lexeme != "init" {
-                        self.resolve_function(params.to_vec(), body.to_vec(), FunctionType::Method);
-                    } else {
-                        self.resolve_function(
-                            params.to_vec(),
-                            body.to_vec(),
-                            FunctionType::Initializer,
-                        );
-                    }
-                }
-                _ => {}
-            }
-        }
-
-        if let Some(_superclass) = superclass {
-            self.end_scope();
-        }
-        self.end_scope();
-
-        self.current_class = enclosing_class;
-        None
+        self.resolve_class(superclass, interfaces, methods)?;
+        Ok(None)
     }
 
-    fn visit_expression_stmt(&mut self, expr: Expr) -> Option<ReturnValue> {
-        self.resolve_expr(&Box::new(expr));
-        None
+    fn visit_interface_stmt(
+        &mut self,
+        name: Token,
+        _methods: Vec<(Token, Vec<Token>)>,
+    ) -> ExecResult {
+        self.declare(name.clone());
+        self.define(name);
+        Ok(None)
     }
 
     fn visit_function_stmt(
@@ -263,59 +255,82 @@ impl StmtVisitor for Resolver {
         name: Token,
         params: Vec<Token>,
         body: Vec<Stmt>,
-    ) -> Option<ReturnValue> {
+    ) -> ExecResult {
         self.declare(name.clone());
         self.define(name.clone());
-        self.resolve_function(params.clone(), body.clone(), FunctionType::Function);
-        None
+        self.resolve_function(params.clone(), body.clone(), FunctionType::Function)?;
+        Ok(None)
     }
 
-    fn visit_if_stmt(
-        &mut self,
-        condition: Expr,
-        then_branch: Box<Stmt>,
-        else_branch: Box<Option<Stmt>>,
-    ) -> Option<ReturnValue> {
-        self.resolve_expr(&Box::new(condition));
-        self.resolve_stmt(*then_branch);
-        if let Some(else_branch) = *else_branch {
-            self.resolve_stmt(else_branch);
+    fn visit_defer_stmt(&mut self, keyword: Token, body: Box<Stmt>) -> ExecResult {
+        if self.current_function == FunctionType::None {
+            self.error(
+                &keyword,
+                ErrorCode::DeferOutsideFunction,
+                "Can't use 'defer' outside of a function.",
+            );
         }
-        None
-    }
-
-    fn visit_print_stmt(&mut self, expr: Expr) -> Option<ReturnValue> {
-        self.resolve_expr(&Box::new(expr));
-        None
+        let enclosing_defer = std::mem::replace(&mut self.in_defer, true);
+        self.resolve_stmt(*body)?;
+        self.in_defer = enclosing_defer;
+        Ok(None)
     }
 
-    fn visit_return_stmt(&mut self, _keyword: Token, value: Option<Expr>) -> Option<ReturnValue> {
+    fn visit_return_stmt(&mut self, keyword: Token, value: Option<Expr>) -> ExecResult {
         if self.current_function == FunctionType::None {
-            panic!("Can't return from top-level code.");
+            self.error(
+                &keyword,
+                ErrorCode::ReturnAtTopLevel,
+                "Can't return from top-level code.",
+            );
+        } else if self.in_defer {
+            self.error(
+                &keyword,
+                ErrorCode::ReturnInDefer,
+                "Can't return from inside 'defer'.",
+            );
         }
 
-        if value.is_some() {
+        if let Some(value) = value {
             if self.current_function == FunctionType::Initializer {
-                panic!("Can't return a value from an initializer.");
+                self.error(
+                    &keyword,
+                    ErrorCode::ReturnValueFromInitializer,
+                    "Can't return a value from an initializer.",
+                );
             }
-            self.resolve_expr(&Box::new(value.unwrap()));
+            self.resolve_expr(&value)?;
         }
-        None
+        Ok(None)
+    }
+
+    fn visit_using_stmt(
+        &mut self,
+        _keyword: Token,
+        name: Token,
+        initializer: Expr,
+        body: Box<Stmt>,
+    ) -> ExecResult {
+        // The resource is evaluated outside the scope that holds it
+        self.resolve_expr(&Box::new(initializer))?;
+        self.begin_scope();
+        self.declare(name.clone());
+        self.define(name);
+        self.resolve_stmt(*body)?;
+        self.end_scope();
+        Ok(None)
     }
 
-    fn visit_var_stmt(&mut self, name: Token, initializer: Option<Expr>) -> Option<ReturnValue> {
+    fn visit_var_stmt(&mut self, name: Token, initializer: Option<Expr>) -> ExecResult {
         self.declare(name.clone());
         if initializer.is_some() {
-            self.resolve_expr(&Box::new(initializer.clone().unwrap()));
+            self.resolve_expr(&Box::new(initializer.clone().unwrap()))?;
         }
         self.define(name.clone());
-        None
-    }
-
-    fn visit_while_stmt(&mut self, condition: Expr, body: Box<Stmt>) -> Option<ReturnValue> {
-        self.resolve_expr(&Box::new(condition));
-        self.resolve_stmt(*body);
-        None
+        if let Some(unread) = self.unread.last_mut() {
+            unread.insert(name.lexeme.clone(), name);
+        }
+        Ok(None)
     }
 }
 
@@ -324,46 +339,163 @@ impl Resolver {
         Resolver {
             interpreter,
             scopes: vec![],
+            unread: vec![],
+            declarations: vec![],
+            globals: HashMap::new(),
+            global_uses: vec![],
+            global_assignments: vec![],
+            bindings: Bindings::default(),
+            errors: vec![],
+            warnings: vec![],
             current_function: FunctionType::None,
             current_class: ClassType::None,
+            in_defer: false,
         }
     }
 
-    pub fn resolve(&mut self, stmts: Vec<Option<Stmt>>) -> Option<ReturnValue> {
+    /// Resolves `stmts`, failing with every error found, in source order.
+    pub fn resolve(&mut self, stmts: &[Stmt]) -> Result<(), LoxError> {
+        self.resolve_stmts(stmts)?;
+        self.check_global_assignments();
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(LoxError::Resolve(std::mem::take(&mut self.errors)))
+        }
+    }
+
+    fn resolve_stmts(&mut self, stmts: &[Stmt]) -> Result<(), LoxError> {
         for stmt in stmts {
-            let ret = self.resolve_stmt(stmt?);
-            if ret.is_some() {
-                return ret;
+            self.resolve_stmt(stmt.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Removes and returns the warnings found so far, in source order.
+    /// Scopes report theirs as they end, innermost first, so they are
+    /// sorted here.
+    pub fn take_warnings(&mut self) -> Vec<ResolveWarning> {
+        let mut warnings = std::mem::take(&mut self.warnings);
+        warnings.sort_by_key(|warning| warning.token.offset);
+        warnings
+    }
+
+    /// Returns which declaration each name used so far refers to. Uses of
+    /// globals are matched by name, wherever the global is declared.
+    pub fn take_bindings(&mut self) -> Bindings {
+        for token in std::mem::take(&mut self.global_uses) {
+            match self.globals.get(&token.lexeme) {
+                Some(&binding) => self.bindings.add_use(binding, token),
+                None => self.bindings.add_unbound(token),
             }
         }
-        None
+        self.globals.clear();
+        let mut bindings = std::mem::take(&mut self.bindings);
+        bindings.sort();
+        bindings
     }
 
-    fn resolve_stmt(&mut self, stmt: Stmt) -> Option<ReturnValue> {
+    fn resolve_stmt(&mut self, stmt: Stmt) -> ExecResult {
         stmt.accept(self)
     }
 
-    fn resolve_expr(&mut self, expr: &Box<Expr>) -> Option<Value> {
+    fn resolve_expr(&mut self, expr: &Expr) -> EvalResult {
         expr.accept_interp(self)
     }
 
+    /// Records an error at `token`. Resolution carries on, so one run
+    /// reports every error in the program.
+    fn error(&mut self, token: &Token, code: ErrorCode, message: &str) {
+        self.errors.push(ResolveError {
+            token: token.clone(),
+            code,
+            message: message.to_string(),
+        });
+    }
+
     fn begin_scope(&mut self) {
         self.scopes.push(HashMap::new());
+        self.unread.push(HashMap::new());
+        self.declarations.push(HashMap::new());
     }
 
     fn end_scope(&mut self) {
         self.scopes.pop();
+        self.declarations.pop();
+        let mut unread: Vec<Token> = self
+            .unread
+            .pop()
+            .unwrap_or_default()
+            .into_values()
+            .collect();
+        unread.sort_by_key(|token| token.offset);
+        for token in unread {
+            // A leading underscore marks a variable as deliberately unused
+            if token.lexeme.starts_with('_') {
+                continue;
+            }
+            self.warnings.push(ResolveWarning {
+                lint: Lint::UnusedLocal,
+                message: format!("local variable '{}' is never used", token.lexeme),
+                token,
+            });
+        }
+    }
+
+    /// Records a read of the innermost local called `name`.
+    fn mark_read(&mut self, name: &Token) {
+        let declared = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(&name.lexeme));
+        if let Some(index) = declared {
+            self.unread[index].remove(&name.lexeme);
+        }
     }
 
     fn declare(&mut self, name: Token) {
         if self.scopes.is_empty() {
+            // Declaring a global again assigns to the same variable
+            match self.globals.get(&name.lexeme) {
+                Some(&binding) => self.bindings.add_use(binding, name),
+                None => {
+                    let binding = self.bindings.declare(name.clone());
+                    self.globals.insert(name.lexeme, binding);
+                }
+            }
             return;
         }
         let scope = self.scopes.last_mut().unwrap();
         if scope.contains_key(&name.lexeme) {
-            panic!("Variable with this name already declared in this scope.");
+            self.error(
+                &name,
+                ErrorCode::AlreadyDeclared,
+                "Already a variable with this name in this scope.",
+            );
+            // Keep the first declaration's record
+            return;
         }
         scope.insert(name.lexeme.clone(), false);
+        let enclosing = &self.scopes[..self.scopes.len() - 1];
+        if !name.lexeme.starts_with('_')
+            && enclosing
+                .iter()
+                .any(|scope| scope.contains_key(&name.lexeme))
+        {
+            self.warnings.push(ResolveWarning {
+                lint: Lint::Shadowing,
+                message: format!(
+                    "local variable '{}' shadows one in an enclosing scope",
+                    name.lexeme
+                ),
+                token: name.clone(),
+            });
+        }
+        let binding = self.bindings.declare(name.clone());
+        self.declarations
+            .last_mut()
+            .unwrap()
+            .insert(name.lexeme, binding);
     }
 
     fn define(&mut self, name: Token) {
@@ -374,13 +506,116 @@ impl Resolver {
         scope.insert(name.lexeme.clone(), true);
     }
 
-    fn resolve_local(&mut self, expr: &Expr, name: &Token) {
-        for (i, scope) in self.scopes.iter().enumerate().rev() {
+    /// Resolves `name` to the innermost scope declaring it, returning
+    /// whether any does. Otherwise it's left to be looked up as a global.
+    /// Resolves the target of `+=` or `++`, which is both read and assigned.
+    fn resolve_target(&mut self, target: &Expr) -> EvalResult {
+        self.resolve_expr(target)?;
+        if let Expr::Variable { name, .. } = target {
+            if !self
+                .scopes
+                .iter()
+                .any(|scope| scope.contains_key(&name.lexeme))
+            {
+                self.global_assignments.push(name.clone());
+            }
+        }
+        Ok(None)
+    }
+
+    fn resolve_local(&mut self, expr: &Expr, name: &Token) -> bool {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
             if scope.contains_key(&name.lexeme) {
-                self.interpreter.borrow_mut().resolve(expr, i);
-                return;
+                self.interpreter.borrow_mut().resolve(expr, depth);
+                // `this` and `super` are bound without a declaration
+                let index = self.scopes.len() - 1 - depth;
+                if let Some(&binding) = self.declarations[index].get(&name.lexeme) {
+                    self.bindings.add_use(binding, name.clone());
+                }
+                return true;
             }
         }
+        if name.type_ == TokenType::Identifier {
+            self.global_uses.push(name.clone());
+        }
+        false
+    }
+
+    /// Warns about assignments to globals that the program doesn't declare
+    /// anywhere and that aren't defined already, once every global
+    /// declaration has been seen.
+    fn check_global_assignments(&mut self) {
+        let interpreter = self.interpreter.borrow();
+        let defined = interpreter.globals.borrow();
+        for name in std::mem::take(&mut self.global_assignments) {
+            if !self.globals.contains_key(&name.lexeme)
+                && !defined.values.contains_key(&name.lexeme)
+            {
+                self.warnings.push(ResolveWarning {
+                    lint: Lint::UndeclaredAssignment,
+                    message: format!("assignment to undeclared variable '{}'", name.lexeme),
+                    token: name,
+                });
+            }
+        }
+    }
+
+    /// Resolves the superclass and methods shared by class declarations and
+    /// class expressions.
+    fn resolve_class(
+        &mut self,
+        superclass: Option<Expr>,
+        interfaces: Vec<Expr>,
+        methods: Vec<Stmt>,
+    ) -> Result<(), LoxError> {
+        let enclosing_class = self.current_class.clone();
+        self.current_class = ClassType::Class;
+
+        if let Some(ref superclass) = superclass {
+            // Assuming stmt.superclass is an Option
+            self.current_class = ClassType::Subclass;
+            self.resolve_expr(&Box::new(superclass.clone()))?; // Assuming self has a resolve method
+        }
+        // Checked in the scope around the class, like the superclass
+        for interface in &interfaces {
+            interface.accept_interp(self)?;
+        }
+
+        if let Some(ref _superclass) = superclass {
+            self.begin_scope();
+
+            // Peek at the top of the stack and insert "super" with true
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.insert("super".to_string(), true);
+            }
+        }
+
+        self.begin_scope();
+        if let Some(current_scope) = self.scopes.last_mut() {
+            current_scope.insert("this".to_string(), true);
+        }
+
+        for method in &methods {
+            if let Stmt::Function { name, params, body } = method {
+                if name.lexeme != "init" {
+                    self.resolve_function(params.to_vec(), body.to_vec(), FunctionType::Method)?;
+                } else {
+                    self.resolve_function(
+                        params.to_vec(),
+                        body.to_vec(),
+                        FunctionType::Initializer,
+                    )?;
+                }
+            }
+        }
+
+        if let Some(_superclass) = superclass {
+            self.end_scope();
+        }
+        self.end_scope();
+
+        self.current_class = enclosing_class;
+        Ok(())
     }
 
     fn resolve_function(
@@ -388,16 +623,20 @@ impl Resolver {
         params: Vec<Token>,
         body: Vec<Stmt>,
         function_type: FunctionType,
-    ) {
+    ) -> Result<(), LoxError> {
         let enclosing_function = self.current_function.clone();
         self.current_function = function_type;
+        // A function declared in a deferred statement returns from itself
+        let enclosing_defer = std::mem::replace(&mut self.in_defer, false);
         self.begin_scope();
         for param in params {
             self.declare(param.clone());
             self.define(param.clone());
         }
-        self.resolve(body.clone().into_iter().map(Some).collect());
+        self.resolve_stmts(&body)?;
         self.end_scope();
         self.current_function = enclosing_function;
+        self.in_defer = enclosing_defer;
+        Ok(())
     }
 }
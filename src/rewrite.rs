@@ -0,0 +1,360 @@
+//! Rewriting syntax trees, for codemods and for passes such as an optimizer
+//! that turn one program into another.
+//!
+//! A `Rewriter` gets every node by value and returns the node to put in its
+//! place. The defaults rebuild each node from its rewritten children, so a
+//! rewriter only overrides the kinds of node it changes. An override picks
+//! the order: call `map_expr` or `map_stmt` first to rewrite the children
+//! before looking at the node, or afterwards to see the node as written.
+
+use crate::error::LoxError;
+use crate::expr::Expr;
+use crate::parser::Parser;
+use crate::printer;
+use crate::scanner::Scanner;
+use crate::stmt::Stmt;
+
+pub trait Rewriter {
+    fn rewrite_expr(&mut self, expr: Expr) -> Expr {
+        map_expr(self, expr)
+    }
+
+    fn rewrite_stmt(&mut self, stmt: Stmt) -> Stmt {
+        map_stmt(self, stmt)
+    }
+}
+
+/// Rewrites each statement of a program.
+pub fn rewrite_program<R: Rewriter + ?Sized>(rewriter: &mut R, stmts: Vec<Stmt>) -> Vec<Stmt> {
+    stmts
+        .into_iter()
+        .map(|stmt| rewriter.rewrite_stmt(stmt))
+        .collect()
+}
+
+/// Parses `source`, rewrites it, and prints the result as Lox source. The
+/// output is laid out by `printer`, so comments and formatting are lost.
+pub fn rewrite_source<R: Rewriter + ?Sized>(
+    rewriter: &mut R,
+    source: &str,
+) -> Result<String, LoxError> {
+    let tokens = Scanner::new(source.to_string()).scan_tokens()?;
+    let stmts = Parser::new(tokens).parse()?;
+    Ok(printer::print_program(&rewrite_program(rewriter, stmts)))
+}
+
+/// Rebuilds `expr` with each of its subexpressions, and the methods of a
+/// class expression, passed through `rewriter`.
+pub fn map_expr<R: Rewriter + ?Sized>(rewriter: &mut R, expr: Expr) -> Expr {
+    let mut rewrite = |expr: Box<Expr>| Box::new(rewriter.rewrite_expr(*expr));
+    match expr {
+        Expr::Assign { name, value } => Expr::Assign {
+            name,
+            value: rewrite(value),
+        },
+        Expr::CompoundAssign {
+            target,
+            operator,
+            value,
+        } => Expr::CompoundAssign {
+            target: rewrite(target),
+            operator,
+            value: rewrite(value),
+        },
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        } => Expr::Binary {
+            left: rewrite(left),
+            operator,
+            right: rewrite(right),
+        },
+        Expr::Grouping { expression } => Expr::Grouping {
+            expression: rewrite(expression),
+        },
+        Expr::Set {
+            object,
+            name,
+            value,
+        } => Expr::Set {
+            object: rewrite(object),
+            name,
+            value: rewrite(value),
+        },
+        Expr::Unary { operator, right } => Expr::Unary {
+            operator,
+            right: rewrite(right),
+        },
+        Expr::Logical {
+            left,
+            operator,
+            right,
+        } => Expr::Logical {
+            left: rewrite(left),
+            operator,
+            right: rewrite(right),
+        },
+        Expr::Ternary {
+            condition,
+            then_branch,
+            else_branch,
+        } => Expr::Ternary {
+            condition: rewrite(condition),
+            then_branch: rewrite(then_branch),
+            else_branch: rewrite(else_branch),
+        },
+        Expr::Increment {
+            operator,
+            target,
+            prefix,
+        } => Expr::Increment {
+            operator,
+            target: rewrite(target),
+            prefix,
+        },
+        Expr::Call {
+            callee,
+            paren,
+            arguments,
+        } => Expr::Call {
+            callee: rewrite(callee),
+            paren,
+            arguments: arguments
+                .into_iter()
+                .map(|argument| rewriter.rewrite_expr(argument))
+                .collect(),
+        },
+        Expr::Get { object, name } => Expr::Get {
+            object: rewrite(object),
+            name,
+        },
+        Expr::Class {
+            keyword,
+            superclass,
+            interfaces,
+            methods,
+        } => Expr::Class {
+            keyword,
+            superclass: superclass.map(rewrite),
+            interfaces: interfaces
+                .into_iter()
+                .map(|interface| rewriter.rewrite_expr(interface))
+                .collect(),
+            methods: rewrite_program(rewriter, methods),
+        },
+        Expr::ListLiteral { bracket, elements } => Expr::ListLiteral {
+            bracket,
+            elements: elements
+                .into_iter()
+                .map(|element| rewriter.rewrite_expr(element))
+                .collect(),
+        },
+        Expr::Index {
+            object,
+            bracket,
+            index,
+        } => Expr::Index {
+            object: rewrite(object),
+            bracket,
+            index: rewrite(index),
+        },
+        Expr::IndexSet {
+            object,
+            bracket,
+            index,
+            value,
+        } => Expr::IndexSet {
+            object: rewrite(object),
+            bracket,
+            index: rewrite(index),
+            value: rewrite(value),
+        },
+        Expr::Literal { .. } | Expr::Variable { .. } | Expr::Super { .. } | Expr::This { .. } => {
+            expr
+        }
+    }
+}
+
+/// Rebuilds `stmt` with each of its expressions and nested statements passed
+/// through `rewriter`.
+pub fn map_stmt<R: Rewriter + ?Sized>(rewriter: &mut R, stmt: Stmt) -> Stmt {
+    match stmt {
+        Stmt::Block(stmts) => Stmt::Block(rewrite_program(rewriter, stmts)),
+        Stmt::Class {
+            name,
+            superclass,
+            interfaces,
+            methods,
+        } => Stmt::Class {
+            name,
+            superclass: superclass.map(|superclass| rewriter.rewrite_expr(superclass)),
+            interfaces: interfaces
+                .into_iter()
+                .map(|interface| rewriter.rewrite_expr(interface))
+                .collect(),
+            methods: rewrite_program(rewriter, methods),
+        },
+        Stmt::Defer { keyword, body } => Stmt::Defer {
+            keyword,
+            body: Box::new(rewriter.rewrite_stmt(*body)),
+        },
+        Stmt::Expression(expr) => Stmt::Expression(rewriter.rewrite_expr(expr)),
+        Stmt::Function { name, params, body } => Stmt::Function {
+            name,
+            params,
+            body: rewrite_program(rewriter, body),
+        },
+        Stmt::Macro { name, params, body } => Stmt::Macro {
+            name,
+            params,
+            body: rewrite_program(rewriter, body),
+        },
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => Stmt::If {
+            condition: rewriter.rewrite_expr(condition),
+            then_branch: Box::new(rewriter.rewrite_stmt(*then_branch)),
+            else_branch: Box::new(else_branch.map(|stmt| rewriter.rewrite_stmt(stmt))),
+        },
+        Stmt::Print(expr) => Stmt::Print(rewriter.rewrite_expr(expr)),
+        Stmt::Return { keyword, value } => Stmt::Return {
+            keyword,
+            value: value.map(|value| rewriter.rewrite_expr(value)),
+        },
+        Stmt::Using {
+            keyword,
+            name,
+            initializer,
+            body,
+        } => Stmt::Using {
+            keyword,
+            name,
+            initializer: rewriter.rewrite_expr(initializer),
+            body: Box::new(rewriter.rewrite_stmt(*body)),
+        },
+        Stmt::Var { name, initializer } => Stmt::Var {
+            name,
+            initializer: initializer.map(|initializer| rewriter.rewrite_expr(initializer)),
+        },
+        Stmt::While { condition, body } => Stmt::While {
+            condition: rewriter.rewrite_expr(condition),
+            body: Box::new(rewriter.rewrite_stmt(*body)),
+        },
+        stmt @ (Stmt::Comment { .. } | Stmt::Interface { .. }) => stmt,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::Token;
+    use crate::token_type::TokenType;
+
+    /// Renames every function, and every variable, called `from`.
+    struct Rename {
+        from: &'static str,
+        to: &'static str,
+    }
+
+    impl Rename {
+        fn token(&self, token: Token) -> Token {
+            if token.lexeme != self.from {
+                return token;
+            }
+            Token {
+                lexeme: self.to.to_string(),
+                ..token
+            }
+        }
+    }
+
+    impl Rewriter for Rename {
+        fn rewrite_expr(&mut self, expr: Expr) -> Expr {
+            match map_expr(self, expr) {
+                Expr::Variable { name, slot } => Expr::Variable {
+                    name: self.token(name),
+                    slot,
+                },
+                Expr::Assign { name, value } => Expr::Assign {
+                    name: self.token(name),
+                    value,
+                },
+                expr => expr,
+            }
+        }
+
+        fn rewrite_stmt(&mut self, stmt: Stmt) -> Stmt {
+            match map_stmt(self, stmt) {
+                Stmt::Function { name, params, body } => Stmt::Function {
+                    name: self.token(name),
+                    params,
+                    body,
+                },
+                stmt => stmt,
+            }
+        }
+    }
+
+    /// Adds up number literals, working from the leaves so that sums of
+    /// sums fold too.
+    struct FoldAdditions;
+
+    impl Rewriter for FoldAdditions {
+        fn rewrite_expr(&mut self, expr: Expr) -> Expr {
+            match map_expr(self, expr) {
+                Expr::Binary {
+                    left,
+                    operator,
+                    right,
+                } if operator.type_ == TokenType::Plus => match (*left, *right) {
+                    (Expr::Literal { value: a }, Expr::Literal { value: b })
+                        if a.type_ == TokenType::Number && b.type_ == TokenType::Number =>
+                    {
+                        let sum: f64 =
+                            a.lexeme.parse::<f64>().unwrap() + b.lexeme.parse::<f64>().unwrap();
+                        Expr::Literal {
+                            value: Token {
+                                lexeme: sum.to_string(),
+                                ..a
+                            },
+                        }
+                    }
+                    (left, right) => Expr::Binary {
+                        left: Box::new(left),
+                        operator,
+                        right: Box::new(right),
+                    },
+                },
+                expr => expr,
+            }
+        }
+    }
+
+    #[test]
+    fn renames_a_function_across_a_file() {
+        let source = "fun area(w, h) { return w * h; }
+                      class Room { size() { return area(this.w, this.h); } }
+                      print area(2, 3);";
+        let mut rename = Rename {
+            from: "area",
+            to: "rectangleArea",
+        };
+        assert_eq!(
+            rewrite_source(&mut rename, source).unwrap(),
+            "fun rectangleArea(w, h) {\n  return w * h;\n}\n\
+             class Room {\n  size() {\n    return rectangleArea(this.w, this.h);\n  }\n}\n\
+             print rectangleArea(2, 3);\n"
+        );
+    }
+
+    #[test]
+    fn children_can_be_rewritten_first() {
+        assert_eq!(
+            rewrite_source(&mut FoldAdditions, "print 1 + 2 + 3; print a + 1 + 2;").unwrap(),
+            "print 6;\nprint a + 1 + 2;\n"
+        );
+    }
+}
@@ -1,16 +1,77 @@
+use crate::error::ErrorCode;
 use crate::token::Token;
+use std::fmt;
 
 #[derive(Debug)]
 pub struct RuntimeError {
     pub token: Token,
+    pub code: ErrorCode,
     pub message: String,
+    /// The calls in progress when the error happened, innermost first. A
+    /// boxed slice rather than a `Vec` keeps `LoxError` small.
+    pub trace: Box<[CallFrame]>,
 }
 
 impl RuntimeError {
-    pub fn new(token: Token, message: &str) -> Self {
+    pub fn new(token: Token, code: ErrorCode, message: &str) -> Self {
         Self {
             token,
+            code,
             message: message.to_string(),
+            trace: Box::new([]),
+        }
+    }
+}
+
+/// A call in progress: the function or class called, and the closing
+/// parenthesis of the call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallFrame {
+    pub function: String,
+    pub paren: Token,
+}
+
+impl fmt::Display for CallFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {} (line {})", self.function, self.paren.line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LoxError;
+    use crate::Lox;
+
+    #[test]
+    fn runtime_errors_record_the_calls_in_progress() {
+        let source = "fun inner() {\n  return -\"x\";\n}\n\
+                      fun outer() { return inner(); }\n\
+                      class Box { init() { outer(); } }\n\
+                      Box();";
+        let frames = match Lox::new("").try_run(source) {
+            Err(LoxError::Runtime(error)) => error.trace,
+            other => panic!("Expected a runtime error, got {:?}", other),
+        };
+        let frames: Vec<String> = frames.iter().map(CallFrame::to_string).collect();
+        assert_eq!(
+            frames,
+            ["at inner (line 4)", "at outer (line 5)", "at Box (line 6)"]
+        );
+
+        // Functions wrapped by natives keep their names
+        let source = "fun sq(x) { return -\"x\"; }\nmemoize(sq)(2);";
+        match Lox::new("").try_run(source) {
+            Err(LoxError::Runtime(error)) => {
+                assert_eq!(error.trace[0].to_string(), "at sq (line 2)")
+            }
+            other => panic!("Expected a runtime error, got {:?}", other),
+        }
+
+        // Errors outside any call have no trace
+        match Lox::new("").try_run("-\"x\";") {
+            Err(LoxError::Runtime(error)) => assert!(error.trace.is_empty()),
+            other => panic!("Expected a runtime error, got {:?}", other),
         }
     }
 }
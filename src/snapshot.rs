@@ -0,0 +1,219 @@
+use crate::environment::Environment;
+use crate::expr::Expr;
+use crate::lox_class::LoxClass;
+use crate::lox_function::LoxFunction;
+use crate::lox_interface::LoxInterface;
+use crate::native_functions::NUMBER_CONSTANTS;
+use crate::prelude;
+use crate::printer;
+use crate::scanner;
+use crate::stmt::Stmt;
+use crate::token_type::TokenType;
+use crate::value::Value;
+
+/// Renders the global environment as Lox source that recreates it when run.
+///
+/// Numbers, strings, booleans, nil, and top-level functions, interfaces,
+/// and classes are kept. Instances, natives, the prelude's functions, and
+/// methods (which need their `this`) are skipped, and functions lose
+/// whatever they captured from enclosing scopes other than the globals.
+pub fn snapshot(globals: &Environment) -> String {
+    let mut globals_by_name: Vec<(&String, &Option<Value>)> = globals.values.iter().collect();
+    globals_by_name.sort_by_key(|(name, _)| *name);
+
+    let mut data = String::new();
+    let mut functions = String::new();
+    let mut interfaces = String::new();
+    let mut classes: Vec<(&String, &LoxClass)> = Vec::new();
+    let mut aliases = String::new();
+    let prelude = prelude::statements();
+
+    for (name, value) in globals_by_name {
+        // The interpreter defines these itself
+        if NUMBER_CONSTANTS
+            .iter()
+            .any(|(constant, _)| constant == name)
+        {
+            continue;
+        }
+        match value {
+            None => data.push_str(&format!("var {};\n", name)),
+            Some(Value::Callable(callable)) => {
+                if let Some(function) = callable.as_any().downcast_ref::<LoxFunction>() {
+                    // As with the natives, every interpreter starts with these
+                    if prelude.contains(&function.declaration) || binds_this(function) {
+                        continue;
+                    }
+                    if let Stmt::Function { name: declared, .. } = &function.declaration {
+                        // An alias of a global function only needs the binding
+                        if &declared.lexeme == name
+                            || !globals.values.contains_key(&declared.lexeme)
+                        {
+                            functions.push_str(&printer::print_stmt(&function.declaration, 0));
+                            functions.push('\n');
+                        }
+                        if &declared.lexeme != name {
+                            aliases.push_str(&format!("var {} = {};\n", name, declared.lexeme));
+                        }
+                    }
+                } else if let Some(class) = callable.as_any().downcast_ref::<LoxClass>() {
+                    classes.push((name, class));
+                } else if let Some(interface) = callable.as_any().downcast_ref::<LoxInterface>() {
+                    // Declared before the classes, which check against them
+                    if interface.name() == name || !globals.values.contains_key(interface.name()) {
+                        interfaces.push_str(&printer::print_stmt(&interface.declaration, 0));
+                        interfaces.push('\n');
+                    }
+                    if interface.name() != name {
+                        aliases.push_str(&format!("var {} = {};\n", name, interface.name()));
+                    }
+                }
+            }
+            Some(Value::Instance(_)) | Some(Value::NativeInstance(_)) | Some(Value::List(_)) => {}
+            Some(value) => {
+                if let Some(literal) = literal(value) {
+                    data.push_str(&format!("var {} = {};\n", name, literal));
+                }
+            }
+        }
+    }
+
+    let mut class_aliases = String::new();
+    let classes = order_classes(classes, &mut class_aliases);
+    data + &functions + &interfaces + &aliases + &classes + &class_aliases
+}
+
+/// Emits classes so that each superclass is declared before its subclasses.
+fn order_classes(mut pending: Vec<(&String, &LoxClass)>, aliases: &mut String) -> String {
+    let mut result = String::new();
+    let mut declared: Vec<String> = Vec::new();
+    while !pending.is_empty() {
+        let position = pending
+            .iter()
+            .position(|(_, class)| match superclass_name(class) {
+                Some(superclass) => {
+                    declared.contains(&superclass)
+                        || !pending
+                            .iter()
+                            .any(|(_, other)| class_name(other) == Some(superclass.clone()))
+                }
+                None => true,
+            })
+            .unwrap_or(0);
+        let (name, class) = pending.remove(position);
+        let Some(declared_name) = class_name(class) else {
+            // An anonymous class is bound straight to its global
+            result.push_str(&format!(
+                "var {} = {};\n",
+                name,
+                printer::print_stmt(&class.declaration, 0)
+            ));
+            continue;
+        };
+        result.push_str(&printer::print_stmt(&class.declaration, 0));
+        result.push('\n');
+        if &declared_name != name {
+            aliases.push_str(&format!("var {} = {};\n", name, declared_name));
+        }
+        declared.push(declared_name);
+    }
+    result
+}
+
+/// Whether `function` is a method, or is declared inside one, so that it
+/// uses a `this` a top-level declaration couldn't have.
+fn binds_this(function: &LoxFunction) -> bool {
+    let mut environment = Some(function.closure.clone());
+    while let Some(current) = environment {
+        if current.borrow().values.contains_key("this") {
+            return true;
+        }
+        environment = current.borrow().enclosing.clone();
+    }
+    false
+}
+
+fn class_name(class: &LoxClass) -> Option<String> {
+    match &class.declaration {
+        Stmt::Class { name, .. } if name.type_ != TokenType::Class => Some(name.lexeme.clone()),
+        _ => None,
+    }
+}
+
+fn superclass_name(class: &LoxClass) -> Option<String> {
+    match &class.declaration {
+        Stmt::Class {
+            superclass: Some(Expr::Variable { name, .. }),
+            ..
+        } => Some(name.lexeme.clone()),
+        _ => None,
+    }
+}
+
+/// Renders a data value as an expression that evaluates back to it.
+fn literal(value: &Value) -> Option<String> {
+    match value {
+        Value::Boolean(b) => Some(b.to_string()),
+        Value::Number(num) => {
+            if num.is_nan() {
+                Some("0 / 0".to_string())
+            } else if num.is_infinite() {
+                Some(if *num > 0.0 { "1 / 0" } else { "-1 / 0" }.to_string())
+            } else if *num < 0.0 {
+                Some(format!("-{}", -num))
+            } else {
+                Some(num.to_string())
+            }
+        }
+        // Strings keep their surrounding quotes
+        Value::String(s) => Some(format!("\"{}\"", scanner::escape(&s[1..s.len() - 1]))),
+        Value::Nil() => Some("nil".to_string()),
+        // Integer literals this large read back as big integers
+        #[cfg(feature = "lox-bigint")]
+        Value::BigInt(big) => Some(if big.sign() == num_bigint::Sign::Minus {
+            format!("-{}", -big)
+        } else {
+            big.to_string()
+        }),
+        // Reads back as a decimal when the restoring run is in decimal mode
+        Value::Decimal(decimal) => Some(decimal.to_string()),
+        Value::Callable(_) | Value::Instance(_) | Value::NativeInstance(_) | Value::List(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Lox;
+
+    #[test]
+    fn snapshot_recreates_globals() {
+        let mut lox = Lox::new("");
+        lox.run(
+            "var count = 3; var name = \"lox\"; var ratio = -0.5; var empty;
+             var quoted = \"say \\\"hi\\\"\\n\";
+             fun twice(n) { return n * 2; }
+             var alias = twice;
+             interface Greeter { greet(); }
+             class Base implements Greeter { greet() { return \"hi\"; } }
+             class Derived < Base { init(x) { this.x = x; } }
+             var Anonymous = class < Derived {};
+             var bound = Derived(1).greet;",
+        );
+        let snapshot = lox.snapshot();
+        assert!(snapshot.contains("var count = 3;\n"));
+        assert!(snapshot.contains("var name = \"lox\";\n"));
+        assert!(snapshot.contains("var ratio = -0.5;\n"));
+        assert!(snapshot.contains("var empty;\n"));
+        assert!(snapshot.contains("var quoted = \"say \\\"hi\\\"\\n\";\n"));
+        assert!(snapshot.contains("var alias = twice;\n"));
+        assert!(!snapshot.contains("var bound"));
+        assert!(!snapshot.contains("INFINITY"));
+        assert!(snapshot.find("class Base").unwrap() < snapshot.find("class Derived").unwrap());
+        assert!(snapshot.find("interface Greeter").unwrap() < snapshot.find("class Base").unwrap());
+        assert!(snapshot.contains("var Anonymous = class < Derived {\n};\n"));
+
+        let mut restored = Lox::new("");
+        assert!(restored.try_run(&snapshot).is_ok());
+        assert_eq!(restored.snapshot(), snapshot);
+    }
+}
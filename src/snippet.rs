@@ -0,0 +1,70 @@
+//! Source excerpts printed under static errors, pointing at the offending
+//! token the way rustc does:
+//!
+//! ```text
+//! [line 2] Error at '=': Expect variable name.
+//!   2 | var = 1;
+//!     |     ^
+//! ```
+
+/// Renders the line of `source` holding the text `lexeme` at byte `offset`,
+/// with a `^~~` caret under it. Returns `None` if `lexeme` isn't there, as
+/// for tokens the parser makes up.
+pub fn render(source: &str, line: i32, offset: usize, lexeme: &str) -> Option<String> {
+    if !source.get(offset..)?.starts_with(lexeme) {
+        return None;
+    }
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |i| offset + i);
+    let text = source[line_start..line_end].trim_end_matches('\r');
+
+    // Keep tabs in the padding so the caret lines up however they display
+    let padding: String = source[line_start..offset]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    // Lexemes spanning lines are underlined to the end of the first
+    let width = lexeme
+        .split('\n')
+        .next()
+        .unwrap_or("")
+        .chars()
+        .count()
+        .max(1);
+
+    let gutter = line.to_string();
+    Some(format!(
+        "{} | {}\n{} | {}^{}",
+        gutter,
+        text,
+        " ".repeat(gutter.len()),
+        padding,
+        "~".repeat(width - 1)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_at_the_token() {
+        let source = "print 1;\nvar a = foo bar;\n";
+        let offset = source.find("bar").unwrap();
+        assert_eq!(
+            render(source, 2, offset, "bar").as_deref(),
+            Some("2 | var a = foo bar;\n  |             ^~~")
+        );
+
+        // At the end of the source, the caret goes past the last character
+        assert_eq!(
+            render("print 1", 1, 7, "").as_deref(),
+            Some("1 | print 1\n  |        ^")
+        );
+
+        // Tokens the parser made up aren't in the source
+        assert_eq!(render(source, 1, 0, "this"), None);
+    }
+}
@@ -1,22 +1,49 @@
 use crate::expr::Expr;
-use crate::interpreter::StmtVisitor;
-use crate::return_value::ReturnValue;
+use crate::interpreter::{ExecResult, StmtVisitor};
 use crate::token::Token;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub enum Stmt {
     Block(Vec<Stmt>),
+    /// A class declaration. `interfaces` are the variables named after
+    /// `implements`.
     Class {
         name: Token,
         superclass: Option<Expr>,
+        interfaces: Vec<Expr>,
         methods: Vec<Stmt>,
     },
+    /// `defer stmt`, which runs `body` when the enclosing function returns.
+    Defer {
+        keyword: Token,
+        body: Box<Stmt>,
+    },
     Expression(Expr),
     Function {
         name: Token,
         params: Vec<Token>,
         body: Vec<Stmt>,
     },
+    /// A `//` comment, kept by `Parser::with_comments` so the formatter can
+    /// print it back. `trailing` if code comes before it on its line.
+    /// Running it does nothing.
+    Comment {
+        text: Token,
+        trailing: bool,
+    },
+    /// `macro name(params) { body }`, which `macros::Macros::expand`
+    /// replaces each later use of. Running it does nothing.
+    Macro {
+        name: Token,
+        params: Vec<Token>,
+        body: Vec<Stmt>,
+    },
+    /// `interface Name { method(params); ... }`, the methods a class that
+    /// implements it must define.
+    Interface {
+        name: Token,
+        methods: Vec<(Token, Vec<Token>)>,
+    },
     If {
         condition: Expr,
         then_branch: Box<Stmt>,
@@ -27,6 +54,14 @@ pub enum Stmt {
         keyword: Token,
         value: Option<Expr>,
     },
+    /// `using (var name = initializer) body`, which calls the resource's
+    /// `close()` method however `body` exits.
+    Using {
+        keyword: Token,
+        name: Token,
+        initializer: Expr,
+        body: Box<Stmt>,
+    },
     Var {
         name: Token,
         initializer: Option<Expr>,
@@ -38,18 +73,31 @@ pub enum Stmt {
 }
 
 impl Stmt {
-    pub fn accept(&self, visitor: &mut impl StmtVisitor) -> Option<ReturnValue> {
+    pub fn accept(&self, visitor: &mut (impl StmtVisitor + ?Sized)) -> ExecResult {
         match self {
             Stmt::Block(block) => visitor.visit_block_stmt(block.clone()),
             Stmt::Class {
                 name,
                 superclass,
+                interfaces,
                 methods,
-            } => visitor.visit_class_stmt(name.clone(), superclass.clone(), methods.clone()),
+            } => visitor.visit_class_stmt(
+                name.clone(),
+                superclass.clone(),
+                interfaces.clone(),
+                methods.clone(),
+            ),
+            Stmt::Comment { .. } | Stmt::Macro { .. } => Ok(None),
+            Stmt::Defer { keyword, body } => {
+                visitor.visit_defer_stmt(keyword.clone(), body.clone())
+            }
             Stmt::Expression(expr) => visitor.visit_expression_stmt(expr.clone()),
             Stmt::Function { name, params, body } => {
                 visitor.visit_function_stmt(name.clone(), params.clone(), body.clone())
             }
+            Stmt::Interface { name, methods } => {
+                visitor.visit_interface_stmt(name.clone(), methods.clone())
+            }
             Stmt::If {
                 condition,
                 then_branch,
@@ -59,6 +107,17 @@ impl Stmt {
             Stmt::Return { keyword, value } => {
                 visitor.visit_return_stmt(keyword.clone(), value.clone())
             }
+            Stmt::Using {
+                keyword,
+                name,
+                initializer,
+                body,
+            } => visitor.visit_using_stmt(
+                keyword.clone(),
+                name.clone(),
+                initializer.clone(),
+                body.clone(),
+            ),
             Stmt::Var { name, initializer } => {
                 visitor.visit_var_stmt(name.clone(), initializer.clone())
             }
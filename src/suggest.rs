@@ -0,0 +1,92 @@
+//! Spelling suggestions for names that don't exist, such as an undefined
+//! variable or a script file that can't be found.
+
+/// Up to `limit` of `candidates` that look like typos of `name`, closest
+/// first and then in alphabetical order.
+pub fn similar_names<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+    limit: usize,
+) -> Vec<String> {
+    // Allow about one edit for every three characters
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    scored.sort();
+    scored.dedup();
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// `message` followed by `Did you mean '...'?` naming the candidate closest
+/// to `name`, if any is close enough.
+pub fn did_you_mean<'a>(
+    message: String,
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> String {
+    match similar_names(name, candidates, 1).first() {
+        Some(suggestion) => format!("{} Did you mean '{}'?", message, suggestion),
+        None => message,
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`, counted in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lox;
+
+    #[test]
+    fn undefined_names_suggest_close_matches() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+
+        let mut lox = Lox::new("");
+        let error = |lox: &mut Lox, source: &str| lox.try_run(source).unwrap_err().to_string();
+        lox.try_run("var counter = 0; class Point { init() { this.x = 1; } norm() {} }")
+            .unwrap();
+
+        assert_eq!(
+            error(&mut lox, "print countr;"),
+            "Undefined variable 'countr'. Did you mean 'counter'? [E3002]\n[line 1:7]"
+        );
+        assert_eq!(
+            error(&mut lox, "{ var total = 1; totl = 2; }"),
+            "Undefined variable 'totl'. Did you mean 'total'? [E3002]\n[line 1:18]"
+        );
+        assert_eq!(
+            error(&mut lox, "print Point().nrm;"),
+            "Undefined property 'nrm'. Did you mean 'norm'? [E3003]\n[line 1:15]"
+        );
+        assert_eq!(
+            error(&mut lox, "print Point().y;"),
+            "Undefined property 'y'. Did you mean 'x'? [E3003]\n[line 1:15]"
+        );
+        // Nothing close enough
+        assert_eq!(
+            error(&mut lox, "print elephant;"),
+            "Undefined variable 'elephant'. [E3002]\n[line 1:7]"
+        );
+    }
+}
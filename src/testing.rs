@@ -0,0 +1,89 @@
+//! Unit tests written in Lox: the `test(name, fn)` and `expectEqual(a, b)`
+//! natives, and finding the `*_test.lox` files `cargo run test` runs.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How many calls to `test` passed and failed, as the interpreter has
+/// counted them so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TestCounts {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+impl TestCounts {
+    pub fn add(&mut self, other: TestCounts) {
+        self.passed += other.passed;
+        self.failed += other.failed;
+    }
+}
+
+/// Every file under `dir` whose name ends in `_test.lox`, searching
+/// subdirectories, in path order. `dir` may also be such a file itself.
+pub fn test_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect_test_files(dir, &mut files);
+    files.sort();
+    files
+}
+
+fn collect_test_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_file() {
+        let is_test = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().ends_with("_test.lox"));
+        if is_test {
+            files.push(path.to_path_buf());
+        }
+        return;
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return;
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        collect_test_files(&entry.path(), files);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lox;
+    use std::env;
+
+    #[test]
+    fn finds_and_counts_tests() {
+        let dir = env::temp_dir().join("lox_testing_files");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        for name in ["b_test.lox", "nested/a_test.lox", "helper.lox", "test.lox"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        assert_eq!(
+            test_files(&dir),
+            [dir.join("b_test.lox"), dir.join("nested/a_test.lox")]
+        );
+
+        let output_file = dir.join("output.txt");
+        let mut lox = Lox::new(&output_file.to_string_lossy());
+        lox.try_run(
+            "fun adds() { expectEqual(1 + 1, 2); }
+             fun fails() { expectEqual(1 + 1, 3); }
+             test(\"adds\", adds);
+             test(\"fails\", fails);",
+        )
+        .unwrap();
+        assert_eq!(
+            lox.test_counts(),
+            TestCounts {
+                passed: 1,
+                failed: 1
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(&output_file).unwrap(),
+            "PASS adds\nFAIL fails: Expected 3 but got 2. [E3016] [line 2:48]\n"
+        );
+    }
+}
@@ -0,0 +1,1127 @@
+//! Workers and channels, behind the `lox-threads` feature.
+//!
+//! Values hold `Rc`s, so they never leave the thread that made them. A
+//! worker is instead a separate interpreter on a thread of its own: `spawn`
+//! sends it the program's top-level declarations as a `Program`, and its
+//! arguments as `Message`s, which copy plain data across threads.
+//! Channels carry messages the same way. Global variables aren't shared, so
+//! workers get their data from their arguments and channels, and coordinate
+//! with the mutexes and atomic counters those can also carry.
+
+use crate::callable::Callable;
+use crate::cancellation::CancellationToken;
+use crate::error::{ErrorCode, LoxError};
+use crate::interpreter::Interpreter;
+use crate::limits::{InterpreterLimits, SharedRun};
+use crate::logging::LogLevel;
+use crate::lox_class::LoxClass;
+use crate::lox_function::LoxFunction;
+use crate::lox_interface::LoxInterface;
+use crate::lox_list;
+use crate::native_instance::NativeInstance;
+use crate::prelude;
+use crate::program::Program;
+use crate::stmt::Stmt;
+use crate::value::Value;
+use crate::Lox;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+
+/// A value copied so it can be sent to another thread.
+pub enum Message {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    List(Vec<Message>),
+    Channel(Channel),
+    Mutex(LoxMutex),
+    Atomic(Atomic),
+}
+
+impl Message {
+    /// A copy of `value`, or `None` if it holds anything but numbers,
+    /// strings, booleans, nil, channels, mutexes, atomics, and lists of
+    /// them. A list that contains itself can't be copied either. Channels,
+    /// mutexes, and atomics are shared rather than copied.
+    pub fn from_value(value: &Value) -> Option<Message> {
+        Message::copy(value, &mut Vec::new())
+    }
+
+    fn copy(value: &Value, path: &mut Vec<*const ()>) -> Option<Message> {
+        Some(match value {
+            Value::Nil() => Message::Nil,
+            Value::Boolean(b) => Message::Boolean(*b),
+            Value::Number(n) => Message::Number(*n),
+            // Strings keep their quotes internally
+            Value::String(s) => Message::String(s.clone()),
+            Value::List(list) => {
+                let address = Rc::as_ptr(list) as *const ();
+                if path.contains(&address) {
+                    return None;
+                }
+                path.push(address);
+                let items: Option<Vec<Message>> = list
+                    .borrow()
+                    .iter()
+                    .map(|item| Message::copy(item, path))
+                    .collect();
+                path.pop();
+                Message::List(items?)
+            }
+            Value::NativeInstance(_) => {
+                if let Some(channel) = handle(value) {
+                    Message::Channel(channel)
+                } else if let Some(mutex) = handle(value) {
+                    Message::Mutex(mutex)
+                } else {
+                    Message::Atomic(handle(value)?)
+                }
+            }
+            _ => return None,
+        })
+    }
+
+    /// The channels in the message, including those in lists.
+    fn channels(&self) -> Vec<Channel> {
+        match self {
+            Message::Channel(channel) => vec![channel.clone()],
+            Message::List(items) => items.iter().flat_map(Message::channels).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The message as a value on the receiving thread, whose interpreter
+    /// counts the lists and handles it makes as allocations.
+    pub fn into_value(self, interpreter: &mut Interpreter) -> Result<Value, LoxError> {
+        Ok(match self {
+            Message::Nil => Value::Nil(),
+            Message::Boolean(b) => Value::Boolean(b),
+            Message::Number(n) => Value::Number(n),
+            Message::String(s) => Value::String(s),
+            Message::List(items) => {
+                interpreter.track_allocation()?;
+                let items = items
+                    .into_iter()
+                    .map(|item| item.into_value(interpreter))
+                    .collect::<Result<_, _>>()?;
+                lox_list::new(items)
+            }
+            Message::Channel(channel) => {
+                interpreter.track_allocation()?;
+                instance(channel)
+            }
+            Message::Mutex(mutex) => {
+                interpreter.track_allocation()?;
+                instance(mutex)
+            }
+            Message::Atomic(atomic) => {
+                interpreter.track_allocation()?;
+                instance(atomic)
+            }
+        })
+    }
+}
+
+fn instance(native: impl NativeInstance + 'static) -> Value {
+    Value::NativeInstance(Rc::new(RefCell::new(native)))
+}
+
+/// A copy of the `T` that `value` holds, if it is one.
+fn handle<T: NativeInstance + Clone + 'static>(value: &Value) -> Option<T> {
+    let Value::NativeInstance(instance) = value else {
+        return None;
+    };
+    let instance = instance.borrow();
+    instance.as_any()?.downcast_ref::<T>().cloned()
+}
+
+/// A queue of messages that any number of threads send to and receive
+/// from. Copies share the queue.
+#[derive(Clone, Default)]
+pub struct Channel {
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+}
+
+#[derive(Default)]
+struct Queue {
+    messages: VecDeque<Message>,
+    /// Whether a worker given the channel has stopped with an error.
+    failed: bool,
+}
+
+/// What waiting on a channel gave.
+pub enum Received {
+    Message(Message),
+    /// Nothing arrived in time.
+    Nothing,
+    /// The channel is empty and a worker given it has failed, so what it
+    /// would have sent may never come.
+    Failed,
+}
+
+impl Channel {
+    pub fn send(&self, message: Message) {
+        let (queue, ready) = &*self.queue;
+        queue.lock().unwrap().messages.push_back(message);
+        ready.notify_one();
+    }
+
+    /// Takes the oldest message, waiting up to `timeout` for one to arrive.
+    pub fn receive(&self, timeout: Duration) -> Received {
+        let (queue, ready) = &*self.queue;
+        let queue = queue.lock().unwrap();
+        let (mut queue, _) = ready
+            .wait_timeout_while(queue, timeout, |queue| {
+                queue.messages.is_empty() && !queue.failed
+            })
+            .unwrap();
+        match queue.messages.pop_front() {
+            Some(message) => Received::Message(message),
+            None if queue.failed => Received::Failed,
+            None => Received::Nothing,
+        }
+    }
+
+    /// Marks the channel as given to a worker that failed, waking every
+    /// thread waiting on it.
+    pub fn fail(&self) {
+        let (queue, ready) = &*self.queue;
+        queue.lock().unwrap().failed = true;
+        ready.notify_all();
+    }
+}
+
+impl NativeInstance for Channel {
+    fn class_name(&self) -> String {
+        "Channel".to_string()
+    }
+
+    fn get(&self, _name: &str) -> Option<Value> {
+        None
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// The handle `spawn` returns. `join()` waits for the worker to finish and
+/// returns what its function returned, or nil if that can't be sent back or
+/// the worker stopped with an error. A worker that stops with an error also
+/// fails the channels it was given, so `receive` on them raises an error
+/// once they are empty rather than waiting forever.
+pub struct Worker {
+    /// Where the worker's thread sends its result, until it is joined.
+    result: Option<Channel>,
+}
+
+impl NativeInstance for Worker {
+    fn class_name(&self) -> String {
+        "Worker".to_string()
+    }
+
+    fn get(&self, _name: &str) -> Option<Value> {
+        None
+    }
+
+    fn method_arity(&self, name: &str) -> Option<usize> {
+        match name {
+            "join" => Some(0),
+            _ => None,
+        }
+    }
+
+    fn call_method(
+        &mut self,
+        _name: &str,
+        interpreter: &mut Interpreter,
+        _arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        // Joining again gives nil
+        let Some(result) = self.result.take() else {
+            return Ok(None);
+        };
+        // Waits in naps like `receive`
+        loop {
+            if let Received::Message(message) = result.receive(Duration::from_millis(10)) {
+                return Ok(Some(message.into_value(interpreter)?));
+            }
+            interpreter.check_interrupts()?;
+        }
+    }
+}
+
+/// `channel()`, which returns a new, empty channel.
+pub struct ChannelFn;
+
+impl Callable for ChannelFn {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        _arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        interpreter.track_allocation()?;
+        Ok(Some(instance(Channel::default())))
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(ChannelFn)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// The channel a `send` or `receive` call names.
+fn channel(interpreter: &Interpreter, value: Option<&Option<Value>>) -> Result<Channel, LoxError> {
+    argument(value).and_then(handle).ok_or_else(|| {
+        interpreter.native_error(
+            ErrorCode::NotAChannel,
+            "Can only use channels made by channel().",
+        )
+    })
+}
+
+fn argument(value: Option<&Option<Value>>) -> Option<&Value> {
+    value.and_then(Option::as_ref)
+}
+
+/// `send(channel, value)`, which adds a copy of `value` to the channel and
+/// returns without waiting for it to be received.
+pub struct Send;
+
+impl Callable for Send {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let channel = channel(interpreter, arguments.first())?;
+        let value = arguments[1].clone().unwrap_or(Value::Nil());
+        channel.send(message(interpreter, &value)?);
+        Ok(Some(Value::Nil()))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Send)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `receive(channel)`, which takes the oldest value from the channel,
+/// waiting for one to be sent if it is empty.
+pub struct Receive;
+
+impl Callable for Receive {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let channel = channel(interpreter, arguments.first())?;
+        // Wait in short naps, so cancellation and the time limit still stop
+        // a script waiting on a channel nothing will send to
+        loop {
+            match channel.receive(Duration::from_millis(10)) {
+                Received::Message(message) => return Ok(Some(message.into_value(interpreter)?)),
+                Received::Failed => {
+                    return Err(interpreter.native_error(
+                        ErrorCode::WorkerFailed,
+                        "A worker using this channel stopped with an error.",
+                    ))
+                }
+                Received::Nothing => interpreter.check_interrupts()?,
+            }
+        }
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Receive)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// A lock that one thread at a time can hold. Copies share the lock.
+#[derive(Clone, Default)]
+pub struct LoxMutex {
+    /// The thread holding the lock, if any.
+    holder: Arc<(Mutex<Option<ThreadId>>, Condvar)>,
+}
+
+impl LoxMutex {
+    /// Whether the current thread holds the lock.
+    pub fn is_held(&self) -> bool {
+        *self.holder.0.lock().unwrap() == Some(thread::current().id())
+    }
+
+    /// Takes the lock for the current thread, waiting up to `timeout` for
+    /// another thread to release it. Returns whether it was taken.
+    pub fn lock(&self, timeout: Duration) -> bool {
+        let (holder, released) = &*self.holder;
+        let holder = holder.lock().unwrap();
+        let (mut holder, _) = released
+            .wait_timeout_while(holder, timeout, |holder| holder.is_some())
+            .unwrap();
+        if holder.is_some() {
+            return false;
+        }
+        *holder = Some(thread::current().id());
+        true
+    }
+
+    /// Releases the lock, or returns `false` if the current thread doesn't
+    /// hold it.
+    pub fn unlock(&self) -> bool {
+        let (holder, released) = &*self.holder;
+        let mut holder = holder.lock().unwrap();
+        if *holder != Some(thread::current().id()) {
+            return false;
+        }
+        *holder = None;
+        released.notify_one();
+        true
+    }
+}
+
+impl NativeInstance for LoxMutex {
+    fn class_name(&self) -> String {
+        "Mutex".to_string()
+    }
+
+    fn get(&self, _name: &str) -> Option<Value> {
+        None
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// A number that threads update in one step. Copies share the number,
+/// which scripts read as the `value` field.
+#[derive(Clone)]
+pub struct Atomic {
+    bits: Arc<AtomicU64>,
+}
+
+impl Atomic {
+    pub fn new(value: f64) -> Self {
+        Atomic {
+            bits: Arc::new(AtomicU64::new(value.to_bits())),
+        }
+    }
+
+    pub fn load(&self) -> f64 {
+        f64::from_bits(self.bits.load(Ordering::SeqCst))
+    }
+
+    /// Adds `amount`, returning the new value.
+    pub fn add(&self, amount: f64) -> f64 {
+        let add = |bits| Some((f64::from_bits(bits) + amount).to_bits());
+        // `add` never refuses, so this always succeeds with the old value
+        let old = match self
+            .bits
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, add)
+        {
+            Ok(bits) | Err(bits) => f64::from_bits(bits),
+        };
+        old + amount
+    }
+}
+
+impl NativeInstance for Atomic {
+    fn class_name(&self) -> String {
+        "Atomic".to_string()
+    }
+
+    fn get(&self, name: &str) -> Option<Value> {
+        match name {
+            "value" => Some(Value::Number(self.load())),
+            _ => None,
+        }
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// `mutex()`, which returns a new, unlocked mutex.
+pub struct MutexFn;
+
+impl Callable for MutexFn {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        _arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        interpreter.track_allocation()?;
+        Ok(Some(instance(LoxMutex::default())))
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(MutexFn)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// The mutex a `lock` or `unlock` call names.
+fn mutex(interpreter: &Interpreter, value: Option<&Option<Value>>) -> Result<LoxMutex, LoxError> {
+    argument(value).and_then(handle).ok_or_else(|| {
+        interpreter.native_error(
+            ErrorCode::NotAMutex,
+            "Can only lock mutexes made by mutex().",
+        )
+    })
+}
+
+/// `lock(mutex)`, which waits until no other thread holds the mutex and
+/// then takes it. Locking a mutex the thread already holds is an error,
+/// since it would wait forever.
+pub struct Lock;
+
+impl Callable for Lock {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mutex = mutex(interpreter, arguments.first())?;
+        if mutex.is_held() {
+            return Err(interpreter.native_error(
+                ErrorCode::MutexAlreadyLocked,
+                "Mutex is already locked on this thread.",
+            ));
+        }
+        // Waits in naps like `receive`
+        while !mutex.lock(Duration::from_millis(10)) {
+            interpreter.check_interrupts()?;
+        }
+        Ok(Some(Value::Nil()))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Lock)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `unlock(mutex)`, which releases a mutex the thread holds.
+pub struct Unlock;
+
+impl Callable for Unlock {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mutex = mutex(interpreter, arguments.first())?;
+        if !mutex.unlock() {
+            return Err(interpreter.native_error(
+                ErrorCode::MutexNotLocked,
+                "Can only unlock a mutex locked on this thread.",
+            ));
+        }
+        Ok(Some(Value::Nil()))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Unlock)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `atomic(value)`, which returns a new atomic holding a number.
+pub struct AtomicFn;
+
+impl Callable for AtomicFn {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let Some(Some(Value::Number(value))) = arguments.first() else {
+            return Err(interpreter.native_error(
+                ErrorCode::OperandMustBeNumber,
+                "Atomic value must be a number.",
+            ));
+        };
+        interpreter.track_allocation()?;
+        Ok(Some(instance(Atomic::new(*value))))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(AtomicFn)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `atomicAdd(atomic, amount)`, which adds to an atomic in one step, so no
+/// other thread's update comes in between, and returns the new value.
+pub struct AtomicAdd;
+
+impl Callable for AtomicAdd {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let Some(atomic) = argument(arguments.first()).and_then(handle::<Atomic>) else {
+            return Err(interpreter.native_error(
+                ErrorCode::NotAnAtomic,
+                "Can only add to atomics made by atomic().",
+            ));
+        };
+        let Some(Some(Value::Number(amount))) = arguments.get(1) else {
+            return Err(interpreter
+                .native_error(ErrorCode::OperandMustBeNumber, "Amount must be a number."));
+        };
+        Ok(Some(Value::Number(atomic.add(*amount))))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(AtomicAdd)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// `value` as a message, or the error for a value that can't be sent.
+fn message(interpreter: &Interpreter, value: &Value) -> Result<Message, LoxError> {
+    Message::from_value(value).ok_or_else(|| {
+        interpreter.native_error(
+            ErrorCode::NotSendable,
+            "Can only send numbers, strings, booleans, nil, channels, mutexes, atomics, and lists of them.",
+        )
+    })
+}
+
+/// `spawn(fn, args...)`, which calls `fn` with copies of `args` on a worker
+/// thread and returns the worker. `fn` must be a function declared at the
+/// top level. Workers still running when the script ends are stopped, and
+/// the script waits for them before returning.
+pub struct Spawn;
+
+impl Callable for Spawn {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, LoxError> {
+        let mut arguments = arguments.into_iter();
+        let function = match arguments.next().flatten() {
+            Some(Value::Callable(callable)) => {
+                callable.as_any().downcast_ref::<LoxFunction>().cloned()
+            }
+            _ => None,
+        };
+        let name = function
+            .as_ref()
+            .and_then(|function| top_level_name(interpreter, function));
+        let (Some(function), Some(name)) = (function, name) else {
+            return Err(interpreter.native_error(
+                ErrorCode::NotSpawnable,
+                "Can only spawn functions declared at the top level.",
+            ));
+        };
+        let arguments: Vec<Value> = arguments
+            .map(|value| value.unwrap_or(Value::Nil()))
+            .collect();
+        if arguments.len() != function.arity() {
+            return Err(interpreter.native_error(
+                ErrorCode::WrongArity,
+                &format!(
+                    "Expected {} arguments but got {}.",
+                    function.arity(),
+                    arguments.len()
+                ),
+            ));
+        }
+        let messages = arguments
+            .iter()
+            .map(|value| message(interpreter, value))
+            .collect::<Result<Vec<_>, _>>()?;
+        interpreter.track_allocation()?;
+
+        let program = worker_program(interpreter).to_bytes();
+        let settings = WorkerSettings::of(interpreter);
+        let result = Channel::default();
+        let sender = result.clone();
+        let channels: Vec<Channel> = messages.iter().flat_map(Message::channels).collect();
+        let handle = thread::spawn(
+            move || match run_worker(settings, &program, &name, messages) {
+                Ok(message) => sender.send(message),
+                Err(()) => {
+                    for channel in channels {
+                        channel.fail();
+                    }
+                    sender.send(Message::Nil);
+                }
+            },
+        );
+        // The run waits for the thread when it ends
+        interpreter.shared_run().add_worker(handle);
+        Ok(Some(instance(Worker {
+            result: Some(result),
+        })))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn variadic(&self) -> bool {
+        true
+    }
+
+    // Threads interleave differently from run to run
+    fn deterministic(&self) -> bool {
+        false
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(Spawn)
+    }
+
+    fn to_string(&self) -> String {
+        "<native fn>".to_string()
+    }
+}
+
+/// What a worker takes from the interpreter that spawned it.
+struct WorkerSettings {
+    output_file: String,
+    prelude: bool,
+    limits: InterpreterLimits,
+    /// The spawning run's instruction count and start time, which the
+    /// worker counts against too.
+    run: SharedRun,
+    cancellation: Option<CancellationToken>,
+    log_level: LogLevel,
+    decimal_literals: bool,
+    checked_division: bool,
+    checked_integers: bool,
+    strict_math: bool,
+    deterministic: bool,
+}
+
+impl WorkerSettings {
+    fn of(interpreter: &Interpreter) -> Self {
+        WorkerSettings {
+            output_file: interpreter.output_file().to_string(),
+            prelude: interpreter.has_prelude(),
+            limits: interpreter.limits().clone(),
+            run: interpreter.shared_run().clone(),
+            cancellation: interpreter.cancellation_token().cloned(),
+            log_level: interpreter.log_level(),
+            decimal_literals: interpreter.decimal_literals(),
+            checked_division: interpreter.checked_division(),
+            checked_integers: interpreter.checked_integers(),
+            strict_math: interpreter.strict_math(),
+            deterministic: interpreter.deterministic(),
+        }
+    }
+
+    /// A fresh interpreter for the worker, set up like its parent.
+    fn lox(&self) -> Lox {
+        let mut lox = if self.prelude {
+            Lox::new(&self.output_file)
+        } else {
+            Lox::without_prelude(&self.output_file)
+        };
+        lox.set_limits(self.limits.clone());
+        if let Some(token) = &self.cancellation {
+            lox.set_cancellation_token(token.clone());
+        }
+        lox.set_log_level(self.log_level);
+        lox.set_decimal_literals(self.decimal_literals);
+        lox.set_checked_division(self.checked_division);
+        lox.set_checked_integers(self.checked_integers);
+        lox.set_strict_math(self.strict_math);
+        lox.set_deterministic(self.deterministic);
+        lox
+    }
+}
+
+/// Declares the program sent to a worker on an interpreter set up from
+/// `settings` and calls the function named `name`, reporting any error as
+/// `Lox::run` would. Gives the function's result, or `Err` if the worker
+/// stopped with an error.
+fn run_worker(
+    settings: WorkerSettings,
+    program: &[u8],
+    name: &str,
+    arguments: Vec<Message>,
+) -> Result<Message, ()> {
+    let mut lox = settings.lox();
+    let program = Program::from_bytes(program).ok_or(())?;
+    lox.run_program(&program).map_err(|_| ())?;
+    let mut interpreter = lox.interpreter.borrow_mut();
+    // Running the declarations started a run of the worker's own
+    interpreter.share_run(settings.run.clone());
+    let Some(Some(Value::Callable(mut function))) =
+        interpreter.globals.borrow().values.get(name).cloned()
+    else {
+        return Err(());
+    };
+    let result = arguments
+        .into_iter()
+        .map(|message| message.into_value(&mut interpreter).map(Some))
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|arguments| function.call(&mut interpreter, arguments));
+    match result {
+        Ok(value) => {
+            Ok(Message::from_value(&value.unwrap_or(Value::Nil())).unwrap_or(Message::Nil))
+        }
+        // A worker stopped because the script ended has nothing to report
+        Err(LoxError::Cancelled) if settings.run.has_ended() => Err(()),
+        Err(error) => {
+            let reporter = interpreter.reporter();
+            crate::report(&mut *reporter.borrow_mut(), &error, "");
+            Err(())
+        }
+    }
+}
+
+/// The global name `function` was declared under, if it is still bound to
+/// it there.
+fn top_level_name(interpreter: &Interpreter, function: &LoxFunction) -> Option<String> {
+    let Stmt::Function { name, .. } = &function.declaration else {
+        return None;
+    };
+    match interpreter.globals.borrow().values.get(&name.lexeme) {
+        Some(Some(Value::Callable(global))) => {
+            let global = global.as_any().downcast_ref::<LoxFunction>()?;
+            (global.declaration == function.declaration).then(|| name.lexeme.clone())
+        }
+        _ => None,
+    }
+}
+
+/// The top-level functions, interfaces, and classes a worker starts with,
+/// in the order they were declared. Prelude functions are left to the
+/// worker's own prelude, and classes whose superclass isn't sent are left
+/// out.
+fn worker_program(interpreter: &Interpreter) -> Program {
+    let prelude = prelude::statements();
+    let mut statements: Vec<Stmt> = Vec::new();
+    let mut classes: Vec<String> = Vec::new();
+    for (name, value) in interpreter.globals.borrow().values.iter() {
+        let Some(Value::Callable(callable)) = value else {
+            continue;
+        };
+        if let Some(function) = callable.as_any().downcast_ref::<LoxFunction>() {
+            if matches!(&function.declaration, Stmt::Function { name: declared, .. } if &declared.lexeme == name)
+                && !prelude.contains(&function.declaration)
+            {
+                statements.push(function.declaration.clone());
+            }
+        } else if let Some(class) = callable.as_any().downcast_ref::<LoxClass>() {
+            let sent = class
+                .superclass()
+                .is_none_or(|superclass| classes.iter().any(|c| c == superclass.name()));
+            if class.name() == name && sent {
+                classes.push(name.clone());
+                statements.push(class.declaration.clone());
+            }
+        } else if let Some(interface) = callable.as_any().downcast_ref::<LoxInterface>() {
+            if interface.name() == name {
+                statements.push(interface.declaration.clone());
+            }
+        }
+    }
+    Program::from_parts(statements, interpreter.locals())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::limits::InterpreterLimits;
+    use crate::reporter::BufferReporter;
+    use crate::Lox;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::thread;
+    use std::time::Duration;
+
+    fn lox() -> Lox {
+        let mut lox = Lox::new("");
+        lox.set_reporter(Rc::new(RefCell::new(BufferReporter::new())));
+        lox
+    }
+
+    #[test]
+    fn workers_talk_over_channels() {
+        let mut lox = lox();
+        let result = lox.try_run(
+            "fun square(n) { return n * n; }
+             fun worker(requests, replies) {
+               var n = receive(requests);
+               while (n != nil) {
+                 send(replies, square(n));
+                 n = receive(requests);
+               }
+               return \"done\";
+             }
+             var requests = channel();
+             var replies = channel();
+             var w = spawn(worker, requests, replies);
+             send(requests, 3);
+             send(requests, 4);
+             send(requests, nil);
+             expectEqual(receive(replies), 9);
+             expectEqual(receive(replies), 16);
+             expectEqual(w.join(), \"done\");",
+        );
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn workers_run_in_the_same_modes() {
+        let mut lox = lox();
+        lox.set_decimal_literals(true);
+        lox.set_strict_math(true);
+        let result = lox.try_run(
+            "fun exact() { return 0.1 + 0.2 == 0.3; }
+             fun undefined() { return 0 / 0; }
+             expectEqual(spawn(exact).join(), true);
+             expectEqual(spawn(undefined).join(), nil);",
+        );
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn workers_share_the_limits_of_their_run() {
+        let mut lox = lox();
+        lox.set_limits(InterpreterLimits {
+            max_instructions: Some(500),
+            ..InterpreterLimits::default()
+        });
+        // One worker takes about 300 instructions, so a second one runs out
+        let result = lox.try_run(
+            "fun work() { for (var i = 0; i < 100; i = i + 1) {} return true; }
+             expectEqual(spawn(work).join(), true);
+             expectEqual(spawn(work).join(), nil);",
+        );
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn workers_stop_when_the_script_ends() {
+        let mut lox = lox();
+        let result = lox.try_run(
+            "fun spin(counter) { while (true) atomicAdd(counter, 1); }
+             var counter = atomic(0);
+             spawn(spin, counter);",
+        );
+        assert!(result.is_ok(), "{:?}", result);
+        // The run waited for the worker to stop, so the count holds still
+        lox.try_run("var seen = counter.value;").unwrap();
+        thread::sleep(Duration::from_millis(20));
+        let result = lox.try_run("expectEqual(counter.value, seen);");
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn failed_workers_fail_their_channels() {
+        let mut lox = lox();
+        let result = lox.try_run(
+            "fun reply(channels) { send(channels[0], 1); return nil + 1; }
+             var replies = channel();
+             expectEqual(spawn(reply, [replies]).join(), nil);
+             expectEqual(receive(replies), 1);",
+        );
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(
+            lox.try_run("receive(replies);").unwrap_err().to_string(),
+            "A worker using this channel stopped with an error. [E3034]\n[line 1:16]"
+        );
+    }
+
+    #[test]
+    fn messages_are_copies() {
+        let mut lox = lox();
+        let result = lox.try_run(
+            "fun grow(items) { items.push(4); return items; }
+             var items = [1, [2, 3]];
+             var grown = spawn(grow, items).join();
+             expectEqual(grown, [1, [2, 3], 4]);
+             expectEqual(items.length, 2);",
+        );
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn workers_coordinate_with_mutexes_and_atomics() {
+        let mut lox = lox();
+        let result = lox.try_run(
+            "fun count(hits, guard, total) {
+               for (var i = 0; i < 100; i = i + 1) {
+                 atomicAdd(hits, 1);
+                 lock(guard);
+                 send(total, receive(total) + 1);
+                 unlock(guard);
+               }
+             }
+             var hits = atomic(0);
+             var guard = mutex();
+             var total = channel();
+             send(total, 0);
+             var workers = [];
+             for (var i = 0; i < 4; i = i + 1) {
+               workers.push(spawn(count, hits, guard, total));
+             }
+             for (var i = 0; i < 4; i = i + 1) workers[i].join();
+             expectEqual(hits.value, 400);
+             expectEqual(receive(total), 400);
+             expectEqual(atomicAdd(hits, -0.5), 399.5);",
+        );
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn mutexes_are_unlocked_once_by_their_holder() {
+        let mut lox = lox();
+        let error = |lox: &mut Lox, source: &str| lox.try_run(source).unwrap_err().to_string();
+        lox.try_run("var m = mutex(); lock(m);").unwrap();
+        assert_eq!(
+            error(&mut lox, "lock(m);"),
+            "Mutex is already locked on this thread. [E3028]\n[line 1:7]"
+        );
+        lox.try_run("unlock(m);").unwrap();
+        assert_eq!(
+            error(&mut lox, "unlock(m);"),
+            "Can only unlock a mutex locked on this thread. [E3029]\n[line 1:9]"
+        );
+        assert_eq!(
+            error(&mut lox, "lock(channel());"),
+            "Can only lock mutexes made by mutex(). [E3027]\n[line 1:15]"
+        );
+        assert_eq!(
+            error(&mut lox, "atomicAdd(m, 1);"),
+            "Can only add to atomics made by atomic(). [E3030]\n[line 1:15]"
+        );
+    }
+
+    #[test]
+    fn only_data_and_top_level_functions_can_be_sent() {
+        let mut lox = lox();
+        let error = |lox: &mut Lox, source: &str| lox.try_run(source).unwrap_err().to_string();
+        lox.try_run("fun id(x) { return x; } class Point {}")
+            .unwrap();
+        assert_eq!(
+            error(&mut lox, "spawn(id, Point());"),
+            "Can only send numbers, strings, booleans, nil, channels, mutexes, atomics, and lists of them. [E3024]\n[line 1:18]"
+        );
+        assert_eq!(
+            error(
+                &mut lox,
+                "fun outer() { fun inner() {} spawn(inner); } outer();"
+            ),
+            "Can only spawn functions declared at the top level. [E3025]\n[line 1:41]"
+        );
+        assert_eq!(
+            error(&mut lox, "spawn(id);"),
+            "Expected 1 arguments but got 0. [E3007]\n[line 1:9]"
+        );
+        assert_eq!(
+            error(&mut lox, "send(1, 2);"),
+            "Can only use channels made by channel(). [E3026]\n[line 1:10]"
+        );
+    }
+}
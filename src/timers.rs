@@ -0,0 +1,131 @@
+//! The queue behind `setTimeout` and `setInterval`. Timers wait until the
+//! main script has finished, and then `Interpreter::run_timers` fires them
+//! in the order they come due.
+
+use crate::callable::Callable;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct Timers {
+    pending: Vec<Timer>,
+    next_id: u64,
+    /// When the clock timers are set by started.
+    started: Instant,
+    /// How far the clock has been moved on in deterministic mode, where
+    /// timers fire without waiting.
+    advanced: Duration,
+}
+
+/// A timer set by a script, and the id it was given.
+#[derive(Debug, Clone)]
+struct Timer {
+    id: u64,
+    due: Duration,
+    /// How often the timer fires again, for `setInterval`.
+    interval: Option<Duration>,
+    callback: Box<dyn Callable>,
+}
+
+impl Default for Timers {
+    fn default() -> Self {
+        Timers {
+            pending: Vec::new(),
+            next_id: 1,
+            started: Instant::now(),
+            advanced: Duration::ZERO,
+        }
+    }
+}
+
+impl Timers {
+    /// How long since the timer clock started: the time that has really
+    /// passed, or in deterministic mode how far the clock has been moved on.
+    pub fn now(&self, deterministic: bool) -> Duration {
+        if deterministic {
+            self.advanced
+        } else {
+            self.started.elapsed()
+        }
+    }
+
+    /// Moves the deterministic clock on to `time`, if it is later.
+    pub fn advance_to(&mut self, time: Duration) {
+        self.advanced = self.advanced.max(time);
+    }
+
+    /// Adds a timer that fires `delay` after `now`, and then every
+    /// `interval` if one is given. Returns the timer's id.
+    pub fn schedule(
+        &mut self,
+        now: Duration,
+        delay: Duration,
+        interval: Option<Duration>,
+        callback: Box<dyn Callable>,
+    ) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.push(Timer {
+            id,
+            due: now + delay,
+            interval,
+            callback,
+        });
+        id
+    }
+
+    /// Stops the timer with `id` from firing again.
+    pub fn cancel(&mut self, id: u64) {
+        self.pending.retain(|timer| timer.id != id);
+    }
+
+    /// Takes the timer that comes due first, the one set first among those
+    /// due together, and returns when it is due and its callback. An
+    /// interval timer is put back to fire again.
+    pub fn take_next(&mut self) -> Option<(Duration, Box<dyn Callable>)> {
+        let (index, _) = self
+            .pending
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, timer)| (timer.due, timer.id))?;
+        let timer = self.pending.remove(index);
+        let callback = timer.callback.clone();
+        let due = timer.due;
+        if let Some(interval) = timer.interval {
+            self.pending.push(Timer {
+                due: due + interval,
+                ..timer
+            });
+        }
+        Some((due, callback))
+    }
+
+    /// Drops every pending timer, as when a script stops with an error.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::native_functions::Clock;
+
+    #[test]
+    fn timers_come_due_in_order() {
+        let mut timers = Timers::default();
+        let ms = Duration::from_millis;
+        let late = timers.schedule(ms(0), ms(30), None, Box::new(Clock));
+        let every = timers.schedule(ms(0), ms(20), Some(ms(20)), Box::new(Clock));
+        let cancelled = timers.schedule(ms(5), ms(0), None, Box::new(Clock));
+        timers.cancel(cancelled);
+        assert_eq!((late, every), (1, 2));
+
+        let dues: Vec<Duration> = std::iter::from_fn(|| timers.take_next())
+            .map(|(due, _)| due)
+            .take(4)
+            .collect();
+        assert_eq!(dues, vec![ms(20), ms(30), ms(40), ms(60)]);
+        timers.cancel(every);
+        assert!(timers.take_next().is_none());
+    }
+}
@@ -5,6 +5,8 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -12,6 +14,8 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    Question,
+    Colon,
 
     // One or two character tokens
     Bang,
@@ -22,6 +26,12 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PlusPlus,
+    MinusMinus,
 
     // Literals
     Identifier,
@@ -31,11 +41,16 @@ pub enum TokenType {
     // Keywords
     And,
     Class,
+    Defer,
     Else,
     False,
     Fun,
     For,
     If,
+    Implements,
+    Interface,
+    Is,
+    Macro,
     Nil,
     Or,
     Print,
@@ -43,8 +58,83 @@ pub enum TokenType {
     Super,
     This,
     True,
+    Using,
     Var,
     While,
 
+    // Trivia, only scanned by `Scanner::with_trivia`
+    Whitespace,
+    Comment,
+    /// A pragma line, such as `#if DEBUG`, or the lines of a section it
+    /// turns off.
+    Pragma,
+    /// Text that failed to scan, kept so the source can be rebuilt.
+    Error,
+
     EoF,
 }
+
+impl TokenType {
+    /// Every token type, in declaration order, so `ALL[t as usize]` is `t`.
+    pub const ALL: [TokenType; TokenType::EoF as usize + 1] = [
+        TokenType::LeftParen,
+        TokenType::RightParen,
+        TokenType::LeftBrace,
+        TokenType::RightBrace,
+        TokenType::LeftBracket,
+        TokenType::RightBracket,
+        TokenType::Comma,
+        TokenType::Dot,
+        TokenType::Minus,
+        TokenType::Plus,
+        TokenType::Semicolon,
+        TokenType::Slash,
+        TokenType::Star,
+        TokenType::Question,
+        TokenType::Colon,
+        TokenType::Bang,
+        TokenType::BangEqual,
+        TokenType::Equal,
+        TokenType::EqualEqual,
+        TokenType::Greater,
+        TokenType::GreaterEqual,
+        TokenType::Less,
+        TokenType::LessEqual,
+        TokenType::PlusEqual,
+        TokenType::MinusEqual,
+        TokenType::StarEqual,
+        TokenType::SlashEqual,
+        TokenType::PlusPlus,
+        TokenType::MinusMinus,
+        TokenType::Identifier,
+        TokenType::String,
+        TokenType::Number,
+        TokenType::And,
+        TokenType::Class,
+        TokenType::Defer,
+        TokenType::Else,
+        TokenType::False,
+        TokenType::Fun,
+        TokenType::For,
+        TokenType::If,
+        TokenType::Implements,
+        TokenType::Interface,
+        TokenType::Is,
+        TokenType::Macro,
+        TokenType::Nil,
+        TokenType::Or,
+        TokenType::Print,
+        TokenType::Return,
+        TokenType::Super,
+        TokenType::This,
+        TokenType::True,
+        TokenType::Using,
+        TokenType::Var,
+        TokenType::While,
+        TokenType::Whitespace,
+        TokenType::Comment,
+        TokenType::Pragma,
+        TokenType::Error,
+        TokenType::EoF,
+    ];
+}
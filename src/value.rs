@@ -1,6 +1,7 @@
 // use crate::token::Token;
 use crate::callable::Callable;
 use crate::lox_instance::LoxInstance;
+use crate::native_instance::NativeInstance;
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -11,7 +12,15 @@ pub enum Value {
     String(String),
     Callable(Box<dyn Callable>),
     Instance(Rc<RefCell<LoxInstance>>),
+    NativeInstance(Rc<RefCell<dyn NativeInstance>>),
+    /// A list, shared by every value that holds it. See `lox_list`.
+    List(Rc<RefCell<Vec<Value>>>),
     Nil(),
+    /// An integer too large for `Number` to hold exactly. See `bigint`.
+    #[cfg(feature = "lox-bigint")]
+    BigInt(num_bigint::BigInt),
+    /// An exact decimal, from a literal in decimal mode. See `decimal`.
+    Decimal(crate::decimal::Decimal),
     // Operator(Token),
 }
 
@@ -23,8 +32,20 @@ impl PartialEq for Value {
             (Value::String(a), Value::String(b)) => a == b,
             // You can handle Callable equality in a meaningful way if needed, e.g. by pointer comparison or skipping
             (Value::Callable(_), Value::Callable(_)) => false, // Callables are not compared
-            (Value::Instance(_), Value::Instance(_)) => false,
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::NativeInstance(a), Value::NativeInstance(b)) => Rc::ptr_eq(a, b),
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
             (Value::Nil(), Value::Nil()) => true,
+            #[cfg(feature = "lox-bigint")]
+            (Value::BigInt(a), Value::BigInt(b)) => a == b,
+            #[cfg(feature = "lox-bigint")]
+            (Value::BigInt(a), Value::Number(b)) | (Value::Number(b), Value::BigInt(a)) => {
+                crate::bigint::equals_number(a, *b)
+            }
+            (Value::Decimal(_), Value::Decimal(_) | Value::Number(_))
+            | (Value::Number(_), Value::Decimal(_)) => {
+                crate::decimal::compare(self, other) == Some(std::cmp::Ordering::Equal)
+            }
             _ => false,
         }
     }
@@ -40,7 +61,27 @@ impl PartialOrd for Value {
             // Skipping Callables for ordering
             (Value::Callable(_), Value::Callable(_)) => None, // Callables cannot be compared
             (Value::Instance(_), Value::Instance(_)) => None,
+            (Value::NativeInstance(_), Value::NativeInstance(_)) => None,
             (Value::Nil(), Value::Nil()) => Some(std::cmp::Ordering::Equal),
+            #[cfg(feature = "lox-bigint")]
+            (Value::BigInt(_), Value::BigInt(_) | Value::Number(_))
+            | (Value::Number(_), Value::BigInt(_)) => crate::bigint::compare(self, other),
+            (Value::Decimal(_), Value::Decimal(_) | Value::Number(_))
+            | (Value::Number(_), Value::Decimal(_)) => crate::decimal::compare(self, other),
+            _ => None,
+        }
+    }
+}
+
+impl Value {
+    /// The value as a float if it is any kind of number. Decimals and big
+    /// integers round to the nearest float.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Decimal(decimal) => Some(decimal.to_f64()),
+            #[cfg(feature = "lox-bigint")]
+            Value::BigInt(big) => num_traits::ToPrimitive::to_f64(big),
             _ => None,
         }
     }
@@ -19,3 +19,20 @@ pub fn write_output(file_name: &str, message: &str) -> io::Result<()> {
     }
     Ok(())
 }
+
+/// Writes `message` like `write_output` but without a newline after it.
+/// Stdout is flushed so a partial line shows up straight away.
+pub fn write_raw(file_name: &str, message: &str) -> io::Result<()> {
+    if file_name.is_empty() {
+        let mut handle = io::stdout().lock();
+        write!(handle, "{}", message)?;
+        handle.flush()?;
+    } else {
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(file_name)?;
+        write!(file, "{}", message)?;
+    }
+    Ok(())
+}
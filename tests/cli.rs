@@ -0,0 +1,310 @@
+//! End-to-end tests of the command line: exit statuses and subcommands.
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Runs `source` as a script file and returns the exit status.
+fn run_file(name: &str, source: &str) -> Option<i32> {
+    let path = env::temp_dir().join(format!("lox_cli_{}.lox", name));
+    fs::write(&path, source).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+        .arg(&path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .expect("Failed to start the interpreter")
+        .code()
+}
+
+/// Feeds `input` to the prompt and returns the exit status.
+fn run_prompt(input: &str) -> Option<i32> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to start the interpreter");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait().unwrap().code()
+}
+
+#[test]
+fn scripts_exit_with_sysexits_codes() {
+    assert_eq!(run_file("ok", "print 1;"), Some(0));
+    // EX_DATAERR for anything the scanner, parser, or resolver rejects
+    assert_eq!(run_file("scan", "print 1; @"), Some(65));
+    assert_eq!(run_file("parse", "print (1;"), Some(65));
+    assert_eq!(run_file("resolve", "return 1;"), Some(65));
+    // EX_SOFTWARE for runtime errors, even after output
+    assert_eq!(run_file("runtime", "print 1; nil();"), Some(70));
+    assert_eq!(run_file("exit", "exit(4);"), Some(4));
+}
+
+#[test]
+fn the_prompt_exits_with_the_status_of_its_last_entry() {
+    assert_eq!(run_prompt("var;\nprint 1;\n"), Some(0));
+    assert_eq!(run_prompt("print 1;\nvar;\n"), Some(65));
+    assert_eq!(run_prompt("print 1;\nnil();\n"), Some(70));
+    assert_eq!(run_prompt("exit(2);\nprint 1;\n"), Some(2));
+}
+
+#[test]
+fn metrics_prints_a_row_per_function() {
+    let path = env::temp_dir().join("lox_cli_metrics.lox");
+    fs::write(&path, "fun f(a, b) {\n  if (a) return b;\n}\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+        .args(["metrics".as_ref(), path.as_os_str()])
+        .output()
+        .expect("Failed to start the interpreter");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "function  line  params  statements  depth  complexity\n\
+         <script>     1       0           1      0           1\n\
+         f            1       2           2      1           2\n"
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn unbounded_recursion_is_a_runtime_error() {
+    let path = env::temp_dir().join("lox_cli_recursion.lox");
+    fs::write(&path, "fun f(n) { return f(n + 1); }\nf(0);\n").unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+            .args(args)
+            .arg(&path)
+            .output()
+            .expect("Failed to start the interpreter")
+    };
+
+    let output = run(&[]);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Stack overflow. [E3008]\n[line 1:26]\n  at f (line 1)\n  \
+         [previous frame repeated 998 more times]\n  at f (line 2)\n"
+    );
+
+    let output = run(&["--max-call-depth", "3"]);
+    assert_eq!(output.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("repeated 1 more time]"));
+}
+
+#[test]
+fn runaway_loops_stop_at_a_step_or_time_limit() {
+    let path = env::temp_dir().join("lox_cli_runaway.lox");
+    fs::write(&path, "print 1;\nwhile (true) {}\n").unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+            .args(args)
+            .arg(&path)
+            .output()
+            .expect("Failed to start the interpreter")
+    };
+
+    let output = run(&["--max-steps", "100"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Instruction limit of 100 exceeded.\n"
+    );
+    assert_eq!(output.status.code(), Some(70));
+
+    let output = run(&["--timeout-ms", "50"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Time limit of 50 ms exceeded.\n"
+    );
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn rename_rewrites_every_reference() {
+    let path = env::temp_dir().join("lox_cli_rename.lox");
+    fs::write(&path, "var n = 1; // n\nprint n + 1;\n").unwrap();
+    let rename = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+            .arg("rename")
+            .arg(&path)
+            .args(args)
+            .output()
+            .expect("Failed to start the interpreter")
+    };
+
+    let output = rename(&["--line", "2", "--col", "7", "--to", "size"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "var size = 1; // n\nprint size + 1;\n"
+    );
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = rename(&["--line", "1", "--col", "1", "--to", "size"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Error: Nothing to rename at line 1, column 1.\n"
+    );
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = rename(&["--line", "1", "--col", "5", "--to", "size", "--write"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "var size = 1; // n\nprint size + 1;\n"
+    );
+}
+
+#[test]
+fn defines_set_globals_before_the_script_runs() {
+    let path = env::temp_dir().join("lox_cli_define.lox");
+    fs::write(
+        &path,
+        "print limit * 2;\nprint name + \"!\";\nprint verbose;\nprint id + \"1\";\n",
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+            .args(args)
+            .arg(&path)
+            .output()
+            .expect("Failed to start the interpreter")
+    };
+
+    let output = run(&[
+        "--define",
+        "limit=21",
+        "--define",
+        "name=world",
+        "--define",
+        "verbose=true",
+        "--define",
+        "id=\"7\"",
+    ]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "42\nworld!\ntrue\n71\n"
+    );
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = run(&["--define", "while=1"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Error: Invalid value 'while=1' for --define. Expected <name>=<value>.\n"
+    );
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn defines_turn_on_pragma_sections() {
+    let path = env::temp_dir().join("lox_cli_pragma.lox");
+    fs::write(
+        &path,
+        "#if DEBUG\nprint \"debug\";\n#else\nprint \"release\";\n#end\n",
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+            .args(args)
+            .arg(&path)
+            .output()
+            .expect("Failed to start the interpreter");
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    assert_eq!(run(&[]), "release\n");
+    assert_eq!(run(&["--define", "DEBUG"]), "debug\n");
+    assert_eq!(run(&["--define", "DEBUG=1"]), "debug\n");
+    assert_eq!(run(&["--define", "DEBUG=false"]), "release\n");
+}
+
+#[test]
+fn deterministic_runs_repeat_exactly() {
+    let path = env::temp_dir().join("lox_cli_deterministic.lox");
+    fs::write(
+        &path,
+        "print clock(); print random(); print random(); logInfo(\"done\");",
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+            .args(args)
+            .arg(&path)
+            .output()
+            .expect("Failed to start the interpreter");
+        (
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    let (stdout, stderr) = run(&["--deterministic"]);
+    assert!(stdout.starts_with("946684800\n"), "{}", stdout);
+    assert_eq!(stderr, "2000-01-01T00:00:00.000Z INFO  done\n");
+    assert_eq!(run(&["--deterministic"]), (stdout.clone(), stderr));
+    assert_ne!(run(&[]).0, stdout);
+}
+
+#[test]
+fn strict_math_checks_decimal_results() {
+    let path = env::temp_dir().join("lox_cli_strict_decimal.lox");
+    fs::write(&path, "print 0.1 + 0.2;\nprint 0.1 / 0.0;\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+        .args(["--decimal", "--strict-math"])
+        .arg(&path)
+        .output()
+        .expect("Failed to start the interpreter");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0.3\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("Math result is infinite. [E3021]\n[line 2:"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_runs_every_test_file_and_counts_results() {
+    let dir = env::temp_dir().join("lox_cli_test");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("math_test.lox"),
+        "fun adds() { expectEqual(1 + 2, 3); }\ntest(\"adds\", adds);\n",
+    )
+    .unwrap();
+    let test = || {
+        Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+            .arg("test")
+            .arg(&dir)
+            .output()
+            .expect("Failed to start the interpreter")
+    };
+
+    let output = test();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!(
+            "{}\nPASS adds\n\n1 passed, 0 failed in 1 files\n",
+            dir.join("math_test.lox").display()
+        )
+    );
+    assert_eq!(output.status.code(), Some(0));
+
+    fs::write(
+        dir.join("text_test.lox"),
+        "fun joins() { expectEqual(\"a\" + \"b\", \"ba\"); }\ntest(\"joins\", joins);\n",
+    )
+    .unwrap();
+    let output = test();
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with(
+        "FAIL joins: Expected \"ba\" but got \"ab\". [E3016] [line 1:42]\n\n\
+         1 passed, 1 failed in 2 files\n"
+    ));
+    assert_eq!(output.status.code(), Some(1));
+}
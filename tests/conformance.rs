@@ -0,0 +1,407 @@
+//! Runs the Crafting Interpreters test suite and reports results per chapter.
+//!
+//! By default this runs the copy of the suite vendored in `tests/`. Point
+//! `LOX_CONFORMANCE_DIR` at the `test/` directory of a craftinginterpreters
+//! checkout to run the full upstream suite instead:
+//!
+//! ```text
+//! git clone https://github.com/munificent/craftinginterpreters
+//! LOX_CONFORMANCE_DIR=craftinginterpreters/test cargo test --test lox-conformance
+//! ```
+//!
+//! A filter argument, as in `cargo test --test lox-conformance -- super`, only
+//! runs scripts whose path contains it.
+//!
+//! Scripts can declare tags in a comment such as `// tags: slow, classes`.
+//! `--tag classes` only runs scripts with that tag, and `--skip slow` leaves
+//! out scripts with that tag. Either flag may be given more than once.
+//!
+//! `--junit <path>` and `--json <path>` also write each script's result,
+//! duration, and failure message to a file for CI systems to read.
+//!
+//! Setting `LOX_REFERENCE` to a command, such as the `jlox` script from a
+//! craftinginterpreters build, switches to differential mode: each script
+//! also runs through the reference, and any difference in stdout or in the
+//! diagnostics on stderr is flagged instead of checking `// expect:`
+//! comments.
+
+use lox_interpreter::expectations::{self, Expectations, ScriptRun};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::time::{Duration, Instant};
+
+// Which chapter of the book introduces the features each directory tests
+const CHAPTERS: [(&str, &[&str]); 9] = [
+    ("04 Scanning", &["comments", "scanning"]),
+    ("06 Parsing Expressions", &["expressions"]),
+    (
+        "07 Evaluating Expressions",
+        &["bool", "nil", "number", "operator", "string"],
+    ),
+    (
+        "08 Statements and State",
+        &["assignment", "block", "print", "variable"],
+    ),
+    (
+        "09 Control Flow",
+        &["for", "if", "logical_operator", "while"],
+    ),
+    ("10 Functions", &["call", "closure", "function", "return"]),
+    ("11 Resolving and Binding", &["regression"]),
+    (
+        "12 Classes",
+        &["class", "constructor", "field", "method", "this"],
+    ),
+    ("13 Inheritance", &["inheritance", "super"]),
+];
+
+// Directories that need a chapter-specific driver or only apply to clox
+const SKIPPED: [&str; 4] = ["benchmark", "expressions", "limit", "scanning"];
+
+#[derive(Default)]
+struct Options {
+    filter: Option<String>,
+    tags: Vec<String>,
+    skipped_tags: Vec<String>,
+    junit: Option<PathBuf>,
+    json: Option<PathBuf>,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Options {
+        let mut options = Options::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--tag" | "--skip" | "--junit" | "--json" => {
+                    let Some(value) = args.next() else {
+                        eprintln!("{} needs a value.", arg);
+                        process::exit(1);
+                    };
+                    match arg.as_str() {
+                        "--tag" => options.tags.push(value),
+                        "--skip" => options.skipped_tags.push(value),
+                        "--junit" => options.junit = Some(PathBuf::from(value)),
+                        _ => options.json = Some(PathBuf::from(value)),
+                    }
+                }
+                // libtest flags such as --nocapture are passed along by `cargo test`
+                _ if arg.starts_with('-') => {}
+                _ if options.filter.is_none() => options.filter = Some(arg),
+                _ => {}
+            }
+        }
+        options
+    }
+
+    /// Whether a script with `tags` should run.
+    fn selects(&self, tags: &[String]) -> bool {
+        if tags.iter().any(|tag| self.skipped_tags.contains(tag)) {
+            return false;
+        }
+        self.tags.is_empty() || tags.iter().any(|tag| self.tags.contains(tag))
+    }
+}
+
+struct ScriptResult {
+    path: PathBuf,
+    duration: Duration,
+    failure: Option<String>,
+}
+
+#[derive(Default)]
+struct ChapterResult {
+    scripts: Vec<ScriptResult>,
+}
+
+impl ChapterResult {
+    fn failures(&self) -> impl Iterator<Item = (&ScriptResult, &String)> {
+        self.scripts
+            .iter()
+            .filter_map(|script| Some((script, script.failure.as_ref()?)))
+    }
+
+    fn failed(&self) -> usize {
+        self.failures().count()
+    }
+
+    fn duration(&self) -> Duration {
+        self.scripts.iter().map(|script| script.duration).sum()
+    }
+}
+
+fn chapter_of(category: &str) -> &'static str {
+    CHAPTERS
+        .iter()
+        .find(|(_, categories)| categories.contains(&category))
+        .map(|(chapter, _)| *chapter)
+        .unwrap_or("Other")
+}
+
+fn collect_scripts(dir: &Path, scripts: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_scripts(&path, scripts);
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            scripts.push(path);
+        }
+    }
+}
+
+/// Runs `script` through the reference interpreter, returning its stdout
+/// and stderr. `reference` is a program followed by any arguments it needs.
+fn run_reference(reference: &str, script: &Path) -> Result<(String, String), String> {
+    let mut words = reference.split_whitespace();
+    let program = words.next().ok_or("LOX_REFERENCE is empty.")?;
+    let output = Command::new(program)
+        .args(words)
+        .arg(script)
+        .output()
+        .map_err(|err| format!("could not run reference '{}'. {}", reference, err))?;
+    Ok((
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    ))
+}
+
+/// Compares a run against the reference's output. The diagnostics must
+/// start the reference's stderr, which may go on with more detail.
+fn diff(run: &ScriptRun, stdout: &str, stderr: &str) -> Result<(), String> {
+    let reference_output: Vec<&str> = stdout.lines().collect();
+    for (index, line) in reference_output.iter().enumerate() {
+        match run.output.get(index) {
+            Some(actual) if actual == line => {}
+            actual => {
+                return Err(format!(
+                    "stdout differs at line {}.\nActual: '{}'\nReference: '{}'",
+                    index + 1,
+                    actual.map(String::as_str).unwrap_or("<end of output>"),
+                    line
+                ));
+            }
+        }
+    }
+    if run.output.len() > reference_output.len() {
+        return Err(format!(
+            "stdout has extra lines.\nActual: '{}'\nReference: '<end of output>'",
+            run.output[reference_output.len()]
+        ));
+    }
+
+    match &run.result {
+        Ok(()) if !stderr.trim().is_empty() => Err(format!(
+            "the reference reported an error.\nReference: '{}'",
+            stderr.trim()
+        )),
+        // The reference gives neither columns nor codes
+        Err(error) if !stderr.starts_with(&expectations::as_reference(&error.to_string())) => {
+            Err(format!(
+                "diagnostics differ.\nActual: '{}'\nReference: '{}'",
+                error,
+                stderr.trim()
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Renders the results as JUnit XML, with one test suite per chapter.
+fn junit_report(results: &BTreeMap<&str, ChapterResult>) -> String {
+    let tests: usize = results.values().map(|result| result.scripts.len()).sum();
+    let failures: usize = results.values().map(ChapterResult::failed).sum();
+    let time: Duration = results.values().map(ChapterResult::duration).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"lox-conformance\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+        tests,
+        failures,
+        time.as_secs_f64()
+    ));
+    for (chapter, result) in results {
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+            xml_escape(chapter),
+            result.scripts.len(),
+            result.failed(),
+            result.duration().as_secs_f64()
+        ));
+        for script in &result.scripts {
+            xml.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                xml_escape(&script.path.to_string_lossy()),
+                xml_escape(chapter),
+                script.duration.as_secs_f64()
+            ));
+            match &script.failure {
+                Some(failure) => xml.push_str(&format!(
+                    ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                    xml_escape(failure.lines().next().unwrap_or("")),
+                    xml_escape(failure)
+                )),
+                None => xml.push_str("/>\n"),
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Renders the results as a JSON object with totals and one entry per script.
+fn json_report(results: &BTreeMap<&str, ChapterResult>, skipped: usize) -> String {
+    let mut entries = Vec::new();
+    for (chapter, result) in results {
+        for script in &result.scripts {
+            entries.push(format!(
+                "    {{\"name\": {}, \"chapter\": {}, \"passed\": {}, \"duration_ms\": {:.3}, \"failure\": {}}}",
+                json_string(&script.path.to_string_lossy()),
+                json_string(chapter),
+                script.failure.is_none(),
+                script.duration.as_secs_f64() * 1000.0,
+                script
+                    .failure
+                    .as_deref()
+                    .map(json_string)
+                    .unwrap_or("null".to_string())
+            ));
+        }
+    }
+    let failed: usize = results.values().map(ChapterResult::failed).sum();
+    format!(
+        "{{\n  \"passed\": {},\n  \"failed\": {},\n  \"skipped\": {},\n  \"results\": [\n{}\n  ]\n}}\n",
+        entries.len() - failed,
+        failed,
+        skipped,
+        entries.join(",\n")
+    )
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn main() {
+    let suite = PathBuf::from(env::var("LOX_CONFORMANCE_DIR").unwrap_or("tests".to_string()));
+    let options = Options::parse(env::args().skip(1));
+    let reference = env::var("LOX_REFERENCE").ok();
+
+    let mut scripts = Vec::new();
+    collect_scripts(&suite, &mut scripts);
+    scripts.sort();
+    if scripts.is_empty() {
+        eprintln!("No .lox scripts found in '{}'.", suite.display());
+        process::exit(1);
+    }
+
+    let output_file = Path::new(env!("CARGO_TARGET_TMPDIR")).join("conformance_output.txt");
+    let mut results: BTreeMap<&str, ChapterResult> = BTreeMap::new();
+    let mut skipped = 0;
+
+    for script in scripts {
+        let relative = script.strip_prefix(&suite).unwrap_or(&script);
+        if let Some(filter) = &options.filter {
+            if !relative.to_string_lossy().contains(filter.as_str()) {
+                continue;
+            }
+        }
+        // Scripts directly in the suite directory have no category
+        let category = match relative.components().count() {
+            1 => "",
+            _ => relative.iter().next().unwrap().to_str().unwrap_or(""),
+        };
+        if SKIPPED.contains(&category) {
+            skipped += 1;
+            continue;
+        }
+        if !options.tags.is_empty() || !options.skipped_tags.is_empty() {
+            let source = fs::read_to_string(&script).unwrap_or_default();
+            if !options.selects(&Expectations::parse(&source).tags) {
+                skipped += 1;
+                continue;
+            }
+        }
+
+        let result = results.entry(chapter_of(category)).or_default();
+        let start = Instant::now();
+        let checked =
+            expectations::run_script(&script, &output_file).and_then(|run| match &reference {
+                Some(reference) => run_reference(reference, &script)
+                    .and_then(|(stdout, stderr)| diff(&run, &stdout, &stderr)),
+                None => run.check(),
+            });
+        result.scripts.push(ScriptResult {
+            path: relative.to_path_buf(),
+            duration: start.elapsed(),
+            failure: checked.err(),
+        });
+    }
+
+    println!();
+    if let Some(reference) = &reference {
+        println!("Differences from {}:", reference);
+    }
+    let mut failed = 0;
+    for (chapter, result) in &results {
+        let total = result.scripts.len();
+        println!(
+            "{:<28} {:>4}/{:<4} passed",
+            chapter,
+            total - result.failed(),
+            total
+        );
+        for (script, err) in result.failures() {
+            println!(
+                "    FAIL {}: {}",
+                script.path.display(),
+                err.replace('\n', " ")
+            );
+        }
+        failed += result.failed();
+    }
+    println!("\n{} failed, {} skipped", failed, skipped);
+
+    let reports = [
+        (&options.junit, junit_report(&results)),
+        (&options.json, json_report(&results, skipped)),
+    ];
+    for (path, report) in reports {
+        if let Some(path) = path {
+            if let Err(err) = fs::write(path, report) {
+                eprintln!("Failed to write report to '{}'. {}", path.display(), err);
+                process::exit(1);
+            }
+        }
+    }
+
+    if failed > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,169 @@
+//! End-to-end tests that drive the interactive prompt through stdin.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Feeds `input` to a fresh prompt and returns what it wrote to stdout, with
+/// the prompts removed, and to stderr.
+fn repl(input: &str) -> (String, String) {
+    repl_with(&[], input)
+}
+
+/// Like `repl`, with `args` on the command line.
+fn repl_with(args: &[&str], input: &str) -> (String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to start the interpreter");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .expect("Failed to write to the prompt");
+    let output = child.wait_with_output().unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout)
+        .replace("... ", "")
+        .replace("> ", "");
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    (stdout, stderr)
+}
+
+#[test]
+fn state_persists_between_lines() {
+    let (stdout, stderr) = repl("var a = 1;\nfun inc() { a = a + 1; }\ninc();\nprint a;\n");
+    assert_eq!(stdout, "2\n");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn unfinished_input_continues_on_the_next_line() {
+    let (stdout, _) = repl("fun greet(name) {\n  print \"hi \" + name;\n}\ngreet(\"bob\");\n");
+    assert_eq!(stdout, "hi bob\n");
+
+    // Braces inside strings and comments don't keep the input open
+    let (stdout, _) = repl("print \"{\"; // (\nprint 1;\n");
+    assert_eq!(stdout, "{\n1\n");
+
+    // So does an unclosed heredoc
+    let (stdout, _) = repl("print <<<END\n{ one\nEND;\n");
+    assert_eq!(stdout, "{ one\n");
+
+    // And an unclosed block comment
+    let (stdout, _) = repl("print 1; /* {\n*/ print 2;\n");
+    assert_eq!(stdout, "1\n2\n");
+
+    // And an #if with no #end yet
+    let (stdout, _) = repl("#if DEBUG\nprint 1;\n#end\nprint 2;\n");
+    assert_eq!(stdout, "2\n");
+}
+
+#[test]
+fn prompt_shows_when_input_continues() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child
+                .stdin
+                .take()
+                .unwrap()
+                .write_all(b"if (true) {\nprint 1;\n}\n")?;
+            child.wait_with_output()
+        })
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "> ... ... 1\n> ");
+}
+
+#[test]
+fn errors_do_not_end_the_session() {
+    let (stdout, stderr) = repl("print missing;\nvar;\nprint 1;\n");
+    assert_eq!(stdout, "1\n");
+    assert_eq!(
+        stderr,
+        "Undefined variable 'missing'. [E3002]\n[line 1:7]\n\
+         [line 1:4] Error at ';': Expect variable name. [E2003]\n\
+         1 | var;\n  |    ^\n"
+    );
+}
+
+#[test]
+fn errors_inside_blocks_and_calls_leave_the_session_usable() {
+    let (stdout, stderr) = repl(
+        "var a = \"global\";\n\
+         { var a = \"local\"; nil(); }\n\
+         fun f() { return missing; }\n\
+         f();\n\
+         print a;\n\
+         fun f() { return 1; }\n\
+         print f();\n",
+    );
+    assert_eq!(stdout, "global\n1\n");
+    assert_eq!(
+        stderr,
+        "Can only call functions and classes. [E3006]\n[line 1:24]\n\
+         Undefined variable 'missing'. [E3002]\n[line 1:18]\n  at f (line 1)\n"
+    );
+}
+
+#[test]
+fn meta_commands() {
+    let (stdout, _) = repl(":help\n");
+    assert!(stdout.contains(":reset"), "{}", stdout);
+
+    let (stdout, stderr) = repl("var a = 1;\n:reset\nprint a;\n");
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, "Undefined variable 'a'. [E3002]\n[line 1:7]\n");
+
+    // The command line's settings outlive a reset
+    let (stdout, stderr) = repl_with(
+        &["--decimal", "--define", "LEVEL=2", "--no-prelude"],
+        "var a = 1;\n:reset\nprint 0.1 + 0.2;\nprint LEVEL;\nprint map;\n",
+    );
+    assert_eq!(stdout, "0.3\n2\n");
+    assert_eq!(stderr, "Undefined variable 'map'. [E3002]\n[line 1:7]\n");
+
+    let (stdout, _) = repl("print 1;\n:quit\nprint 2;\n");
+    assert_eq!(stdout, "1\n");
+
+    let (_, stderr) = repl(":bogus\n");
+    assert_eq!(stderr, "Unknown command ':bogus'. Type :help for a list.\n");
+}
+
+#[test]
+fn logging_goes_to_stderr() {
+    let (stdout, stderr) = repl("logWarn(\"careful\");\nprint 1;\n");
+    assert_eq!(stdout, "1\n");
+    assert!(stderr.ends_with("Z WARN  careful\n"), "{}", stderr);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lox_interpreter"))
+        .args(["--log-level", "error"])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .and_then(|mut child| {
+            child
+                .stdin
+                .take()
+                .unwrap()
+                .write_all(b"logInfo(\"hidden\");\nlogError(\"shown\");\n")?;
+            child.wait_with_output()
+        })
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("hidden"), "{}", stderr);
+    assert!(stderr.contains("ERROR shown"), "{}", stderr);
+}
+
+#[test]
+fn env_lists_the_globals_a_session_defined() {
+    let (stdout, stderr) = repl("var a = 1;\nfun f() {}\n:env\n");
+    assert_eq!(stdout, "globals\n  a = 1\n  f = <fn f>\n");
+    assert_eq!(stderr, "");
+}
@@ -0,0 +1,227 @@
+//! Project automation, run as `cargo xtask <task>`.
+//!
+//! `cargo xtask bench` times every script in `benchmarks/`, plus the scanner
+//! and parser on their combined source and the scanner on each of its lines
+//! separately, and compares each median against
+//! `benchmarks/baseline.txt`. It fails when a benchmark is slower than its
+//! baseline by more than the threshold.
+
+use lox_interpreter::parser::Parser;
+use lox_interpreter::scanner::Scanner;
+use lox_interpreter::Lox;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Instant;
+
+const USAGE: &str =
+    "Usage: cargo xtask bench [--save] [--warn-only] [--threshold <percent>] [--runs <count>]
+
+  --save                 Record this run as the new baseline
+  --warn-only            Report regressions without failing
+  --threshold <percent>  Slowdown allowed before a benchmark counts as a regression (default 25)
+  --runs <count>         Timed runs per benchmark; the median is reported (default 5)";
+
+// The scanner and parser benchmarks work on this many copies of the scripts
+const FRONT_END_COPIES: usize = 50;
+
+struct Options {
+    save: bool,
+    warn_only: bool,
+    threshold: f64,
+    runs: usize,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("bench") => bench(parse_options(&args[1..])),
+        _ => exit_with_usage(),
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1);
+}
+
+fn parse_options(args: &[String]) -> Options {
+    let mut options = Options {
+        save: false,
+        warn_only: false,
+        threshold: 25.0,
+        runs: 5,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--save" => options.save = true,
+            "--warn-only" => options.warn_only = true,
+            "--threshold" => match args.next().and_then(|value| value.parse().ok()) {
+                Some(threshold) => options.threshold = threshold,
+                None => exit_with_usage(),
+            },
+            "--runs" => match args.next().and_then(|value| value.parse().ok()) {
+                Some(runs) if runs > 0 => options.runs = runs,
+                _ => exit_with_usage(),
+            },
+            _ => exit_with_usage(),
+        }
+    }
+    options
+}
+
+fn project_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .to_path_buf()
+}
+
+/// Runs `task` once to warm up, then `runs` more times, and returns the
+/// median time in milliseconds.
+fn median_millis(runs: usize, mut task: impl FnMut()) -> f64 {
+    task();
+    let mut times: Vec<f64> = (0..runs)
+        .map(|_| {
+            let start = Instant::now();
+            task();
+            start.elapsed().as_secs_f64() * 1000.0
+        })
+        .collect();
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    times[times.len() / 2]
+}
+
+fn bench(options: Options) {
+    let root = project_root();
+    let baseline_path = root.join("benchmarks").join("baseline.txt");
+    let output_path = env::temp_dir().join("lox_bench_output.txt");
+
+    let mut scripts: Vec<PathBuf> = fs::read_dir(root.join("benchmarks"))
+        .expect("Failed to read benchmarks directory")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    scripts.sort();
+
+    let mut results: BTreeMap<String, f64> = BTreeMap::new();
+    let mut all_sources = String::new();
+    for script in &scripts {
+        let name = script.file_stem().unwrap().to_string_lossy().to_string();
+        let source = fs::read_to_string(script).expect("Failed to read benchmark");
+        all_sources.push_str(&source);
+        all_sources.push('\n');
+
+        let mut failure = None;
+        let millis = median_millis(options.runs, || {
+            let _ = fs::remove_file(&output_path);
+            if let Err(error) = Lox::new(&output_path.to_string_lossy()).try_run(&source) {
+                failure = Some(error.to_string());
+            }
+        });
+        if let Some(error) = failure {
+            eprintln!("Benchmark '{}' failed: {}", name, error);
+            process::exit(1);
+        }
+        results.insert(name, millis);
+    }
+    let _ = fs::remove_file(&output_path);
+
+    let front_end_source = all_sources.repeat(FRONT_END_COPIES);
+    results.insert(
+        "scan".to_string(),
+        median_millis(options.runs, || {
+            let _ = Scanner::new(front_end_source.clone()).scan_tokens();
+        }),
+    );
+    // Many small inputs, as the REPL and tools that scan a name at a time
+    // produce, so the cost of setting up each scanner shows
+    let lines: Vec<&str> = front_end_source.lines().collect();
+    results.insert(
+        "scan_lines".to_string(),
+        median_millis(options.runs, || {
+            for line in &lines {
+                let _ = Scanner::new(line.to_string()).scan_tokens();
+            }
+        }),
+    );
+    let tokens = Scanner::new(front_end_source)
+        .scan_tokens()
+        .expect("benchmark sources scan");
+    results.insert(
+        "parse".to_string(),
+        median_millis(options.runs, || {
+            let _ = Parser::new(tokens.clone()).parse();
+        }),
+    );
+
+    if options.save {
+        let mut baseline =
+            String::from("# Median milliseconds per benchmark, from `cargo xtask bench --save`\n");
+        for (name, millis) in &results {
+            baseline.push_str(&format!("{} {:.3}\n", name, millis));
+        }
+        fs::write(&baseline_path, baseline).expect("Failed to write baseline");
+        println!("Saved baseline to {}", baseline_path.display());
+        return;
+    }
+
+    let baseline = read_baseline(&baseline_path);
+    let mut regressions = 0;
+    println!(
+        "{:<12} {:>12} {:>12} {:>9}",
+        "benchmark", "baseline ms", "current ms", "change"
+    );
+    for (name, millis) in &results {
+        match baseline.get(name) {
+            Some(&base) => {
+                let change = (millis - base) / base * 100.0;
+                let regressed = change > options.threshold;
+                if regressed {
+                    regressions += 1;
+                }
+                println!(
+                    "{:<12} {:>12.3} {:>12.3} {:>+8.1}%{}",
+                    name,
+                    base,
+                    millis,
+                    change,
+                    if regressed { "  REGRESSED" } else { "" }
+                );
+            }
+            None => println!("{:<12} {:>12} {:>12.3}       new", name, "-", millis),
+        }
+    }
+
+    if regressions > 0 {
+        println!(
+            "\n{} benchmark(s) regressed by more than {}%.",
+            regressions, options.threshold
+        );
+        if !options.warn_only {
+            process::exit(1);
+        }
+    }
+}
+
+fn read_baseline(path: &Path) -> BTreeMap<String, f64> {
+    let Ok(contents) = fs::read_to_string(path) else {
+        eprintln!(
+            "No baseline at {}. Record one with `cargo xtask bench --save`.",
+            path.display()
+        );
+        process::exit(1);
+    };
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (name, millis) = line.split_once(' ')?;
+            Some((name.to_string(), millis.trim().parse().ok()?))
+        })
+        .collect()
+}